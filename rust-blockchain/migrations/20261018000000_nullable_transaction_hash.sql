@@ -0,0 +1,2 @@
+-- Transactions built for client-side signing have no hash until they are broadcast
+ALTER TABLE transactions ALTER COLUMN tx_hash DROP NOT NULL;
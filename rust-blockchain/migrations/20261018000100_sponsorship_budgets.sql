@@ -0,0 +1,9 @@
+-- Per-user budgets for gas paid by the platform relayer (relayed v3 transactions).
+-- Amounts are in the smallest EGLD denomination.
+CREATE TABLE sponsorship_budgets (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    allowance BIGINT NOT NULL DEFAULT 0 CHECK (allowance >= 0),
+    spent BIGINT NOT NULL DEFAULT 0 CHECK (spent >= 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,11 @@
+-- History of USD token prices recorded by the price service.
+-- Tokens are stored by ticker (EGLD, USDC, MEX), wrapped EGLD being recorded as EGLD.
+CREATE TABLE token_prices (
+    id BIGSERIAL PRIMARY KEY,
+    token TEXT NOT NULL,
+    price_usd DOUBLE PRECISION NOT NULL CHECK (price_usd >= 0),
+    source TEXT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_token_prices_token_recorded_at ON token_prices(token, recorded_at DESC);
@@ -0,0 +1,3 @@
+-- Auto-compounding: how often each position's rewards are reinvested and when they last were
+ALTER TABLE positions ADD COLUMN compound_frequency TEXT NOT NULL DEFAULT 'Manual';
+ALTER TABLE positions ADD COLUMN last_compound TIMESTAMPTZ;
@@ -0,0 +1,12 @@
+-- Pending rewards of each position, recorded periodically by the position service.
+-- One row per reward token; the USD value is missing when the token had no price.
+CREATE TABLE position_reward_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    position_id UUID NOT NULL REFERENCES positions(id) ON DELETE CASCADE,
+    token TEXT NOT NULL,
+    amount DOUBLE PRECISION NOT NULL CHECK (amount >= 0),
+    value_usd DOUBLE PRECISION,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_position_reward_snapshots_position_recorded_at ON position_reward_snapshots(position_id, recorded_at DESC);
@@ -0,0 +1,14 @@
+-- Risk scores of each protocol, computed periodically by the risk service.
+-- Factors hold the rating of every factor; TVL and APY are kept to measure trend and volatility.
+CREATE TABLE protocol_risk_scores (
+    id BIGSERIAL PRIMARY KEY,
+    protocol_id TEXT NOT NULL,
+    score DOUBLE PRECISION NOT NULL CHECK (score >= 0 AND score <= 100),
+    level TEXT NOT NULL,
+    tvl_usd DOUBLE PRECISION,
+    apy DOUBLE PRECISION,
+    factors JSONB NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_protocol_risk_scores_protocol_computed_at ON protocol_risk_scores(protocol_id, computed_at DESC);
@@ -0,0 +1,16 @@
+-- APY and TVL of every pool, sampled periodically by the pool history service.
+-- Raw samples are rolled up into hourly, then daily averages as they age; TVL is in the
+-- protocol's TVL unit, and in USD when that unit could be priced.
+CREATE TABLE pool_metrics (
+    id BIGSERIAL PRIMARY KEY,
+    protocol_id TEXT NOT NULL,
+    pool_id TEXT NOT NULL,
+    apy DOUBLE PRECISION NOT NULL,
+    tvl DOUBLE PRECISION NOT NULL,
+    tvl_usd DOUBLE PRECISION,
+    resolution TEXT NOT NULL DEFAULT 'raw' CHECK (resolution IN ('raw', 'hour', 'day')),
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_pool_metrics_pool_recorded_at ON pool_metrics(pool_id, recorded_at DESC);
+CREATE INDEX idx_pool_metrics_resolution_recorded_at ON pool_metrics(resolution, recorded_at);
@@ -0,0 +1,5 @@
+-- Payload of transactions built for client-side signing, checked against the signed transaction on broadcast
+ALTER TABLE transactions ADD COLUMN receiver TEXT;
+ALTER TABLE transactions ADD COLUMN value TEXT;
+ALTER TABLE transactions ADD COLUMN data TEXT;
+ALTER TABLE transactions ADD COLUMN nonce BIGINT;
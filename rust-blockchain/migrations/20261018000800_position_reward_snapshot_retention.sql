@@ -0,0 +1,2 @@
+-- Reward snapshots older than their retention are deleted by recorded_at across all positions.
+CREATE INDEX idx_position_reward_snapshots_recorded_at ON position_reward_snapshots(recorded_at);
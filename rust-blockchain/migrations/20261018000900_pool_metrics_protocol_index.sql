@@ -0,0 +1,3 @@
+-- Pool IDs are only unique within a protocol, so pool histories are looked up by both.
+DROP INDEX idx_pool_metrics_pool_recorded_at;
+CREATE INDEX idx_pool_metrics_protocol_pool_recorded_at ON pool_metrics(protocol_id, pool_id, recorded_at DESC);
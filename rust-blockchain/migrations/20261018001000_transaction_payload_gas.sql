@@ -0,0 +1,4 @@
+-- Gas and relayer of transactions built for client-side signing, checked on broadcast along with the payload
+ALTER TABLE transactions ADD COLUMN gas_limit BIGINT;
+ALTER TABLE transactions ADD COLUMN gas_price BIGINT;
+ALTER TABLE transactions ADD COLUMN relayer TEXT;
\ No newline at end of file
@@ -0,0 +1,44 @@
+use crate::api::middleware::with_auth;
+use crate::models::transaction::FarmTransactionRequest;
+use crate::services::ServiceContainer;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+
+pub fn routes(
+    services: Arc<ServiceContainer>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let position_service = services.position_service.clone();
+    
+    let get_farm_positions = warp::path!("farms" / "positions")
+        .and(warp::get())
+        .and(with_auth(services.clone()))
+        .and_then(move |user_id| {
+            let position_service = position_service.clone();
+            async move {
+                position_service
+                    .get_farm_positions(user_id)
+                    .await
+                    .map(|positions| warp::reply::json(&positions))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let transaction_service = services.transaction_service.clone();
+    
+    let build_farm_transaction = warp::path!("farms" / "build")
+        .and(warp::post())
+        .and(with_auth(services.clone()))
+        .and(warp::body::json())
+        .and_then(move |user_id, request: FarmTransactionRequest| {
+            let transaction_service = transaction_service.clone();
+            async move {
+                transaction_service
+                    .build_farm_transaction(user_id, request)
+                    .await
+                    .map(|transaction| warp::reply::json(&transaction))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    get_farm_positions.or(build_farm_transaction)
+}
\ No newline at end of file
@@ -0,0 +1,26 @@
+use crate::api::middleware::with_auth;
+use crate::models::transaction::LendingTransactionRequest;
+use crate::services::ServiceContainer;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+
+pub fn routes(
+    services: Arc<ServiceContainer>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let transaction_service = services.transaction_service.clone();
+    
+    warp::path!("lending" / "build")
+        .and(warp::post())
+        .and(with_auth(services.clone()))
+        .and(warp::body::json())
+        .and_then(move |user_id, request: LendingTransactionRequest| {
+            let transaction_service = transaction_service.clone();
+            async move {
+                transaction_service
+                    .build_lending_transaction(user_id, request)
+                    .await
+                    .map(|transaction| warp::reply::json(&transaction))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        })
+}
\ No newline at end of file
@@ -3,6 +3,7 @@ use crate::services::ServiceContainer;
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 use warp::{Filter, Rejection};
 
@@ -43,4 +44,28 @@ pub fn with_auth(
 
             Ok(user_id)
         })
+}
+
+// Only operators holding the admin key may call administrative endpoints
+pub fn with_admin(
+    services: Arc<ServiceContainer>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-admin-key")
+        .and_then(move |admin_key: Option<String>| {
+            let services = services.clone();
+            async move {
+                match (&services.config.api.admin_api_key, admin_key) {
+                    // Compared in constant time so the key can't be guessed from response timings
+                    (Some(expected), Some(provided))
+                        if !expected.is_empty() && bool::from(expected.as_bytes().ct_eq(provided.as_bytes())) => Ok(()),
+                    (None, _) => Err(warp::reject::custom(AppError::Auth(
+                        "Administrative endpoints are disabled".to_string(),
+                    ))),
+                    _ => Err(warp::reject::custom(AppError::Auth(
+                        "Invalid admin key".to_string(),
+                    ))),
+                }
+            }
+        })
+        .untuple_one()
 }
\ No newline at end of file
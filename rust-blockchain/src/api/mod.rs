@@ -1,7 +1,12 @@
 mod auth;
+mod delegation;
+mod farms;
+mod lending;
 mod middleware;
 mod positions;
+mod prices;
 mod protocols;
+mod swaps;
 mod transactions;
 mod users;
 
@@ -61,7 +66,12 @@ pub async fn start_server(services: Arc<ServiceContainer>, port: u16) -> Result<
                 .or(users::routes(services.clone()))
                 .or(protocols::routes(services.clone()))
                 .or(positions::routes(services.clone()))
+                .or(swaps::routes(services.clone()))
                 .or(transactions::routes(services.clone()))
+                .or(farms::routes(services.clone()))
+                .or(lending::routes(services.clone()))
+                .or(delegation::routes(services.clone()))
+                .or(prices::routes(services.clone()))
         );
 
     // Combine all routes
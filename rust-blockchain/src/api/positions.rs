@@ -25,12 +25,14 @@ pub fn routes(
             }
         });
     
+    let position_service_list = services.position_service.clone();
+    
     let get_positions = warp::path!("positions")
         .and(warp::get())
         .and(with_auth(services.clone()))
         .and(warp::query::<std::collections::HashMap<String, String>>())
         .and_then(move |user_id, params: std::collections::HashMap<String, String>| {
-            let position_service = position_service.clone();
+            let position_service = position_service_list.clone();
             let protocol_id = params.get("protocol_id").cloned();
             async move {
                 position_service
@@ -41,11 +43,13 @@ pub fn routes(
             }
         });
     
+    let position_service_get = services.position_service.clone();
+    
     let get_position = warp::path!("positions" / Uuid)
         .and(warp::get())
         .and(with_auth(services.clone()))
         .and_then(move |id, user_id| {
-            let position_service = position_service.clone();
+            let position_service = position_service_get.clone();
             async move {
                 position_service
                     .get_position_by_id(id, user_id)
@@ -55,12 +59,14 @@ pub fn routes(
             }
         });
     
+    let position_service_update = services.position_service.clone();
+    
     let update_position = warp::path!("positions" / Uuid)
         .and(warp::put())
         .and(with_auth(services.clone()))
         .and(warp::body::json())
         .and_then(move |id, user_id, request: UpdatePositionRequest| {
-            let position_service = position_service.clone();
+            let position_service = position_service_update.clone();
             async move {
                 position_service
                     .update_position(id, user_id, request)
@@ -70,11 +76,13 @@ pub fn routes(
             }
         });
     
+    let position_service_delete = services.position_service.clone();
+    
     let delete_position = warp::path!("positions" / Uuid)
         .and(warp::delete())
         .and(with_auth(services.clone()))
         .and_then(move |id, user_id| {
-            let position_service = position_service.clone();
+            let position_service = position_service_delete.clone();
             async move {
                 position_service
                     .delete_position(id, user_id)
@@ -84,11 +92,13 @@ pub fn routes(
             }
         });
     
+    let position_service_rebalance = services.position_service.clone();
+    
     let rebalance_position = warp::path!("positions" / Uuid / "rebalance")
         .and(warp::post())
         .and(with_auth(services.clone()))
         .and_then(move |id, user_id| {
-            let position_service = position_service.clone();
+            let position_service = position_service_rebalance.clone();
             async move {
                 position_service
                     .rebalance_position(id, user_id)
@@ -98,10 +108,27 @@ pub fn routes(
             }
         });
     
+    let position_service_rewards = services.position_service.clone();
+    
+    let get_position_rewards = warp::path!("positions" / Uuid / "rewards")
+        .and(warp::get())
+        .and(with_auth(services.clone()))
+        .and_then(move |id, user_id| {
+            let position_service = position_service_rewards.clone();
+            async move {
+                position_service
+                    .get_position_rewards(id, user_id)
+                    .await
+                    .map(|rewards| warp::reply::json(&rewards))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
     create_position
         .or(get_positions)
         .or(get_position)
         .or(update_position)
         .or(delete_position)
         .or(rebalance_position)
+        .or(get_position_rewards)
 }
\ No newline at end of file
@@ -0,0 +1,53 @@
+use crate::services::ServiceContainer;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+// Period of the price history when no start is given
+const DEFAULT_PRICE_HISTORY_DAYS: i64 = 7;
+
+pub fn routes(
+    services: Arc<ServiceContainer>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let price_service = services.price_service.clone();
+    
+    let get_all_prices = warp::path!("prices")
+        .and(warp::get())
+        .and_then(move || {
+            let price_service = price_service.clone();
+            async move {
+                price_service
+                    .get_all_prices()
+                    .await
+                    .map(|prices| warp::reply::json(&prices))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let price_service_history = services.price_service.clone();
+    
+    let get_price_history = warp::path!("prices" / String / "history")
+        .and(warp::get())
+        .and(warp::query::<HistoryQuery>())
+        .and_then(move |token: String, query: HistoryQuery| {
+            let price_service = price_service_history.clone();
+            async move {
+                let to = query.to.unwrap_or_else(Utc::now);
+                let from = query.from.unwrap_or_else(|| to - Duration::days(DEFAULT_PRICE_HISTORY_DAYS));
+                price_service
+                    .get_price_history(&token, from, to)
+                    .await
+                    .map(|history| warp::reply::json(&history))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    get_all_prices.or(get_price_history)
+}
\ No newline at end of file
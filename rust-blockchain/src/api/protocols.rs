@@ -1,7 +1,32 @@
+use crate::api::middleware::with_admin;
+use crate::error::AppError;
 use crate::services::ServiceContainer;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
 use std::sync::Arc;
 use warp::{Filter, Rejection, Reply};
 
+#[derive(Debug, Deserialize)]
+struct PauseRequest {
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolHistoryQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    interval: Option<String>,
+}
+
+// Period of the pool history when no start is given
+const DEFAULT_POOL_HISTORY_DAYS: i64 = 7;
+
 pub fn routes(
     services: Arc<ServiceContainer>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
@@ -20,10 +45,12 @@ pub fn routes(
             }
         });
     
+    let protocol_service_by_id = services.protocol_service.clone();
+    
     let get_protocol_by_id = warp::path!("protocols" / String)
         .and(warp::get())
-        .and_then(move |id| {
-            let protocol_service = protocol_service.clone();
+        .and_then(move |id: String| {
+            let protocol_service = protocol_service_by_id.clone();
             async move {
                 protocol_service
                     .get_protocol_by_id(&id)
@@ -37,7 +64,7 @@ pub fn routes(
     
     let get_pools_by_protocol = warp::path!("protocols" / String / "pools")
         .and(warp::get())
-        .and_then(move |protocol_id| {
+        .and_then(move |protocol_id: String| {
             let protocol_service = protocol_service_pools.clone();
             async move {
                 protocol_service
@@ -67,7 +94,7 @@ pub fn routes(
     
     let get_pool_by_id = warp::path!("pools" / String)
         .and(warp::get())
-        .and_then(move |id| {
+        .and_then(move |id: String| {
             let protocol_service = protocol_service_pool.clone();
             async move {
                 protocol_service
@@ -78,9 +105,135 @@ pub fn routes(
             }
         });
     
-    get_all_protocols
+    let protocol_service_health = services.protocol_service.clone();
+    
+    let get_protocol_health = warp::path!("protocols" / "health")
+        .and(warp::get())
+        .map(move || warp::reply::json(&protocol_service_health.get_protocol_health()));
+    
+    let protocol_service_pause = services.protocol_service.clone();
+    
+    let pause_protocol = warp::path!("protocols" / String / "pause")
+        .and(warp::post())
+        .and(with_admin(services.clone()))
+        .and(warp::body::json())
+        .and_then(move |id: String, request: PauseRequest| {
+            let protocol_service = protocol_service_pause.clone();
+            async move {
+                protocol_service
+                    .pause_protocol(&id, &request.reason)
+                    .await
+                    .map(|health| warp::reply::json(&health))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let protocol_service_resume = services.protocol_service.clone();
+    
+    let resume_protocol = warp::path!("protocols" / String / "resume")
+        .and(warp::post())
+        .and(with_admin(services.clone()))
+        .and_then(move |id: String| {
+            let protocol_service = protocol_service_resume.clone();
+            async move {
+                protocol_service
+                    .resume_protocol(&id)
+                    .await
+                    .map(|health| warp::reply::json(&health))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let risk_service = services.risk_service.clone();
+    
+    let get_all_risk_scores = warp::path!("protocols" / "risk")
+        .and(warp::get())
+        .map(move || warp::reply::json(&risk_service.get_all_risk_scores()));
+    
+    let risk_service_by_id = services.risk_service.clone();
+    
+    let get_risk_score = warp::path!("protocols" / String / "risk")
+        .and(warp::get())
+        .and_then(move |id: String| {
+            let risk_service = risk_service_by_id.clone();
+            async move {
+                risk_service
+                    .get_risk_score(&id)
+                    .await
+                    .map(|score| warp::reply::json(&score))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let risk_service_history = services.risk_service.clone();
+    
+    let get_risk_history = warp::path!("protocols" / String / "risk" / "history")
+        .and(warp::get())
+        .and(warp::query::<HistoryQuery>())
+        .and_then(move |id: String, query: HistoryQuery| {
+            let risk_service = risk_service_history.clone();
+            async move {
+                let to = query.to.unwrap_or_else(Utc::now);
+                let from = query.from.unwrap_or_else(|| to - risk_service.history_window());
+                risk_service
+                    .get_risk_history(&id, from, to)
+                    .await
+                    .map(|history| warp::reply::json(&history))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let pool_history_service = services.pool_history_service.clone();
+    
+    let get_pool_history = warp::path!("protocols" / String / "pools" / String / "history")
+        .and(warp::get())
+        .and(warp::query::<PoolHistoryQuery>())
+        .and_then(move |id: String, pool_id: String, query: PoolHistoryQuery| {
+            let pool_history_service = pool_history_service.clone();
+            async move {
+                let to = query.to.unwrap_or_else(Utc::now);
+                let from = query.from.unwrap_or_else(|| to - Duration::days(DEFAULT_POOL_HISTORY_DAYS));
+                pool_history_service
+                    .get_pool_history(&id, &pool_id, from, to, query.interval.as_deref())
+                    .await
+                    .map(|history| warp::reply::json(&history))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let pool_history_service_by_key = services.pool_history_service.clone();
+    
+    // Pools are named across protocols by a "protocol:pool" ID, as in allocations
+    let get_pool_history_by_key = warp::path!("pools" / String / "history")
+        .and(warp::get())
+        .and(warp::query::<PoolHistoryQuery>())
+        .and_then(move |key: String, query: PoolHistoryQuery| {
+            let pool_history_service = pool_history_service_by_key.clone();
+            async move {
+                let (id, pool_id) = key.split_once(':').ok_or_else(|| warp::reject::custom(
+                    AppError::Validation(format!("Pool ID {} must be given as protocol:pool", key))
+                ))?;
+                let to = query.to.unwrap_or_else(Utc::now);
+                let from = query.from.unwrap_or_else(|| to - Duration::days(DEFAULT_POOL_HISTORY_DAYS));
+                pool_history_service
+                    .get_pool_history(id, pool_id, from, to, query.interval.as_deref())
+                    .await
+                    .map(|history| warp::reply::json(&history))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    get_protocol_health
+        .or(get_all_risk_scores)
+        .or(get_risk_score)
+        .or(get_risk_history)
+        .or(pause_protocol)
+        .or(resume_protocol)
+        .or(get_all_protocols)
         .or(get_protocol_by_id)
         .or(get_pools_by_protocol)
         .or(get_all_pools)
         .or(get_pool_by_id)
+        .or(get_pool_history)
+        .or(get_pool_history_by_key)
 }
\ No newline at end of file
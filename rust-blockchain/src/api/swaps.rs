@@ -0,0 +1,33 @@
+use crate::services::ServiceContainer;
+use crate::smart_contracts::router::DEFAULT_MAX_SLIPPAGE;
+use serde::Deserialize;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+
+#[derive(Debug, Deserialize)]
+struct QuoteQuery {
+    token_in: String,
+    token_out: String,
+    amount: f64,
+    slippage: Option<f64>,
+}
+
+pub fn routes(
+    services: Arc<ServiceContainer>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let swap_router = services.swap_router.clone();
+    
+    warp::path!("quote")
+        .and(warp::get())
+        .and(warp::query::<QuoteQuery>())
+        .and_then(move |query: QuoteQuery| {
+            let swap_router = swap_router.clone();
+            async move {
+                swap_router
+                    .quote(&query.token_in, &query.token_out, query.amount, query.slippage.unwrap_or(DEFAULT_MAX_SLIPPAGE))
+                    .await
+                    .map(|route| warp::reply::json(&route))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        })
+}
\ No newline at end of file
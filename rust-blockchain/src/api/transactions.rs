@@ -1,5 +1,7 @@
 use crate::api::middleware::with_auth;
-use crate::models::transaction::CreateTransactionRequest;
+use crate::models::transaction::{
+    BroadcastTransactionRequest, BuildTransactionRequest, CreateTransactionRequest,
+};
 use crate::services::ServiceContainer;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -27,11 +29,47 @@ pub fn routes(
             }
         });
     
+    let transaction_service_build = services.transaction_service.clone();
+    
+    let build_transaction = warp::path!("transactions" / "build")
+        .and(warp::post())
+        .and(with_auth(services.clone()))
+        .and(warp::body::json())
+        .and_then(move |user_id, request: BuildTransactionRequest| {
+            let transaction_service = transaction_service_build.clone();
+            async move {
+                transaction_service
+                    .build_unsigned_transaction(user_id, request)
+                    .await
+                    .map(|transaction| warp::reply::json(&transaction))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let transaction_service_broadcast = services.transaction_service.clone();
+    
+    let broadcast_transaction = warp::path!("transactions" / "broadcast")
+        .and(warp::post())
+        .and(with_auth(services.clone()))
+        .and(warp::body::json())
+        .and_then(move |user_id, request: BroadcastTransactionRequest| {
+            let transaction_service = transaction_service_broadcast.clone();
+            async move {
+                transaction_service
+                    .broadcast_transaction(user_id, request)
+                    .await
+                    .map(|transaction| warp::reply::json(&transaction))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let transaction_service_list = services.transaction_service.clone();
+    
     let get_transactions = warp::path!("transactions")
         .and(warp::get())
         .and(with_auth(services.clone()))
         .and_then(move |user_id| {
-            let transaction_service = transaction_service.clone();
+            let transaction_service = transaction_service_list.clone();
             async move {
                 transaction_service
                     .get_transactions_by_user(user_id)
@@ -41,11 +79,13 @@ pub fn routes(
             }
         });
     
+    let transaction_service_get = services.transaction_service.clone();
+    
     let get_transaction = warp::path!("transactions" / Uuid)
         .and(warp::get())
         .and(with_auth(services.clone()))
         .and_then(move |id, user_id| {
-            let transaction_service = transaction_service.clone();
+            let transaction_service = transaction_service_get.clone();
             async move {
                 transaction_service
                     .get_transaction_by_id(id, user_id)
@@ -55,10 +95,12 @@ pub fn routes(
             }
         });
     
+    let transaction_service_hash = services.transaction_service.clone();
+    
     let get_transaction_by_hash = warp::path!("transactions" / "hash" / String)
         .and(warp::get())
-        .and_then(move |tx_hash| {
-            let transaction_service = transaction_service.clone();
+        .and_then(move |tx_hash: String| {
+            let transaction_service = transaction_service_hash.clone();
             async move {
                 transaction_service
                     .get_transaction_by_hash(&tx_hash)
@@ -69,6 +111,8 @@ pub fn routes(
         });
     
     create_transaction
+        .or(build_transaction)
+        .or(broadcast_transaction)
         .or(get_transactions)
         .or(get_transaction)
         .or(get_transaction_by_hash)
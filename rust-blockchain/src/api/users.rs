@@ -1,12 +1,15 @@
-use crate::api::middleware::with_auth;
+use crate::api::middleware::{with_admin, with_auth};
+use crate::models::sponsorship::SetSponsorshipAllowanceRequest;
 use crate::services::ServiceContainer;
 use std::sync::Arc;
+use uuid::Uuid;
 use warp::{Filter, Rejection, Reply};
 
 pub fn routes(
     services: Arc<ServiceContainer>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     let user_service = services.user_service.clone();
+    let sponsorship_service = services.sponsorship_service.clone();
     
     let get_me = warp::path!("users" / "me")
         .and(warp::get())
@@ -22,5 +25,36 @@ pub fn routes(
             }
         });
     
-    warp::path("users").and(get_me)
+    let get_sponsorship = warp::path!("users" / "me" / "sponsorship")
+        .and(warp::get())
+        .and(with_auth(services.clone()))
+        .and_then(move |user_id| {
+            let sponsorship_service = sponsorship_service.clone();
+            async move {
+                sponsorship_service
+                    .get_budget(user_id)
+                    .await
+                    .map(|budget| warp::reply::json(&budget))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    let sponsorship_service_allowance = services.sponsorship_service.clone();
+    
+    let set_sponsorship = warp::path!("users" / Uuid / "sponsorship")
+        .and(warp::put())
+        .and(with_admin(services.clone()))
+        .and(warp::body::json())
+        .and_then(move |user_id, request: SetSponsorshipAllowanceRequest| {
+            let sponsorship_service = sponsorship_service_allowance.clone();
+            async move {
+                sponsorship_service
+                    .set_allowance(user_id, request.allowance)
+                    .await
+                    .map(|budget| warp::reply::json(&budget))
+                    .map_err(|e| warp::reject::custom(e))
+            }
+        });
+    
+    warp::path("users").and(get_me.or(get_sponsorship).or(set_sponsorship))
 }
\ No newline at end of file
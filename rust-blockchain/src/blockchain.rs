@@ -2,8 +2,11 @@ use crate::config::AppConfig;
 use crate::error::{AppError, AppResult};
 use crate::models::transaction::{Transaction, TransactionStatus};
 use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{debug, error, info};
 
@@ -31,9 +34,50 @@ pub struct Account {
     pub address: String,
     pub balance: String,
     pub nonce: u64,
+    #[serde(default, rename = "isGuarded")]
+    pub is_guarded: bool,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize)]
+struct GuardianDataResponse {
+    data: GuardianDataWrapper,
+}
+
+#[derive(Debug, Deserialize)]
+struct GuardianDataWrapper {
+    #[serde(rename = "guardianData")]
+    guardian_data: GuardianData,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GuardianData {
+    #[serde(rename = "activeGuardian")]
+    pub active_guardian: Option<Guardian>,
+    #[serde(rename = "pendingGuardian")]
+    pub pending_guardian: Option<Guardian>,
+    #[serde(default)]
+    pub guarded: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Guardian {
+    pub address: String,
+    #[serde(rename = "activationEpoch")]
+    pub activation_epoch: u64,
+    #[serde(default, rename = "serviceUID")]
+    pub service_uid: String,
+}
+
+/// Transaction version that supports the options field
+pub const TX_VERSION_WITH_OPTIONS: u32 = 2;
+/// Options bit marking a transaction co-signed by the sender's guardian
+pub const TX_OPTION_GUARDED: u32 = 0b10;
+/// Gas charged for each byte of transaction data
+pub const GAS_PER_DATA_BYTE: u64 = 1_500;
+/// Share of the gas price charged for the gas a transaction spends executing a contract
+pub const GAS_PRICE_MODIFIER: f64 = 0.01;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionRequest {
     pub nonce: u64,
     pub value: String,
@@ -41,10 +85,126 @@ pub struct TransactionRequest {
     pub sender: String,
     pub gasPrice: u64,
     pub gasLimit: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "base64_data")]
     pub data: Option<String>,
     pub chainID: String,
     pub version: u32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub options: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guardian: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relayer: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub signature: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guardianSignature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relayerSignature: Option<String>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl TransactionRequest {
+    /// Serialize the transaction the way it is signed: every field except the signatures,
+    /// in protocol field order, with the data field base64 encoded
+    pub fn serialize_for_signing(&self) -> AppResult<Vec<u8>> {
+        let unsigned = TransactionRequest {
+            signature: String::new(),
+            guardianSignature: None,
+            relayerSignature: None,
+            ..self.clone()
+        };
+        
+        Ok(serde_json::to_vec(&unsigned)?)
+    }
+    
+    /// Check that the signature was produced by the key behind the sender address
+    pub fn verify_signature(&self) -> AppResult<bool> {
+        if self.signature.is_empty() {
+            return Ok(false);
+        }
+        
+        self.verify_signed_by(&self.sender, &self.signature)
+    }
+    
+    /// Mark the transaction as guarded, to be co-signed by the sender's guardian.
+    /// Must be called before the sender signs, since the guardian is part of the signed payload.
+    pub fn set_guardian(&mut self, guardian: &str) {
+        self.version = self.version.max(TX_VERSION_WITH_OPTIONS);
+        self.options |= TX_OPTION_GUARDED;
+        self.guardian = Some(guardian.to_string());
+    }
+    
+    /// Whether the transaction requires a guardian co-signature
+    pub fn is_guarded(&self) -> bool {
+        self.options & TX_OPTION_GUARDED != 0 && self.guardian.is_some()
+    }
+    
+    /// Check that the guardian signature was produced by the key behind the guardian address
+    pub fn verify_guardian_signature(&self) -> AppResult<bool> {
+        match (&self.guardian, &self.guardianSignature) {
+            (Some(guardian), Some(signature)) => self.verify_signed_by(guardian, signature),
+            _ => Ok(false),
+        }
+    }
+    
+    /// Turn the transaction into a relayed (v3) transaction paid for by the relayer.
+    /// Must be called before the sender signs, since the relayer is part of the signed payload.
+    pub fn set_relayer(&mut self, relayer: &str, relayer_gas_limit: u64) {
+        self.relayer = Some(relayer.to_string());
+        // The relayer pays for its own move balance on top of the inner transaction gas
+        self.gasLimit += relayer_gas_limit;
+    }
+    
+    /// Check that the relayer signature was produced by the key behind the relayer address
+    pub fn verify_relayer_signature(&self) -> AppResult<bool> {
+        match (&self.relayer, &self.relayerSignature) {
+            (Some(relayer), Some(signature)) => self.verify_signed_by(relayer, signature),
+            _ => Ok(false),
+        }
+    }
+    
+    fn verify_signed_by(&self, address: &str, signature: &str) -> AppResult<bool> {
+        let public_key: [u8; 32] = crate::utils::address_to_public_key(address)?
+            .try_into()
+            .map_err(|_| AppError::Validation(format!("Invalid signer address: {}", address)))?;
+        let verifying_key = VerifyingKey::from_bytes(&public_key)
+            .map_err(|e| AppError::Validation(format!("Invalid signer public key: {}", e)))?;
+        
+        let signature_bytes = hex::decode(signature)
+            .map_err(|e| AppError::Validation(format!("Invalid signature encoding: {}", e)))?;
+        let signature = Signature::from_slice(&signature_bytes)
+            .map_err(|e| AppError::Validation(format!("Invalid signature: {}", e)))?;
+        
+        let message = self.serialize_for_signing()?;
+        Ok(verifying_key.verify(&message, &signature).is_ok())
+    }
+}
+
+// The gateway expects transaction data base64 encoded, while we keep it as plain text in memory
+mod base64_data {
+    use base64::{Engine as _, engine::general_purpose};
+    use serde::{Deserialize, Deserializer, Serializer};
+    
+    pub fn serialize<S: Serializer>(data: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+        match data {
+            Some(data) => serializer.serialize_str(&general_purpose::STANDARD.encode(data)),
+            None => serializer.serialize_none(),
+        }
+    }
+    
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+        let encoded = Option::<String>::deserialize(deserializer)?;
+        encoded
+            .map(|encoded| {
+                let bytes = general_purpose::STANDARD.decode(encoded).map_err(serde::de::Error::custom)?;
+                String::from_utf8(bytes).map_err(serde::de::Error::custom)
+            })
+            .transpose()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -57,6 +217,18 @@ struct TransactionData {
     txHash: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct MultipleTransactionsResponse {
+    data: MultipleTransactionsData,
+}
+
+#[derive(Debug, Deserialize)]
+struct MultipleTransactionsData {
+    numOfSentTxs: usize,
+    // Hashes keyed by the index of the transaction in the request; rejected ones are missing
+    txsHashes: HashMap<String, String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct NetworkStatusResponse {
     data: NetworkStatusData,
@@ -68,11 +240,179 @@ struct NetworkStatusData {
 }
 
 #[derive(Debug, Deserialize)]
-struct NetworkStatus {
-    erd_current_round: u64,
-    erd_epoch_number: u64,
-    erd_highest_final_nonce: u64,
-    erd_nonce: u64,
+pub struct NetworkStatus {
+    pub erd_current_round: u64,
+    pub erd_epoch_number: u64,
+    pub erd_highest_final_nonce: u64,
+    pub erd_nonce: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsdtTokensResponse {
+    data: EsdtTokensData,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsdtTokensData {
+    esdts: HashMap<String, EsdtToken>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsdtTokenResponse {
+    data: EsdtTokenData,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsdtTokenData {
+    #[serde(rename = "tokenData")]
+    token_data: EsdtToken,
+}
+
+/// Fungible or non-fungible ESDT balance held by an account
+#[derive(Debug, Clone, Deserialize)]
+pub struct EsdtToken {
+    /// Token identifier, including the hex nonce suffix for NFTs and SFTs
+    #[serde(rename = "tokenIdentifier")]
+    pub token_identifier: String,
+    pub balance: String,
+    #[serde(default)]
+    pub nonce: u64,
+    /// Base64 encoded NFT attributes
+    #[serde(default)]
+    pub attributes: Option<String>,
+}
+
+impl EsdtToken {
+    /// Collection identifier, without the nonce suffix
+    pub fn collection(&self) -> &str {
+        if self.nonce == 0 {
+            return &self.token_identifier;
+        }
+        self.token_identifier
+            .rsplit_once('-')
+            .map(|(collection, _)| collection)
+            .unwrap_or(&self.token_identifier)
+    }
+    
+    /// Decoded NFT attributes
+    pub fn attribute_bytes(&self) -> AppResult<Vec<u8>> {
+        general_purpose::STANDARD
+            .decode(self.attributes.as_deref().unwrap_or_default())
+            .map_err(|e| AppError::Blockchain(format!("Invalid attributes of {}: {}", self.token_identifier, e)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidatorStatisticsResponse {
+    data: ValidatorStatisticsData,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidatorStatisticsData {
+    statistics: HashMap<String, ValidatorStatistics>,
+}
+
+/// Consensus performance of a validator node since genesis
+#[derive(Debug, Clone, Deserialize)]
+pub struct ValidatorStatistics {
+    #[serde(default)]
+    pub rating: f64,
+    #[serde(default, rename = "totalNumValidatorSuccess")]
+    pub total_validator_success: u64,
+    #[serde(default, rename = "totalNumValidatorFailure")]
+    pub total_validator_failure: u64,
+    #[serde(default, rename = "validatorStatus")]
+    pub validator_status: String,
+}
+
+#[derive(Debug, Serialize)]
+struct VmQueryRequest<'a> {
+    scAddress: &'a str,
+    funcName: &'a str,
+    args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VmQueryResponse {
+    data: VmQueryData,
+}
+
+#[derive(Debug, Deserialize)]
+struct VmQueryData {
+    data: VmQueryResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct VmQueryResult {
+    #[serde(default)]
+    returnData: Option<Vec<Option<String>>>,
+    returnCode: String,
+    #[serde(default)]
+    returnMessage: String,
+}
+
+/// Shard ID of the metachain, which notarizes the blocks of all shards
+pub const METACHAIN_SHARD_ID: u32 = 4294967295;
+
+#[derive(Debug, Deserialize)]
+struct HyperblockResponse {
+    data: HyperblockData,
+}
+
+#[derive(Debug, Deserialize)]
+struct HyperblockData {
+    hyperblock: Hyperblock,
+}
+
+/// A metachain block together with the transactions of the shard blocks it notarizes
+#[derive(Debug, Clone, Deserialize)]
+pub struct Hyperblock {
+    pub nonce: u64,
+    pub round: u64,
+    pub hash: String,
+    pub epoch: u64,
+    #[serde(default)]
+    pub timestamp: u64,
+    #[serde(default)]
+    pub transactions: Vec<HyperblockTransaction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HyperblockTransaction {
+    pub hash: String,
+    #[serde(default, rename = "type")]
+    pub tx_type: String,
+    #[serde(default)]
+    pub nonce: u64,
+    #[serde(default)]
+    pub value: String,
+    pub sender: String,
+    pub receiver: String,
+    /// Base64 encoded transaction data
+    #[serde(default)]
+    pub data: Option<String>,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub logs: Option<TransactionLogs>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransactionLogs {
+    #[serde(default)]
+    pub events: Vec<TransactionEvent>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransactionEvent {
+    /// Address of the contract or account that emitted the event
+    pub address: String,
+    pub identifier: String,
+    /// Base64 encoded topics
+    #[serde(default)]
+    pub topics: Option<Vec<String>>,
+    #[serde(default)]
+    pub data: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -80,6 +420,19 @@ pub struct NetworkConfig {
     pub chain_id: String,
     pub min_gas_price: u64,
     pub min_gas_limit: u64,
+    pub gas_per_data_byte: u64,
+    pub gas_price_modifier: f64,
+}
+
+impl NetworkConfig {
+    /// Fee of a transaction at the minimum gas price, in EGLD. The gas moving the value and data
+    /// is charged at the full price, the rest of the gas limit at the price times the modifier
+    pub fn transaction_fee(&self, gas_limit: u64, data_len: usize) -> f64 {
+        let move_gas = (self.min_gas_limit + self.gas_per_data_byte * data_len as u64).min(gas_limit);
+        let execution_gas = gas_limit - move_gas;
+        let gas_price = self.min_gas_price as f64;
+        (move_gas as f64 * gas_price + execution_gas as f64 * gas_price * self.gas_price_modifier) / 1e18
+    }
 }
 
 #[async_trait]
@@ -88,6 +441,7 @@ pub trait BlockchainProvider {
     async fn get_balance(&self, address: &str) -> AppResult<String>;
     async fn get_nonce(&self, address: &str) -> AppResult<u64>;
     async fn send_transaction(&self, tx: TransactionRequest) -> AppResult<String>;
+    async fn send_transactions(&self, txs: &[TransactionRequest]) -> AppResult<HashMap<usize, String>>;
     async fn get_transaction_status(&self, tx_hash: &str) -> AppResult<TransactionStatus>;
     async fn get_network_status(&self) -> AppResult<NetworkStatus>;
     fn get_network_config(&self) -> NetworkConfig;
@@ -109,7 +463,193 @@ impl MultiversXClient {
             chain_id: self.chain_id.clone(),
             min_gas_price: self.min_gas_price,
             min_gas_limit: self.min_gas_limit,
+            gas_per_data_byte: GAS_PER_DATA_BYTE,
+            gas_price_modifier: GAS_PRICE_MODIFIER,
+        }
+    }
+    
+    /// Build a fully-populated unsigned transaction, using the sender's current account nonce.
+    /// Transactions from guarded accounts are built for co-signing by the active guardian.
+    pub async fn build_transaction(
+        &self,
+        sender: &str,
+        receiver: &str,
+        value: String,
+        data: Option<String>,
+        gas_limit: Option<u64>,
+    ) -> AppResult<TransactionRequest> {
+        let account = self.get_account(sender).await?;
+        
+        let mut tx = TransactionRequest {
+            nonce: account.nonce,
+            value,
+            receiver: receiver.to_string(),
+            sender: sender.to_string(),
+            gasPrice: self.min_gas_price,
+            gasLimit: gas_limit.unwrap_or(self.min_gas_limit),
+            data,
+            chainID: self.chain_id.clone(),
+            version: 1,
+            options: 0,
+            guardian: None,
+            relayer: None,
+            signature: String::new(),
+            guardianSignature: None,
+            relayerSignature: None,
+        };
+        
+        if account.is_guarded {
+            let guardian = self.get_guardian_data(sender).await?
+                .active_guardian
+                .ok_or_else(|| AppError::Blockchain(format!("Account {} is guarded but has no active guardian", sender)))?;
+            
+            tx.set_guardian(&guardian.address);
+            // Guardian co-signing costs an extra move balance
+            tx.gasLimit += self.min_gas_limit;
+        }
+        
+        Ok(tx)
+    }
+    
+    /// Get the status of the metachain, whose highest final nonce is the latest final hyperblock
+    pub async fn get_metachain_status(&self) -> AppResult<NetworkStatus> {
+        let url = format!("{}/network/status/{}", self.gateway_url, METACHAIN_SHARD_ID);
+        debug!("Fetching metachain status from {}", url);
+        
+        let response = self.client.get(&url).send().await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("Failed to get metachain status: {}", error_text);
+            return Err(AppError::Blockchain(format!("Failed to get metachain status: {}", error_text)));
+        }
+        
+        let status_response: NetworkStatusResponse = response.json().await?;
+        Ok(status_response.data.status)
+    }
+    
+    /// Get a hyperblock with the logs of its transactions
+    pub async fn get_hyperblock(&self, nonce: u64) -> AppResult<Hyperblock> {
+        let url = format!("{}/hyperblock/by-nonce/{}?withLogs=true", self.gateway_url, nonce);
+        debug!("Fetching hyperblock from {}", url);
+        
+        let response = self.client.get(&url).send().await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("Failed to get hyperblock {}: {}", nonce, error_text);
+            return Err(AppError::Blockchain(format!("Failed to get hyperblock {}: {}", nonce, error_text)));
+        }
+        
+        let hyperblock_response: HyperblockResponse = response.json().await?;
+        Ok(hyperblock_response.data.hyperblock)
+    }
+    
+    /// Run a read-only smart contract view, with hex encoded arguments, returning the raw results
+    pub async fn query_vm(&self, sc_address: &str, function: &str, args: Vec<String>) -> AppResult<Vec<Vec<u8>>> {
+        let url = format!("{}/vm-values/query", self.gateway_url);
+        debug!("Querying {} on {}", function, sc_address);
+        
+        let request = VmQueryRequest {
+            scAddress: sc_address,
+            funcName: function,
+            args,
+        };
+        let response = self.client.post(&url).json(&request).send().await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("Failed to query {}: {}", function, error_text);
+            return Err(AppError::Blockchain(format!("Failed to query {}: {}", function, error_text)));
         }
+        
+        let query_response: VmQueryResponse = response.json().await?;
+        let result = query_response.data.data;
+        
+        if result.returnCode != "ok" {
+            return Err(AppError::Blockchain(format!(
+                "Query {} on {} failed: {} {}",
+                function, sc_address, result.returnCode, result.returnMessage
+            )));
+        }
+        
+        result.returnData
+            .unwrap_or_default()
+            .into_iter()
+            .map(|value| {
+                general_purpose::STANDARD
+                    .decode(value.unwrap_or_default())
+                    .map_err(|e| AppError::Blockchain(format!("Invalid query result encoding: {}", e)))
+            })
+            .collect()
+    }
+    
+    /// Get all ESDT tokens, including NFTs and SFTs, held by an account
+    pub async fn get_esdt_tokens(&self, address: &str) -> AppResult<Vec<EsdtToken>> {
+        let url = format!("{}/address/{}/esdt", self.gateway_url, address);
+        debug!("Fetching ESDT tokens from {}", url);
+        
+        let response = self.client.get(&url).send().await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("Failed to get ESDT tokens: {}", error_text);
+            return Err(AppError::Blockchain(format!("Failed to get ESDT tokens: {}", error_text)));
+        }
+        
+        let tokens_response: EsdtTokensResponse = response.json().await?;
+        Ok(tokens_response.data.esdts.into_values().collect())
+    }
+    
+    /// Get a single NFT or SFT held by an account
+    pub async fn get_nft(&self, address: &str, collection: &str, nonce: u64) -> AppResult<EsdtToken> {
+        let url = format!("{}/address/{}/nft/{}/nonce/{}", self.gateway_url, address, collection, nonce);
+        debug!("Fetching NFT from {}", url);
+        
+        let response = self.client.get(&url).send().await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("Failed to get NFT: {}", error_text);
+            return Err(AppError::Blockchain(format!("Failed to get NFT: {}", error_text)));
+        }
+        
+        let token_response: EsdtTokenResponse = response.json().await?;
+        Ok(token_response.data.token_data)
+    }
+    
+    /// Get the consensus statistics of all validators, keyed by hex BLS key
+    pub async fn get_validator_statistics(&self) -> AppResult<HashMap<String, ValidatorStatistics>> {
+        let url = format!("{}/validator/statistics", self.gateway_url);
+        debug!("Fetching validator statistics from {}", url);
+        
+        let response = self.client.get(&url).send().await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("Failed to get validator statistics: {}", error_text);
+            return Err(AppError::Blockchain(format!("Failed to get validator statistics: {}", error_text)));
+        }
+        
+        let statistics_response: ValidatorStatisticsResponse = response.json().await?;
+        Ok(statistics_response.data.statistics)
+    }
+    
+    /// Get the guardian setup of an account
+    pub async fn get_guardian_data(&self, address: &str) -> AppResult<GuardianData> {
+        let url = format!("{}/address/{}/guardian-data", self.gateway_url, address);
+        debug!("Fetching guardian data from {}", url);
+        
+        let response = self.client.get(&url).send().await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("Failed to get guardian data: {}", error_text);
+            return Err(AppError::Blockchain(format!("Failed to get guardian data: {}", error_text)));
+        }
+        
+        let guardian_response: GuardianDataResponse = response.json().await?;
+        Ok(guardian_response.data.guardian_data)
     }
 }
 
@@ -158,6 +698,31 @@ impl BlockchainProvider for MultiversXClient {
         Ok(tx_response.data.txHash)
     }
 
+    async fn send_transactions(&self, txs: &[TransactionRequest]) -> AppResult<HashMap<usize, String>> {
+        let url = format!("{}/transaction/send-multiple", self.gateway_url);
+        debug!("Sending {} transactions to {}", txs.len(), url);
+
+        let response = self.client.post(&url).json(txs).send().await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("Failed to send transactions: {}", error_text);
+            return Err(AppError::Transaction(format!("Failed to send transactions: {}", error_text)));
+        }
+
+        let multiple_response: MultipleTransactionsResponse = response.json().await?;
+        info!("Sent {} of {} transactions", multiple_response.data.numOfSentTxs, txs.len());
+        
+        let mut hashes = HashMap::new();
+        for (index, hash) in multiple_response.data.txsHashes {
+            let index = index.parse::<usize>()
+                .map_err(|e| AppError::Blockchain(format!("Invalid transaction index in response: {}", e)))?;
+            hashes.insert(index, hash);
+        }
+        
+        Ok(hashes)
+    }
+
     async fn get_transaction_status(&self, tx_hash: &str) -> AppResult<TransactionStatus> {
         let url = format!("{}/transaction/{}", self.gateway_url, tx_hash);
         debug!("Checking transaction status from {}", url);
@@ -217,6 +782,8 @@ impl BlockchainProvider for MultiversXClient {
             chain_id: self.chain_id.clone(),
             min_gas_price: self.min_gas_price,
             min_gas_limit: self.min_gas_limit,
+            gas_per_data_byte: GAS_PER_DATA_BYTE,
+            gas_price_modifier: GAS_PRICE_MODIFIER,
         }
     }
 }
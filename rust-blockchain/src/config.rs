@@ -21,12 +21,21 @@ pub struct BlockchainConfig {
     pub chain_id: String,
     pub min_gas_price: u64,
     pub min_gas_limit: u64,
+    pub relayer_private_key: Option<String>,
+    pub compound_private_key: Option<String>,
+    pub guardian_private_key: Option<String>,
+    pub guardian_totp_secret: Option<String>,
+    pub protocol_definitions_dir: String,
+    pub price_fixture_path: Option<String>,
+    pub risk_config_path: Option<String>,
+    pub maiar_farms_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ApiConfig {
     pub jwt_secret: String,
     pub token_expiration: u64, // in seconds
+    pub admin_api_key: Option<String>,
 }
 
 impl AppConfig {
@@ -42,14 +51,27 @@ impl AppConfig {
                 chain_id: "D".to_string(),
                 min_gas_price: 1000000000,
                 min_gas_limit: 50000,
+                relayer_private_key: env::var("RELAYER_PRIVATE_KEY").ok(),
+                compound_private_key: env::var("COMPOUND_PRIVATE_KEY").ok(),
+                // Local 2FA guardian, for testing guarded accounts on devnet
+                guardian_private_key: env::var("GUARDIAN_PRIVATE_KEY").ok(),
+                guardian_totp_secret: env::var("GUARDIAN_TOTP_SECRET").ok(),
+                protocol_definitions_dir: env::var("PROTOCOL_DEFINITIONS_DIR").unwrap_or_else(|_| "protocols".to_string()),
+                // Local token prices, used for tokens xExchange can't price
+                price_fixture_path: env::var("PRICE_FIXTURE_PATH").ok(),
+                // Factor weights and protocol facts for risk scoring
+                risk_config_path: env::var("RISK_CONFIG_PATH").ok(),
+                // xExchange farm and metastaking contracts to follow
+                maiar_farms_path: env::var("MAIAR_FARMS_PATH").ok(),
             },
             api: ApiConfig {
                 jwt_secret: env::var("JWT_SECRET").unwrap_or_else(|_| "development_secret_key".to_string()),
                 token_expiration: 86400, // 24 hours
+                admin_api_key: env::var("ADMIN_API_KEY").ok(),
             },
         }
     }
-
+    
     pub fn production() -> Self {
         Self {
             database: DatabaseConfig {
@@ -62,6 +84,14 @@ impl AppConfig {
                 chain_id: env::var("CHAIN_ID").unwrap_or_else(|_| "1".to_string()),
                 min_gas_price: 1000000000,
                 min_gas_limit: 50000,
+                relayer_private_key: env::var("RELAYER_PRIVATE_KEY").ok(),
+                compound_private_key: env::var("COMPOUND_PRIVATE_KEY").ok(),
+                guardian_private_key: None,
+                guardian_totp_secret: None,
+                protocol_definitions_dir: env::var("PROTOCOL_DEFINITIONS_DIR").unwrap_or_else(|_| "protocols".to_string()),
+                price_fixture_path: None,
+                risk_config_path: env::var("RISK_CONFIG_PATH").ok(),
+                maiar_farms_path: env::var("MAIAR_FARMS_PATH").ok(),
             },
             api: ApiConfig {
                 jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set in production"),
@@ -69,6 +99,7 @@ impl AppConfig {
                     .ok()
                     .and_then(|v| v.parse().ok())
                     .unwrap_or(3600), // 1 hour
+                admin_api_key: env::var("ADMIN_API_KEY").ok(),
             },
         }
     }
@@ -0,0 +1,348 @@
+//! Backtesting for ElizaOS
+//!
+//! Replays a history of pool APYs, TVLs and token prices through the allocation and
+//! rebalancing logic of the yield optimizer, charging gas and slippage on every move.
+
+use crate::elizaos::yield_optimizer::{
+    allocation_deviation, RiskProfile, YieldOpportunity, YieldOptimizer, REBALANCE_DEVIATION,
+    REBALANCE_OPPORTUNITIES,
+};
+use crate::error::{AppError, AppResult};
+use crate::models::backtest::{BacktestDataset, BacktestPool, BacktestSnapshot};
+use crate::models::position::PositionStrategy;
+use crate::services::price_ticker;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::{info, debug};
+
+/// Changes smaller than this, in EGLD, don't count as a move into or out of a pool
+const MIN_MOVE: f64 = 1e-9;
+
+/// Strategy, costs and rebalancing rules of a backtest
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    /// Strategy the allocations are computed for
+    pub strategy: PositionStrategy,
+    /// Capital invested at the start, in EGLD
+    pub initial_capital: f64,
+    /// Days between checks of the allocation against the optimal one
+    pub rebalance_interval_days: i64,
+    /// Gas paid for each pool entered, exited or resized, in EGLD
+    pub gas_cost: f64,
+    /// Share of every amount moved into a pool lost to slippage and swap fees
+    pub slippage: f64,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            strategy: PositionStrategy::Balanced,
+            initial_capital: 100.0,
+            rebalance_interval_days: 7,
+            gas_cost: 0.002,
+            slippage: 0.003,
+        }
+    }
+}
+
+/// Portfolio at one snapshot of a backtest
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestStep {
+    pub date: DateTime<Utc>,
+    /// Portfolio value, in EGLD
+    pub value: f64,
+    /// Whether the portfolio was rebalanced at this snapshot
+    pub rebalanced: bool,
+}
+
+/// Returns, risk and costs of a strategy over a backtest
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestReport {
+    pub strategy: PositionStrategy,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Capital invested at the start, in EGLD
+    pub initial_value: f64,
+    /// Portfolio value at the end, in EGLD
+    pub final_value: f64,
+    /// Return over the whole period, as a fraction
+    pub total_return: f64,
+    /// Compounded yearly return, as a fraction
+    pub annualized_return: f64,
+    /// Annualized standard deviation of the returns between snapshots
+    pub volatility: f64,
+    /// Annualized return over volatility, without a risk-free rate; None without volatility
+    pub sharpe_ratio: Option<f64>,
+    /// Largest drop from a previous peak, as a fraction
+    pub max_drawdown: f64,
+    /// Number of rebalances, not counting the initial allocation
+    pub rebalances: usize,
+    /// Value moved into pools over the average portfolio value, including the initial allocation
+    pub turnover: f64,
+    /// Gas paid, in EGLD
+    pub gas_paid: f64,
+    /// Value lost to slippage, in EGLD
+    pub slippage_paid: f64,
+    pub steps: Vec<BacktestStep>,
+}
+
+/// Portfolio being replayed, in EGLD
+#[derive(Debug, Default)]
+struct Portfolio {
+    /// Value held in each pool by "protocol_id:pool_id"
+    holdings: HashMap<String, f64>,
+    /// Value left unallocated, held as EGLD
+    idle: f64,
+    /// Value moved into pools so far
+    moved: f64,
+    gas_paid: f64,
+    slippage_paid: f64,
+}
+
+impl Portfolio {
+    fn value(&self) -> f64 {
+        self.holdings.values().sum::<f64>() + self.idle
+    }
+    
+    /// Move to a target allocation, paying gas for every pool whose balance changes and
+    /// slippage on the value moved into pools; costs reduce the target proportionally
+    fn rebalance(&mut self, target: HashMap<String, f64>, config: &BacktestConfig) {
+        let value = self.value();
+        
+        let mut changed_pools = 0;
+        let mut moved_in = 0.0;
+        for key in self.holdings.keys().chain(target.keys().filter(|k| !self.holdings.contains_key(*k))) {
+            let current = self.holdings.get(key).cloned().unwrap_or(0.0);
+            let wanted = target.get(key).cloned().unwrap_or(0.0);
+            if (wanted - current).abs() > MIN_MOVE {
+                changed_pools += 1;
+            }
+            moved_in += (wanted - current).max(0.0);
+        }
+        
+        let gas = changed_pools as f64 * config.gas_cost;
+        let slippage = moved_in * config.slippage;
+        let remaining = (value - gas - slippage).max(0.0);
+        
+        // Scale the target to what is left after costs; the rest of the value stays idle
+        let scale = if value > 0.0 { remaining / value } else { 0.0 };
+        self.holdings = target.into_iter()
+            .filter(|(_, amount)| *amount > MIN_MOVE)
+            .map(|(key, amount)| (key, amount * scale))
+            .collect();
+        self.idle = (remaining - self.holdings.values().sum::<f64>()).max(0.0);
+        
+        self.moved += moved_in;
+        self.gas_paid += gas;
+        self.slippage_paid += slippage;
+    }
+}
+
+/// Backtester running allocation strategies against a dataset
+pub struct Backtester {
+    /// Optimizer without protocols, used only for its allocation logic
+    optimizer: YieldOptimizer,
+    config: BacktestConfig,
+}
+
+impl Backtester {
+    /// Create a new backtester
+    pub fn new(config: BacktestConfig) -> Self {
+        Self {
+            optimizer: YieldOptimizer::new(Vec::new()),
+            config,
+        }
+    }
+    
+    /// Replay the dataset from its first snapshot to its last
+    pub async fn run(&self, dataset: &BacktestDataset) -> AppResult<BacktestReport> {
+        if self.config.initial_capital <= 0.0 {
+            return Err(AppError::Validation("Backtest capital must be positive".to_string()));
+        }
+        
+        let mut snapshots: Vec<&BacktestSnapshot> = dataset.snapshots.iter().collect();
+        snapshots.sort_by_key(|s| s.date);
+        if snapshots.len() < 2 {
+            return Err(AppError::Validation("A backtest needs at least two snapshots".to_string()));
+        }
+        
+        let pools: HashMap<String, &BacktestPool> = dataset.pools.iter().map(|p| (p.key(), p)).collect();
+        
+        info!("Backtesting {:?} strategy over {} snapshots from {} to {}",
+              self.config.strategy, snapshots.len(), snapshots[0].date, snapshots[snapshots.len() - 1].date);
+        
+        // Start fully idle and move into the optimal allocation of the first snapshot
+        let mut portfolio = Portfolio {
+            idle: self.config.initial_capital,
+            ..Portfolio::default()
+        };
+        let target = self.optimal_allocation(snapshots[0], &pools, portfolio.value()).await?;
+        portfolio.rebalance(target, &self.config);
+        
+        let mut steps = vec![BacktestStep {
+            date: snapshots[0].date,
+            value: portfolio.value(),
+            rebalanced: true,
+        }];
+        let mut rebalances = 0;
+        let mut last_check = snapshots[0].date;
+        
+        for window in snapshots.windows(2) {
+            let (previous, current) = (window[0], window[1]);
+            let days = (current.date - previous.date).num_seconds() as f64 / 86_400.0;
+            
+            // Pools earn the APY of the start of the period and follow the prices of their tokens
+            for (key, amount) in portfolio.holdings.iter_mut() {
+                let apy = previous.pools.get(key).map(|s| s.apy).unwrap_or(0.0);
+                let tokens = pools.get(key).map(|p| p.tokens.as_slice()).unwrap_or(&[]);
+                *amount *= (1.0 + apy / 100.0 * days / 365.0) * price_change(tokens, previous, current);
+            }
+            
+            let mut rebalanced = false;
+            if (current.date - last_check).num_days() >= self.config.rebalance_interval_days {
+                last_check = current.date;
+                
+                let value = portfolio.value();
+                let target = self.optimal_allocation(current, &pools, value).await?;
+                if allocation_deviation(&portfolio.holdings, &target, value) > REBALANCE_DEVIATION {
+                    debug!("Rebalancing {:.4} EGLD on {}", value, current.date);
+                    portfolio.rebalance(target, &self.config);
+                    rebalances += 1;
+                    rebalanced = true;
+                }
+            }
+            
+            steps.push(BacktestStep {
+                date: current.date,
+                value: portfolio.value(),
+                rebalanced,
+            });
+        }
+        
+        Ok(self.report(steps, rebalances, &portfolio))
+    }
+    
+    /// Optimal allocation of a portfolio value at a snapshot, from the pools the strategy accepts
+    async fn optimal_allocation(
+        &self,
+        snapshot: &BacktestSnapshot,
+        pools: &HashMap<String, &BacktestPool>,
+        value: f64,
+    ) -> AppResult<HashMap<String, f64>> {
+        let risk_profile = RiskProfile::for_strategy(&self.config.strategy);
+        
+        let mut opportunities: Vec<YieldOpportunity> = snapshot.pools.iter()
+            .filter_map(|(key, state)| {
+                let pool = pools.get(key)?;
+                let (volatility, liquidity, security) = YieldOptimizer::score_opportunity(&pool.risk, None, state.tvl);
+                Some(YieldOpportunity {
+                    protocol_id: pool.protocol_id.clone(),
+                    protocol_name: pool.protocol_id.clone(),
+                    pool_id: pool.pool_id.clone(),
+                    pool_name: pool.name.clone(),
+                    apy: state.apy,
+                    staking_apy: 0.0,
+                    tvl: state.tvl,
+                    tvl_denomination: "USD".to_string(),
+                    risk: pool.risk.clone(),
+                    risk_score: pool.risk_score,
+                    tokens: pool.tokens.clone(),
+                    impermanent_loss: None,
+                    volatility,
+                    liquidity,
+                    security,
+                })
+            })
+            .filter(|opp| risk_profile.accepts(opp))
+            .collect();
+        
+        // Same order and cut as a live scan; ties are broken by pool so runs are repeatable
+        opportunities.sort_by(|a, b| {
+            b.apy.partial_cmp(&a.apy)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| (&a.protocol_id, &a.pool_id).cmp(&(&b.protocol_id, &b.pool_id)))
+        });
+        opportunities.truncate(REBALANCE_OPPORTUNITIES);
+        
+        self.optimizer.calculate_optimal_allocation(&self.config.strategy, value, &opportunities).await
+    }
+    
+    /// Compute the returns, risk and costs of a replayed portfolio
+    fn report(&self, steps: Vec<BacktestStep>, rebalances: usize, portfolio: &Portfolio) -> BacktestReport {
+        let first = &steps[0];
+        let last = &steps[steps.len() - 1];
+        let initial_value = self.config.initial_capital;
+        let final_value = last.value;
+        
+        let total_days = (last.date - first.date).num_seconds() as f64 / 86_400.0;
+        let total_return = final_value / initial_value - 1.0;
+        let annualized_return = if total_days > 0.0 && final_value > 0.0 {
+            (final_value / initial_value).powf(365.0 / total_days) - 1.0
+        } else {
+            total_return
+        };
+        
+        // Returns between snapshots, annualized by the average snapshot spacing
+        let returns: Vec<f64> = steps.windows(2)
+            .filter(|w| w[0].value > 0.0)
+            .map(|w| w[1].value / w[0].value - 1.0)
+            .collect();
+        let periods_per_year = if total_days > 0.0 { 365.0 * returns.len() as f64 / total_days } else { 0.0 };
+        let mean = returns.iter().sum::<f64>() / returns.len().max(1) as f64;
+        let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / returns.len().max(1) as f64;
+        let volatility = variance.sqrt() * periods_per_year.sqrt();
+        let sharpe_ratio = (volatility > 0.0).then(|| annualized_return / volatility);
+        
+        let mut peak = initial_value;
+        let mut max_drawdown: f64 = 0.0;
+        for step in &steps {
+            peak = peak.max(step.value);
+            if peak > 0.0 {
+                max_drawdown = max_drawdown.max(1.0 - step.value / peak);
+            }
+        }
+        
+        let average_value = steps.iter().map(|s| s.value).sum::<f64>() / steps.len() as f64;
+        let turnover = if average_value > 0.0 { portfolio.moved / average_value } else { 0.0 };
+        
+        BacktestReport {
+            strategy: self.config.strategy.clone(),
+            start: first.date,
+            end: last.date,
+            initial_value,
+            final_value,
+            total_return,
+            annualized_return,
+            volatility,
+            sharpe_ratio,
+            max_drawdown,
+            rebalances,
+            turnover,
+            gas_paid: portfolio.gas_paid,
+            slippage_paid: portfolio.slippage_paid,
+            steps,
+        }
+    }
+}
+
+/// Change in the EGLD value of a pool's tokens between two snapshots: the geometric mean of
+/// their USD price changes, as for a constant product pool, over the change of the EGLD price;
+/// tokens without prices in both snapshots are taken as unchanged
+fn price_change(tokens: &[String], previous: &BacktestSnapshot, current: &BacktestSnapshot) -> f64 {
+    let ratio = |token: &str| {
+        let ticker = price_ticker(token);
+        match (previous.prices.get(&ticker), current.prices.get(&ticker)) {
+            (Some(before), Some(after)) if *before > 0.0 => after / before,
+            _ => 1.0,
+        }
+    };
+    
+    let usd_change = if tokens.is_empty() {
+        1.0
+    } else {
+        (tokens.iter().map(|t| ratio(t).ln()).sum::<f64>() / tokens.len() as f64).exp()
+    };
+    
+    usd_change / ratio("EGLD")
+}
\ No newline at end of file
@@ -0,0 +1,155 @@
+//! Protocol data cache for ElizaOS
+//!
+//! A concurrent cache shared between the protocol adapters and their background refreshers,
+//! with a time-to-live per key, a single fetch per key at a time, and stale values served
+//! while they are refreshed in the background.
+
+use crate::error::AppResult;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+/// Cache entry for protocol data
+#[derive(Clone)]
+struct CacheEntry {
+    /// When the data was last updated
+    last_updated: DateTime<Utc>,
+    /// The cached data
+    data: serde_json::Value,
+    /// Time-to-live in seconds
+    ttl: u64,
+}
+
+impl CacheEntry {
+    fn age(&self) -> Duration {
+        Utc::now() - self.last_updated
+    }
+    
+    fn is_fresh(&self) -> bool {
+        self.age() < Duration::seconds(self.ttl as i64)
+    }
+    
+    /// Expired entries are still served, and refreshed in the background, for another TTL
+    fn is_usable(&self) -> bool {
+        self.age() < Duration::seconds(2 * self.ttl as i64)
+    }
+}
+
+/// Concurrent cache of protocol data
+#[derive(Default)]
+pub struct ProtocolCache {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    /// One lock per key, held while the key is being fetched
+    fetch_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl ProtocolCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Get a value from the cache, fetching it on a miss
+    ///
+    /// Concurrent misses on the same key wait for a single fetch. An expired value is
+    /// returned as is while a background task fetches the new one.
+    pub async fn get_or_fetch<F, Fut>(
+        self: &Arc<Self>,
+        key: &str,
+        ttl: u64,
+        fetch: F,
+    ) -> AppResult<serde_json::Value>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = AppResult<serde_json::Value>> + Send + 'static,
+    {
+        if let Some(entry) = self.get_entry(key) {
+            if entry.is_fresh() {
+                return Ok(entry.data);
+            }
+            
+            if entry.is_usable() {
+                // Stale while revalidate; the refresh is skipped if another fetch is running
+                let cache = self.clone();
+                let key = key.to_string();
+                tokio::spawn(async move {
+                    if let Err(e) = cache.refresh(&key, ttl, fetch).await {
+                        warn!("Failed to refresh cache entry {}: {}", key, e);
+                    }
+                });
+                return Ok(entry.data);
+            }
+        }
+        
+        let lock = self.fetch_lock(key);
+        let _guard = lock.lock().await;
+        
+        // Another caller may have fetched the value while we waited
+        if let Some(entry) = self.get_entry(key) {
+            if entry.is_fresh() {
+                return Ok(entry.data);
+            }
+        }
+        
+        debug!("Cache miss for {}", key);
+        let data = fetch().await?;
+        self.insert(key, data.clone(), ttl);
+        Ok(data)
+    }
+    
+    /// Fetch a value and store it, unless the key is already being fetched
+    pub async fn refresh<F, Fut>(&self, key: &str, ttl: u64, fetch: F) -> AppResult<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = AppResult<serde_json::Value>>,
+    {
+        let lock = self.fetch_lock(key);
+        let _guard = match lock.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return Ok(()),
+        };
+        
+        let data = fetch().await?;
+        self.insert(key, data, ttl);
+        Ok(())
+    }
+    
+    /// Whether a key is missing or close enough to expiry to be refreshed ahead of time
+    pub fn needs_refresh(&self, key: &str) -> bool {
+        match self.get_entry(key) {
+            Some(entry) => entry.age() >= Duration::seconds(entry.ttl as i64 * 4 / 5),
+            None => true,
+        }
+    }
+    
+    /// Store a value
+    pub fn insert(&self, key: &str, data: serde_json::Value, ttl: u64) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key.to_string(), CacheEntry {
+            last_updated: Utc::now(),
+            data,
+            ttl,
+        });
+    }
+    
+    /// Remove every entry
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+    
+    /// Remove a specific entry
+    pub fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+    
+    fn get_entry(&self, key: &str) -> Option<CacheEntry> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+    
+    fn fetch_lock(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.fetch_locks.lock().unwrap();
+        locks.entry(key.to_string()).or_default().clone()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,243 @@
+//! Chain Watcher for ElizaOS
+//!
+//! Follows final hyperblocks from the gateway and publishes the transactions and
+//! events that touch registered protocol contracts or watched user addresses.
+
+use crate::blockchain::{Hyperblock, HyperblockTransaction, MultiversXClient, TransactionEvent};
+use crate::error::AppResult;
+use crate::utils::address_to_public_key;
+use base64::{Engine as _, engine::general_purpose};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tracing::{info, debug, warn};
+
+/// A transaction from a hyperblock that touches something we watch
+#[derive(Debug, Clone)]
+pub struct WatchedTransaction {
+    /// Nonce of the hyperblock containing the transaction
+    pub block_nonce: u64,
+    /// Hash of the hyperblock containing the transaction
+    pub block_hash: String,
+    /// The transaction itself
+    pub transaction: HyperblockTransaction,
+    /// Protocols whose contracts the transaction or its events touch
+    pub protocol_ids: Vec<String>,
+    /// Watched user addresses involved in the transaction or its events
+    pub users: Vec<String>,
+    /// Events emitted by watched contracts or mentioning watched users
+    pub events: Vec<TransactionEvent>,
+}
+
+/// Configuration for the chain watcher
+#[derive(Debug, Clone)]
+pub struct ChainWatcherConfig {
+    /// Delay between polls for new hyperblocks
+    pub poll_interval: Duration,
+    /// Maximum number of hyperblocks to catch up on in a single poll
+    pub max_blocks_per_poll: u64,
+    /// Capacity of the subscriber channel; slow subscribers miss older items
+    pub channel_capacity: usize,
+}
+
+impl Default for ChainWatcherConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(6), // one round
+            max_blocks_per_poll: 50,
+            channel_capacity: 1024,
+        }
+    }
+}
+
+/// Background watcher that follows hyperblocks and publishes matching transactions
+pub struct ChainWatcher {
+    blockchain_client: MultiversXClient,
+    config: ChainWatcherConfig,
+    /// Protocol contract addresses mapped to protocol IDs
+    contracts: RwLock<HashMap<String, String>>,
+    /// Watched user addresses mapped to their public keys, used to match event topics
+    users: RwLock<HashMap<String, Vec<u8>>>,
+    /// Next hyperblock nonce to process
+    next_nonce: RwLock<Option<u64>>,
+    sender: broadcast::Sender<WatchedTransaction>,
+    shutdown_tx: watch::Sender<bool>,
+}
+
+impl ChainWatcher {
+    /// Create a new chain watcher
+    pub fn new(blockchain_client: MultiversXClient, config: ChainWatcherConfig) -> Self {
+        let (sender, _) = broadcast::channel(config.channel_capacity);
+        let (shutdown_tx, _) = watch::channel(false);
+        
+        Self {
+            blockchain_client,
+            config,
+            contracts: RwLock::new(HashMap::new()),
+            users: RwLock::new(HashMap::new()),
+            next_nonce: RwLock::new(None),
+            sender,
+            shutdown_tx,
+        }
+    }
+    
+    /// Watch a protocol contract
+    pub fn watch_contract(&self, address: &str, protocol_id: &str) {
+        let mut contracts = self.contracts.write().unwrap();
+        contracts.insert(address.to_string(), protocol_id.to_string());
+    }
+    
+    /// Watch the given contracts of a protocol, replacing those watched for it before
+    pub fn set_protocol_contracts(&self, protocol_id: &str, addresses: &[String]) {
+        let mut contracts = self.contracts.write().unwrap();
+        contracts.retain(|_, id| id != protocol_id);
+        for address in addresses {
+            contracts.insert(address.clone(), protocol_id.to_string());
+        }
+    }
+    
+    /// Number of watched contracts
+    pub fn contract_count(&self) -> usize {
+        self.contracts.read().unwrap().len()
+    }
+    
+    /// Watch a user address
+    pub fn watch_address(&self, address: &str) -> AppResult<()> {
+        let public_key = address_to_public_key(address)?;
+        let mut users = self.users.write().unwrap();
+        users.insert(address.to_string(), public_key);
+        Ok(())
+    }
+    
+    /// Stop watching a user address
+    pub fn unwatch_address(&self, address: &str) {
+        let mut users = self.users.write().unwrap();
+        users.remove(address);
+    }
+    
+    /// Subscribe to watched transactions
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchedTransaction> {
+        self.sender.subscribe()
+    }
+    
+    /// Resume from a specific hyperblock nonce instead of the latest final one
+    pub fn start_from(&self, nonce: u64) {
+        let mut next_nonce = self.next_nonce.write().unwrap();
+        *next_nonce = Some(nonce);
+    }
+    
+    /// Start following hyperblocks in the background
+    pub fn start(self: Arc<Self>) -> JoinHandle<()> {
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        
+        tokio::spawn(async move {
+            info!("Chain watcher started");
+            
+            loop {
+                if let Err(e) = self.poll().await {
+                    warn!("Chain watcher poll failed: {}", e);
+                }
+                
+                tokio::select! {
+                    _ = tokio::time::sleep(self.config.poll_interval) => {},
+                    _ = shutdown_rx.changed() => break,
+                }
+            }
+            
+            info!("Chain watcher stopped");
+        })
+    }
+    
+    /// Stop the background watcher
+    pub fn stop(&self) {
+        let _ = self.shutdown_tx.send(true);
+    }
+    
+    /// Process all final hyperblocks since the last poll
+    async fn poll(&self) -> AppResult<()> {
+        let latest_nonce = self.blockchain_client.get_metachain_status().await?.erd_highest_final_nonce;
+        
+        let next_nonce = {
+            let next_nonce = self.next_nonce.read().unwrap();
+            next_nonce.unwrap_or(latest_nonce)
+        };
+        
+        let last_nonce = latest_nonce.min(next_nonce + self.config.max_blocks_per_poll - 1);
+        
+        for nonce in next_nonce..=last_nonce {
+            let hyperblock = self.blockchain_client.get_hyperblock(nonce).await?;
+            self.process_hyperblock(&hyperblock);
+            
+            // Only advance once the block has been fully processed, so failures are retried
+            let mut next_nonce = self.next_nonce.write().unwrap();
+            *next_nonce = Some(nonce + 1);
+        }
+        
+        Ok(())
+    }
+    
+    /// Publish the transactions of a hyperblock that match the watched contracts and users
+    fn process_hyperblock(&self, hyperblock: &Hyperblock) {
+        debug!("Processing hyperblock {} with {} transactions", hyperblock.nonce, hyperblock.transactions.len());
+        
+        let contracts = self.contracts.read().unwrap();
+        let users = self.users.read().unwrap();
+        
+        for transaction in &hyperblock.transactions {
+            let mut protocol_ids = HashSet::new();
+            let mut matched_users = HashSet::new();
+            
+            for address in [&transaction.sender, &transaction.receiver] {
+                if let Some(protocol_id) = contracts.get(address) {
+                    protocol_ids.insert(protocol_id.clone());
+                }
+                if users.contains_key(address) {
+                    matched_users.insert(address.clone());
+                }
+            }
+            
+            let mut events = Vec::new();
+            if let Some(logs) = &transaction.logs {
+                for event in &logs.events {
+                    let mut matched = false;
+                    
+                    if let Some(protocol_id) = contracts.get(&event.address) {
+                        protocol_ids.insert(protocol_id.clone());
+                        matched = true;
+                    }
+                    
+                    // Events reference accounts by public key in their topics
+                    for topic in event.topics.iter().flatten() {
+                        let Ok(bytes) = general_purpose::STANDARD.decode(topic) else { continue };
+                        for (address, public_key) in users.iter() {
+                            if *public_key == bytes {
+                                matched_users.insert(address.clone());
+                                matched = true;
+                            }
+                        }
+                    }
+                    
+                    if matched {
+                        events.push(event.clone());
+                    }
+                }
+            }
+            
+            if protocol_ids.is_empty() && matched_users.is_empty() {
+                continue;
+            }
+            
+            // Sending only fails when there are no subscribers
+            let _ = self.sender.send(WatchedTransaction {
+                block_nonce: hyperblock.nonce,
+                block_hash: hyperblock.hash.clone(),
+                transaction: transaction.clone(),
+                protocol_ids: protocol_ids.into_iter().collect(),
+                users: matched_users.into_iter().collect(),
+                events,
+            });
+        }
+    }
+}
\ No newline at end of file
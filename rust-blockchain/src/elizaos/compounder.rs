@@ -0,0 +1,345 @@
+//! Auto-compounder for ElizaOS
+//!
+//! Claims the rewards of a position, swaps them into the position's underlying tokens
+//! and deposits the proceeds back, when reinvesting them is projected to earn more
+//! than the gas it costs.
+
+use crate::blockchain::{BlockchainProvider, MultiversXClient};
+use crate::elizaos::protocol_adapter::ProtocolAdapter;
+use crate::elizaos::transaction_manager::{BatchCall, TransactionManager};
+use crate::error::{AppError, AppResult};
+use crate::models::position::Position;
+use crate::services::price_ticker;
+use crate::smart_contracts::protocol_interface::PendingReward;
+use crate::smart_contracts::router::{token_decimals, SwapRouter, DEFAULT_MAX_SLIPPAGE};
+use crate::wallet::Wallet;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, debug, warn};
+
+/// Gas budgeted for swapping one reward token, before its route is known
+const SWAP_GAS_ESTIMATE: u64 = 25_000_000;
+/// Data budgeted for a swap or deposit call, before its amounts are known
+const CALL_DATA_ESTIMATE: usize = 200;
+
+/// Configuration of the auto-compounder
+#[derive(Debug, Clone)]
+pub struct CompoundConfig {
+    /// Period over which the reinvested rewards must earn back the gas, in days, for positions
+    /// compounded by hand; scheduled positions have until their next compound
+    pub gain_horizon_days: f64,
+    /// Maximum slippage accepted when swapping rewards
+    pub max_slippage: f64,
+}
+
+impl Default for CompoundConfig {
+    fn default() -> Self {
+        Self {
+            gain_horizon_days: 365.0,
+            max_slippage: DEFAULT_MAX_SLIPPAGE,
+        }
+    }
+}
+
+/// Outcome of compounding a position
+#[derive(Debug, Clone)]
+pub struct CompoundResult {
+    pub position_id: String,
+    /// Pending rewards, valued in EGLD at the price of swapping them
+    pub rewards_value: f64,
+    /// Yield the reinvested rewards are projected to earn over the gain horizon, in EGLD
+    pub projected_gain: f64,
+    /// Gas for claiming, swapping and depositing, in EGLD
+    pub gas_cost: f64,
+    /// Whether any rewards were reinvested; false when the gain doesn't cover the gas
+    pub compounded: bool,
+    /// Hash of the confirmed claim, once the rewards were claimed
+    pub claim_tx: Option<String>,
+    /// Confirmed deposits back into the position, one per underlying token
+    pub deposits: Vec<CompoundDeposit>,
+    /// Hashes of the claim, swap and deposit transactions
+    pub tx_hashes: Vec<String>,
+}
+
+/// Rewards deposited back into a position as one of its underlying tokens
+#[derive(Debug, Clone)]
+pub struct CompoundDeposit {
+    pub token: String,
+    /// Amount deposited, in token units
+    pub amount: f64,
+    pub tx_hash: Option<String>,
+}
+
+/// Reinvests the rewards of positions
+pub struct AutoCompounder {
+    blockchain_client: MultiversXClient,
+    tx_manager: Arc<TransactionManager>,
+    swap_router: SwapRouter,
+    config: CompoundConfig,
+}
+
+impl AutoCompounder {
+    /// Create a new auto-compounder
+    pub fn new(
+        blockchain_client: MultiversXClient,
+        tx_manager: Arc<TransactionManager>,
+        swap_router: SwapRouter,
+    ) -> Self {
+        Self {
+            blockchain_client,
+            tx_manager,
+            swap_router,
+            config: CompoundConfig::default(),
+        }
+    }
+    
+    /// Use a custom configuration
+    pub fn with_config(mut self, config: CompoundConfig) -> Self {
+        self.config = config;
+        self
+    }
+    
+    /// Claim the rewards of a position, swap them into its underlying tokens and deposit them,
+    /// unless the projected gain of reinvesting them doesn't cover the gas. Protocols whose claims
+    /// or deposits can't be built are refused before anything is sent. Once the rewards are
+    /// claimed, a failing swap or deposit is logged and what completed is returned
+    pub async fn compound(
+        &self,
+        wallet: &Wallet,
+        adapter: &ProtocolAdapter,
+        position: &Position,
+    ) -> AppResult<CompoundResult> {
+        if !adapter.supports_reinvestment() {
+            return Err(AppError::Validation(format!(
+                "{} deposits can't be built, so rewards can't be compounded", adapter.get_name()
+            )));
+        }
+        
+        let weights = underlying_weights(position);
+        let user_address = wallet.address();
+        let claims = adapter.build_claim_rewards_calls(&user_address, position).await?;
+        let pending = adapter.get_pending_rewards(&user_address, position).await?;
+        let reward_tokens: Vec<String> = pending.iter().map(|reward| reward.token.clone()).collect();
+        
+        // Rewards reinvested now earn until the next scheduled compound would have reinvested them
+        let gain_horizon_days = position.compound_frequency.interval()
+            .map(|interval| interval.num_seconds() as f64 / 86_400.0)
+            .unwrap_or(self.config.gain_horizon_days);
+        let rewards_value = self.value_rewards(&pending).await;
+        let projected_gain = rewards_value * position.apy / 100.0 * gain_horizon_days / 365.0;
+        
+        // Rewards paid in an underlying token need no swap into it
+        let swaps = reward_tokens.iter()
+            .flat_map(|reward| weights.iter().filter(move |(token, _)| price_ticker(reward) != price_ticker(token)))
+            .count();
+        let network_config = self.blockchain_client.get_network_config();
+        let claim_cost: f64 = claims.iter()
+            .map(|call| network_config.transaction_fee(
+                call.gas_limit.unwrap_or(network_config.min_gas_limit),
+                call.data.as_ref().map_or(0, |data| data.len()),
+            ))
+            .sum();
+        let gas_cost = claim_cost
+            + swaps as f64 * network_config.transaction_fee(SWAP_GAS_ESTIMATE, CALL_DATA_ESTIMATE)
+            + weights.len() as f64 * network_config.transaction_fee(adapter.get_gas_limit_for_operation("deposit"), CALL_DATA_ESTIMATE);
+        
+        let mut result = CompoundResult {
+            position_id: position.id.to_string(),
+            rewards_value,
+            projected_gain,
+            gas_cost,
+            compounded: false,
+            claim_tx: None,
+            deposits: Vec::new(),
+            tx_hashes: Vec::new(),
+        };
+        
+        if claims.is_empty() {
+            debug!("Skipping compound of position {}: no rewards to claim", position.id);
+            return Ok(result);
+        }
+        if projected_gain <= gas_cost {
+            debug!("Skipping compound of position {}: projected gain {} EGLD doesn't cover gas {} EGLD",
+                   position.id, projected_gain, gas_cost);
+            return Ok(result);
+        }
+        
+        // Claim, then measure what was claimed from the balances it changed
+        let before = self.get_balances(&user_address).await?;
+        let batch = self.tx_manager.submit_batch(wallet, claims).await?;
+        result.tx_hashes.extend(batch.items.iter().filter_map(|item| item.hash.clone()));
+        if !batch.is_complete() {
+            return Err(AppError::Transaction(format!("Claiming the rewards of position {} failed", position.id)));
+        }
+        result.claim_tx = batch.items.first().and_then(|item| item.hash.clone());
+        
+        let after = match self.get_balances(&user_address).await {
+            Ok(after) => after,
+            Err(e) => {
+                warn!("Failed to read the rewards claimed by position {}; keeping them in the wallet: {}", position.id, e);
+                return Ok(result);
+            }
+        };
+        
+        let pools = self.swap_router.load_pools().await.items;
+        let mut deposit_amounts: HashMap<String, f64> = HashMap::new();
+        for (token_id, raw_after) in &after {
+            let is_reward = reward_tokens.iter().any(|t| price_ticker(token_id) == price_ticker(t));
+            let raw_before = before.get(token_id).cloned().unwrap_or_default();
+            if !is_reward || *raw_after <= raw_before {
+                continue;
+            }
+            
+            let decimals = if token_id == "EGLD" {
+                18
+            } else {
+                token_decimals(&pools, token_id).unwrap_or(18)
+            };
+            let claimed = (raw_after - &raw_before).to_f64().unwrap_or(0.0) / 10f64.powi(decimals as i32);
+            
+            // Split the claimed rewards across the underlying tokens
+            for (token, weight) in &weights {
+                let share = claimed * weight;
+                if price_ticker(token_id) == price_ticker(token) {
+                    *deposit_amounts.entry(token.clone()).or_insert(0.0) += share;
+                    continue;
+                }
+                
+                // Only the guaranteed output of a swap is deposited, so the deposit can't overdraw
+                match self.swap(wallet, token_id, token, share, &mut result.tx_hashes).await {
+                    Ok(min_amount_out) => *deposit_amounts.entry(token.clone()).or_insert(0.0) += min_amount_out,
+                    Err(e) => warn!("Swapping {} {} claimed by position {} into {} failed; keeping it in the wallet: {}",
+                                    share, token_id, position.id, token, e),
+                }
+            }
+        }
+        
+        for (token, _) in &weights {
+            let amount = deposit_amounts.get(token).cloned().unwrap_or(0.0);
+            if amount <= 0.0 {
+                continue;
+            }
+            
+            match self.deposit(wallet, adapter, amount, token, &mut result.tx_hashes).await {
+                Ok(tx_hash) => {
+                    result.deposits.push(CompoundDeposit {
+                        token: token.clone(),
+                        amount,
+                        tx_hash,
+                    });
+                    info!("Compounded {} {} into position {}", amount, token, position.id);
+                },
+                Err(e) => warn!("Depositing {} {} into position {} failed; keeping it in the wallet: {}",
+                                amount, token, position.id, e),
+            }
+        }
+        
+        if result.deposits.is_empty() {
+            warn!("Position {} had no rewards to reinvest after claiming", position.id);
+        }
+        result.compounded = !result.deposits.is_empty();
+        Ok(result)
+    }
+    
+    /// Swap an amount of a token into another, returning the guaranteed output once the swap
+    /// confirmed; the hashes of its transactions are added to `tx_hashes`
+    async fn swap(
+        &self,
+        wallet: &Wallet,
+        token_in: &str,
+        token_out: &str,
+        amount: f64,
+        tx_hashes: &mut Vec<String>,
+    ) -> AppResult<f64> {
+        let route = self.swap_router.quote(token_in, token_out, amount, self.config.max_slippage).await?;
+        let calls = route.calls.iter()
+            .cloned()
+            .map(BatchCall::from)
+            .collect::<Vec<_>>();
+        let batch = self.tx_manager.submit_batch(wallet, calls).await?;
+        tx_hashes.extend(batch.items.iter().filter_map(|item| item.hash.clone()));
+        if !batch.is_complete() {
+            return Err(AppError::Transaction(format!("Swap of {} {} did not confirm", amount, token_in)));
+        }
+        Ok(route.min_amount_out)
+    }
+    
+    /// Deposit an amount of an underlying token into a position's protocol, returning the hash
+    /// of the confirmed deposit; it is also added to `tx_hashes`
+    async fn deposit(
+        &self,
+        wallet: &Wallet,
+        adapter: &ProtocolAdapter,
+        amount: f64,
+        token: &str,
+        tx_hashes: &mut Vec<String>,
+    ) -> AppResult<Option<String>> {
+        let call = adapter.build_deposit_call(&wallet.address(), amount, token).await?;
+        let batch = self.tx_manager.submit_batch(wallet, vec![call]).await?;
+        tx_hashes.extend(batch.items.iter().filter_map(|item| item.hash.clone()));
+        if !batch.is_complete() {
+            return Err(AppError::Transaction(format!("Deposit of {} {} did not confirm", amount, token)));
+        }
+        Ok(batch.items.first().and_then(|item| item.hash.clone()))
+    }
+    
+    /// Value of pending rewards in EGLD, at the price a swap into EGLD would get
+    async fn value_rewards(&self, rewards: &[PendingReward]) -> f64 {
+        let mut value = 0.0;
+        for reward in rewards {
+            if price_ticker(&reward.token) == "EGLD" {
+                value += reward.amount;
+                continue;
+            }
+            match self.swap_router.quote(&reward.token, "EGLD", reward.amount, self.config.max_slippage).await {
+                Ok(route) => value += route.amount_out,
+                Err(e) => warn!("Failed to value {} {} in EGLD: {}", reward.amount, reward.token, e),
+            }
+        }
+        value
+    }
+    
+    /// Raw balances of an account by token identifier, EGLD included
+    async fn get_balances(&self, address: &str) -> AppResult<HashMap<String, BigUint>> {
+        let mut balances: HashMap<String, BigUint> = self.blockchain_client.get_esdt_tokens(address).await?
+            .into_iter()
+            .filter(|token| token.nonce == 0)
+            .filter_map(|token| token.balance.parse().ok().map(|balance| (token.token_identifier, balance)))
+            .collect();
+        
+        let egld = self.blockchain_client.get_balance(address).await?;
+        let egld = egld.parse()
+            .map_err(|_| AppError::Blockchain(format!("Invalid balance for {}: {}", address, egld)))?;
+        balances.insert("EGLD".to_string(), egld);
+        Ok(balances)
+    }
+}
+
+/// Share of the reinvested rewards each underlying token of a position gets, from its allocation,
+/// or evenly when the allocation doesn't name its tokens
+fn underlying_weights(position: &Position) -> Vec<(String, f64)> {
+    if position.tokens.is_empty() {
+        return vec![("EGLD".to_string(), 1.0)];
+    }
+    
+    let allocated: Vec<f64> = position.tokens.iter()
+        .map(|token| {
+            position.allocation.iter()
+                .filter(|allocation| price_ticker(&allocation.token) == price_ticker(token))
+                .map(|allocation| allocation.percentage)
+                .sum()
+        })
+        .collect();
+    let total: f64 = allocated.iter().sum();
+    if total <= 0.0 {
+        let share = 1.0 / position.tokens.len() as f64;
+        return position.tokens.iter().map(|token| (token.clone(), share)).collect();
+    }
+    
+    position.tokens.iter()
+        .cloned()
+        .zip(allocated.into_iter().map(|percentage| percentage / total))
+        .collect()
+}
\ No newline at end of file
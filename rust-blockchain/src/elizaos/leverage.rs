@@ -0,0 +1,479 @@
+//! Leveraged Looping Strategy for ElizaOS
+//!
+//! Loops supply, borrow and re-supply of a single asset on a lending market to reach a
+//! target leverage, and unwinds part of the loop when the health factor approaches a floor.
+
+use crate::error::{AppError, AppResult};
+use async_trait::async_trait;
+use std::sync::Mutex;
+use tracing::{info, debug, warn};
+
+/// Supply and borrow balances of a looped asset, in token units
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoopPosition {
+    /// Tokens supplied as collateral
+    pub supplied: f64,
+    /// Tokens borrowed, including accrued interest
+    pub borrowed: f64,
+}
+
+/// Operations the looping strategy needs from a lending market
+#[async_trait]
+pub trait LendingMarket: Send + Sync {
+    /// Annual supply rate of a token, in percent
+    async fn supply_apy(&self, token: &str) -> AppResult<f64>;
+    
+    /// Annual borrow rate of a token, in percent
+    async fn borrow_apy(&self, token: &str) -> AppResult<f64>;
+    
+    /// Share of the supplied value that can be borrowed against
+    async fn collateral_factor(&self, token: &str) -> AppResult<f64>;
+    
+    /// Current supply and borrow balances
+    async fn position(&self, token: &str) -> AppResult<LoopPosition>;
+    
+    /// Supply tokens and use them as collateral
+    async fn supply(&self, token: &str, amount: f64) -> AppResult<()>;
+    
+    /// Withdraw supplied tokens
+    async fn withdraw(&self, token: &str, amount: f64) -> AppResult<()>;
+    
+    /// Borrow tokens against the collateral
+    async fn borrow(&self, token: &str, amount: f64) -> AppResult<()>;
+    
+    /// Repay borrowed tokens
+    async fn repay(&self, token: &str, amount: f64) -> AppResult<()>;
+}
+
+/// Configuration for the looping strategy
+#[derive(Debug, Clone)]
+pub struct LeverageConfig {
+    /// Token supplied and borrowed
+    pub token: String,
+    /// Supplied amount over own capital
+    pub target_leverage: f64,
+    /// Health factor below which the position is deleveraged
+    pub health_factor_floor: f64,
+    /// Maximum number of borrow and re-supply rounds per operation
+    pub max_loops: usize,
+    /// Steps smaller than this are skipped
+    pub min_step: f64,
+}
+
+impl Default for LeverageConfig {
+    fn default() -> Self {
+        Self {
+            token: "EGLD".to_string(),
+            target_leverage: 2.5,
+            health_factor_floor: 1.1,
+            max_loops: 10,
+            min_step: 0.0001,
+        }
+    }
+}
+
+/// Net annual yield of a looped position on own capital, in percent
+pub fn net_apy(supply_apy: f64, borrow_apy: f64, leverage: f64) -> f64 {
+    supply_apy * leverage - borrow_apy * (leverage - 1.0)
+}
+
+/// Health factor of a single asset loop, None when nothing is borrowed
+pub fn health_factor(position: &LoopPosition, collateral_factor: f64) -> Option<f64> {
+    if position.borrowed > 0.0 {
+        Some(position.supplied * collateral_factor / position.borrowed)
+    } else {
+        None
+    }
+}
+
+/// Leverage of a single asset loop, supplied over own capital
+pub fn leverage(position: &LoopPosition) -> f64 {
+    let equity = position.supplied - position.borrowed;
+    if equity > 0.0 {
+        position.supplied / equity
+    } else {
+        f64::INFINITY
+    }
+}
+
+/// State of a looped position after an operation
+#[derive(Debug, Clone)]
+pub struct LeverageReport {
+    pub supplied: f64,
+    pub borrowed: f64,
+    pub leverage: f64,
+    pub health_factor: Option<f64>,
+    pub net_apy: f64,
+    /// Borrow and re-supply (or withdraw and repay) rounds executed
+    pub loops: usize,
+}
+
+/// Strategy that loops a single asset to a target leverage
+pub struct LeverageStrategy<M: LendingMarket> {
+    market: M,
+    config: LeverageConfig,
+}
+
+impl<M: LendingMarket> LeverageStrategy<M> {
+    /// Create a new looping strategy, rejecting targets that start below the health factor floor
+    pub async fn new(market: M, config: LeverageConfig) -> AppResult<Self> {
+        if config.target_leverage < 1.0 {
+            return Err(AppError::Validation("Target leverage must be at least 1".to_string()));
+        }
+        if config.health_factor_floor <= 1.0 {
+            return Err(AppError::Validation("Health factor floor must be above 1".to_string()));
+        }
+        
+        let collateral_factor = market.collateral_factor(&config.token).await?;
+        let strategy = Self { market, config };
+        
+        if let Some(target_health) = strategy.target_health_factor(collateral_factor) {
+            if target_health <= strategy.config.health_factor_floor {
+                return Err(AppError::Validation(format!(
+                    "Leverage {:.2}x gives a health factor of {:.3}, below the floor of {:.3} (max leverage is {:.2}x)",
+                    strategy.config.target_leverage,
+                    target_health,
+                    strategy.config.health_factor_floor,
+                    1.0 / (1.0 - collateral_factor),
+                )));
+            }
+        }
+        
+        Ok(strategy)
+    }
+    
+    /// Get the lending market
+    pub fn market(&self) -> &M {
+        &self.market
+    }
+    
+    /// Get the strategy configuration
+    pub fn config(&self) -> &LeverageConfig {
+        &self.config
+    }
+    
+    /// Net APY at the target leverage with the current market rates
+    pub async fn expected_net_apy(&self) -> AppResult<f64> {
+        let supply_apy = self.market.supply_apy(&self.config.token).await?;
+        let borrow_apy = self.market.borrow_apy(&self.config.token).await?;
+        Ok(net_apy(supply_apy, borrow_apy, self.config.target_leverage))
+    }
+    
+    /// Supply own capital and loop up to the target leverage
+    pub async fn open(&self, amount: f64) -> AppResult<LeverageReport> {
+        let token = &self.config.token;
+        info!("Opening {:.2}x {} loop with {}", self.config.target_leverage, token, amount);
+        
+        self.market.supply(token, amount).await?;
+        let loops = self.lever_up().await?;
+        
+        self.report(loops).await
+    }
+    
+    /// Deleverage back to the target when the health factor is below the floor
+    pub async fn check_and_deleverage(&self) -> AppResult<Option<LeverageReport>> {
+        let collateral_factor = self.market.collateral_factor(&self.config.token).await?;
+        let position = self.market.position(&self.config.token).await?;
+        
+        match health_factor(&position, collateral_factor) {
+            Some(health) if health < self.config.health_factor_floor => {
+                warn!("{} loop health factor {:.3} is below the floor of {:.3}, deleveraging",
+                      self.config.token, health, self.config.health_factor_floor);
+                
+                let target_health = self.target_health_factor(collateral_factor)
+                    .unwrap_or(self.config.health_factor_floor);
+                let loops = self.deleverage_to(target_health).await?;
+                
+                Ok(Some(self.report(loops).await?))
+            },
+            _ => Ok(None),
+        }
+    }
+    
+    /// Repay all borrows and withdraw everything
+    pub async fn close(&self) -> AppResult<LeverageReport> {
+        let token = &self.config.token;
+        info!("Closing {} loop", token);
+        
+        // Repaying everything means an infinite health factor target
+        let loops = self.deleverage_to(f64::INFINITY).await?;
+        
+        let position = self.market.position(token).await?;
+        if position.borrowed < self.config.min_step && position.supplied > 0.0 {
+            self.market.withdraw(token, position.supplied).await?;
+        }
+        
+        self.report(loops).await
+    }
+    
+    /// Current state of the position
+    pub async fn report(&self, loops: usize) -> AppResult<LeverageReport> {
+        let token = &self.config.token;
+        let position = self.market.position(token).await?;
+        let collateral_factor = self.market.collateral_factor(token).await?;
+        let supply_apy = self.market.supply_apy(token).await?;
+        let borrow_apy = self.market.borrow_apy(token).await?;
+        let current_leverage = leverage(&position);
+        
+        Ok(LeverageReport {
+            supplied: position.supplied,
+            borrowed: position.borrowed,
+            leverage: current_leverage,
+            health_factor: health_factor(&position, collateral_factor),
+            net_apy: net_apy(supply_apy, borrow_apy, current_leverage),
+            loops,
+        })
+    }
+    
+    // Health factor of a position at the target leverage: cf * L / (L - 1)
+    fn target_health_factor(&self, collateral_factor: f64) -> Option<f64> {
+        let leverage = self.config.target_leverage;
+        if leverage > 1.0 {
+            Some(collateral_factor * leverage / (leverage - 1.0))
+        } else {
+            None
+        }
+    }
+    
+    // Borrow and re-supply until the target leverage is reached
+    async fn lever_up(&self) -> AppResult<usize> {
+        let token = &self.config.token;
+        let collateral_factor = self.market.collateral_factor(token).await?;
+        let mut loops = 0;
+        
+        while loops < self.config.max_loops {
+            let position = self.market.position(token).await?;
+            let equity = position.supplied - position.borrowed;
+            let target_supplied = equity * self.config.target_leverage;
+            
+            // Borrow no more than keeps the health factor at the floor
+            let headroom = position.supplied * collateral_factor / self.config.health_factor_floor - position.borrowed;
+            let step = (target_supplied - position.supplied).min(headroom);
+            
+            if step < self.config.min_step {
+                break;
+            }
+            
+            debug!("Loop {}: borrowing and re-supplying {} {}", loops + 1, step, token);
+            self.market.borrow(token, step).await?;
+            self.market.supply(token, step).await?;
+            loops += 1;
+        }
+        
+        Ok(loops)
+    }
+    
+    // Withdraw and repay until the health factor reaches the target
+    async fn deleverage_to(&self, target_health: f64) -> AppResult<usize> {
+        let token = &self.config.token;
+        let collateral_factor = self.market.collateral_factor(token).await?;
+        let mut loops = 0;
+        
+        while loops < self.config.max_loops {
+            let position = self.market.position(token).await?;
+            if position.borrowed < self.config.min_step {
+                break;
+            }
+            
+            // Repaying r keeps cf * (s - r) / (b - r) = target, so r = (target * b - cf * s) / (target - cf)
+            let needed = if target_health.is_finite() {
+                (target_health * position.borrowed - collateral_factor * position.supplied) / (target_health - collateral_factor)
+            } else {
+                position.borrowed
+            };
+            
+            // Only withdraw what keeps the account clear of liquidation in between
+            let withdrawable = position.supplied - position.borrowed * self.config.health_factor_floor.min(1.01) / collateral_factor;
+            let step = needed.min(position.borrowed).min(withdrawable);
+            
+            if step < self.config.min_step {
+                if needed >= self.config.min_step {
+                    warn!("{} loop can't be unwound further without outside funds", token);
+                }
+                break;
+            }
+            
+            debug!("Unwind {}: withdrawing and repaying {} {}", loops + 1, step, token);
+            self.market.withdraw(token, step).await?;
+            self.market.repay(token, step).await?;
+            loops += 1;
+        }
+        
+        Ok(loops)
+    }
+}
+
+/// In-memory lending market for simulating the strategy without funds
+pub struct MockLendingProvider {
+    token: String,
+    collateral_factor: f64,
+    state: Mutex<MockLendingState>,
+}
+
+struct MockLendingState {
+    supply_apy: f64,
+    borrow_apy: f64,
+    position: LoopPosition,
+}
+
+impl MockLendingProvider {
+    /// Create a new mock market for a token
+    pub fn new(token: &str, supply_apy: f64, borrow_apy: f64, collateral_factor: f64) -> Self {
+        Self {
+            token: token.to_string(),
+            collateral_factor,
+            state: Mutex::new(MockLendingState {
+                supply_apy,
+                borrow_apy,
+                position: LoopPosition::default(),
+            }),
+        }
+    }
+    
+    /// Change the market rates
+    pub fn set_rates(&self, supply_apy: f64, borrow_apy: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.supply_apy = supply_apy;
+        state.borrow_apy = borrow_apy;
+    }
+    
+    /// Accrue interest on supply and borrows for a number of days
+    pub fn accrue(&self, days: f64) {
+        let mut state = self.state.lock().unwrap();
+        let supply_growth = (1.0 + state.supply_apy / 100.0).powf(days / 365.0);
+        let borrow_growth = (1.0 + state.borrow_apy / 100.0).powf(days / 365.0);
+        state.position.supplied *= supply_growth;
+        state.position.borrowed *= borrow_growth;
+    }
+    
+    fn check_token(&self, token: &str) -> AppResult<()> {
+        if token == self.token {
+            Ok(())
+        } else {
+            Err(AppError::Validation(format!("Mock market only supports {}", self.token)))
+        }
+    }
+}
+
+#[async_trait]
+impl LendingMarket for MockLendingProvider {
+    async fn supply_apy(&self, token: &str) -> AppResult<f64> {
+        self.check_token(token)?;
+        Ok(self.state.lock().unwrap().supply_apy)
+    }
+    
+    async fn borrow_apy(&self, token: &str) -> AppResult<f64> {
+        self.check_token(token)?;
+        Ok(self.state.lock().unwrap().borrow_apy)
+    }
+    
+    async fn collateral_factor(&self, token: &str) -> AppResult<f64> {
+        self.check_token(token)?;
+        Ok(self.collateral_factor)
+    }
+    
+    async fn position(&self, token: &str) -> AppResult<LoopPosition> {
+        self.check_token(token)?;
+        Ok(self.state.lock().unwrap().position)
+    }
+    
+    async fn supply(&self, token: &str, amount: f64) -> AppResult<()> {
+        self.check_token(token)?;
+        self.state.lock().unwrap().position.supplied += amount;
+        Ok(())
+    }
+    
+    async fn withdraw(&self, token: &str, amount: f64) -> AppResult<()> {
+        self.check_token(token)?;
+        let mut state = self.state.lock().unwrap();
+        let remaining = LoopPosition {
+            supplied: state.position.supplied - amount,
+            borrowed: state.position.borrowed,
+        };
+        
+        // Same rule as the controller: withdrawals can't leave the account undercollateralized
+        if remaining.supplied < 0.0 || remaining.supplied * self.collateral_factor < remaining.borrowed {
+            return Err(AppError::Validation("Withdrawal would leave the account undercollateralized".to_string()));
+        }
+        
+        state.position = remaining;
+        Ok(())
+    }
+    
+    async fn borrow(&self, token: &str, amount: f64) -> AppResult<()> {
+        self.check_token(token)?;
+        let mut state = self.state.lock().unwrap();
+        
+        if state.position.supplied * self.collateral_factor < state.position.borrowed + amount {
+            return Err(AppError::Validation("Borrow exceeds the borrow limit".to_string()));
+        }
+        
+        state.position.borrowed += amount;
+        Ok(())
+    }
+    
+    async fn repay(&self, token: &str, amount: f64) -> AppResult<()> {
+        self.check_token(token)?;
+        let mut state = self.state.lock().unwrap();
+        state.position.borrowed = (state.position.borrowed - amount).max(0.0);
+        Ok(())
+    }
+}
+
+/// A change of market rates during a simulation
+#[derive(Debug, Clone)]
+pub struct RateChange {
+    pub day: u32,
+    pub supply_apy: f64,
+    pub borrow_apy: f64,
+}
+
+/// State of a simulated position at the end of a day
+#[derive(Debug, Clone)]
+pub struct SimulationStep {
+    pub day: u32,
+    pub equity: f64,
+    pub report: LeverageReport,
+    /// Whether the position was deleveraged that day
+    pub deleveraged: bool,
+}
+
+/// Run the strategy day by day against the mock market, deleveraging as needed
+pub async fn simulate(
+    strategy: &LeverageStrategy<MockLendingProvider>,
+    amount: f64,
+    days: u32,
+    rate_changes: &[RateChange],
+) -> AppResult<Vec<SimulationStep>> {
+    let opened = strategy.open(amount).await?;
+    let mut steps = vec![SimulationStep {
+        day: 0,
+        equity: opened.supplied - opened.borrowed,
+        report: opened,
+        deleveraged: false,
+    }];
+    
+    for day in 1..=days {
+        for change in rate_changes.iter().filter(|c| c.day == day) {
+            info!("Day {}: rates change to {:.2}% supply / {:.2}% borrow", day, change.supply_apy, change.borrow_apy);
+            strategy.market().set_rates(change.supply_apy, change.borrow_apy);
+        }
+        
+        strategy.market().accrue(1.0);
+        
+        let deleveraged = strategy.check_and_deleverage().await?;
+        let report = match &deleveraged {
+            Some(report) => report.clone(),
+            None => strategy.report(0).await?,
+        };
+        
+        steps.push(SimulationStep {
+            day,
+            equity: report.supplied - report.borrowed,
+            report,
+            deleveraged: deleveraged.is_some(),
+        });
+    }
+    
+    Ok(steps)
+}
\ No newline at end of file
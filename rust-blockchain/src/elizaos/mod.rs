@@ -4,28 +4,45 @@
 //! blockchain, with a focus on DeFi protocol integration and yield optimization.
 
 pub mod transaction_manager;
+pub mod cache;
 pub mod protocol_adapter;
 pub mod yield_optimizer;
+pub mod chain_watcher;
+pub mod leverage;
+pub mod compounder;
+pub mod backtest;
 
 use crate::blockchain::{BlockchainProvider, MultiversXClient};
 use crate::error::{AppError, AppResult};
 use crate::models::position::{Position, PositionStrategy};
+use crate::services::{PositionService, PriceService, SponsorshipService};
 use crate::smart_contracts::ProtocolRegistry;
+use crate::smart_contracts::protocol_interface::PendingReward;
+use crate::smart_contracts::router::{SwapRoute, SwapRouter, DEFAULT_MAX_SLIPPAGE};
+use crate::smart_contracts::scan::{ScanLimits, ScanResult};
 use crate::wallet::Wallet;
-use transaction_manager::{TransactionManager, TransactionStatus};
+use transaction_manager::{BatchCall, BatchResult, TransactionManager, TransactionStatus};
 use protocol_adapter::ProtocolAdapter;
+use chain_watcher::{ChainWatcher, ChainWatcherConfig};
+use compounder::{AutoCompounder, CompoundResult};
 use yield_optimizer::{YieldOptimizer, YieldOpportunity, RiskMetrics};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::{info, debug, error};
+use tracing::{info, debug, error, warn};
+
+/// How often the contracts of every protocol are listed again for the chain watcher
+const WATCHED_CONTRACTS_REFRESH_SECONDS: u64 = 600;
 
 /// Core ElizaOS system that coordinates all blockchain operations
 pub struct ElizaOS {
     blockchain_client: MultiversXClient,
     protocol_registry: ProtocolRegistry,
     tx_manager: Arc<TransactionManager>,
+    chain_watcher: Arc<ChainWatcher>,
     protocol_adapters: HashMap<String, Arc<ProtocolAdapter>>,
     yield_optimizer: YieldOptimizer,
+    swap_router: SwapRouter,
+    compounder: AutoCompounder,
     config: ElizaOSConfig,
 }
 
@@ -44,6 +61,10 @@ pub struct ElizaOSConfig {
     pub max_concurrent_txs: usize,
     /// Transaction timeout in seconds
     pub tx_timeout_seconds: u64,
+    /// Maximum number of protocols, or pools of a protocol, queried at once by opportunity scans
+    pub scan_concurrency: usize,
+    /// Time given to each protocol to answer an opportunity scan, in seconds
+    pub scan_timeout_seconds: u64,
 }
 
 impl Default for ElizaOSConfig {
@@ -55,22 +76,59 @@ impl Default for ElizaOSConfig {
             rebalance_threshold: 0.05, // 5%
             max_concurrent_txs: 10,
             tx_timeout_seconds: 300, // 5 minutes
+            scan_concurrency: 8,
+            scan_timeout_seconds: 10,
         }
     }
 }
 
 impl ElizaOS {
-    /// Create a new ElizaOS instance
-    pub fn new(blockchain_client: MultiversXClient, config: Option<ElizaOSConfig>) -> Self {
+    /// Create a new ElizaOS instance on top of the shared protocol registry
+    pub fn new(
+        blockchain_client: MultiversXClient,
+        protocol_registry: ProtocolRegistry,
+        config: Option<ElizaOSConfig>,
+    ) -> Self {
         let config = config.unwrap_or_default();
-        let protocol_registry = ProtocolRegistry::new(blockchain_client.clone());
         
         // Create transaction manager
-        let tx_manager = Arc::new(TransactionManager::new(
+        let tx_manager = TransactionManager::new(
             blockchain_client.clone(),
             config.max_concurrent_txs,
             config.tx_timeout_seconds,
-        ));
+        );
+        
+        Self::with_transaction_manager(blockchain_client, protocol_registry, config, tx_manager)
+    }
+    
+    /// Create a new ElizaOS instance that sponsors user gas through a relayer wallet
+    pub fn with_relayer(
+        blockchain_client: MultiversXClient,
+        protocol_registry: ProtocolRegistry,
+        config: Option<ElizaOSConfig>,
+        relayer: Wallet,
+        sponsorship: SponsorshipService,
+    ) -> Self {
+        let config = config.unwrap_or_default();
+        
+        // Create transaction manager with relaying enabled
+        let tx_manager = TransactionManager::new(
+            blockchain_client.clone(),
+            config.max_concurrent_txs,
+            config.tx_timeout_seconds,
+        ).with_relayer(relayer, sponsorship);
+        
+        Self::with_transaction_manager(blockchain_client, protocol_registry, config, tx_manager)
+    }
+    
+    /// Wire up protocols, adapters and the optimizer around a transaction manager
+    fn with_transaction_manager(
+        blockchain_client: MultiversXClient,
+        protocol_registry: ProtocolRegistry,
+        config: ElizaOSConfig,
+        tx_manager: TransactionManager,
+    ) -> Self {
+        let tx_manager = Arc::new(tx_manager);
         
         // Create protocol adapters
         let mut protocol_adapters = HashMap::new();
@@ -83,21 +141,49 @@ impl ElizaOS {
             protocol_adapters.insert(protocol.get_id().to_string(), adapter.clone());
         }
         
-        // Create yield optimizer
+        // Create chain watcher following the protocol contracts
+        let chain_watcher = Arc::new(ChainWatcher::new(
+            blockchain_client.clone(),
+            ChainWatcherConfig::default(),
+        ));
+        for protocol in protocol_registry.get_all_protocols() {
+            chain_watcher.watch_contract(protocol.get_contract_address(), protocol.get_id());
+        }
+        
+        // Create yield optimizer and swap router, which scan the protocols under the same limits
+        let scan_limits = ScanLimits {
+            max_concurrency: config.scan_concurrency,
+            protocol_timeout: std::time::Duration::from_secs(config.scan_timeout_seconds),
+        };
         let yield_optimizer = YieldOptimizer::new(
             protocol_adapters.values().cloned().collect(),
+        ).with_scan_limits(scan_limits.clone());
+        let swap_router = SwapRouter::new(protocol_registry.clone()).with_scan_limits(scan_limits);
+        let compounder = AutoCompounder::new(
+            blockchain_client.clone(),
+            tx_manager.clone(),
+            swap_router.clone(),
         );
         
         Self {
             blockchain_client,
             protocol_registry,
             tx_manager,
+            chain_watcher,
             protocol_adapters,
             yield_optimizer,
+            swap_router,
+            compounder,
             config,
         }
     }
     
+    /// Measure the impermanent loss of opportunities from the recorded token prices
+    pub fn with_price_service(mut self, price_service: PriceService) -> Self {
+        self.yield_optimizer = self.yield_optimizer.with_price_service(price_service);
+        self
+    }
+    
     /// Initialize ElizaOS and connect to the blockchain
     pub async fn initialize(&self) -> AppResult<()> {
         info!("Initializing ElizaOS on {} network", self.config.network);
@@ -116,10 +202,70 @@ impl ElizaOS {
             debug!("Initialized protocol: {}", protocol.get_name());
         }
         
+        // Follow the chain, feeding confirmations back to the transaction manager
+        let mut watched_txs = self.chain_watcher.subscribe();
+        let tx_manager = self.tx_manager.clone();
+        tokio::spawn(async move {
+            loop {
+                match watched_txs.recv().await {
+                    Ok(watched) => {
+                        tx_manager.apply_chain_status(&watched.transaction.hash, &watched.transaction.status);
+                    },
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("Transaction manager missed {} watched transactions", skipped);
+                    },
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        // The relayer's own transactions are followed along with the protocol contracts
+        if let Some(relayer_address) = self.tx_manager.relayer_address() {
+            self.watch_addresses(&[relayer_address]);
+        }
+        refresh_watched_contracts(&self.chain_watcher, &self.protocol_registry).await;
+        self.start_contract_refresher();
+        self.chain_watcher.clone().start();
+        
+        // Keep protocol TVL, APY and pools cached so status and opportunity scans don't hit every protocol
+        for adapter in self.protocol_adapters.values() {
+            adapter.clone().start_refresher();
+        }
+        
         info!("ElizaOS initialization complete");
         Ok(())
     }
     
+    /// Keep the chain watcher on every pool, farm and provider contract, picking up protocols as
+    /// they are registered and pools as the protocols discover them
+    fn start_contract_refresher(&self) {
+        let chain_watcher = self.chain_watcher.clone();
+        let protocol_registry = self.protocol_registry.clone();
+        let mut changes = protocol_registry.subscribe_changes();
+        
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(WATCHED_CONTRACTS_REFRESH_SECONDS)) => {},
+                    changed = changes.changed() => if changed.is_err() { break },
+                }
+                refresh_watched_contracts(&chain_watcher, &protocol_registry).await;
+            }
+        });
+    }
+    
+    /// Follow the transactions of wallet and position addresses, skipping invalid ones
+    pub fn watch_addresses(&self, addresses: &[String]) -> usize {
+        addresses.iter()
+            .filter(|address| match self.chain_watcher.watch_address(address) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Not watching address {}: {}", address, e);
+                    false
+                }
+            })
+            .count()
+    }
+    
     /// Create a new position
     pub async fn create_position(
         &self,
@@ -156,6 +302,7 @@ impl ElizaOS {
         
         // Deposit funds into protocol
         let tx_hash = adapter.deposit(wallet, amount, "EGLD").await?;
+        self.watch_addresses(&[user_address]);
         
         info!("Position created successfully with transaction hash: {}", tx_hash);
         Ok(tx_hash)
@@ -224,9 +371,81 @@ impl ElizaOS {
         Ok(tx_hash)
     }
     
-    /// Get the current value of a position
+    /// Get the rewards a position can claim, per reward token
+    pub async fn get_pending_rewards(
+        &self,
+        user_address: &str,
+        position: &Position,
+    ) -> AppResult<Vec<PendingReward>> {
+        debug!("Getting pending rewards for position {}", position.id);
+        
+        // Get protocol adapter
+        let adapter = self.get_protocol_adapter(&position.protocol_id)?;
+        
+        adapter.get_pending_rewards(user_address, position).await
+    }
+    
+    /// Reinvest the rewards of a position if the projected gain covers the gas
+    pub async fn compound_position(
+        &self,
+        wallet: &Wallet,
+        position: &Position,
+    ) -> AppResult<CompoundResult> {
+        info!("Compounding position {}", position.id);
+        
+        // Get protocol adapter
+        let adapter = self.get_protocol_adapter(&position.protocol_id)?;
+        
+        self.compounder.compound(wallet, &adapter, position).await
+    }
+    
+    /// Compound the positions of a wallet whose compound frequency has elapsed and record the
+    /// claims and deposits that completed; a position that fails to compound or record is
+    /// logged and skipped
+    pub async fn compound_due_positions(
+        &self,
+        wallet: &Wallet,
+        position_service: &PositionService,
+    ) -> AppResult<Vec<CompoundResult>> {
+        let positions = position_service.get_positions_due_for_compound(&wallet.address()).await?;
+        debug!("{} positions due for compounding", positions.len());
+        
+        let mut results = Vec::new();
+        for position in positions {
+            let result = match self.compound_position(wallet, &position).await {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to compound position {}: {}", position.id, e);
+                    continue;
+                }
+            };
+            
+            if let Some(claim_tx) = &result.claim_tx {
+                if let Err(e) = position_service.record_compound_claim(&position, claim_tx).await {
+                    error!("Failed to record the reward claim of position {}: {}", position.id, e);
+                }
+            }
+            for deposit in &result.deposits {
+                if let Err(e) = position_service.record_compound(
+                    &position,
+                    deposit.amount,
+                    &deposit.token,
+                    deposit.tx_hash.clone(),
+                ).await {
+                    error!("Failed to record the deposit of {} {} into position {}: {}",
+                           deposit.amount, deposit.token, position.id, e);
+                }
+            }
+            results.push(result);
+        }
+        
+        Ok(results)
+    }
+    
+    /// Get the current value of a position held by an address; protocols value what the address holds
     pub async fn get_position_value(
         &self,
+        user_address: &str,
         position: &Position,
     ) -> AppResult<f64> {
         debug!("Getting current value for position {}", position.id);
@@ -235,19 +454,19 @@ impl ElizaOS {
         let adapter = self.get_protocol_adapter(&position.protocol_id)?;
         
         // Get position value
-        let value = adapter.get_position_value(&position.id.to_string()).await?;
+        let value = adapter.get_position_value(user_address).await?;
         
         debug!("Position {} current value: {}", position.id, value);
         Ok(value)
     }
     
-    /// Find the best yield opportunities based on strategy and token
+    /// Find the best yield opportunities based on strategy and token, with the protocols skipped by the scan
     pub async fn find_best_opportunities(
         &self,
         strategy: &PositionStrategy,
         token: Option<String>,
         limit: usize,
-    ) -> AppResult<Vec<YieldOpportunity>> {
+    ) -> AppResult<ScanResult<YieldOpportunity>> {
         debug!("Finding best yield opportunities for strategy {:?}, token: {:?}", strategy, token);
         
         // Use yield optimizer to find opportunities
@@ -269,7 +488,7 @@ impl ElizaOS {
         debug!("Calculating optimal allocation for strategy {:?}, amount: {}", strategy, investment_amount);
         
         // Get best opportunities
-        let opportunities = self.find_best_opportunities(strategy, None, 10).await?;
+        let opportunities = self.find_best_opportunities(strategy, None, 10).await?.items;
         
         // Calculate optimal allocation
         let allocation = self.yield_optimizer.calculate_optimal_allocation(
@@ -312,6 +531,42 @@ impl ElizaOS {
         Ok(should_rebalance)
     }
     
+    /// Find the best route for swapping a token across the exchange protocols
+    pub async fn quote_swap(
+        &self,
+        token_in: &str,
+        token_out: &str,
+        amount: f64,
+        max_slippage: Option<f64>,
+    ) -> AppResult<SwapRoute> {
+        self.swap_router.quote(token_in, token_out, amount, max_slippage.unwrap_or(DEFAULT_MAX_SLIPPAGE)).await
+    }
+    
+    /// Swap a token along the best route; the calls of the route are submitted as one batch so they
+    /// execute in order, and each reverts if it pays less than the route's minimum output
+    pub async fn swap(
+        &self,
+        wallet: &Wallet,
+        token_in: &str,
+        token_out: &str,
+        amount: f64,
+        max_slippage: Option<f64>,
+    ) -> AppResult<SwapExecution> {
+        info!("Swapping {} {} for {}", amount, token_in, token_out);
+        
+        let route = self.quote_swap(token_in, token_out, amount, max_slippage).await?;
+        let calls = route.calls.iter()
+            .cloned()
+            .map(BatchCall::from)
+            .collect::<Vec<_>>();
+        
+        let batch = self.tx_manager.submit_batch(wallet, calls).await?;
+        
+        info!("Swap of {} {} submitted in batch {}, expecting {} {} (at least {})",
+              amount, route.token_in, batch.batch_id, route.amount_out, route.token_out, route.min_amount_out);
+        Ok(SwapExecution { route, batch })
+    }
+    
     /// Get system status
     pub async fn get_system_status(&self) -> AppResult<ElizaOSStatus> {
         debug!("Getting ElizaOS system status");
@@ -323,16 +578,22 @@ impl ElizaOS {
         let mut protocol_statuses = Vec::new();
         
         for (id, adapter) in &self.protocol_adapters {
-            // Get TVL and APY
-            let tvl = adapter.get_tvl().await?;
-            let apy = adapter.get_apy().await?;
+            // Get TVL and APY; a failing protocol is reported through its status instead of failing the whole status
+            let tvl = adapter.get_tvl().await.unwrap_or(0.0);
+            let apy = adapter.get_apy().await.unwrap_or(0.0);
+            
+            // Health reflects recent call failures and whether deposits are paused
+            let status = self.protocol_registry.get_health(id)
+                .map(|health| format!("{:?}", health.status))
+                .unwrap_or_else(|_| "Unknown".to_string());
             
             protocol_statuses.push(ProtocolStatus {
                 id: id.clone(),
                 name: adapter.get_name().to_string(),
                 tvl,
+                tvl_denomination: adapter.get_tvl_denomination().to_string(),
                 apy,
-                status: "Online".to_string(),
+                status,
             });
         }
         
@@ -373,12 +634,24 @@ impl ElizaOS {
         self.tx_manager.clone()
     }
     
+    /// Get chain watcher
+    pub fn get_chain_watcher(&self) -> Arc<ChainWatcher> {
+        self.chain_watcher.clone()
+    }
+    
     /// Get yield optimizer
     pub fn get_yield_optimizer(&self) -> &YieldOptimizer {
         &self.yield_optimizer
     }
 }
 
+/// A swap submitted along its route
+#[derive(Debug, Clone)]
+pub struct SwapExecution {
+    pub route: SwapRoute,
+    pub batch: BatchResult,
+}
+
 /// ElizaOS system status
 #[derive(Debug, Clone)]
 pub struct ElizaOSStatus {
@@ -397,6 +670,20 @@ pub struct ProtocolStatus {
     pub id: String,
     pub name: String,
     pub tvl: f64,
+    /// Unit the TVL is expressed in, a token ticker or USD
+    pub tvl_denomination: String,
     pub apy: f64,
     pub status: String,
+}
+
+/// Point the chain watcher at the current contracts of every registered protocol; a protocol that
+/// can't list its contracts keeps the ones watched before
+async fn refresh_watched_contracts(chain_watcher: &ChainWatcher, protocol_registry: &ProtocolRegistry) {
+    for protocol in protocol_registry.get_all_protocols() {
+        match protocol.get_contract_addresses().await {
+            Ok(addresses) => chain_watcher.set_protocol_contracts(protocol.get_id(), &addresses),
+            Err(e) => warn!("Failed to list the contracts of protocol {}: {}", protocol.get_id(), e),
+        }
+    }
+    debug!("Chain watcher following {} protocol contracts", chain_watcher.contract_count());
 }
\ No newline at end of file
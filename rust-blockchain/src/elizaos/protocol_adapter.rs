@@ -6,12 +6,18 @@
 use crate::blockchain::MultiversXClient;
 use crate::error::{AppError, AppResult};
 use crate::models::position::{Position, PositionStrategy};
-use crate::smart_contracts::protocol_interface::ProtocolInterface;
-use crate::elizaos::transaction_manager::{TransactionManager, TransactionStatus};
+use crate::smart_contracts::protocol_interface::{LiquidStakingToken, PendingReward, PoolInfo, ProtocolInterface};
+use crate::smart_contracts::risk::RiskScore;
+use crate::elizaos::cache::ProtocolCache;
+use crate::elizaos::transaction_manager::{BatchCall, TransactionManager, TransactionStatus};
+use crate::utils::egld_to_denomination;
 use crate::wallet::Wallet;
-use std::collections::HashMap;
+use serde::{de::DeserializeOwned, Serialize};
+use std::future::Future;
 use std::sync::Arc;
-use tracing::{info, debug, error, warn};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tracing::{info, debug, warn};
 
 /// Protocol adapter for a specific DeFi protocol
 pub struct ProtocolAdapter {
@@ -21,19 +27,18 @@ pub struct ProtocolAdapter {
     tx_manager: Arc<TransactionManager>,
     /// Blockchain client
     blockchain_client: MultiversXClient,
-    /// Cache for protocol data
-    cache: HashMap<String, CacheEntry>,
+    /// Cache for protocol data, shared with the background refresher
+    cache: Arc<ProtocolCache>,
 }
 
-/// Cache entry for protocol data
-struct CacheEntry {
-    /// When the data was last updated
-    last_updated: chrono::DateTime<chrono::Utc>,
-    /// The cached data
-    data: serde_json::Value,
-    /// Time-to-live in seconds
-    ttl: u64,
-}
+// Time-to-live of cached rates and TVL, in seconds
+const MARKET_DATA_TTL: u64 = 300;
+// Time-to-live of cached pool lists, tokens and metadata, in seconds
+const METADATA_TTL: u64 = 3600;
+// Time-to-live of cached position values, in seconds
+const POSITION_VALUE_TTL: u64 = 60;
+// Delay between passes of the background refresher; hot keys are refreshed before they expire
+const REFRESH_INTERVAL_SECONDS: u64 = 60;
 
 impl ProtocolAdapter {
     /// Create a new protocol adapter
@@ -46,7 +51,7 @@ impl ProtocolAdapter {
             protocol,
             tx_manager,
             blockchain_client,
-            cache: HashMap::new(),
+            cache: Arc::new(ProtocolCache::new()),
         }
     }
     
@@ -70,174 +75,76 @@ impl ProtocolAdapter {
         self.protocol.get_risk_level()
     }
     
+    /// Get the latest risk score of the protocol, once it has been scored
+    pub fn get_risk_score(&self) -> Option<RiskScore> {
+        self.protocol.get_risk_score()
+    }
+    
+    /// Whether the protocol takes new deposits
+    pub fn accepts_deposits(&self) -> bool {
+        self.protocol.accepts_deposits()
+    }
+    
+    /// Get the unit TVL is expressed in, a token ticker or USD
+    pub fn get_tvl_denomination(&self) -> &str {
+        self.protocol.get_tvl_denomination()
+    }
+    
     /// Get the current TVL (Total Value Locked) in the protocol
     pub async fn get_tvl(&self) -> AppResult<f64> {
-        // Check cache first
-        if let Some(entry) = self.cache.get("tvl") {
-            if entry.last_updated + chrono::Duration::seconds(entry.ttl as i64) > chrono::Utc::now() {
-                if let Some(tvl) = entry.data.as_f64() {
-                    return Ok(tvl);
-                }
-            }
-        }
-        
-        // Cache miss or expired, get from protocol
-        let tvl = self.protocol.get_tvl().await?;
-        
-        // Update cache
-        let cache_entry = CacheEntry {
-            last_updated: chrono::Utc::now(),
-            data: serde_json::Value::from(tvl),
-            ttl: 300, // 5 minutes
-        };
-        
-        let mut cache = self.cache.clone();
-        cache.insert("tvl".to_string(), cache_entry);
-        
-        Ok(tvl)
+        self.cached("tvl", MARKET_DATA_TTL, |protocol| async move {
+            protocol.get_tvl().await
+        }).await
     }
     
     /// Get the current APY (Annual Percentage Yield) for the protocol
     pub async fn get_apy(&self) -> AppResult<f64> {
-        // Check cache first
-        if let Some(entry) = self.cache.get("apy") {
-            if entry.last_updated + chrono::Duration::seconds(entry.ttl as i64) > chrono::Utc::now() {
-                if let Some(apy) = entry.data.as_f64() {
-                    return Ok(apy);
-                }
-            }
-        }
-        
-        // Cache miss or expired, get from protocol
-        let apy = self.protocol.get_apy().await?;
-        
-        // Update cache
-        let cache_entry = CacheEntry {
-            last_updated: chrono::Utc::now(),
-            data: serde_json::Value::from(apy),
-            ttl: 300, // 5 minutes
-        };
-        
-        let mut cache = self.cache.clone();
-        cache.insert("apy".to_string(), cache_entry);
-        
-        Ok(apy)
+        self.cached("apy", MARKET_DATA_TTL, |protocol| async move {
+            protocol.get_apy().await
+        }).await
     }
     
     /// Get the list of supported tokens
     pub async fn get_supported_tokens(&self) -> AppResult<Vec<String>> {
-        // Check cache first
-        if let Some(entry) = self.cache.get("tokens") {
-            if entry.last_updated + chrono::Duration::seconds(entry.ttl as i64) > chrono::Utc::now() {
-                if let Some(tokens_array) = entry.data.as_array() {
-                    let tokens = tokens_array.iter()
-                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                        .collect();
-                    return Ok(tokens);
-                }
-            }
-        }
-        
-        // Cache miss or expired, get from protocol
-        let tokens = self.protocol.get_supported_tokens().await?;
-        
-        // Update cache
-        let cache_entry = CacheEntry {
-            last_updated: chrono::Utc::now(),
-            data: serde_json::Value::from(tokens.clone()),
-            ttl: 3600, // 1 hour
-        };
-        
-        let mut cache = self.cache.clone();
-        cache.insert("tokens".to_string(), cache_entry);
-        
-        Ok(tokens)
+        self.cached("tokens", METADATA_TTL, |protocol| async move {
+            protocol.get_supported_tokens().await
+        }).await
     }
     
     /// Get the list of available pools
     pub async fn get_pools(&self) -> AppResult<Vec<String>> {
-        // Check cache first
-        if let Some(entry) = self.cache.get("pools") {
-            if entry.last_updated + chrono::Duration::seconds(entry.ttl as i64) > chrono::Utc::now() {
-                if let Some(pools_array) = entry.data.as_array() {
-                    let pools = pools_array.iter()
-                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                        .collect();
-                    return Ok(pools);
-                }
-            }
-        }
-        
-        // Cache miss or expired, get from protocol
-        let pools = self.protocol.get_pools().await?;
-        
-        // Update cache
-        let cache_entry = CacheEntry {
-            last_updated: chrono::Utc::now(),
-            data: serde_json::Value::from(pools.clone()),
-            ttl: 3600, // 1 hour
-        };
-        
-        let mut cache = self.cache.clone();
-        cache.insert("pools".to_string(), cache_entry);
-        
-        Ok(pools)
+        self.cached("pools", METADATA_TTL, |protocol| async move {
+            protocol.get_pools().await
+        }).await
     }
     
     /// Get the APY for a specific pool
     pub async fn get_pool_apy(&self, pool_id: &str) -> AppResult<f64> {
-        // Check cache first
-        let cache_key = format!("pool_apy_{}", pool_id);
-        if let Some(entry) = self.cache.get(&cache_key) {
-            if entry.last_updated + chrono::Duration::seconds(entry.ttl as i64) > chrono::Utc::now() {
-                if let Some(apy) = entry.data.as_f64() {
-                    return Ok(apy);
-                }
-            }
-        }
-        
-        // Cache miss or expired, get from protocol
-        let apy = self.protocol.get_pool_apy(pool_id).await?;
-        
-        // Update cache
-        let cache_entry = CacheEntry {
-            last_updated: chrono::Utc::now(),
-            data: serde_json::Value::from(apy),
-            ttl: 300, // 5 minutes
-        };
-        
-        let mut cache = self.cache.clone();
-        cache.insert(cache_key, cache_entry);
-        
-        Ok(apy)
+        let pool_id = pool_id.to_string();
+        self.cached(&format!("pool_apy_{}", pool_id), MARKET_DATA_TTL, |protocol| async move {
+            protocol.get_pool_apy(&pool_id).await
+        }).await
     }
     
     /// Get the TVL for a specific pool
     pub async fn get_pool_tvl(&self, pool_id: &str) -> AppResult<f64> {
-        // Check cache first
-        let cache_key = format!("pool_tvl_{}", pool_id);
-        if let Some(entry) = self.cache.get(&cache_key) {
-            if entry.last_updated + chrono::Duration::seconds(entry.ttl as i64) > chrono::Utc::now() {
-                if let Some(tvl) = entry.data.as_f64() {
-                    return Ok(tvl);
-                }
-            }
-        }
-        
-        // Cache miss or expired, get from protocol
-        let tvl = self.protocol.get_pool_tvl(pool_id).await?;
-        
-        // Update cache
-        let cache_entry = CacheEntry {
-            last_updated: chrono::Utc::now(),
-            data: serde_json::Value::from(tvl),
-            ttl: 300, // 5 minutes
-        };
-        
-        let mut cache = self.cache.clone();
-        cache.insert(cache_key, cache_entry);
-        
-        Ok(tvl)
+        let pool_id = pool_id.to_string();
+        self.cached(&format!("pool_tvl_{}", pool_id), MARKET_DATA_TTL, |protocol| async move {
+            protocol.get_pool_tvl(&pool_id).await
+        }).await
+    }
+    
+    /// Get the metadata of a specific pool
+    pub async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo> {
+        let pool_id = pool_id.to_string();
+        self.cached(&format!("pool_info_{}", pool_id), METADATA_TTL, |protocol| async move {
+            protocol.get_pool_info(&pool_id).await
+        }).await
+    }
+    
+    /// Get the liquid token minted by the protocol, for liquid staking protocols
+    pub async fn get_liquid_staking_token(&self) -> AppResult<Option<LiquidStakingToken>> {
+        self.protocol.get_liquid_staking_token().await
     }
     
     /// Deposit funds into the protocol
@@ -249,28 +156,10 @@ impl ProtocolAdapter {
     ) -> AppResult<String> {
         debug!("Depositing {} {} into {} protocol", amount, token, self.get_name());
         
-        // Check if token is supported
-        if !self.protocol.is_token_supported(token) {
-            return Err(AppError::Validation(format!("Token {} is not supported by {} protocol", token, self.get_name())));
-        }
-        
-        // Get contract address
-        let contract_address = self.get_contract_address();
-        
-        // Prepare transaction data
-        let data = Some(format!("deposit@{}", token));
-        
-        // Get gas limit for deposit operation
-        let gas_limit = self.protocol.get_gas_limit_for_operation("deposit");
+        let call = self.build_deposit_call(&wallet.address(), amount, token).await?;
         
         // Submit transaction
-        let tx_hash = self.tx_manager.submit_transaction(
-            wallet,
-            contract_address,
-            amount,
-            data,
-            Some(gas_limit),
-        ).await?;
+        let tx_hash = self.tx_manager.submit_call(wallet, call).await?;
         
         info!("Deposit transaction submitted: {}", tx_hash);
         Ok(tx_hash)
@@ -290,11 +179,11 @@ impl ProtocolAdapter {
             return Err(AppError::Validation(format!("Token {} is not supported by {} protocol", token, self.get_name())));
         }
         
-        // Get contract address
-        let contract_address = self.get_contract_address();
+        // Get the receiver, a pool's own contract for some protocols
+        let receiver = self.protocol.get_operation_receiver("withdraw", None, &wallet.address(), amount).await?;
         
         // Prepare transaction data
-        let data = Some(format!("withdraw@{}", token));
+        let data = Some(self.protocol.build_withdraw_data(amount, token).await?);
         
         // Get gas limit for withdraw operation
         let gas_limit = self.protocol.get_gas_limit_for_operation("withdraw");
@@ -302,7 +191,7 @@ impl ProtocolAdapter {
         // Submit transaction
         let tx_hash = self.tx_manager.submit_transaction(
             wallet,
-            contract_address,
+            &receiver,
             0.0, // No value transfer for withdraw
             data,
             Some(gas_limit),
@@ -321,20 +210,11 @@ impl ProtocolAdapter {
         debug!("Rebalancing position {} with strategy {:?} in {} protocol", 
                position.id, position.strategy, self.get_name());
         
-        // Get optimal allocation based on strategy
-        let optimal_allocation = self.protocol.get_optimal_allocation(&position.strategy).await?;
-        
         // Get contract address
         let contract_address = self.get_contract_address();
         
-        // Prepare transaction data
-        let mut data_parts = vec!["rebalance"];
-        for (token, percentage) in optimal_allocation {
-            data_parts.push(&token);
-            data_parts.push(&format!("{}", (percentage * 100.0) as u64));
-        }
-        
-        let data = Some(data_parts.join("@"));
+        // Prepare transaction data for the optimal allocation of the strategy
+        let data = Some(self.protocol.build_rebalance_data(position).await?);
         
         // Get gas limit for rebalance operation
         let gas_limit = self.protocol.get_gas_limit_for_operation("rebalance");
@@ -364,7 +244,7 @@ impl ProtocolAdapter {
         let contract_address = self.get_contract_address();
         
         // Prepare transaction data
-        let data = Some(format!("claimRewards@{}", position_id));
+        let data = Some(self.protocol.build_claim_rewards_data(position_id).await?);
         
         // Get gas limit for claim rewards operation
         let gas_limit = self.protocol.get_gas_limit_for_operation("claimRewards");
@@ -382,35 +262,69 @@ impl ProtocolAdapter {
         Ok(tx_hash)
     }
     
+    /// Build the call depositing a token for a user, to submit as part of a batch
+    pub async fn build_deposit_call(
+        &self,
+        user_address: &str,
+        amount: f64,
+        token: &str,
+    ) -> AppResult<BatchCall> {
+        // Check if token is supported
+        if !self.protocol.is_token_supported(token) {
+            return Err(AppError::Validation(format!("Token {} is not supported by {} protocol", token, self.get_name())));
+        }
+        
+        // Get the receiver, a pool's own contract for some protocols
+        let receiver = self.protocol.get_operation_receiver("deposit", None, user_address, amount).await?;
+        
+        // EGLD is sent as value, ESDTs are transferred by the call data
+        Ok(BatchCall {
+            receiver,
+            value: if token == "EGLD" { egld_to_denomination(amount)? } else { "0".to_string() },
+            data: Some(self.protocol.build_deposit_data(amount, token).await?),
+            gas_limit: Some(self.protocol.get_gas_limit_for_operation("deposit")),
+        })
+    }
+    
+    /// Build the calls claiming a user's rewards for a position, to submit as a batch; fails for
+    /// protocols whose claims can't be built from their on-chain state
+    pub async fn build_claim_rewards_calls(
+        &self,
+        user_address: &str,
+        position: &Position,
+    ) -> AppResult<Vec<BatchCall>> {
+        let calls = self.protocol.build_claim_rewards_calls(user_address, position).await?;
+        Ok(calls.into_iter().map(BatchCall::from).collect())
+    }
+    
+    /// Whether claimed rewards can be deposited back into the protocol
+    pub fn supports_reinvestment(&self) -> bool {
+        self.protocol.supports_reinvestment()
+    }
+    
+    /// Get the rewards a user's position can claim, per reward token
+    pub async fn get_pending_rewards(
+        &self,
+        user_address: &str,
+        position: &Position,
+    ) -> AppResult<Vec<PendingReward>> {
+        self.protocol.get_pending_rewards(user_address, position).await
+    }
+    
+    /// Get the gas limit for a specific operation
+    pub fn get_gas_limit_for_operation(&self, operation: &str) -> u64 {
+        self.protocol.get_gas_limit_for_operation(operation)
+    }
+    
     /// Get the current value of a position
     pub async fn get_position_value(
         &self,
         position_id: &str,
     ) -> AppResult<f64> {
-        // Check cache first
-        let cache_key = format!("position_value_{}", position_id);
-        if let Some(entry) = self.cache.get(&cache_key) {
-            if entry.last_updated + chrono::Duration::seconds(entry.ttl as i64) > chrono::Utc::now() {
-                if let Some(value) = entry.data.as_f64() {
-                    return Ok(value);
-                }
-            }
-        }
-        
-        // Cache miss or expired, get from protocol
-        let value = self.protocol.get_position_value(position_id).await?;
-        
-        // Update cache
-        let cache_entry = CacheEntry {
-            last_updated: chrono::Utc::now(),
-            data: serde_json::Value::from(value),
-            ttl: 60, // 1 minute
-        };
-        
-        let mut cache = self.cache.clone();
-        cache.insert(cache_key, cache_entry);
-        
-        Ok(value)
+        let position_id = position_id.to_string();
+        self.cached(&format!("position_value_{}", position_id), POSITION_VALUE_TTL, |protocol| async move {
+            protocol.get_position_value(&position_id).await
+        }).await
     }
     
     /// Get the optimal allocation for a strategy
@@ -418,52 +332,10 @@ impl ProtocolAdapter {
         &self,
         strategy: &PositionStrategy,
     ) -> AppResult<Vec<(String, f64)>> {
-        // Check cache first
-        let cache_key = format!("optimal_allocation_{:?}", strategy);
-        if let Some(entry) = self.cache.get(&cache_key) {
-            if entry.last_updated + chrono::Duration::seconds(entry.ttl as i64) > chrono::Utc::now() {
-                if let Some(allocation_array) = entry.data.as_array() {
-                    let allocation = allocation_array.iter()
-                        .filter_map(|v| {
-                            if let Some(obj) = v.as_object() {
-                                if let (Some(token), Some(percentage)) = (
-                                    obj.get("token").and_then(|t| t.as_str()),
-                                    obj.get("percentage").and_then(|p| p.as_f64())
-                                ) {
-                                    return Some((token.to_string(), percentage));
-                                }
-                            }
-                            None
-                        })
-                        .collect();
-                    return Ok(allocation);
-                }
-            }
-        }
-        
-        // Cache miss or expired, get from protocol
-        let allocation = self.protocol.get_optimal_allocation(strategy).await?;
-        
-        // Update cache
-        let allocation_json: Vec<serde_json::Value> = allocation.iter()
-            .map(|(token, percentage)| {
-                serde_json::json!({
-                    "token": token,
-                    "percentage": percentage
-                })
-            })
-            .collect();
-        
-        let cache_entry = CacheEntry {
-            last_updated: chrono::Utc::now(),
-            data: serde_json::Value::from(allocation_json),
-            ttl: 3600, // 1 hour
-        };
-        
-        let mut cache = self.cache.clone();
-        cache.insert(cache_key, cache_entry);
-        
-        Ok(allocation)
+        let strategy = strategy.clone();
+        self.cached(&format!("optimal_allocation_{:?}", strategy), METADATA_TTL, |protocol| async move {
+            protocol.get_optimal_allocation(&strategy).await
+        }).await
     }
     
     /// Check if a token is supported by the protocol
@@ -472,12 +344,102 @@ impl ProtocolAdapter {
     }
     
     /// Clear the cache
-    pub fn clear_cache(&mut self) {
+    pub fn clear_cache(&self) {
         self.cache.clear();
     }
     
     /// Clear a specific cache entry
-    pub fn clear_cache_entry(&mut self, key: &str) {
+    pub fn clear_cache_entry(&self, key: &str) {
         self.cache.remove(key);
     }
+    
+    /// Keep the hot keys (TVL, APY, pool list and pool rates) warm in the background,
+    /// so readers are served from the cache instead of waiting on the protocol
+    pub fn start_refresher(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            debug!("Cache refresher started for {} protocol", self.get_name());
+            let mut interval = tokio::time::interval(Duration::from_secs(REFRESH_INTERVAL_SECONDS));
+            
+            loop {
+                interval.tick().await;
+                self.refresh_hot_keys().await;
+            }
+        })
+    }
+    
+    // Refresh the hot keys that are missing or about to expire
+    async fn refresh_hot_keys(&self) {
+        // Let the protocol reload its own state first, so the values cached below are current
+        if let Err(e) = self.protocol.refresh_state().await {
+            warn!("Failed to refresh the state of {} protocol, using previous data: {}", self.get_name(), e);
+        }
+        
+        let mut results = vec![
+            ("tvl".to_string(), self.refresh("tvl", MARKET_DATA_TTL, |protocol| async move {
+                protocol.get_tvl().await
+            }).await),
+            ("apy".to_string(), self.refresh("apy", MARKET_DATA_TTL, |protocol| async move {
+                protocol.get_apy().await
+            }).await),
+            ("pools".to_string(), self.refresh("pools", METADATA_TTL, |protocol| async move {
+                protocol.get_pools().await
+            }).await),
+        ];
+        
+        // Pool rates are read for every pool on each opportunity scan
+        if let Ok(pools) = self.get_pools().await {
+            for pool_id in pools {
+                let apy_key = format!("pool_apy_{}", pool_id);
+                let id = pool_id.clone();
+                let result = self.refresh(&apy_key, MARKET_DATA_TTL, |protocol| async move {
+                    protocol.get_pool_apy(&id).await
+                }).await;
+                results.push((apy_key, result));
+                
+                let tvl_key = format!("pool_tvl_{}", pool_id);
+                let result = self.refresh(&tvl_key, MARKET_DATA_TTL, |protocol| async move {
+                    protocol.get_pool_tvl(&pool_id).await
+                }).await;
+                results.push((tvl_key, result));
+            }
+        }
+        
+        for (key, result) in results {
+            if let Err(e) = result {
+                warn!("Failed to refresh {} of {} protocol: {}", key, self.get_name(), e);
+            }
+        }
+    }
+    
+    // Read a value through the cache, fetching it from the protocol on a miss
+    async fn cached<T, F, Fut>(&self, key: &str, ttl: u64, fetch: F) -> AppResult<T>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+        F: FnOnce(Arc<dyn ProtocolInterface + Send + Sync>) -> Fut + Send + 'static,
+        Fut: Future<Output = AppResult<T>> + Send + 'static,
+    {
+        let protocol = self.protocol.clone();
+        let data = self.cache.get_or_fetch(key, ttl, move || async move {
+            Ok(serde_json::to_value(fetch(protocol).await?)?)
+        }).await?;
+        
+        Ok(serde_json::from_value(data)?)
+    }
+    
+    // Fetch a value from the protocol into the cache if it is missing or about to expire
+    async fn refresh<T, F, Fut>(&self, key: &str, ttl: u64, fetch: F) -> AppResult<()>
+    where
+        T: Serialize,
+        F: FnOnce(Arc<dyn ProtocolInterface + Send + Sync>) -> Fut,
+        Fut: Future<Output = AppResult<T>>,
+    {
+        if !self.cache.needs_refresh(key) {
+            return Ok(());
+        }
+        
+        let protocol = self.protocol.clone();
+        self.cache.refresh(key, ttl, || async move {
+            Ok(serde_json::to_value(fetch(protocol).await?)?)
+        }).await
+    }
 }
\ No newline at end of file
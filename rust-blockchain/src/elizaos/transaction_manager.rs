@@ -5,8 +5,10 @@
 
 use crate::blockchain::{BlockchainProvider, MultiversXClient, TransactionRequest};
 use crate::error::{AppError, AppResult};
+use crate::services::SponsorshipService;
+use crate::smart_contracts::protocol_interface::ContractCall;
 use crate::wallet::Wallet;
-use crate::utils::{egld_to_denomination, denomination_to_egld};
+use crate::utils::egld_to_denomination;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
@@ -54,6 +56,10 @@ pub struct ManagedTransaction {
     pub gas_limit: u64,
     /// Nonce
     pub nonce: Option<u64>,
+    /// Relayer paying the gas, for sponsored (relayed v3) transactions
+    pub relayer: Option<String>,
+    /// Batch this transaction was submitted with, if any
+    pub batch_id: Option<String>,
     /// Timestamp when the transaction was created
     pub created_at: chrono::DateTime<chrono::Utc>,
     /// Timestamp when the transaction was last updated
@@ -82,6 +88,8 @@ impl ManagedTransaction {
             gas_price,
             gas_limit,
             nonce: None,
+            relayer: None,
+            batch_id: None,
             created_at: now,
             updated_at: now,
         }
@@ -106,23 +114,93 @@ impl ManagedTransaction {
     }
 }
 
+/// A single call in a transaction batch
+#[derive(Debug, Clone)]
+pub struct BatchCall {
+    /// Receiver address
+    pub receiver: String,
+    /// EGLD value to transfer, in its smallest denomination
+    pub value: String,
+    /// Transaction data (for smart contract calls)
+    pub data: Option<String>,
+    /// Gas limit, defaults to the network minimum
+    pub gas_limit: Option<u64>,
+}
+
+impl From<ContractCall> for BatchCall {
+    fn from(call: ContractCall) -> Self {
+        Self {
+            receiver: call.receiver,
+            value: call.value,
+            data: Some(call.data),
+            gas_limit: Some(call.gas_limit),
+        }
+    }
+}
+
+/// Outcome of a single transaction in a batch
+#[derive(Debug, Clone)]
+pub struct BatchItemResult {
+    /// Managed transaction ID
+    pub tx_id: String,
+    /// Nonce allocated to the transaction
+    pub nonce: u64,
+    /// Transaction hash, if the gateway accepted the transaction
+    pub hash: Option<String>,
+    /// Final status of the transaction
+    pub status: TransactionStatus,
+}
+
+/// Outcome of a transaction batch
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    /// Batch ID
+    pub batch_id: String,
+    /// Per-transaction outcomes, in submission order
+    pub items: Vec<BatchItemResult>,
+}
+
+impl BatchResult {
+    /// Whether every transaction in the batch was confirmed
+    pub fn is_complete(&self) -> bool {
+        self.items.iter().all(|item| item.status == TransactionStatus::Confirmed)
+    }
+    
+    /// Transactions of the batch that did not confirm
+    pub fn failed(&self) -> Vec<&BatchItemResult> {
+        self.items.iter()
+            .filter(|item| item.status != TransactionStatus::Confirmed)
+            .collect()
+    }
+}
+
 /// Command sent to the transaction processor
 enum TxCommand {
     /// Submit a new transaction
     Submit(ManagedTransaction, mpsc::Sender<AppResult<String>>),
+    /// Submit a transaction that has already been fully signed
+    SubmitSigned(ManagedTransaction, TransactionRequest, mpsc::Sender<AppResult<String>>),
     /// Check the status of a transaction
     CheckStatus(String, mpsc::Sender<AppResult<TransactionStatus>>),
     /// Shutdown the processor
     Shutdown,
 }
 
+/// Platform wallet that signs and pays gas for sponsored transactions
+struct Relayer {
+    wallet: Wallet,
+    sponsorship: SponsorshipService,
+}
+
 /// Transaction Manager for ElizaOS
 pub struct TransactionManager {
     blockchain_client: MultiversXClient,
     transactions: Arc<Mutex<HashMap<String, ManagedTransaction>>>,
+    batches: Arc<Mutex<HashMap<String, Vec<String>>>>,
     command_tx: mpsc::Sender<TxCommand>,
     max_concurrent_txs: usize,
     tx_timeout_seconds: u64,
+    relayer: Option<Arc<Relayer>>,
 }
 
 impl TransactionManager {
@@ -151,12 +229,123 @@ impl TransactionManager {
         Self {
             blockchain_client,
             transactions,
+            batches: Arc::new(Mutex::new(HashMap::new())),
             command_tx,
             max_concurrent_txs,
             tx_timeout_seconds,
+            relayer: None,
         }
     }
     
+    /// Enable gas sponsorship, with the relayer wallet paying fees within each user's budget
+    pub fn with_relayer(mut self, wallet: Wallet, sponsorship: SponsorshipService) -> Self {
+        info!("Gas sponsorship enabled with relayer {}", wallet.address());
+        self.relayer = Some(Arc::new(Relayer { wallet, sponsorship }));
+        self
+    }
+    
+    /// Get the address of the relayer wallet, if gas sponsorship is enabled
+    pub fn relayer_address(&self) -> Option<String> {
+        self.relayer.as_ref().map(|relayer| relayer.wallet.address())
+    }
+    
+    /// Build a relayed (v3) transaction for the user to sign, with the platform relayer paying the gas.
+    /// The value is given in the smallest EGLD denomination.
+    pub async fn prepare_relayed_transaction(
+        &self,
+        sender: &str,
+        receiver: &str,
+        value: String,
+        data: Option<String>,
+        gas_limit: Option<u64>,
+    ) -> AppResult<TransactionRequest> {
+        let relayer_address = self.relayer_address()
+            .ok_or_else(|| AppError::Transaction("Gas sponsorship is not enabled".to_string()))?;
+        
+        let mut tx_request = self.blockchain_client.build_transaction(
+            sender,
+            receiver,
+            value,
+            data,
+            gas_limit,
+        ).await?;
+        tx_request.set_relayer(&relayer_address, self.blockchain_client.get_network_config().min_gas_limit);
+        
+        Ok(tx_request)
+    }
+    
+    /// Co-sign a user-signed relayed transaction with the relayer wallet and submit it,
+    /// charging the gas fee to the sender's sponsorship budget; returns the hash once submitted and
+    /// tracks the confirmation in the background
+    pub async fn submit_relayed_transaction(&self, inner_tx: TransactionRequest) -> AppResult<String> {
+        let relayer = self.relayer.clone()
+            .ok_or_else(|| AppError::Transaction("Gas sponsorship is not enabled".to_string()))?;
+        let relayer_address = relayer.wallet.address();
+        
+        // Only sign transactions that name our relayer and carry a valid user signature
+        if inner_tx.relayer.as_deref() != Some(relayer_address.as_str()) {
+            return Err(AppError::Validation("Transaction is not relayed by the platform relayer".to_string()));
+        }
+        
+        if inner_tx.sender == relayer_address {
+            return Err(AppError::Validation("The relayer cannot sponsor its own transactions".to_string()));
+        }
+        
+        if !inner_tx.verify_signature()? {
+            return Err(AppError::Auth("Invalid sender signature".to_string()));
+        }
+        
+        if inner_tx.is_guarded() && !inner_tx.verify_guardian_signature()? {
+            return Err(AppError::Auth("Missing or invalid guardian signature".to_string()));
+        }
+        
+        // Reserve the maximum fee the relayer can be charged for this transaction
+        let fee = i64::try_from(inner_tx.gasLimit as u128 * inner_tx.gasPrice as u128)
+            .map_err(|_| AppError::Validation("Transaction fee exceeds the sponsorship limit".to_string()))?;
+        let user_id = relayer.sponsorship.reserve(&inner_tx.sender, fee).await?;
+        
+        // Relayer signs the same payload as the sender
+        let relayer_signature = relayer.wallet.sign_transaction(&inner_tx.serialize_for_signing()?);
+        let final_tx = TransactionRequest {
+            relayerSignature: Some(relayer_signature),
+            ..inner_tx
+        };
+        
+        let mut tx = ManagedTransaction::new(
+            final_tx.sender.clone(),
+            final_tx.receiver.clone(),
+            final_tx.value.clone(),
+            final_tx.data.clone(),
+            final_tx.gasPrice,
+            final_tx.gasLimit,
+        );
+        tx.set_nonce(final_tx.nonce);
+        tx.relayer = final_tx.relayer.clone();
+        tx.update_status(TransactionStatus::Signed);
+        
+        let tx_id = tx.id.clone();
+        {
+            let mut txs = self.transactions.lock().unwrap();
+            txs.insert(tx_id.clone(), tx.clone());
+        }
+        
+        let (response_tx, mut response_rx) = mpsc::channel(1);
+        let result = match self.command_tx.send(TxCommand::SubmitSigned(tx, final_tx, response_tx)).await {
+            Ok(()) => response_rx.recv().await
+                .unwrap_or_else(|| Err(AppError::Internal("Transaction processor dropped the response".to_string()))),
+            Err(e) => Err(AppError::Internal(format!("Failed to send transaction command: {}", e))),
+        };
+        
+        // Fees are only charged for transactions that reached the chain
+        if result.is_err() && self.get_transaction(&tx_id).and_then(|tx| tx.hash).is_none() {
+            if let Err(e) = relayer.sponsorship.release(user_id, fee).await {
+                warn!("Failed to release sponsorship reservation for user {}: {}", user_id, e);
+            }
+        }
+        
+        result
+    }
+    
     /// Submit a transaction to the blockchain
     pub async fn submit_transaction(
         &self,
@@ -169,14 +358,28 @@ impl TransactionManager {
         // Convert EGLD to smallest denomination
         let value_denomination = egld_to_denomination(value)?;
         
+        self.submit_call(wallet, BatchCall {
+            receiver: receiver.to_string(),
+            value: value_denomination,
+            data,
+            gas_limit,
+        }).await
+    }
+    
+    /// Submit a single call whose value is already in the smallest denomination
+    pub async fn submit_call(
+        &self,
+        wallet: &Wallet,
+        call: BatchCall,
+    ) -> AppResult<String> {
         // Create managed transaction
         let tx = ManagedTransaction::new(
             wallet.address(),
-            receiver.to_string(),
-            value_denomination,
-            data,
+            call.receiver,
+            call.value,
+            call.data,
             self.blockchain_client.get_network_config().min_gas_price,
-            gas_limit.unwrap_or(self.blockchain_client.get_network_config().min_gas_limit),
+            call.gas_limit.unwrap_or(self.blockchain_client.get_network_config().min_gas_limit),
         );
         
         // Store transaction
@@ -203,6 +406,214 @@ impl TransactionManager {
         result
     }
     
+    /// Submit several transactions from one wallet in a single gateway call.
+    /// Nonces are allocated consecutively, so the calls execute in the given order.
+    pub async fn submit_batch(
+        &self,
+        wallet: &Wallet,
+        calls: Vec<BatchCall>,
+    ) -> AppResult<BatchResult> {
+        if calls.is_empty() {
+            return Err(AppError::Validation("A batch needs at least one transaction".to_string()));
+        }
+        
+        let sender = wallet.address();
+        let network_config = self.blockchain_client.get_network_config();
+        let account = self.blockchain_client.get_account(&sender).await?;
+        
+        if account.is_guarded {
+            return Err(AppError::Transaction(format!(
+                "Account {} is guarded; its transactions must be co-signed by the guardian",
+                sender
+            )));
+        }
+        
+        let batch_id = Uuid::new_v4().to_string();
+        let mut managed_txs = Vec::with_capacity(calls.len());
+        let mut signed_txs = Vec::with_capacity(calls.len());
+        
+        // Allocate consecutive nonces and sign every transaction
+        for (index, call) in calls.into_iter().enumerate() {
+            let mut tx = ManagedTransaction::new(
+                sender.clone(),
+                call.receiver,
+                call.value,
+                call.data,
+                network_config.min_gas_price,
+                call.gas_limit.unwrap_or(network_config.min_gas_limit),
+            );
+            tx.batch_id = Some(batch_id.clone());
+            tx.set_nonce(account.nonce + index as u64);
+            
+            let mut tx_request = TransactionRequest {
+                nonce: account.nonce + index as u64,
+                value: tx.value.clone(),
+                receiver: tx.receiver.clone(),
+                sender: tx.sender.clone(),
+                gasPrice: tx.gas_price,
+                gasLimit: tx.gas_limit,
+                data: tx.data.clone(),
+                chainID: network_config.chain_id.clone(),
+                version: 1,
+                options: 0,
+                guardian: None,
+                relayer: None,
+                signature: String::new(),
+                guardianSignature: None,
+                relayerSignature: None,
+            };
+            tx_request.signature = wallet.sign_transaction(&tx_request.serialize_for_signing()?);
+            tx.update_status(TransactionStatus::Signed);
+            
+            managed_txs.push(tx);
+            signed_txs.push(tx_request);
+        }
+        
+        {
+            let mut txs = self.transactions.lock().unwrap();
+            for tx in &managed_txs {
+                txs.insert(tx.id.clone(), tx.clone());
+            }
+        }
+        {
+            let mut batches = self.batches.lock().unwrap();
+            batches.insert(batch_id.clone(), managed_txs.iter().map(|tx| tx.id.clone()).collect());
+        }
+        
+        // Post the whole batch in one round trip
+        let hashes = match self.blockchain_client.send_transactions(&signed_txs).await {
+            Ok(hashes) => hashes,
+            Err(e) => {
+                let error_msg = format!("Failed to send batch: {}", e);
+                for tx in &mut managed_txs {
+                    tx.update_status(TransactionStatus::Failed(error_msg.clone()));
+                }
+                self.store_transactions(&managed_txs);
+                return Err(AppError::Transaction(error_msg));
+            }
+        };
+        
+        let mut first_rejected_nonce = None;
+        for (index, tx) in managed_txs.iter_mut().enumerate() {
+            match hashes.get(&index) {
+                Some(hash) => {
+                    tx.set_hash(hash.clone());
+                    tx.update_status(TransactionStatus::Submitted);
+                },
+                None => {
+                    first_rejected_nonce.get_or_insert(tx.nonce.unwrap_or_default());
+                    tx.update_status(TransactionStatus::Failed("Rejected by the gateway".to_string()));
+                }
+            }
+        }
+        
+        // Transactions after a rejected nonce can't execute until that nonce is used,
+        // so fail them now rather than waiting for the timeout
+        if let Some(rejected_nonce) = first_rejected_nonce {
+            warn!("Batch {} has a rejected transaction at nonce {}; failing the transactions after it", batch_id, rejected_nonce);
+            for tx in managed_txs.iter_mut().filter(|tx| tx.nonce.unwrap_or_default() > rejected_nonce && tx.hash.is_some()) {
+                tx.update_status(TransactionStatus::Failed(format!(
+                    "Blocked by the rejected transaction at nonce {}",
+                    rejected_nonce
+                )));
+            }
+        }
+        self.store_transactions(&managed_txs);
+        
+        // Wait for the accepted transactions that can still execute to confirm
+        let timeout_duration = Duration::from_secs(self.tx_timeout_seconds);
+        let confirmations = futures::future::join_all(managed_txs.iter().map(|tx| {
+            let blockchain_client = self.blockchain_client.clone();
+            let hash = match tx.status {
+                TransactionStatus::Submitted => tx.hash.clone(),
+                _ => None,
+            };
+            async move {
+                match hash {
+                    Some(hash) => Some(timeout(timeout_duration, Self::wait_for_confirmation(blockchain_client, hash)).await),
+                    None => None,
+                }
+            }
+        })).await;
+        
+        for (tx, confirmation) in managed_txs.iter_mut().zip(confirmations) {
+            match confirmation {
+                Some(Ok(Ok(crate::models::transaction::TransactionStatus::Success))) => {
+                    tx.update_status(TransactionStatus::Confirmed);
+                },
+                Some(Ok(Ok(_))) => {
+                    tx.update_status(TransactionStatus::Failed("Transaction failed on blockchain".to_string()));
+                },
+                Some(Ok(Err(e))) => {
+                    tx.update_status(TransactionStatus::Failed(format!("Error checking transaction status: {}", e)));
+                },
+                Some(Err(_)) => {
+                    tx.update_status(TransactionStatus::Failed(format!(
+                        "Timeout waiting for transaction confirmation after {} seconds",
+                        self.tx_timeout_seconds
+                    )));
+                },
+                None => {}
+            }
+        }
+        self.store_transactions(&managed_txs);
+        
+        let result = BatchResult {
+            batch_id,
+            items: managed_txs.into_iter()
+                .map(|tx| BatchItemResult {
+                    tx_id: tx.id,
+                    nonce: tx.nonce.unwrap_or_default(),
+                    hash: tx.hash,
+                    status: tx.status,
+                })
+                .collect(),
+        };
+        
+        info!("Batch {} finished: {} of {} transactions confirmed",
+              result.batch_id, result.items.len() - result.failed().len(), result.items.len());
+        
+        Ok(result)
+    }
+    
+    /// Get the transactions of a batch, in submission order
+    pub fn get_batch(&self, batch_id: &str) -> Option<Vec<ManagedTransaction>> {
+        let tx_ids = {
+            let batches = self.batches.lock().unwrap();
+            batches.get(batch_id).cloned()?
+        };
+        
+        let txs = self.transactions.lock().unwrap();
+        Some(tx_ids.iter().filter_map(|tx_id| txs.get(tx_id).cloned()).collect())
+    }
+    
+    /// Update a submitted transaction from its status as observed on chain
+    pub fn apply_chain_status(&self, tx_hash: &str, chain_status: &str) {
+        let mut txs = self.transactions.lock().unwrap();
+        let tx = match txs.values_mut().find(|tx| tx.hash.as_deref() == Some(tx_hash)) {
+            Some(tx) => tx,
+            None => return,
+        };
+        
+        if tx.status != TransactionStatus::Submitted {
+            return;
+        }
+        
+        match chain_status {
+            "success" | "executed" => tx.update_status(TransactionStatus::Confirmed),
+            "fail" | "invalid" => tx.update_status(TransactionStatus::Failed("Transaction failed on blockchain".to_string())),
+            _ => {}
+        }
+    }
+    
+    /// Save the latest state of managed transactions
+    fn store_transactions(&self, managed_txs: &[ManagedTransaction]) {
+        let mut txs = self.transactions.lock().unwrap();
+        for tx in managed_txs {
+            txs.insert(tx.id.clone(), tx.clone());
+        }
+    }
+    
     /// Get the status of a transaction
     pub async fn get_transaction_status(&self, tx_id: &str) -> AppResult<TransactionStatus> {
         // Check if we have the transaction in memory
@@ -290,6 +701,49 @@ impl TransactionManager {
                         active_txs -= 1;
                     });
                 },
+                Some(TxCommand::SubmitSigned(tx, signed_tx, response_tx)) => {
+                    if active_txs >= max_concurrent_txs {
+                        let _ = response_tx.send(Err(AppError::Transaction(
+                            "Too many concurrent transactions".to_string()
+                        ))).await;
+                        continue;
+                    }
+                    
+                    active_txs += 1;
+                    
+                    let blockchain_client = blockchain_client.clone();
+                    let transactions_clone = transactions.clone();
+                    
+                    // Signed transactions go straight to broadcasting; the caller gets the hash
+                    // once submitted while the confirmation is tracked here
+                    tokio::spawn(async move {
+                        match Self::broadcast(
+                            blockchain_client.clone(),
+                            transactions_clone.clone(),
+                            tx,
+                            signed_tx,
+                        ).await {
+                            Ok((tx, tx_hash)) => {
+                                let _ = response_tx.send(Ok(tx_hash.clone())).await;
+                                
+                                if let Err(e) = Self::confirm(
+                                    blockchain_client,
+                                    transactions_clone,
+                                    tx,
+                                    tx_hash.clone(),
+                                    tx_timeout_seconds,
+                                ).await {
+                                    warn!("Relayed transaction {} was not confirmed: {}", tx_hash, e);
+                                }
+                            },
+                            Err(e) => {
+                                let _ = response_tx.send(Err(e)).await;
+                            }
+                        }
+                        
+                        active_txs -= 1;
+                    });
+                },
                 Some(TxCommand::CheckStatus(tx_id, response_tx)) => {
                     // Check transaction status
                     let status = {
@@ -355,6 +809,17 @@ impl TransactionManager {
             }
         };
         
+        // Guarded accounts need their guardian's co-signature, which the manager cannot provide
+        if account.is_guarded {
+            let error_msg = format!("Account {} is guarded; its transactions must be co-signed by the guardian", tx.sender);
+            tx.update_status(TransactionStatus::Failed(error_msg.clone()));
+            {
+                let mut txs = transactions.lock().unwrap();
+                txs.insert(tx.id.clone(), tx);
+            }
+            return Err(AppError::Transaction(error_msg));
+        }
+        
         // Set nonce
         tx.set_nonce(account.nonce);
         {
@@ -373,7 +838,12 @@ impl TransactionManager {
             data: tx.data.clone(),
             chainID: blockchain_client.get_network_config().chain_id.clone(),
             version: 1,
+            options: 0,
+            guardian: None,
+            relayer: None,
             signature: "".to_string(), // Will be filled below
+            guardianSignature: None,
+            relayerSignature: None,
         };
         
         // Serialize transaction for signing
@@ -406,6 +876,28 @@ impl TransactionManager {
             ..tx_request
         };
         
+        Self::broadcast_and_confirm(blockchain_client, transactions, tx, final_tx, tx_timeout_seconds).await
+    }
+    
+    /// Send a signed transaction and wait for its confirmation
+    async fn broadcast_and_confirm(
+        blockchain_client: MultiversXClient,
+        transactions: Arc<Mutex<HashMap<String, ManagedTransaction>>>,
+        tx: ManagedTransaction,
+        final_tx: TransactionRequest,
+        tx_timeout_seconds: u64,
+    ) -> AppResult<String> {
+        let (tx, tx_hash) = Self::broadcast(blockchain_client.clone(), transactions.clone(), tx, final_tx).await?;
+        Self::confirm(blockchain_client, transactions, tx, tx_hash, tx_timeout_seconds).await
+    }
+    
+    /// Send a signed transaction and mark it as submitted under its hash
+    async fn broadcast(
+        blockchain_client: MultiversXClient,
+        transactions: Arc<Mutex<HashMap<String, ManagedTransaction>>>,
+        mut tx: ManagedTransaction,
+        final_tx: TransactionRequest,
+    ) -> AppResult<(ManagedTransaction, String)> {
         // Send transaction to blockchain
         let tx_hash = match blockchain_client.send_transaction(final_tx).await {
             Ok(hash) => hash,
@@ -428,6 +920,17 @@ impl TransactionManager {
             txs.insert(tx.id.clone(), tx.clone());
         }
         
+        Ok((tx, tx_hash))
+    }
+    
+    /// Wait for a submitted transaction to be confirmed and record its final status
+    async fn confirm(
+        blockchain_client: MultiversXClient,
+        transactions: Arc<Mutex<HashMap<String, ManagedTransaction>>>,
+        mut tx: ManagedTransaction,
+        tx_hash: String,
+        tx_timeout_seconds: u64,
+    ) -> AppResult<String> {
         // Wait for transaction confirmation
         let timeout_duration = Duration::from_secs(tx_timeout_seconds);
         let confirmation_result = timeout(timeout_duration, Self::wait_for_confirmation(
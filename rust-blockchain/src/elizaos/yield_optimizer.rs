@@ -1,4 +1,397 @@
-let (score, opp) = scored_opps[i];
+//! Yield Optimizer for ElizaOS
+//!
+//! Scans the registered protocols for yield opportunities and calculates
+//! portfolio allocations that respect the risk profile of each strategy.
+
+use crate::elizaos::protocol_adapter::ProtocolAdapter;
+use crate::error::AppResult;
+use crate::models::position::PositionStrategy;
+use crate::services::{impermanent_loss, PriceService};
+use crate::smart_contracts::risk::{RiskFactor, RiskScore};
+use crate::smart_contracts::scan::{scan_pools, scan_protocols, ScanLimits, ScanResult, SkippedProtocol};
+use chrono::{Duration, Utc};
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, debug, warn};
+
+/// Deviation from the optimal allocation, as a share of the portfolio, above which it is rebalanced
+pub const REBALANCE_DEVIATION: f64 = 0.1;
+/// Number of best opportunities the optimal allocation is chosen from when rebalancing
+pub const REBALANCE_OPPORTUNITIES: usize = 10;
+/// Number of days of price history the impermanent loss of a pool is measured over
+pub const IMPERMANENT_LOSS_WINDOW_DAYS: i64 = 30;
+/// Largest relative difference between the prices of tokens that are pegged to each other
+pub const PEG_TOLERANCE: f64 = 0.02;
+
+/// A yield opportunity in a specific protocol pool
+#[derive(Debug, Clone)]
+pub struct YieldOpportunity {
+    /// Protocol ID
+    pub protocol_id: String,
+    /// Protocol display name
+    pub protocol_name: String,
+    /// Pool ID within the protocol
+    pub pool_id: String,
+    /// Pool display name
+    pub pool_name: String,
+    /// Current APY in percent, including the staking yield of liquid staking tokens in the pool
+    pub apy: f64,
+    /// Part of the APY earned by liquid staking tokens held in the pool
+    pub staking_apy: f64,
+    /// Total value locked in the pool
+    pub tvl: f64,
+    /// Unit the TVL is expressed in, a token ticker or USD
+    pub tvl_denomination: String,
+    /// Risk level of the protocol, from its risk score once it has been scored
+    pub risk: String,
+    /// Protocol risk score (0-100, higher is riskier), once the protocol has been scored from enough factors
+    pub risk_score: Option<f64>,
+    /// Tokens involved in the pool
+    pub tokens: Vec<String>,
+    /// Impermanent loss expected over a year from the recent moves of the pool's token prices,
+    /// as a fraction (0.0 or below); zero for pegged pools, None when the prices couldn't be read
+    pub impermanent_loss: Option<f64>,
+    /// Volatility score (0-100, higher is more volatile)
+    pub volatility: u8,
+    /// Liquidity score (0-100, higher is more liquid)
+    pub liquidity: u8,
+    /// Security score (0-100, higher is more secure)
+    pub security: u8,
+}
+
+/// Risk constraints applied to a strategy
+#[derive(Debug, Clone)]
+pub struct RiskProfile {
+    /// Maximum accepted volatility score
+    pub max_volatility: u8,
+    /// Minimum accepted liquidity score
+    pub min_liquidity: u8,
+    /// Minimum accepted security score
+    pub min_security: u8,
+    /// Maximum share of the portfolio in a single protocol (0.0-1.0)
+    pub max_protocol_allocation: f64,
+    /// Maximum share of the portfolio exposed to a single token (0.0-1.0)
+    pub max_token_allocation: f64,
+    /// Minimum number of protocols to spread the portfolio over
+    pub min_protocol_count: usize,
+    /// Maximum accepted protocol risk score; protocols without a conclusive score are filtered on their risk level only
+    pub max_risk_score: f64,
+}
+
+impl RiskProfile {
+    /// Get the risk profile for a strategy
+    pub fn for_strategy(strategy: &PositionStrategy) -> Self {
+        match strategy {
+            PositionStrategy::Conservative => Self {
+                max_volatility: 40,
+                min_liquidity: 60,
+                min_security: 70,
+                max_protocol_allocation: 0.5,
+                max_token_allocation: 0.6,
+                min_protocol_count: 2,
+                max_risk_score: 30.0,
+            },
+            PositionStrategy::Balanced => Self {
+                max_volatility: 70,
+                min_liquidity: 40,
+                min_security: 50,
+                max_protocol_allocation: 0.7,
+                max_token_allocation: 0.8,
+                min_protocol_count: 2,
+                max_risk_score: 55.0,
+            },
+            PositionStrategy::Aggressive => Self {
+                max_volatility: 100,
+                min_liquidity: 20,
+                min_security: 30,
+                max_protocol_allocation: 1.0,
+                max_token_allocation: 1.0,
+                min_protocol_count: 1,
+                max_risk_score: 100.0,
+            },
+        }
+    }
+    
+    /// Whether an opportunity fits the profile
+    pub fn accepts(&self, opportunity: &YieldOpportunity) -> bool {
+        opportunity.volatility <= self.max_volatility
+            && opportunity.liquidity >= self.min_liquidity
+            && opportunity.security >= self.min_security
+            && opportunity.risk_score.map_or(true, |score| score <= self.max_risk_score)
+    }
+}
+
+/// Yield optimizer working on top of the protocol adapters
+pub struct YieldOptimizer {
+    /// Adapters for all registered protocols
+    protocol_adapters: Vec<Arc<ProtocolAdapter>>,
+    /// Concurrency and timeout limits of opportunity scans
+    scan_limits: ScanLimits,
+    /// Token prices the impermanent loss of pools is measured from
+    price_service: Option<PriceService>,
+}
+
+impl YieldOptimizer {
+    /// Create a new yield optimizer
+    pub fn new(protocol_adapters: Vec<Arc<ProtocolAdapter>>) -> Self {
+        Self {
+            protocol_adapters,
+            scan_limits: ScanLimits::default(),
+            price_service: None,
+        }
+    }
+    
+    /// Set the concurrency and timeout limits of opportunity scans
+    pub fn with_scan_limits(mut self, scan_limits: ScanLimits) -> Self {
+        self.scan_limits = scan_limits;
+        self
+    }
+    
+    /// Measure the impermanent loss of pools from the recorded token prices
+    pub fn with_price_service(mut self, price_service: PriceService) -> Self {
+        self.price_service = Some(price_service);
+        self
+    }
+    
+    /// Find the best yield opportunities for a strategy, optionally filtered by token
+    ///
+    /// Protocols and their pools are queried concurrently; protocols that fail or time out
+    /// are reported as skipped along with the opportunities found in the others.
+    pub async fn find_best_opportunities(
+        &self,
+        strategy: &PositionStrategy,
+        token: Option<String>,
+        limit: usize,
+    ) -> AppResult<ScanResult<YieldOpportunity>> {
+        debug!("Scanning {} protocols for yield opportunities", self.protocol_adapters.len());
+        
+        let risk_profile = RiskProfile::for_strategy(strategy);
+        
+        // Liquid staking tokens keep earning staking yield when supplied or paired elsewhere
+        let staking_tokens = join_all(self.protocol_adapters.iter().map(|adapter| async move {
+            (adapter.get_id().to_string(), adapter.get_liquid_staking_token().await)
+        })).await;
+        
+        let mut staking_apys: HashMap<String, (String, f64)> = HashMap::new();
+        for (protocol_id, result) in staking_tokens {
+            match result {
+                Ok(Some(token)) => {
+                    if let Some(apy) = token.apy {
+                        staking_apys.insert(token.ticker, (protocol_id, apy));
+                    }
+                },
+                Ok(None) => {},
+                Err(e) => warn!("Failed to get liquid staking token of {}: {}", protocol_id, e),
+            }
+        }
+        
+        let scans = self.protocol_adapters.iter()
+            .filter(|adapter| {
+                // Paused protocols can't take the deposits an opportunity leads to
+                if !adapter.accepts_deposits() {
+                    debug!("Skipping protocol {}, which is paused for deposits", adapter.get_id());
+                }
+                adapter.accepts_deposits()
+            })
+            .map(|adapter| {
+                let scan = self.scan_protocol(adapter, &risk_profile, token.as_deref(), &staking_apys);
+                (adapter.get_id().to_string(), scan)
+            })
+            .collect();
+        
+        let mut scan = scan_protocols(scans, &self.scan_limits).await;
+        
+        // Sort by APY (descending)
+        scan.items.sort_by(|a, b| b.apy.partial_cmp(&a.apy).unwrap_or(std::cmp::Ordering::Equal));
+        scan.items.truncate(limit);
+        
+        info!("Found {} yield opportunities, skipped {} protocols or pools", scan.items.len(), scan.skipped.len());
+        Ok(scan)
+    }
+    
+    /// Scan the pools of a protocol for opportunities that fit the risk profile
+    async fn scan_protocol(
+        &self,
+        adapter: &Arc<ProtocolAdapter>,
+        risk_profile: &RiskProfile,
+        token: Option<&str>,
+        staking_apys: &HashMap<String, (String, f64)>,
+    ) -> AppResult<ScanResult<YieldOpportunity>> {
+        let pools = adapter.get_pools().await?;
+        let risk = adapter.get_risk_level().to_string();
+        let risk = &risk;
+        let risk_score = adapter.get_risk_score();
+        let risk_score = &risk_score;
+        
+        let scan = scan_pools(adapter.get_id(), pools, &self.scan_limits, |pool_id| async move {
+            let pool_info = adapter.get_pool_info(&pool_id).await?;
+            
+            // Filter by token if specified
+            if let Some(token_filter) = token {
+                if !pool_info.tokens.iter().any(|t| t == token_filter) {
+                    return Ok(None);
+                }
+            }
+            
+            // Pool value is split evenly over its tokens, so each liquid token adds its share of staking yield
+            let staking_apy: f64 = pool_info.tokens.iter()
+                .filter_map(|t| staking_apys.get(t))
+                .filter(|(issuer, _)| issuer != adapter.get_id())
+                .map(|(_, apy)| apy / pool_info.tokens.len() as f64)
+                .sum();
+            
+            let (pool_apy, tvl) = tokio::try_join!(
+                adapter.get_pool_apy(&pool_id),
+                adapter.get_pool_tvl(&pool_id),
+            )?;
+            let apy = pool_apy + staking_apy;
+            let impermanent_loss = self.measure_impermanent_loss(&pool_info.tokens).await;
+            let (volatility, liquidity, security) = Self::score_opportunity(risk, risk_score.as_ref(), tvl);
+            
+            let opportunity = YieldOpportunity {
+                protocol_id: adapter.get_id().to_string(),
+                protocol_name: adapter.get_name().to_string(),
+                pool_id,
+                pool_name: pool_info.name,
+                apy,
+                staking_apy,
+                tvl,
+                tvl_denomination: adapter.get_tvl_denomination().to_string(),
+                risk: risk.clone(),
+                risk_score: risk_score.as_ref().filter(|score| score.is_conclusive()).map(|score| score.score),
+                tokens: pool_info.tokens,
+                impermanent_loss,
+                volatility,
+                liquidity,
+                security,
+            };
+            
+            // Skip opportunities outside the risk profile
+            if !risk_profile.accepts(&opportunity) {
+                return Ok(None);
+            }
+            
+            Ok(Some(opportunity))
+        }).await;
+        
+        Ok(scan)
+    }
+    
+    /// Impermanent loss a pool is expected to cause over a year, from how its token prices moved
+    /// against each other over the last IMPERMANENT_LOSS_WINDOW_DAYS; pools of tokens pegged to
+    /// each other follow a stableswap invariant and are taken to cause none
+    async fn measure_impermanent_loss(&self, tokens: &[String]) -> Option<f64> {
+        if tokens.len() < 2 {
+            return Some(0.0);
+        }
+        let price_service = self.price_service.as_ref()?;
+        
+        let now = Utc::now();
+        let from = now - Duration::days(IMPERMANENT_LOSS_WINDOW_DAYS);
+        let mut prices = Vec::with_capacity(tokens.len());
+        let mut ratios = Vec::with_capacity(tokens.len());
+        let mut window_start = from;
+        for token in tokens {
+            let price = match price_service.get_price(token).await {
+                Ok(price) => price,
+                Err(e) => {
+                    debug!("Can't measure impermanent loss without the price of {}: {}", token, e);
+                    return None;
+                }
+            };
+            let history = price_service.get_price_history(token, from, now).await.ok()?;
+            let first = history.first()?;
+            
+            window_start = window_start.max(first.recorded_at);
+            prices.push(price);
+            ratios.push(price / first.price_usd);
+        }
+        
+        let reference = prices[0];
+        if prices.iter().all(|price| (price / reference - 1.0).abs() <= PEG_TOLERANCE) {
+            return Some(0.0);
+        }
+        
+        // Loss grows about linearly with time for a given volatility, so the window is scaled to a year
+        let window_days = (now - window_start).num_seconds() as f64 / 86_400.0;
+        if window_days < 1.0 {
+            return None;
+        }
+        Some((impermanent_loss(&ratios) * 365.0 / window_days).max(-1.0))
+    }
+    
+    /// Derive volatility, liquidity and security scores from the risk level and TVL;
+    /// volatility is measured from the APY history once the protocol is scored
+    pub fn score_opportunity(risk: &str, risk_score: Option<&RiskScore>, tvl: f64) -> (u8, u8, u8) {
+        let (mut volatility, security) = match risk {
+            "Low" => (25, 85),
+            "Medium" => (50, 65),
+            _ => (80, 40),
+        };
+        
+        if let Some(apy_volatility) = risk_score.and_then(|score| score.factor(RiskFactor::ApyVolatility)) {
+            volatility = apy_volatility.score.round() as u8;
+        }
+        
+        let liquidity = if tvl >= 25_000_000.0 {
+            90
+        } else if tvl >= 10_000_000.0 {
+            70
+        } else if tvl >= 1_000_000.0 {
+            50
+        } else {
+            20
+        };
+        
+        (volatility, liquidity, security)
+    }
+    
+    /// Calculate the optimal allocation of an investment across opportunities
+    pub async fn calculate_optimal_allocation(
+        &self,
+        strategy: &PositionStrategy,
+        investment_amount: f64,
+        opportunities: &[YieldOpportunity],
+    ) -> AppResult<HashMap<String, f64>> {
+        let mut allocation = HashMap::new();
+        
+        if opportunities.is_empty() || investment_amount <= 0.0 {
+            return Ok(allocation);
+        }
+        
+        let risk_profile = RiskProfile::for_strategy(strategy);
+        
+        match strategy {
+            PositionStrategy::Conservative => {
+                // Conservative allocation spreads funds evenly over the most secure opportunities
+                let mut sorted_opps = opportunities.to_vec();
+                sorted_opps.sort_by(|a, b| b.security.cmp(&a.security));
+                
+                let count = sorted_opps.len().min(risk_profile.min_protocol_count.max(3));
+                let allocation_per_opp = investment_amount / count as f64;
+                
+                for opp in sorted_opps.iter().take(count) {
+                    let key = format!("{}:{}", opp.protocol_id, opp.pool_id);
+                    allocation.insert(key, allocation_per_opp);
+                }
+            },
+            PositionStrategy::Balanced => {
+                // Balanced allocation weights the top opportunities by risk-adjusted yield,
+                // net of the impermanent loss their pools are expected to cause
+                let scored_opps: Vec<(f64, &YieldOpportunity)> = opportunities.iter()
+                    .take(5)
+                    .map(|opp| {
+                        let score = (opp.apy + expected_impermanent_loss(opp) * 100.0).max(0.01)
+                            * (opp.security as f64 / 100.0)
+                            * (opp.liquidity as f64 / 100.0).max(0.01);
+                        (score, opp)
+                    })
+                    .collect();
+                let total_score: f64 = scored_opps.iter().map(|(score, _)| score).sum();
+                
+                for i in 0..scored_opps.len() {
+                    let (score, opp) = scored_opps[i];
                     let allocation_amount = investment_amount * (score / total_score);
                     let key = format!("{}:{}", opp.protocol_id, opp.pool_id);
                     allocation.insert(key, allocation_amount);
@@ -141,26 +534,23 @@ let (score, opp) = scored_opps[i];
         total_value: f64,
     ) -> AppResult<bool> {
         // Get best opportunities
-        let opportunities = self.find_best_opportunities(strategy, None, 10).await?;
+        let scan = self.find_best_opportunities(strategy, None, REBALANCE_OPPORTUNITIES).await?;
+        
+        // A holding whose protocol or pool couldn't be scanned would look like one worth leaving,
+        // so wait for a complete scan before moving anything
+        if let Some(key) = current_allocation.keys().find(|key| is_skipped(&scan.skipped, key)) {
+            info!("Not rebalancing while the current holding {} couldn't be scanned", key);
+            return Ok(false);
+        }
         
         // Calculate optimal allocation
         let optimal_allocation = self.calculate_optimal_allocation(
             strategy,
             total_value,
-            &opportunities,
+            &scan.items,
         ).await?;
         
-        // Compare current allocation with optimal allocation
-        let mut total_deviation = 0.0;
-        
-        for (key, optimal_amount) in &optimal_allocation {
-            let current_amount = current_allocation.get(key).cloned().unwrap_or(0.0);
-            let deviation = (current_amount - optimal_amount).abs() / total_value;
-            total_deviation += deviation;
-        }
-        
-        // If total deviation is more than 10%, rebalancing is needed
-        Ok(total_deviation > 0.1)
+        Ok(allocation_deviation(current_allocation, &optimal_allocation, total_value) > REBALANCE_DEVIATION)
     }
     
     /// Calculate risk metrics for a given allocation
@@ -173,6 +563,9 @@ let (score, opp) = scored_opps[i];
         let mut weighted_volatility = 0.0;
         let mut weighted_liquidity = 0.0;
         let mut weighted_security = 0.0;
+        let mut weighted_impermanent_loss = 0.0;
+        let mut weighted_risk_score = 0.0;
+        let mut scored_amount = 0.0;
         
         let mut protocol_allocations: HashMap<String, f64> = HashMap::new();
         let mut token_allocations: HashMap<String, f64> = HashMap::new();
@@ -194,6 +587,11 @@ let (score, opp) = scored_opps[i];
                 weighted_volatility += opp.volatility as f64 * amount;
                 weighted_liquidity += opp.liquidity as f64 * amount;
                 weighted_security += opp.security as f64 * amount;
+                weighted_impermanent_loss += expected_impermanent_loss(opp) * amount;
+                if let Some(risk_score) = opp.risk_score {
+                    weighted_risk_score += risk_score * amount;
+                    scored_amount += amount;
+                }
                 
                 // Update token allocations
                 for token in &opp.tokens {
@@ -229,10 +627,54 @@ let (score, opp) = scored_opps[i];
             max_protocol_allocation,
             max_token_allocation,
             protocol_count,
+            impermanent_loss: weighted_impermanent_loss / total_amount,
+            risk_score: (scored_amount > 0.0).then(|| weighted_risk_score / scored_amount),
         })
     }
 }
 
+/// Deviation of an allocation from the optimal one, as a share of the portfolio value
+pub fn allocation_deviation(
+    current_allocation: &HashMap<String, f64>,
+    optimal_allocation: &HashMap<String, f64>,
+    total_value: f64,
+) -> f64 {
+    if total_value <= 0.0 {
+        return 0.0;
+    }
+    
+    optimal_allocation.iter()
+        .map(|(key, optimal_amount)| {
+            let current_amount = current_allocation.get(key).cloned().unwrap_or(0.0);
+            (current_amount - optimal_amount).abs() / total_value
+        })
+        .sum()
+}
+
+/// Whether a scan left out the protocol or pool of a "protocol:pool" allocation key
+fn is_skipped(skipped: &[SkippedProtocol], key: &str) -> bool {
+    let (protocol_id, pool_id) = key.split_once(':').unwrap_or((key, ""));
+    skipped.iter().any(|s| {
+        s.protocol_id == protocol_id && s.pool_id.as_deref().map_or(true, |skipped_pool| skipped_pool == pool_id)
+    })
+}
+
+/// Impermanent loss a pool is expected to cause over a year, as a fraction; measured from its
+/// token prices, or else the loss of one of its tokens moving against the others by its
+/// volatility score; zero for single token pools
+fn expected_impermanent_loss(opportunity: &YieldOpportunity) -> f64 {
+    if let Some(impermanent_loss) = opportunity.impermanent_loss {
+        return impermanent_loss;
+    }
+    if opportunity.tokens.len() < 2 {
+        return 0.0;
+    }
+    
+    let mut ratios = vec![1.0; opportunity.tokens.len()];
+    ratios[0] += opportunity.volatility as f64 / 100.0;
+    impermanent_loss(&ratios)
+}
+
 /// Risk metrics for a portfolio allocation
 #[derive(Debug, Clone)]
 pub struct RiskMetrics {
@@ -248,6 +690,18 @@ pub struct RiskMetrics {
     pub max_token_allocation: f64,
     /// Number of protocols in the allocation
     pub protocol_count: usize,
+    /// Impermanent loss the liquidity pools in the allocation are expected to cause over a year,
+    /// as a fraction (0.0 or below)
+    pub impermanent_loss: f64,
+    /// Average protocol risk score (0-100) of the scored protocols in the allocation
+    pub risk_score: Option<f64>,
+}
+
+impl RiskMetrics {
+    /// Expected APY of the allocation net of its expected impermanent loss, in percent
+    pub fn risk_adjusted_apy(&self, expected_apy: f64) -> f64 {
+        expected_apy + self.impermanent_loss * 100.0
+    }
 }
 
 impl Default for RiskMetrics {
@@ -259,6 +713,8 @@ impl Default for RiskMetrics {
             max_protocol_allocation: 1.0,
             max_token_allocation: 1.0,
             protocol_count: 0,
+            impermanent_loss: 0.0,
+            risk_score: None,
         }
     }
 }
\ No newline at end of file
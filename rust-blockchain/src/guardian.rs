@@ -0,0 +1,108 @@
+use crate::blockchain::TransactionRequest;
+use crate::error::{AppError, AppResult};
+use crate::wallet::Wallet;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+
+// Co-signs transactions of guarded accounts once the user has passed a second factor
+#[async_trait]
+pub trait GuardianSigner: Send + Sync {
+    // Address of the guardian this signer holds the key for
+    fn guardian_address(&self) -> String;
+    
+    // Return the hex guardian signature for a transaction, given the user's 2FA code
+    async fn co_sign(&self, tx: &TransactionRequest, code: &str) -> AppResult<String>;
+}
+
+// Guardian that checks time-based one-time passwords (RFC 6238, HMAC-SHA256) and signs
+// with a local key. Meant for devnet and testing; production accounts use a hosted guardian.
+pub struct LocalTotpGuardian {
+    wallet: Wallet,
+    secret: Vec<u8>,
+    step_seconds: u64,
+    digits: u32,
+    // Last accepted time step per sender, so a code can't be replayed within its window
+    last_steps: Mutex<HashMap<String, u64>>,
+}
+
+impl LocalTotpGuardian {
+    pub fn new(wallet: Wallet, secret: Vec<u8>) -> Self {
+        Self {
+            wallet,
+            secret,
+            step_seconds: 30,
+            digits: 6,
+            last_steps: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    // Generate the code for the time step containing the given unix timestamp
+    pub fn generate_code(&self, timestamp: u64) -> String {
+        let counter = timestamp / self.step_seconds;
+        
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .expect("HMAC accepts keys of any length");
+        mac.update(&counter.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+        
+        // Dynamic truncation
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+        let code = binary % 10u32.pow(self.digits);
+        
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+    
+    // Accept codes from the current time step and one step either side to allow for clock drift,
+    // returning the time step the code belongs to
+    pub fn verify_code(&self, code: &str, timestamp: u64) -> Option<u64> {
+        [timestamp.saturating_sub(self.step_seconds), timestamp, timestamp + self.step_seconds]
+            .into_iter()
+            .find(|t| bool::from(self.generate_code(*t).as_bytes().ct_eq(code.as_bytes())))
+            .map(|t| t / self.step_seconds)
+    }
+    
+    // Accept a time step for a sender only if it is later than the last one it used
+    fn accept_step(&self, sender: &str, step: u64) -> bool {
+        let mut last_steps = self.last_steps.lock().unwrap();
+        match last_steps.get(sender) {
+            Some(last) if *last >= step => false,
+            _ => {
+                last_steps.insert(sender.to_string(), step);
+                true
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl GuardianSigner for LocalTotpGuardian {
+    fn guardian_address(&self) -> String {
+        self.wallet.address()
+    }
+    
+    async fn co_sign(&self, tx: &TransactionRequest, code: &str) -> AppResult<String> {
+        if tx.guardian.as_deref() != Some(self.guardian_address().as_str()) {
+            return Err(AppError::Validation("Transaction is not guarded by this guardian".to_string()));
+        }
+        
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| AppError::Internal(format!("System clock error: {}", e)))?
+            .as_secs();
+        
+        let step = self.verify_code(code, now)
+            .ok_or_else(|| AppError::Auth("Invalid 2FA code".to_string()))?;
+        
+        if !self.accept_step(&tx.sender, step) {
+            return Err(AppError::Auth("2FA code has already been used".to_string()));
+        }
+        
+        Ok(self.wallet.sign_transaction(&tx.serialize_for_signing()?))
+    }
+}
\ No newline at end of file
@@ -4,17 +4,21 @@ mod config;
 mod db;
 mod elizaos;
 mod error;
+mod guardian;
 mod models;
 mod services;
 mod smart_contracts;
 mod utils;
 mod wallet;
 
+use chrono::{DateTime, Duration, Utc};
 use clap::{Parser, Subcommand};
 use config::AppConfig;
+use elizaos::backtest::{BacktestConfig, Backtester};
 use elizaos::{ElizaOS, ElizaOSConfig};
+use models::backtest::BacktestDataset;
 use std::sync::Arc;
-use tracing::{info, Level};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
 #[derive(Parser)]
@@ -69,6 +73,81 @@ enum Commands {
         #[arg(short, long, default_value_t = 100.0)]
         amount: f64,
     },
+    /// Simulate a leveraged looping strategy on Hatom rates against a mock market
+    SimulateLeverage {
+        /// Token to loop
+        #[arg(short, long, default_value = "EGLD")]
+        token: String,
+        
+        /// Target leverage
+        #[arg(short, long, default_value_t = 2.5)]
+        leverage: f64,
+        
+        /// Health factor below which the position is deleveraged
+        #[arg(short, long, default_value_t = 1.1)]
+        floor: f64,
+        
+        /// Collateral factor of the market
+        #[arg(short, long, default_value_t = 0.7)]
+        collateral_factor: f64,
+        
+        /// Own capital to loop
+        #[arg(short, long, default_value_t = 100.0)]
+        amount: f64,
+        
+        /// Number of days to simulate
+        #[arg(short, long, default_value_t = 365)]
+        days: u32,
+        
+        /// Day on which the borrow APY jumps to --shock-borrow-apy
+        #[arg(long)]
+        shock_day: Option<u32>,
+        
+        /// Borrow APY from --shock-day on
+        #[arg(long)]
+        shock_borrow_apy: Option<f64>,
+    },
+    /// Auto-compound the positions of the COMPOUND_PRIVATE_KEY wallet as they fall due
+    AutoCompound {
+        /// Minutes between checks for positions due for compounding
+        #[arg(short, long, default_value_t = 60)]
+        interval_minutes: u64,
+    },
+    /// Backtest allocation strategies on historical pool APY, TVL and prices
+    Backtest {
+        /// Strategy (conservative, balanced, aggressive), or all to compare them
+        #[arg(short, long, default_value = "all")]
+        strategy: String,
+        
+        /// JSON dataset to replay offline, e.g. fixtures/backtest.json; without it, the history
+        /// recorded in the database is replayed
+        #[arg(short, long)]
+        dataset: Option<String>,
+        
+        /// Start of the recorded history to replay (RFC 3339), defaults to 90 days ago
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+        
+        /// End of the recorded history to replay (RFC 3339), defaults to now
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+        
+        /// Capital invested at the start, in EGLD
+        #[arg(short, long, default_value_t = 100.0)]
+        amount: f64,
+        
+        /// Days between rebalancing checks
+        #[arg(short, long, default_value_t = 7)]
+        rebalance_days: i64,
+        
+        /// Gas per pool entered, exited or resized, in EGLD
+        #[arg(short, long, default_value_t = 0.002)]
+        gas: f64,
+        
+        /// Share of the value moved into pools lost to slippage
+        #[arg(long, default_value_t = 0.003)]
+        slippage: f64,
+    },
 }
 
 #[tokio::main]
@@ -96,6 +175,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
     let config = Arc::new(config);
 
+    // Backtests of a dataset file run offline, without the database or the network
+    if let Some(Commands::Backtest { strategy, dataset: Some(dataset), amount, rebalance_days, gas, slippage, .. }) = &cli.command {
+        let dataset = BacktestDataset::load(std::path::Path::new(dataset))?;
+        let config = BacktestConfig {
+            initial_capital: *amount,
+            rebalance_interval_days: *rebalance_days,
+            gas_cost: *gas,
+            slippage: *slippage,
+            ..BacktestConfig::default()
+        };
+        return run_backtests(&dataset, strategy, config).await;
+    }
+    
     // Initialize database connection
     let db_pool = db::init_db(&config).await?;
     info!("Database connection established");
@@ -104,6 +196,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let blockchain_client = blockchain::MultiversXClient::new(&config);
     info!("Blockchain client initialized");
     
+    // Initialize the protocol registry shared by ElizaOS and the services
+    let protocol_registry = smart_contracts::ProtocolRegistry::new(blockchain_client.clone());
+    let definitions_dir = std::path::Path::new(&config.blockchain.protocol_definitions_dir);
+    let defined = protocol_registry.load_definitions(definitions_dir, blockchain_client.clone())?;
+    info!("Protocol registry initialized with {} defined protocols", defined);
+    
+    // Follow the xExchange farms and metastaking contracts listed in the configuration
+    if let Some(path) = &config.blockchain.maiar_farms_path {
+        match smart_contracts::maiar_farm::MaiarFarmConfig::load(std::path::Path::new(path)) {
+            Ok(farm_config) => protocol_registry.maiar_exchange().set_farm_config(farm_config),
+            Err(e) => error!("xExchange farms disabled, failed to load {}: {}", path, e),
+        }
+    }
+    
     // Initialize ElizaOS
     let elizaos_config = ElizaOSConfig {
         network: config.blockchain.network.clone(),
@@ -112,19 +218,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         rebalance_threshold: 0.05,
         max_concurrent_txs: 10,
         tx_timeout_seconds: 300,
+        scan_concurrency: 8,
+        scan_timeout_seconds: 10,
     };
     
-    let elizaos = ElizaOS::new(blockchain_client.clone(), Some(elizaos_config));
+    let elizaos = match config.blockchain.relayer_private_key {
+        Some(ref private_key) => {
+            // Sponsor user gas through relayed transactions
+            let relayer = wallet::Wallet::from_private_key(&hex::decode(private_key)?)?;
+            let sponsorship = services::SponsorshipService::new(db_pool.clone());
+            ElizaOS::with_relayer(blockchain_client.clone(), protocol_registry.clone(), Some(elizaos_config), relayer, sponsorship)
+        }
+        None => ElizaOS::new(blockchain_client.clone(), protocol_registry.clone(), Some(elizaos_config)),
+    };
     elizaos.initialize().await?;
     info!("ElizaOS initialized successfully");
 
     // Create service container
-    let services = services::ServiceContainer::new(db_pool, blockchain_client, config.clone());
+    let services = services::ServiceContainer::new(db_pool, blockchain_client, protocol_registry, config.clone())
+        .with_transaction_manager(elizaos.get_transaction_manager());
     let services = Arc::new(services);
+    // Opportunities are scored net of the impermanent loss measured from recorded prices
+    let elizaos = elizaos.with_price_service(services.price_service.clone());
 
+    // Follow the transactions of every address holding a position
+    let position_addresses = services.position_service.get_position_addresses().await?;
+    let watched = elizaos.watch_addresses(&position_addresses);
+    info!("Watching {} position addresses", watched);
+    // Revalue positions as their owners transact with the protocols holding them
+    services.position_service.start_chain_updates(elizaos.get_chain_watcher().subscribe());
+    
+    // Pause protocols that are inactive in the database and follow later changes
+    services.protocol_service.sync_protocol_status().await?;
+    services.protocol_service.start_status_sync();
+    
     match cli.command {
         Some(Commands::Serve { port }) => {
             info!("Starting API server on port {}", port);
+            
+            // Build up the price history used to value positions at their entry date
+            services.price_service.start_price_recording();
+            // Record pending rewards so users can follow them until claiming is worth the gas
+            services.position_service.start_reward_snapshots();
+            // Score protocol risk so strategies and the API work from current scores
+            services.risk_service.start_risk_scoring();
+            // Sample pool APY and TVL for the history endpoints and charts
+            services.pool_history_service.start_metrics_recording();
+            
             api::start_server(services, port).await?;
         }
         Some(Commands::GenerateWallet) => {
@@ -146,8 +286,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 _ => models::position::PositionStrategy::Balanced,
             };
             
+            // Score protocol risk first so the strategy filters on current scores
+            services.risk_service.record_risk_scores().await?;
+            
             // Find yield opportunities
-            let opportunities = elizaos.find_best_opportunities(&strategy_enum, token, limit).await?;
+            let scan = elizaos.find_best_opportunities(&strategy_enum, token, limit).await?;
+            let opportunities = scan.items;
             
             println!("Top {} yield opportunities for {} strategy:", 
                      opportunities.len(), strategy);
@@ -156,13 +300,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("{}", "-".repeat(70));
             
             for opp in opportunities {
-                println!("{:<20} {:<20} {:<10.2}% ${:<10.2}M {:<10}", 
+                // TVL is reported in the protocol's own unit, shown in USD when it can be priced
+                let tvl_usd = services.price_service.value(opp.tvl, &opp.tvl_denomination).await
+                    .map(|value| format!("${:.2}M", value.usd / 1_000_000.0))
+                    .unwrap_or_else(|_| format!("{:.2}M {}", opp.tvl / 1_000_000.0, opp.tvl_denomination));
+                
+                println!("{:<20} {:<20} {:<10.2}% {:<11} {:<10}", 
                          opp.protocol_name, 
                          opp.pool_name, 
                          opp.apy, 
-                         opp.tvl / 1_000_000.0,
+                         tvl_usd,
                          opp.risk);
             }
+            
+            if !scan.skipped.is_empty() {
+                println!("\nSkipped:");
+                for skipped in scan.skipped {
+                    match skipped.pool_id {
+                        Some(pool_id) => println!("{} pool {}: {}", skipped.protocol_id, pool_id, skipped.reason),
+                        None => println!("{}: {}", skipped.protocol_id, skipped.reason),
+                    }
+                }
+            }
         }
         Some(Commands::Status) => {
             // Get ElizaOS system status
@@ -180,9 +339,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("{}", "-".repeat(50));
             
             for protocol in status.protocols {
-                println!("{:<20} ${:<10.2} {:<10.2}% {:<10}", 
+                let tvl_usd = services.price_service.value(protocol.tvl, &protocol.tvl_denomination).await
+                    .map(|value| format!("${:.2}", value.usd / 1_000_000.0))
+                    .unwrap_or_else(|_| "n/a".to_string());
+                
+                println!("{:<20} {:<10} {:<10.2}% {:<10}", 
                          protocol.name, 
-                         protocol.tvl / 1_000_000.0,
+                         tvl_usd,
                          protocol.apy,
                          protocol.status);
             }
@@ -195,15 +358,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 _ => models::position::PositionStrategy::Balanced,
             };
             
+            // Score protocol risk first so the strategy filters on current scores
+            services.risk_service.record_risk_scores().await?;
+            
             // Calculate optimal allocation
-            let allocation = elizaos.calculate_optimal_allocation(&strategy_enum, amount).await?;
+            let yield_optimizer = elizaos.get_yield_optimizer();
+            let opportunities = elizaos.find_best_opportunities(&strategy_enum, None, 10).await?.items;
+            let allocation = yield_optimizer.calculate_optimal_allocation(&strategy_enum, amount, &opportunities).await?;
             
-            // Calculate expected APY
-            let expected_apy = elizaos.get_yield_optimizer().calculate_expected_apy(&allocation).await?;
+            // Calculate expected APY, and what is left of it after impermanent loss
+            let expected_apy = yield_optimizer.calculate_expected_apy(&allocation).await?;
+            let risk_metrics = yield_optimizer.calculate_risk_metrics(&allocation, &opportunities).await?;
             
             println!("Optimal portfolio allocation for {} EGLD with {} strategy:", 
                      amount, strategy);
             println!("Expected APY: {:.2}%", expected_apy);
+            println!("Expected impermanent loss per year: {:.2}%", risk_metrics.impermanent_loss * 100.0);
+            println!("Risk-adjusted APY: {:.2}%", risk_metrics.risk_adjusted_apy(expected_apy));
             println!("\n{:<30} {:<15} {:<15}", 
                      "Protocol:Pool", "Amount (EGLD)", "Allocation (%)");
             println!("{}", "-".repeat(60));
@@ -216,12 +387,164 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                          key, value, percentage);
             }
         }
+        Some(Commands::SimulateLeverage { token, leverage, floor, collateral_factor, amount, days, shock_day, shock_borrow_apy }) => {
+            use elizaos::leverage::{simulate, LeverageConfig, LeverageStrategy, MockLendingProvider, RateChange};
+            
+            // Start from the current Hatom rates of the token, or its static rates when the money market can't be read
+            let hatom = smart_contracts::hatom_protocol::HatomProtocol::new(services.blockchain_client.clone());
+            let pool_id = format!("{}-lending", token.to_lowercase());
+            let (supply_apy, borrow_apy, rates_source) = match hatom.get_live_market_rates(&pool_id).await {
+                Ok((supply_apy, borrow_apy)) => (supply_apy, borrow_apy, "live"),
+                Err(e) => {
+                    warn!("Failed to read the live {} rates from Hatom, simulating with static rates: {}", token, e);
+                    let (supply_apy, borrow_apy) = hatom.get_market_rates(&pool_id)?;
+                    (supply_apy, borrow_apy, "static")
+                }
+            };
+            
+            let market = MockLendingProvider::new(&token, supply_apy, borrow_apy, collateral_factor);
+            let strategy = LeverageStrategy::new(market, LeverageConfig {
+                token: token.clone(),
+                target_leverage: leverage,
+                health_factor_floor: floor,
+                ..LeverageConfig::default()
+            }).await?;
+            
+            let rate_changes: Vec<RateChange> = match (shock_day, shock_borrow_apy) {
+                (Some(day), Some(shocked_apy)) => vec![RateChange { day, supply_apy, borrow_apy: shocked_apy }],
+                _ => Vec::new(),
+            };
+            
+            println!("Simulating {:.2}x {} loop with {} {} over {} days", leverage, token, amount, token, days);
+            println!("Supply APY: {:.2}%, Borrow APY: {:.2}% ({} rates), Expected net APY: {:.2}%", 
+                     supply_apy, borrow_apy, rates_source, strategy.expected_net_apy().await?);
+            println!("\n{:<6} {:<12} {:<12} {:<12} {:<10} {:<10} {:<10}", 
+                     "Day", "Supplied", "Borrowed", "Equity", "Leverage", "Health", "Net APY");
+            println!("{}", "-".repeat(76));
+            
+            let steps = simulate(&strategy, amount, days, &rate_changes).await?;
+            for step in &steps {
+                // Print monthly, on the last day, and whenever the position was deleveraged
+                if step.day % 30 != 0 && step.day != days && !step.deleveraged {
+                    continue;
+                }
+                
+                println!("{:<6} {:<12.4} {:<12.4} {:<12.4} {:<10.2} {:<10} {:<10.2}%{}", 
+                         step.day,
+                         step.report.supplied,
+                         step.report.borrowed,
+                         step.equity,
+                         step.report.leverage,
+                         step.report.health_factor.map(|h| format!("{:.3}", h)).unwrap_or_else(|| "-".to_string()),
+                         step.report.net_apy,
+                         if step.deleveraged { " (deleveraged)" } else { "" });
+            }
+            
+            let final_equity = steps.last().map(|s| s.equity).unwrap_or(amount);
+            let return_pct = (final_equity - amount) / amount * 100.0;
+            let closed = strategy.close().await?;
+            println!("\nReturn on capital: {:.2}%; unwound in {} rounds leaving {:.4} {} borrowed", 
+                     return_pct, closed.loops, closed.borrowed, token);
+        }
+        Some(Commands::AutoCompound { interval_minutes }) => {
+            let private_key = config.blockchain.compound_private_key.as_ref()
+                .ok_or("COMPOUND_PRIVATE_KEY must be set to auto-compound")?;
+            let wallet = wallet::Wallet::from_private_key(&hex::decode(private_key)?)?;
+            
+            info!("Auto-compounding positions of {} every {} minutes", wallet.address(), interval_minutes);
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_minutes * 60));
+            
+            loop {
+                interval.tick().await;
+                
+                let results = match elizaos.compound_due_positions(&wallet, &services.position_service).await {
+                    Ok(results) => results,
+                    Err(e) => {
+                        error!("Auto-compound run failed: {}", e);
+                        continue;
+                    }
+                };
+                
+                for result in results {
+                    if result.compounded {
+                        let deposits: Vec<String> = result.deposits.iter()
+                            .map(|deposit| format!("{:.6} {}", deposit.amount, deposit.token))
+                            .collect();
+                        println!("Position {}: deposited {} ({:.6} EGLD gain vs {:.6} EGLD gas)", 
+                                 result.position_id, deposits.join(", "), result.projected_gain, result.gas_cost);
+                    } else {
+                        println!("Position {}: skipped, {:.6} EGLD gain doesn't cover {:.6} EGLD gas", 
+                                 result.position_id, result.projected_gain, result.gas_cost);
+                    }
+                }
+            }
+        }
+        Some(Commands::Backtest { strategy, from, to, amount, rebalance_days, gas, slippage, .. }) => {
+            let to = to.unwrap_or_else(Utc::now);
+            let from = from.unwrap_or_else(|| to - Duration::days(90));
+            let dataset = services.pool_history_service.get_backtest_dataset(from, to).await?;
+            
+            let config = BacktestConfig {
+                initial_capital: amount,
+                rebalance_interval_days: rebalance_days,
+                gas_cost: gas,
+                slippage,
+                ..BacktestConfig::default()
+            };
+            run_backtests(&dataset, &strategy, config).await?;
+        }
         None => {
             // Default to serving the API
             info!("Starting API server on default port 3030");
+            services.risk_service.start_risk_scoring();
             api::start_server(services, 3030).await?;
         }
     }
 
+    Ok(())
+}
+
+// Backtest one strategy, or all of them side by side, and print their reports
+async fn run_backtests(dataset: &BacktestDataset, strategy: &str, config: BacktestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let strategies = match strategy.to_lowercase().as_str() {
+        "all" => vec![
+            models::position::PositionStrategy::Conservative,
+            models::position::PositionStrategy::Balanced,
+            models::position::PositionStrategy::Aggressive,
+        ],
+        "conservative" => vec![models::position::PositionStrategy::Conservative],
+        "aggressive" => vec![models::position::PositionStrategy::Aggressive],
+        _ => vec![models::position::PositionStrategy::Balanced],
+    };
+    
+    let mut reports = Vec::with_capacity(strategies.len());
+    for strategy in strategies {
+        let backtester = Backtester::new(BacktestConfig { strategy, ..config.clone() });
+        reports.push(backtester.run(dataset).await?);
+    }
+    
+    if let Some(report) = reports.first() {
+        println!("Backtest from {} to {} with {} EGLD, rebalancing checked every {} days", 
+                 report.start.format("%Y-%m-%d"), report.end.format("%Y-%m-%d"), config.initial_capital, config.rebalance_interval_days);
+        println!("Gas: {} EGLD per pool moved, slippage: {:.2}%", config.gas_cost, config.slippage * 100.0);
+    }
+    println!("\n{:<14} {:<12} {:<10} {:<10} {:<11} {:<8} {:<10} {:<12} {:<10} {:<10}", 
+             "Strategy", "Final EGLD", "Return", "Annual", "Volatility", "Sharpe", "Drawdown", "Rebalances", "Turnover", "Costs");
+    println!("{}", "-".repeat(112));
+    
+    for report in &reports {
+        println!("{:<14} {:<12.4} {:<9.2}% {:<9.2}% {:<10.2}% {:<8} {:<9.2}% {:<12} {:<10.2} {:<10.4}", 
+                 format!("{:?}", report.strategy),
+                 report.final_value,
+                 report.total_return * 100.0,
+                 report.annualized_return * 100.0,
+                 report.volatility * 100.0,
+                 report.sharpe_ratio.map(|s| format!("{:.2}", s)).unwrap_or_else(|| "-".to_string()),
+                 report.max_drawdown * 100.0,
+                 report.rebalances,
+                 report.turnover,
+                 report.gas_paid + report.slippage_paid);
+    }
+    
     Ok(())
 }
\ No newline at end of file
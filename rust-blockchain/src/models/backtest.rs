@@ -0,0 +1,60 @@
+use crate::error::AppResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+// A pool a backtest can allocate to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestPool {
+    pub protocol_id: String,
+    pub pool_id: String,
+    pub name: String,
+    pub tokens: Vec<String>,
+    // Low, Medium or High
+    pub risk: String,
+    // Protocol risk score (0-100), when the protocol was scored
+    #[serde(default)]
+    pub risk_score: Option<f64>,
+}
+
+impl BacktestPool {
+    // Key of the pool in snapshots and allocations
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.protocol_id, self.pool_id)
+    }
+}
+
+// APY and TVL of a pool at a point of the history
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PoolState {
+    pub apy: f64,
+    // TVL in USD
+    pub tvl: f64,
+}
+
+// Market at a point of the history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestSnapshot {
+    pub date: DateTime<Utc>,
+    // State of each pool by "protocol_id:pool_id"; pools missing from a snapshot can't be allocated to
+    pub pools: HashMap<String, PoolState>,
+    // USD prices by ticker
+    #[serde(default)]
+    pub prices: HashMap<String, f64>,
+}
+
+// History of pools and prices replayed by a backtest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BacktestDataset {
+    pub pools: Vec<BacktestPool>,
+    pub snapshots: Vec<BacktestSnapshot>,
+}
+
+impl BacktestDataset {
+    // Load a dataset from a JSON file, see fixtures/backtest.json
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
\ No newline at end of file
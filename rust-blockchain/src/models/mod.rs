@@ -2,4 +2,7 @@ pub mod transaction;
 pub mod user;
 pub mod position;
 pub mod protocol;
-pub mod pool;
\ No newline at end of file
+pub mod pool;
+pub mod sponsorship;
+pub mod price;
+pub mod backtest;
\ No newline at end of file
@@ -1,7 +1,9 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::types::Json;
 use std::collections::HashMap;
 
+use super::price::Valuation;
 use super::protocol::RiskLevel;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +30,9 @@ pub struct PoolResponse {
     pub apy: f64,
     pub tokens: Vec<String>,
     pub risk: String,
+    // TVL in USD and EGLD, when the protocol reports it live and its denomination is priced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tvl_value: Option<Valuation>,
 }
 
 impl Pool {
@@ -41,6 +46,30 @@ impl Pool {
             apy: self.apy,
             tokens: self.tokens.clone(),
             risk: format!("{:?}", self.risk),
+            tvl_value: None,
         }
     }
+}
+
+// APY and TVL of a pool over one interval of its history, averaged over the samples in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolMetric {
+    pub apy: f64,
+    // TVL in the protocol's TVL unit
+    pub tvl: f64,
+    pub tvl_usd: Option<f64>,
+    // Start of the interval
+    pub recorded_at: DateTime<Utc>,
+}
+
+// History of a pool between two dates, one point per interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolHistory {
+    pub protocol_id: String,
+    pub pool_id: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    // Length of each point: minute, hour, day or week
+    pub interval: String,
+    pub points: Vec<PoolMetric>,
 }
\ No newline at end of file
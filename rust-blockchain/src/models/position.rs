@@ -1,4 +1,6 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use crate::models::price::{ImpermanentLoss, PositionValuation, Valuation};
+use crate::smart_contracts::protocol_interface::{AccountHealth, UnbondingEntry};
 use serde::{Deserialize, Serialize};
 use sqlx::types::{Uuid, Json};
 use std::collections::HashMap;
@@ -18,6 +20,18 @@ pub enum RebalanceFrequency {
     Manual,
 }
 
+impl RebalanceFrequency {
+    // Time between two scheduled runs; None when only run by hand
+    pub fn interval(&self) -> Option<Duration> {
+        match self {
+            RebalanceFrequency::Daily => Some(Duration::days(1)),
+            RebalanceFrequency::Weekly => Some(Duration::weeks(1)),
+            RebalanceFrequency::Monthly => Some(Duration::days(30)),
+            RebalanceFrequency::Manual => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenAllocation {
     pub token: String,
@@ -41,6 +55,9 @@ pub struct Position {
     pub rebalance_frequency: RebalanceFrequency,
     pub allocation: Vec<TokenAllocation>,
     pub metadata: Json<HashMap<String, serde_json::Value>>,
+    // How often claimed rewards are reinvested; Manual turns auto-compounding off
+    pub compound_frequency: RebalanceFrequency,
+    pub last_compound: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -55,6 +72,9 @@ pub struct CreatePositionRequest {
     pub strategy: PositionStrategy,
     pub rebalance_frequency: RebalanceFrequency,
     pub allocation: Vec<TokenAllocation>,
+    // Defaults to Manual
+    #[serde(default)]
+    pub compound_frequency: Option<RebalanceFrequency>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -64,6 +84,7 @@ pub struct UpdatePositionRequest {
     pub strategy: Option<PositionStrategy>,
     pub rebalance_frequency: Option<RebalanceFrequency>,
     pub allocation: Option<Vec<TokenAllocation>>,
+    pub compound_frequency: Option<RebalanceFrequency>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -81,7 +102,21 @@ pub struct PositionResponse {
     pub last_rebalance: DateTime<Utc>,
     pub rebalance_frequency: String,
     pub allocation: Vec<TokenAllocation>,
+    pub compound_frequency: String,
+    pub last_compound: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    // Collateral health of the user's account, for positions in protocols that support borrowing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<AccountHealth>,
+    // Stake the user is unbonding, for positions in protocols with an unbonding period
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unbonding: Option<Vec<UnbondingEntry>>,
+    // Value and profit in USD and EGLD, when token prices are available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valuation: Option<PositionValuation>,
+    // Loss against holding the deposited tokens, for liquidity positions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impermanent_loss: Option<ImpermanentLoss>,
 }
 
 impl From<Position> for PositionResponse {
@@ -100,7 +135,46 @@ impl From<Position> for PositionResponse {
             last_rebalance: position.last_rebalance,
             rebalance_frequency: format!("{:?}", position.rebalance_frequency),
             allocation: position.allocation,
+            compound_frequency: format!("{:?}", position.compound_frequency),
+            last_compound: position.last_compound,
             created_at: position.created_at,
+            health: None,
+            unbonding: None,
+            valuation: None,
+            impermanent_loss: None,
         }
     }
+}
+
+// Rewards a position could claim at a point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewardSnapshot {
+    pub position_id: Uuid,
+    pub token: String,
+    pub amount: f64,
+    pub value_usd: Option<f64>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+// A reward a position can claim, valued when its token is priced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimableReward {
+    pub token: String,
+    pub amount: f64,
+    pub value: Option<Valuation>,
+}
+
+// Claimable rewards of a position, weighed against the gas of claiming them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionRewards {
+    pub position_id: Uuid,
+    pub rewards: Vec<ClaimableReward>,
+    // Total value of the rewards; None unless every reward token is priced
+    pub total_value: Option<Valuation>,
+    // Network fee of the claim transactions, in EGLD
+    pub claim_gas_cost: f64,
+    // Whether the rewards are worth more than the gas of claiming them
+    pub worth_claiming: Option<bool>,
+    // Recorded rewards, most recent first
+    pub snapshots: Vec<RewardSnapshot>,
 }
\ No newline at end of file
@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// USD price of a token, by ticker (EGLD, USDC, MEX)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenPrice {
+    pub token: String,
+    pub price_usd: f64,
+    // Feed the price came from
+    pub source: String,
+    pub recorded_at: DateTime<Utc>,
+}
+
+// An amount expressed in USD and in EGLD
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Valuation {
+    pub usd: f64,
+    pub egld: f64,
+}
+
+// Value and profit of a position in USD and EGLD
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionValuation {
+    pub value: Valuation,
+    // Deposit valued at the prices of the entry date
+    pub deposited: Valuation,
+    pub pnl: Valuation,
+}
+
+// Loss of a liquidity position against holding the tokens it was opened with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpermanentLoss {
+    // Change in value against holding, as a fraction; negative once prices diverge
+    pub loss: f64,
+    pub loss_value: Valuation,
+    // Value of the deposit had its tokens been held instead of pooled
+    pub value_if_held: Valuation,
+    // Units of each pool token deposited, by ticker
+    pub entry_amounts: HashMap<String, f64>,
+    // USD prices of the pool tokens at entry and now, by ticker
+    pub entry_prices: HashMap<String, f64>,
+    pub current_prices: HashMap<String, f64>,
+}
\ No newline at end of file
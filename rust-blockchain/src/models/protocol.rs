@@ -1,3 +1,4 @@
+use crate::models::price::Valuation;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Json;
 use std::collections::HashMap;
@@ -36,6 +37,9 @@ pub struct ProtocolResponse {
     pub risk: String,
     pub tokens: Vec<String>,
     pub website_url: String,
+    // TVL in USD and EGLD, when the protocol reports it live and its denomination is priced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tvl_value: Option<Valuation>,
 }
 
 impl From<Protocol> for ProtocolResponse {
@@ -50,6 +54,7 @@ impl From<Protocol> for ProtocolResponse {
             risk: format!("{:?}", protocol.risk),
             tokens: protocol.tokens,
             website_url: protocol.website_url,
+            tvl_value: None,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+
+// Gas sponsorship budget of a user, in the smallest EGLD denomination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SponsorshipBudget {
+    pub user_id: Uuid,
+    pub allowance: i64,
+    pub spent: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SponsorshipBudgetResponse {
+    pub user_id: Uuid,
+    pub allowance: i64,
+    pub spent: i64,
+    pub remaining: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetSponsorshipAllowanceRequest {
+    pub allowance: i64,
+}
+
+impl SponsorshipBudget {
+    pub fn remaining(&self) -> i64 {
+        (self.allowance - self.spent).max(0)
+    }
+}
+
+impl From<SponsorshipBudget> for SponsorshipBudgetResponse {
+    fn from(budget: SponsorshipBudget) -> Self {
+        Self {
+            remaining: budget.remaining(),
+            user_id: budget.user_id,
+            allowance: budget.allowance,
+            spent: budget.spent,
+        }
+    }
+}
\ No newline at end of file
@@ -1,4 +1,5 @@
 use chrono::{DateTime, Utc};
+use crate::blockchain::TransactionRequest;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Uuid;
 
@@ -24,7 +25,7 @@ pub enum TransactionStatus {
 pub struct Transaction {
     pub id: Uuid,
     pub user_id: Uuid,
-    pub tx_hash: String,
+    pub tx_hash: Option<String>,
     pub tx_type: TransactionType,
     pub amount: Option<String>,
     pub token: Option<String>,
@@ -45,10 +46,101 @@ pub struct CreateTransactionRequest {
     pub position_id: Option<Uuid>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildTransactionRequest {
+    pub tx_type: TransactionType,
+    pub protocol_id: String,
+    pub position_id: Option<Uuid>,
+    pub amount: Option<f64>,
+    pub token: Option<String>,
+    #[serde(default)]
+    pub pool_id: Option<String>,
+    // Have the platform relayer pay the gas, within the user's sponsorship budget
+    #[serde(default)]
+    pub sponsored: bool,
+}
+
+// xExchange farm and metastaking operations on a pool's LP tokens
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum FarmOperation {
+    EnterFarm,
+    ExitFarm,
+    ClaimFarmRewards,
+    EnterMetastaking,
+    ExitMetastaking,
+    ClaimMetastaking,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FarmTransactionRequest {
+    pub operation: FarmOperation,
+    pub pool_id: String,
+    // LP tokens to stake when entering a farm
+    pub lp_amount: Option<f64>,
+    // Nonce and raw amount of the farm or metastaking token, for every other operation
+    pub token_nonce: Option<u64>,
+    pub token_amount: Option<String>,
+    #[serde(default)]
+    pub sponsored: bool,
+}
+
+// Hatom money market operations beyond supplying and withdrawing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum LendingOperation {
+    Borrow,
+    Repay,
+    EnterMarket,
+    ExitMarket,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LendingTransactionRequest {
+    pub operation: LendingOperation,
+    // Underlying token to borrow or repay
+    pub token: Option<String>,
+    // Lending pool whose hTokens enter or exit collateral
+    pub pool_id: Option<String>,
+    // Underlying tokens to borrow or repay, or hTokens to move in or out of collateral
+    pub amount: f64,
+    #[serde(default)]
+    pub sponsored: bool,
+}
+
+// Delegation operations beyond delegating, undelegating and claiming
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum DelegationOperation {
+    WithdrawUnbonded,
+    RedelegateRewards,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DelegationTransactionRequest {
+    pub operation: DelegationOperation,
+    // Staking provider the stake or rewards are with
+    pub provider: String,
+    #[serde(default)]
+    pub sponsored: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnsignedTransactionResponse {
+    pub transaction_id: Uuid,
+    pub transaction: TransactionRequest,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BroadcastTransactionRequest {
+    pub transaction_id: Uuid,
+    pub transaction: TransactionRequest,
+    // 2FA code for guarded accounts, when the platform guardian co-signs
+    #[serde(default)]
+    pub guardian_code: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionResponse {
     pub id: Uuid,
-    pub tx_hash: String,
+    pub tx_hash: Option<String>,
     pub tx_type: String,
     pub amount: Option<String>,
     pub token: Option<String>,
@@ -1,22 +1,37 @@
 mod auth_service;
+mod pool_history_service;
 mod position_service;
+mod price_service;
 mod protocol_service;
+mod risk_service;
+mod sponsorship_service;
 mod transaction_service;
 mod user_service;
 mod yield_optimizer_service;
 
 pub use auth_service::AuthService;
+pub use pool_history_service::PoolHistoryService;
 pub use position_service::PositionService;
+pub use price_service::{entry_amounts, impermanent_loss, price_ticker, FixturePriceFeed, PriceService};
 pub use protocol_service::ProtocolService;
+pub use risk_service::RiskService;
+pub use sponsorship_service::SponsorshipService;
 pub use transaction_service::TransactionService;
 pub use user_service::UserService;
 pub use yield_optimizer_service::YieldOptimizerService;
 
 use crate::blockchain::MultiversXClient;
 use crate::config::AppConfig;
+use crate::elizaos::transaction_manager::TransactionManager;
+use crate::error::AppError;
+use crate::guardian::LocalTotpGuardian;
+use crate::smart_contracts::risk::RiskConfig;
 use crate::smart_contracts::ProtocolRegistry;
+use crate::smart_contracts::router::SwapRouter;
+use crate::wallet::Wallet;
 use sqlx::PgPool;
 use std::sync::Arc;
+use tracing::{info, warn};
 
 pub struct ServiceContainer {
     pub db_pool: PgPool,
@@ -26,21 +41,74 @@ pub struct ServiceContainer {
     pub auth_service: AuthService,
     pub user_service: UserService,
     pub protocol_service: ProtocolService,
+    pub risk_service: RiskService,
+    pub pool_history_service: PoolHistoryService,
     pub position_service: PositionService,
+    pub price_service: PriceService,
+    pub swap_router: SwapRouter,
     pub transaction_service: TransactionService,
+    pub sponsorship_service: SponsorshipService,
     pub yield_optimizer_service: YieldOptimizerService,
 }
 
 impl ServiceContainer {
-    pub fn new(db_pool: PgPool, blockchain_client: MultiversXClient, config: Arc<AppConfig>) -> Self {
-        let protocol_registry = ProtocolRegistry::new(blockchain_client.clone());
-        
+    pub fn new(
+        db_pool: PgPool,
+        blockchain_client: MultiversXClient,
+        protocol_registry: ProtocolRegistry,
+        config: Arc<AppConfig>,
+    ) -> Self {
         let auth_service = AuthService::new(db_pool.clone(), config.clone());
         let user_service = UserService::new(db_pool.clone());
-        let protocol_service = ProtocolService::new(db_pool.clone(), protocol_registry.clone());
-        let position_service = PositionService::new(db_pool.clone(), blockchain_client.clone());
-        let transaction_service = TransactionService::new(db_pool.clone(), blockchain_client.clone());
+        
+        // Price tokens from xExchange, then from local fixture prices when configured
+        let mut price_service = PriceService::new(db_pool.clone(), protocol_registry.clone());
+        if let Some(path) = &config.blockchain.price_fixture_path {
+            match FixturePriceFeed::load(std::path::Path::new(path)) {
+                Ok(feed) => {
+                    info!("Using fixture prices from {}", path);
+                    price_service = price_service.with_feed(Arc::new(feed));
+                }
+                Err(e) => warn!("Fixture prices disabled, failed to load {}: {}", path, e),
+            }
+        }
+        
+        let protocol_service = ProtocolService::new(db_pool.clone(), protocol_registry.clone(), price_service.clone());
+        
+        // Score protocol risk from the configured weights and protocol facts, or from the defaults
+        let mut risk_service = RiskService::new(db_pool.clone(), protocol_registry.clone(), price_service.clone());
+        if let Some(path) = &config.blockchain.risk_config_path {
+            match RiskConfig::load(std::path::Path::new(path)) {
+                Ok(risk_config) => {
+                    info!("Using risk configuration from {}", path);
+                    risk_service = risk_service.with_config(risk_config);
+                }
+                Err(e) => warn!("Default risk configuration used, failed to load {}: {}", path, e),
+            }
+        }
+        
+        let pool_history_service = PoolHistoryService::new(db_pool.clone(), protocol_registry.clone(), price_service.clone());
+        let position_service = PositionService::new(db_pool.clone(), blockchain_client.clone(), protocol_registry.clone(), price_service.clone());
+        let mut transaction_service = TransactionService::new(db_pool.clone(), blockchain_client.clone(), protocol_registry.clone());
+        
+        // Co-sign guarded transactions with a local 2FA guardian when one is configured
+        if let (Some(private_key), Some(totp_secret)) = (&config.blockchain.guardian_private_key, &config.blockchain.guardian_totp_secret) {
+            let guardian_wallet = hex::decode(private_key)
+                .map_err(|e| AppError::Wallet(format!("Invalid guardian private key: {}", e)))
+                .and_then(|key| Wallet::from_private_key(&key));
+            
+            match guardian_wallet {
+                Ok(wallet) => {
+                    info!("Using local guardian {}", wallet.address());
+                    let guardian = LocalTotpGuardian::new(wallet, totp_secret.as_bytes().to_vec());
+                    transaction_service = transaction_service.with_guardian_signer(Arc::new(guardian));
+                }
+                Err(e) => warn!("Guardian co-signing disabled: {}", e),
+            }
+        }
+        let sponsorship_service = SponsorshipService::new(db_pool.clone());
         let yield_optimizer_service = YieldOptimizerService::new(protocol_registry.clone());
+        let swap_router = SwapRouter::new(protocol_registry.clone());
         
         Self {
             db_pool,
@@ -50,9 +118,23 @@ impl ServiceContainer {
             auth_service,
             user_service,
             protocol_service,
+            risk_service,
+            pool_history_service,
             position_service,
+            price_service,
+            swap_router,
             transaction_service,
+            sponsorship_service,
             yield_optimizer_service,
         }
     }
+    
+    // Let users submit gas-sponsored transactions when the transaction manager has a relayer
+    pub fn with_transaction_manager(mut self, tx_manager: Arc<TransactionManager>) -> Self {
+        if tx_manager.relayer_address().is_some() {
+            self.transaction_service = self.transaction_service
+                .with_relayed_transactions(tx_manager, self.sponsorship_service.clone());
+        }
+        self
+    }
 }
\ No newline at end of file
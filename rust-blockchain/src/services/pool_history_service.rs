@@ -0,0 +1,364 @@
+use crate::error::{AppError, AppResult};
+use crate::models::backtest::{BacktestDataset, BacktestPool, BacktestSnapshot, PoolState};
+use crate::models::pool::{PoolHistory, PoolMetric};
+use crate::services::PriceService;
+use crate::smart_contracts::protocol_interface::ProtocolInterface;
+use crate::smart_contracts::scan::{scan_pools, scan_protocols, ScanLimits, ScanResult};
+use crate::smart_contracts::ProtocolRegistry;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{info, error, warn};
+
+// How often every pool is sampled
+const SAMPLE_INTERVAL_SECONDS: u64 = 300;
+// Age from which raw samples are rolled up into hourly averages
+const RAW_RETENTION_DAYS: i64 = 2;
+// Age from which hourly averages are rolled up into daily averages
+const HOURLY_RETENTION_DAYS: i64 = 30;
+// Age from which daily averages are deleted
+const DAILY_RETENTION_DAYS: i64 = 730;
+// Intervals a history can be returned in, as Postgres date_trunc units
+const HISTORY_INTERVALS: [&str; 4] = ["minute", "hour", "day", "week"];
+
+// APY and TVL of a pool at the time of a sample
+struct PoolSample {
+    protocol_id: String,
+    pool_id: String,
+    apy: f64,
+    tvl: f64,
+    tvl_usd: Option<f64>,
+}
+
+#[derive(Clone)]
+pub struct PoolHistoryService {
+    db_pool: PgPool,
+    protocol_registry: ProtocolRegistry,
+    price_service: PriceService,
+    scan_limits: ScanLimits,
+}
+
+impl PoolHistoryService {
+    pub fn new(db_pool: PgPool, protocol_registry: ProtocolRegistry, price_service: PriceService) -> Self {
+        Self {
+            db_pool,
+            protocol_registry,
+            price_service,
+            scan_limits: ScanLimits::default(),
+        }
+    }
+    
+    // Set the concurrency and timeout limits of the pool sampling
+    pub fn with_scan_limits(mut self, scan_limits: ScanLimits) -> Self {
+        self.scan_limits = scan_limits;
+        self
+    }
+    
+    // Sample the APY and TVL of every pool in the registry; protocols and pools that fail
+    // or time out, and samples that fail to be stored, are left out of this round
+    pub async fn record_pool_metrics(&self) -> AppResult<usize> {
+        let scans = self.protocol_registry.get_all_protocols().into_iter()
+            .map(|protocol| {
+                let protocol_id = protocol.get_id().to_string();
+                let scan = self.sample_protocol(protocol);
+                (protocol_id, scan)
+            })
+            .collect();
+        
+        let scan = scan_protocols(scans, &self.scan_limits).await;
+        
+        let recorded_at = Utc::now();
+        let mut count = 0;
+        for sample in &scan.items {
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO pool_metrics (protocol_id, pool_id, apy, tvl, tvl_usd, recorded_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+                sample.protocol_id,
+                sample.pool_id,
+                sample.apy,
+                sample.tvl,
+                sample.tvl_usd,
+                recorded_at
+            )
+            .execute(&self.db_pool)
+            .await;
+            match result {
+                Ok(_) => count += 1,
+                Err(e) => warn!("Failed to record metrics of pool {}:{}: {}", sample.protocol_id, sample.pool_id, e),
+            }
+        }
+        
+        Ok(count)
+    }
+    
+    // Roll aged samples up into coarser averages and delete the daily averages past their retention,
+    // returning the number of rows removed
+    pub async fn downsample_pool_metrics(&self) -> AppResult<u64> {
+        let now = Utc::now();
+        let mut removed = self.roll_up("raw", "hour", now - Duration::days(RAW_RETENTION_DAYS)).await?;
+        removed += self.roll_up("hour", "day", now - Duration::days(HOURLY_RETENTION_DAYS)).await?;
+        
+        let expired = sqlx::query!(
+            r#"
+            DELETE FROM pool_metrics WHERE resolution = 'day' AND recorded_at < $1
+            "#,
+            now - Duration::days(DAILY_RETENTION_DAYS)
+        )
+        .execute(&self.db_pool)
+        .await?;
+        
+        Ok(removed + expired.rows_affected())
+    }
+    
+    // Sample pools periodically in the background, downsampling the history after each round
+    pub fn start_metrics_recording(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(SAMPLE_INTERVAL_SECONDS));
+            loop {
+                interval.tick().await;
+                match service.record_pool_metrics().await {
+                    Ok(count) => info!("Recorded metrics of {} pools", count),
+                    Err(e) => error!("Failed to record pool metrics: {}", e),
+                }
+                if let Err(e) = service.downsample_pool_metrics().await {
+                    error!("Failed to downsample pool metrics: {}", e);
+                }
+            }
+        });
+    }
+    
+    // Get the APY and TVL of a protocol's pool over a period, averaged per interval; without an
+    // interval, the finest one still kept for the start of the period is used
+    pub async fn get_pool_history(&self,
+                                 protocol_id: &str,
+                                 pool_id: &str,
+                                 from: DateTime<Utc>,
+                                 to: DateTime<Utc>,
+                                 interval: Option<&str>) -> AppResult<PoolHistory> {
+        if from > to {
+            return Err(AppError::Validation("History must start before it ends".to_string()));
+        }
+        
+        let interval = match interval {
+            Some(interval) if HISTORY_INTERVALS.contains(&interval) => interval,
+            Some(interval) => return Err(AppError::Validation(format!(
+                "Invalid interval {}; expected one of {}", interval, HISTORY_INTERVALS.join(", ")
+            ))),
+            None => {
+                let age = Utc::now() - from;
+                if age <= Duration::days(RAW_RETENTION_DAYS) {
+                    "minute"
+                } else if age <= Duration::days(HOURLY_RETENTION_DAYS) {
+                    "hour"
+                } else {
+                    "day"
+                }
+            }
+        };
+        
+        let rows = sqlx::query!(
+            r#"
+            SELECT 
+                date_trunc($5, recorded_at) as "recorded_at!",
+                AVG(apy) as "apy!",
+                AVG(tvl) as "tvl!",
+                AVG(tvl_usd) as tvl_usd
+            FROM pool_metrics
+            WHERE protocol_id = $1 AND pool_id = $2 AND recorded_at BETWEEN $3 AND $4
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+            protocol_id,
+            pool_id,
+            from,
+            to,
+            interval
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        Ok(PoolHistory {
+            protocol_id: protocol_id.to_string(),
+            pool_id: pool_id.to_string(),
+            from,
+            to,
+            interval: interval.to_string(),
+            points: rows.into_iter()
+                .map(|row| PoolMetric {
+                    apy: row.apy,
+                    tvl: row.tvl,
+                    tvl_usd: row.tvl_usd,
+                    recorded_at: row.recorded_at,
+                })
+                .collect(),
+        })
+    }
+    
+    // Build a backtest dataset from the recorded history, with one snapshot per day holding
+    // the average APY and USD TVL of every pool still in the registry and the average token prices.
+    // Samples without a USD TVL are left out, as their TVL is in the protocol's own unit
+    pub async fn get_backtest_dataset(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> AppResult<BacktestDataset> {
+        let metrics = sqlx::query!(
+            r#"
+            SELECT 
+                date_trunc('day', recorded_at) as "day!",
+                protocol_id,
+                pool_id,
+                AVG(apy) as "apy!",
+                AVG(tvl_usd) as "tvl!"
+            FROM pool_metrics
+            WHERE recorded_at BETWEEN $1 AND $2 AND tvl_usd IS NOT NULL
+            GROUP BY 1, 2, 3
+            "#,
+            from,
+            to
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        let prices = sqlx::query!(
+            r#"
+            SELECT date_trunc('day', recorded_at) as "day!", token, AVG(price_usd) as "price_usd!"
+            FROM token_prices
+            WHERE recorded_at BETWEEN $1 AND $2
+            GROUP BY 1, 2
+            "#,
+            from,
+            to
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        let recorded: BTreeSet<(String, String)> = metrics.iter()
+            .map(|row| (row.protocol_id.clone(), row.pool_id.clone()))
+            .collect();
+        let pools = self.backtest_pools(recorded).await;
+        let known: HashSet<String> = pools.iter().map(|pool| pool.key()).collect();
+        
+        // Pools no longer in the registry have no tokens or risk to allocate on, so they are left out
+        let mut snapshots: BTreeMap<DateTime<Utc>, BacktestSnapshot> = BTreeMap::new();
+        for row in metrics {
+            let snapshot = snapshots.entry(row.day)
+                .or_insert_with(|| BacktestSnapshot { date: row.day, pools: HashMap::new(), prices: HashMap::new() });
+            let key = format!("{}:{}", row.protocol_id, row.pool_id);
+            if known.contains(&key) {
+                snapshot.pools.insert(key, PoolState { apy: row.apy, tvl: row.tvl });
+            }
+        }
+        if let Some(snapshot) = snapshots.values().find(|snapshot| snapshot.pools.is_empty()) {
+            return Err(AppError::Validation(format!(
+                "No pool recorded on {} is still in the registry", snapshot.date.format("%Y-%m-%d")
+            )));
+        }
+        
+        // Prices only matter on days with pool metrics
+        for row in prices {
+            if let Some(snapshot) = snapshots.get_mut(&row.day) {
+                snapshot.prices.insert(row.token, row.price_usd);
+            }
+        }
+        
+        Ok(BacktestDataset {
+            pools,
+            snapshots: snapshots.into_values().collect(),
+        })
+    }
+    
+    // Helper method to describe recorded pools from the registry; pools whose protocol or info
+    // can't be found are left out
+    async fn backtest_pools(&self, recorded: BTreeSet<(String, String)>) -> Vec<BacktestPool> {
+        let mut pools = Vec::with_capacity(recorded.len());
+        for (protocol_id, pool_id) in recorded {
+            let Some(protocol) = self.protocol_registry.get_protocol(&protocol_id) else {
+                warn!("Leaving pool {}:{} out of the backtest: its protocol is not registered", protocol_id, pool_id);
+                continue;
+            };
+            
+            match protocol.get_pool_info(&pool_id).await {
+                Ok(info) => pools.push(BacktestPool {
+                    protocol_id,
+                    pool_id,
+                    name: info.name,
+                    tokens: info.tokens,
+                    risk: protocol.get_risk_level().to_string(),
+                    // Today's score would leak the outcome into the past, so pools are filtered on their level only
+                    risk_score: None,
+                }),
+                Err(e) => warn!("Leaving pool {}:{} out of the backtest: {}", protocol_id, pool_id, e),
+            }
+        }
+        pools
+    }
+    
+    // Helper method to sample every pool of a protocol, pricing its TVL unit once
+    async fn sample_protocol(&self, protocol: Arc<dyn ProtocolInterface + Send + Sync>) -> AppResult<ScanResult<PoolSample>> {
+        let pools = protocol.get_pools().await?;
+        
+        let denomination = protocol.get_tvl_denomination();
+        let usd_price = match self.price_service.get_price(denomination).await {
+            Ok(price) => Some(price),
+            Err(e) => {
+                warn!("Failed to price TVL of protocol {} in {}: {}", protocol.get_id(), denomination, e);
+                None
+            }
+        };
+        
+        let protocol = &protocol;
+        let scan = scan_pools(protocol.get_id(), pools, &self.scan_limits, |pool_id| async move {
+            let (apy, tvl) = tokio::try_join!(
+                protocol.get_pool_apy(&pool_id),
+                protocol.get_pool_tvl(&pool_id),
+            )?;
+            
+            Ok(Some(PoolSample {
+                protocol_id: protocol.get_id().to_string(),
+                pool_id,
+                apy,
+                tvl,
+                tvl_usd: usd_price.map(|price| tvl * price),
+            }))
+        }).await;
+        
+        Ok(scan)
+    }
+    
+    // Helper method to replace the samples of a resolution recorded before a date with their
+    // averages over each interval of the coarser resolution; only whole intervals are rolled up,
+    // so no interval is averaged twice
+    async fn roll_up(&self, resolution: &str, coarser_resolution: &str, before: DateTime<Utc>) -> AppResult<u64> {
+        let mut tx = self.db_pool.begin().await?;
+        
+        sqlx::query!(
+            r#"
+            INSERT INTO pool_metrics (protocol_id, pool_id, apy, tvl, tvl_usd, resolution, recorded_at)
+            SELECT protocol_id, pool_id, AVG(apy), AVG(tvl), AVG(tvl_usd), $2, date_trunc($2, recorded_at)
+            FROM pool_metrics
+            WHERE resolution = $1 AND recorded_at < date_trunc($2, $3::timestamptz)
+            GROUP BY protocol_id, pool_id, date_trunc($2, recorded_at)
+            "#,
+            resolution,
+            coarser_resolution,
+            before
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        let removed = sqlx::query!(
+            r#"
+            DELETE FROM pool_metrics WHERE resolution = $1 AND recorded_at < date_trunc($2, $3::timestamptz)
+            "#,
+            resolution,
+            coarser_resolution,
+            before
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        tx.commit().await?;
+        Ok(removed.rows_affected())
+    }
+}
\ No newline at end of file
@@ -1,24 +1,41 @@
 use crate::error::{AppError, AppResult};
 use crate::models::position::{
-    CreatePositionRequest, Position, PositionResponse, PositionStrategy,
-    RebalanceFrequency, TokenAllocation, UpdatePositionRequest,
+    ClaimableReward, CreatePositionRequest, Position, PositionResponse, PositionRewards,
+    PositionStrategy, RebalanceFrequency, RewardSnapshot, TokenAllocation, UpdatePositionRequest,
 };
+use crate::models::price::Valuation;
 use crate::blockchain::{BlockchainProvider, MultiversXClient};
+use crate::elizaos::chain_watcher::WatchedTransaction;
+use crate::services::{entry_amounts, price_ticker, PriceService};
+use crate::smart_contracts::ProtocolRegistry;
+use crate::smart_contracts::maiar_farm::LpFarmPosition;
+use crate::smart_contracts::protocol_interface::{AccountHealth, SwapCurve, UnbondingEntry};
 use crate::utils::{egld_to_denomination, denomination_to_egld};
 use chrono::Utc;
 use sqlx::PgPool;
 use std::collections::HashMap;
-use tracing::{info, error, debug};
+use tokio::sync::broadcast;
+use tracing::{info, error, debug, warn};
 use uuid::Uuid;
 
+// How often the pending rewards of every position are recorded
+const REWARD_SNAPSHOT_INTERVAL_SECONDS: u64 = 3600;
+// Number of reward snapshots returned with a position's rewards
+const REWARD_SNAPSHOT_LIMIT: i64 = 500;
+// Age past which reward snapshots are deleted
+const REWARD_SNAPSHOT_RETENTION_DAYS: i64 = 90;
+
+#[derive(Clone)]
 pub struct PositionService {
     db_pool: PgPool,
     blockchain_client: MultiversXClient,
+    protocol_registry: ProtocolRegistry,
+    price_service: PriceService,
 }
 
 impl PositionService {
-    pub fn new(db_pool: PgPool, blockchain_client: MultiversXClient) -> Self {
-        Self { db_pool, blockchain_client }
+    pub fn new(db_pool: PgPool, blockchain_client: MultiversXClient, protocol_registry: ProtocolRegistry, price_service: PriceService) -> Self {
+        Self { db_pool, blockchain_client, protocol_registry, price_service }
     }
     
     pub async fn create_position(
@@ -55,6 +72,10 @@ impl PositionService {
             ));
         }
         
+        // Record token prices and amounts at entry, against which impermanent loss is measured
+        let entry_prices = self.price_service.entry_prices(&request.tokens).await;
+        let entry_amounts = entry_amounts(request.amount, &request.allocation, &entry_prices);
+        
         // Begin database transaction
         let mut tx = self.db_pool.begin().await?;
         
@@ -65,7 +86,9 @@ impl PositionService {
             "created_by": "agentx_blockchain",
             "version": "1.0.0",
             "blockchain_tx": null,
-            "last_rebalance_tx": null
+            "last_rebalance_tx": null,
+            "entry_prices": entry_prices,
+            "entry_amounts": entry_amounts
         });
         
         // Create position record
@@ -76,10 +99,10 @@ impl PositionService {
                 id, user_id, protocol_id, name, position_type, tokens,
                 deposited, current_value, apy, strategy, entry_date,
                 last_rebalance, rebalance_frequency, allocation, metadata,
-                created_at, updated_at
+                compound_frequency, created_at, updated_at
             )
             VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18
             )
             RETURNING 
                 id, user_id, protocol_id, name, position_type, tokens,
@@ -87,6 +110,7 @@ impl PositionService {
                 entry_date, last_rebalance, 
                 rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                 allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                 created_at, updated_at
             "#,
             position_id,
@@ -104,6 +128,7 @@ impl PositionService {
             request.rebalance_frequency as _,
             &request.allocation as _,
             serde_json::to_value(metadata)? as _,
+            request.compound_frequency.unwrap_or(RebalanceFrequency::Manual) as _,
             now,
             now
         )
@@ -163,6 +188,8 @@ impl PositionService {
             let amount = request.amount;
             let position_id = position_id;
             let tx_id = tx_id;
+            let entry_prices = entry_prices.clone();
+            let entry_amounts = entry_amounts.clone();
             
             async move {
                 // In a real implementation, this would create and submit a blockchain transaction
@@ -199,7 +226,9 @@ impl PositionService {
                     "created_by": "agentx_blockchain",
                     "version": "1.0.0",
                     "blockchain_tx": tx_hash,
-                    "last_rebalance_tx": null
+                    "last_rebalance_tx": null,
+                    "entry_prices": entry_prices,
+                    "entry_amounts": entry_amounts
                 });
                 
                 let result = sqlx::query!(
@@ -243,6 +272,7 @@ impl PositionService {
                         entry_date, last_rebalance, 
                         rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                         allocation as "allocation: Vec<TokenAllocation>", metadata,
+                        compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                         created_at, updated_at
                     FROM positions
                     WHERE user_id = $1 AND protocol_id = $2
@@ -264,6 +294,7 @@ impl PositionService {
                         entry_date, last_rebalance, 
                         rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                         allocation as "allocation: Vec<TokenAllocation>", metadata,
+                        compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                         created_at, updated_at
                     FROM positions
                     WHERE user_id = $1
@@ -279,7 +310,13 @@ impl PositionService {
         // Update current values from blockchain if needed
         let updated_positions = self.update_position_values(positions).await?;
         
-        Ok(updated_positions.into_iter().map(PositionResponse::from).collect())
+        let entries: Vec<_> = updated_positions.iter().map(stored_entry).collect();
+        let mut responses: Vec<PositionResponse> = updated_positions.into_iter().map(PositionResponse::from).collect();
+        self.attach_account_state(user_id, &mut responses).await?;
+        self.attach_valuations(&mut responses).await;
+        self.attach_impermanent_loss(&mut responses, &entries).await;
+        
+        Ok(responses)
     }
     
     pub async fn get_position_by_id(&self, id: Uuid, user_id: Uuid) -> AppResult<PositionResponse> {
@@ -292,6 +329,7 @@ impl PositionService {
                 entry_date, last_rebalance, 
                 rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                 allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                 created_at, updated_at
             FROM positions
             WHERE id = $1 AND user_id = $2
@@ -305,7 +343,13 @@ impl PositionService {
         // Get latest position value from blockchain
         let updated_position = self.update_position_value(position).await?;
         
-        Ok(PositionResponse::from(updated_position))
+        let entries = vec![stored_entry(&updated_position)];
+        let mut responses = vec![PositionResponse::from(updated_position)];
+        self.attach_account_state(user_id, &mut responses).await?;
+        self.attach_valuations(&mut responses).await;
+        self.attach_impermanent_loss(&mut responses, &entries).await;
+        
+        Ok(responses.remove(0))
     }
     
     pub async fn update_position(
@@ -334,6 +378,7 @@ impl PositionService {
                 entry_date, last_rebalance, 
                 rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                 allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                 created_at, updated_at
             FROM positions
             WHERE id = $1 AND user_id = $2
@@ -363,7 +408,8 @@ impl PositionService {
                 strategy = COALESCE($5, strategy),
                 rebalance_frequency = COALESCE($6, rebalance_frequency),
                 allocation = COALESCE($7, allocation),
-                updated_at = $8
+                compound_frequency = COALESCE($8, compound_frequency),
+                updated_at = $9
             WHERE id = $1 AND user_id = $2
             RETURNING 
                 id, user_id, protocol_id, name, position_type, tokens,
@@ -371,6 +417,7 @@ impl PositionService {
                 entry_date, last_rebalance, 
                 rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                 allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                 created_at, updated_at
             "#,
             id,
@@ -380,6 +427,7 @@ impl PositionService {
             request.strategy as _,
             request.rebalance_frequency as _,
             request.allocation as _,
+            request.compound_frequency as _,
             now
         )
         .fetch_one(&mut *tx)
@@ -516,6 +564,7 @@ impl PositionService {
                 entry_date, last_rebalance, 
                 rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                 allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                 created_at, updated_at
             FROM positions
             WHERE id = $1 AND user_id = $2
@@ -646,6 +695,7 @@ impl PositionService {
                 entry_date, last_rebalance, 
                 rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                 allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                 created_at, updated_at
             FROM positions
             WHERE id = $1 AND user_id = $2
@@ -714,6 +764,7 @@ impl PositionService {
                 entry_date, last_rebalance, 
                 rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                 allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                 created_at, updated_at
             "#,
             id,
@@ -796,6 +847,536 @@ impl PositionService {
         Ok(PositionResponse::from(updated_position))
     }
     
+    // Get the user's LP, farm and metastaking holdings in xExchange pools
+    pub async fn get_farm_positions(&self, user_id: Uuid) -> AppResult<Vec<LpFarmPosition>> {
+        let user = sqlx::query!(
+            r#"
+            SELECT address FROM users WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        self.protocol_registry.maiar_exchange().get_lp_farm_positions(&user.address).await
+    }
+    
+    // Get the addresses of the users holding positions, for the chain watcher to follow
+    pub async fn get_position_addresses(&self) -> AppResult<Vec<String>> {
+        let addresses = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT u.address
+            FROM users u
+            JOIN positions p ON p.user_id = u.id
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        Ok(addresses)
+    }
+    
+    // Get the positions of the user owning an address that are due for auto-compounding
+    pub async fn get_positions_due_for_compound(&self, address: &str) -> AppResult<Vec<Position>> {
+        let positions = sqlx::query_as!(
+            Position,
+            r#"
+            SELECT 
+                id, user_id, protocol_id, name, position_type, tokens,
+                deposited, current_value, apy, strategy as "strategy: PositionStrategy",
+                entry_date, last_rebalance, 
+                rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
+                allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
+                created_at, updated_at
+            FROM positions
+            WHERE user_id = (SELECT id FROM users WHERE address = $1)
+              AND compound_frequency <> 'Manual'
+            "#,
+            address
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        let now = Utc::now();
+        Ok(positions.into_iter()
+            .filter(|position| {
+                let since = position.last_compound.unwrap_or(position.entry_date);
+                position.compound_frequency.interval().map_or(false, |interval| since + interval <= now)
+            })
+            .collect())
+    }
+    
+    // Record the claim of a position's rewards made to compound them, which restarts its
+    // compound interval even if nothing could be deposited back
+    pub async fn record_compound_claim(&self, position: &Position, tx_hash: &str) -> AppResult<()> {
+        let now = Utc::now();
+        let mut tx = self.db_pool.begin().await?;
+        
+        sqlx::query!(
+            r#"
+            UPDATE positions
+            SET last_compound = $2, updated_at = $2
+            WHERE id = $1
+            "#,
+            position.id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        sqlx::query!(
+            r#"
+            INSERT INTO transactions (
+                id, user_id, tx_hash, tx_type, amount, token,
+                status, protocol_id, position_id, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            Uuid::new_v4(),
+            position.user_id,
+            Some(tx_hash),
+            "Claim",
+            None::<String>,
+            None::<String>,
+            "Success",
+            Some(position.protocol_id.clone()),
+            Some(position.id),
+            now,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        tx.commit().await?;
+        Ok(())
+    }
+    
+    // Record rewards reinvested into a position as one of its tokens; the position's value and
+    // deposit are increased by their EGLD value, so the value recomputed from the deposit keeps
+    // them, or left as is when the token can't be priced
+    pub async fn record_compound(
+        &self,
+        position: &Position,
+        amount: f64,
+        token: &str,
+        tx_hash: Option<String>,
+    ) -> AppResult<()> {
+        let value = match self.price_service.value(amount, token).await {
+            Ok(value) => value.egld,
+            Err(e) => {
+                warn!("Failed to value {} {} compounded into position {}; its value is left unchanged: {}",
+                      amount, token, position.id, e);
+                0.0
+            }
+        };
+        
+        let now = Utc::now();
+        let mut tx = self.db_pool.begin().await?;
+        
+        sqlx::query!(
+            r#"
+            UPDATE positions
+            SET deposited = deposited + $2, current_value = current_value + $2, updated_at = $3
+            WHERE id = $1
+            "#,
+            position.id,
+            value,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        sqlx::query!(
+            r#"
+            INSERT INTO transactions (
+                id, user_id, tx_hash, tx_type, amount, token,
+                status, protocol_id, position_id, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            Uuid::new_v4(),
+            position.user_id,
+            tx_hash,
+            "Deposit",
+            Some(amount.to_string()),
+            Some(token),
+            "Success",
+            Some(position.protocol_id.clone()),
+            Some(position.id),
+            now,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        
+        tx.commit().await?;
+        
+        info!("Compounded {} {} ({} EGLD) into position {}", amount, token, value, position.id);
+        Ok(())
+    }
+    
+    // Get the rewards a position can claim, their value against the gas of claiming them, and
+    // the rewards recorded over time
+    pub async fn get_position_rewards(&self, id: Uuid, user_id: Uuid) -> AppResult<PositionRewards> {
+        let position = sqlx::query_as!(
+            Position,
+            r#"
+            SELECT 
+                id, user_id, protocol_id, name, position_type, tokens,
+                deposited, current_value, apy, strategy as "strategy: PositionStrategy",
+                entry_date, last_rebalance, 
+                rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
+                allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
+                created_at, updated_at
+            FROM positions
+            WHERE id = $1 AND user_id = $2
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Position {} not found", id)))?;
+        
+        let user_address: String = sqlx::query_scalar!(
+            "SELECT address FROM users WHERE id = $1",
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        let protocol = self.protocol_registry.get_protocol(&position.protocol_id)
+            .ok_or_else(|| AppError::NotFound(format!("Protocol {} not found", position.protocol_id)))?;
+        let pending = protocol.get_pending_rewards(&user_address, &position).await?;
+        
+        let mut rewards = Vec::with_capacity(pending.len());
+        for reward in pending {
+            // Rewards are still useful without prices
+            let value = match self.price_service.value(reward.amount, &reward.token).await {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    warn!("Failed to value {} rewards of position {}: {}", reward.token, id, e);
+                    None
+                }
+            };
+            rewards.push(ClaimableReward {
+                token: reward.token,
+                amount: reward.amount,
+                value,
+            });
+        }
+        
+        let total_value = rewards.iter()
+            .map(|reward| reward.value)
+            .collect::<Option<Vec<Valuation>>>()
+            .map(|values| Valuation {
+                usd: values.iter().map(|v| v.usd).sum(),
+                egld: values.iter().map(|v| v.egld).sum(),
+            });
+        // Fee of the claim calls the position's holdings need; protocols whose claims can't be
+        // built yet are estimated as a single claim
+        let network_config = self.blockchain_client.get_network_config();
+        let claim_gas_cost = match protocol.build_claim_rewards_calls(&user_address, &position).await {
+            Ok(calls) => calls.iter()
+                .map(|call| network_config.transaction_fee(call.gas_limit, call.data.len()))
+                .sum(),
+            Err(_) => network_config.transaction_fee(protocol.get_gas_limit_for_operation("claimRewards"), "claimRewards".len()),
+        };
+        
+        let snapshots = sqlx::query_as!(
+            RewardSnapshot,
+            r#"
+            SELECT position_id, token, amount, value_usd, recorded_at
+            FROM position_reward_snapshots
+            WHERE position_id = $1
+            ORDER BY recorded_at DESC
+            LIMIT $2
+            "#,
+            id,
+            REWARD_SNAPSHOT_LIMIT
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        Ok(PositionRewards {
+            position_id: id,
+            rewards,
+            total_value,
+            claim_gas_cost,
+            worth_claiming: total_value.map(|value| value.egld > claim_gas_cost),
+            snapshots,
+        })
+    }
+    
+    // Record the pending rewards of every position, returning the number of snapshots stored
+    pub async fn record_reward_snapshots(&self) -> AppResult<usize> {
+        let positions = sqlx::query_as!(
+            Position,
+            r#"
+            SELECT 
+                id, user_id, protocol_id, name, position_type, tokens,
+                deposited, current_value, apy, strategy as "strategy: PositionStrategy",
+                entry_date, last_rebalance, 
+                rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
+                allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
+                created_at, updated_at
+            FROM positions
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        let mut addresses: HashMap<Uuid, String> = HashMap::new();
+        let mut count = 0;
+        for position in positions {
+            let Some(protocol) = self.protocol_registry.get_protocol(&position.protocol_id) else {
+                continue;
+            };
+            
+            if !addresses.contains_key(&position.user_id) {
+                let address = sqlx::query_scalar!(
+                    "SELECT address FROM users WHERE id = $1",
+                    position.user_id
+                )
+                .fetch_one(&self.db_pool)
+                .await;
+                match address {
+                    Ok(address) => { addresses.insert(position.user_id, address); },
+                    Err(e) => {
+                        warn!("Failed to get the address of the owner of position {}: {}", position.id, e);
+                        continue;
+                    }
+                }
+            }
+            
+            // A failing protocol shouldn't stop the snapshots of other positions
+            let pending = match protocol.get_pending_rewards(&addresses[&position.user_id], &position).await {
+                Ok(pending) => pending,
+                Err(e) => {
+                    warn!("Failed to get pending rewards of position {}: {}", position.id, e);
+                    continue;
+                }
+            };
+            
+            let now = Utc::now();
+            for reward in pending {
+                let value_usd = self.price_service.value(reward.amount, &reward.token).await
+                    .ok()
+                    .map(|value| value.usd);
+                
+                // A failing insert shouldn't stop the snapshots of other rewards either
+                let result = sqlx::query!(
+                    r#"
+                    INSERT INTO position_reward_snapshots (position_id, token, amount, value_usd, recorded_at)
+                    VALUES ($1, $2, $3, $4, $5)
+                    "#,
+                    position.id,
+                    reward.token,
+                    reward.amount,
+                    value_usd,
+                    now
+                )
+                .execute(&self.db_pool)
+                .await;
+                match result {
+                    Ok(_) => count += 1,
+                    Err(e) => warn!("Failed to record {} rewards of position {}: {}", reward.token, position.id, e),
+                }
+            }
+        }
+        
+        Ok(count)
+    }
+    
+    // Delete the reward snapshots past their retention, returning the number of rows removed
+    pub async fn prune_reward_snapshots(&self) -> AppResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM position_reward_snapshots WHERE recorded_at < $1
+            "#,
+            Utc::now() - chrono::Duration::days(REWARD_SNAPSHOT_RETENTION_DAYS)
+        )
+        .execute(&self.db_pool)
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    // Record the pending rewards of every position periodically in the background, pruning
+    // expired snapshots after each round
+    pub fn start_reward_snapshots(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(REWARD_SNAPSHOT_INTERVAL_SECONDS));
+            loop {
+                interval.tick().await;
+                match service.record_reward_snapshots().await {
+                    Ok(count) => info!("Recorded {} position reward snapshots", count),
+                    Err(e) => error!("Failed to record position reward snapshots: {}", e),
+                }
+                if let Err(e) = service.prune_reward_snapshots().await {
+                    error!("Failed to prune position reward snapshots: {}", e);
+                }
+            }
+        });
+    }
+    
+    // Revalue the positions an address holds in the given protocols, after the chain watcher saw
+    // it transact with them
+    pub async fn update_positions_for_address(&self, address: &str, protocol_ids: &[String]) -> AppResult<usize> {
+        let positions = sqlx::query_as!(
+            Position,
+            r#"
+            SELECT 
+                id, user_id, protocol_id, name, position_type, tokens,
+                deposited, current_value, apy, strategy as "strategy: PositionStrategy",
+                entry_date, last_rebalance, 
+                rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
+                allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
+                created_at, updated_at
+            FROM positions
+            WHERE user_id = (SELECT id FROM users WHERE address = $1)
+              AND protocol_id = ANY($2)
+            "#,
+            address,
+            protocol_ids
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        let count = positions.len();
+        self.update_position_values(positions).await?;
+        Ok(count)
+    }
+    
+    // Revalue positions whenever the chain watcher sees their owner transact with their protocol
+    pub fn start_chain_updates(&self, mut watched_txs: broadcast::Receiver<WatchedTransaction>) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let watched = match watched_txs.recv().await {
+                    Ok(watched) => watched,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Position updates missed {} watched transactions", skipped);
+                        continue;
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                
+                if watched.protocol_ids.is_empty() {
+                    continue;
+                }
+                
+                for address in &watched.users {
+                    match service.update_positions_for_address(address, &watched.protocol_ids).await {
+                        Ok(count) => debug!("Updated {} positions of {} after transaction {}", count, address, watched.transaction.hash),
+                        Err(e) => error!("Failed to update the positions of {}: {}", address, e),
+                    }
+                }
+            }
+        });
+    }
+    
+    // Helper method to add the account health of borrowing protocols and unbonding stake to positions
+    async fn attach_account_state(&self, user_id: Uuid, positions: &mut [PositionResponse]) -> AppResult<()> {
+        if positions.is_empty() {
+            return Ok(());
+        }
+        
+        let user_address: String = sqlx::query_scalar!(
+            "SELECT address FROM users WHERE id = $1",
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        // Health and unbonding are per account, so query each protocol once
+        let mut state_by_protocol: HashMap<String, (Option<AccountHealth>, Option<Vec<UnbondingEntry>>)> = HashMap::new();
+        for position in positions.iter_mut() {
+            if !state_by_protocol.contains_key(&position.protocol_id) {
+                let state = match self.protocol_registry.get_protocol(&position.protocol_id) {
+                    Some(protocol) => {
+                        // Positions are still useful without health or unbonding data
+                        let health = match protocol.get_account_health(&user_address).await {
+                            Ok(health) => health,
+                            Err(e) => {
+                                warn!("Failed to get account health from {}: {}", position.protocol_id, e);
+                                None
+                            }
+                        };
+                        let unbonding = match protocol.get_unbonding(&user_address).await {
+                            Ok(entries) if !entries.is_empty() => Some(entries),
+                            Ok(_) => None,
+                            Err(e) => {
+                                warn!("Failed to get unbonding stake from {}: {}", position.protocol_id, e);
+                                None
+                            }
+                        };
+                        (health, unbonding)
+                    },
+                    None => (None, None),
+                };
+                state_by_protocol.insert(position.protocol_id.clone(), state);
+            }
+            
+            let (health, unbonding) = &state_by_protocol[&position.protocol_id];
+            position.health = health.clone();
+            position.unbonding = unbonding.clone();
+        }
+        
+        Ok(())
+    }
+    
+    // Helper method to add the value and profit of positions in USD and EGLD
+    async fn attach_valuations(&self, positions: &mut [PositionResponse]) {
+        for position in positions.iter_mut() {
+            // Positions are still useful without prices
+            match self.price_service.value_position(position).await {
+                Ok(valuation) => position.valuation = Some(valuation),
+                Err(e) => warn!("Failed to value position {}: {}", position.id, e),
+            }
+        }
+    }
+    
+    // Helper method to add the impermanent loss of liquidity positions, given their entry prices and amounts
+    async fn attach_impermanent_loss(&self,
+                                     positions: &mut [PositionResponse],
+                                     entries: &[(HashMap<String, f64>, HashMap<String, f64>)]) {
+        for (position, (entry_prices, entry_amounts)) in positions.iter_mut().zip(entries) {
+            // The constant product formula doesn't hold for stableswap pools, so their loss isn't reported
+            match self.pool_curve(position).await {
+                Ok(Some(SwapCurve::StableSwap { .. })) => continue,
+                Ok(_) => {},
+                Err(e) => {
+                    warn!("Failed to find the pool of position {}: {}", position.id, e);
+                    continue;
+                }
+            }
+            
+            match self.price_service.value_impermanent_loss(position, entry_prices, entry_amounts).await {
+                Ok(impermanent_loss) => position.impermanent_loss = impermanent_loss,
+                Err(e) => warn!("Failed to compute impermanent loss of position {}: {}", position.id, e),
+            }
+        }
+    }
+    
+    // Helper method to find the curve of the swap pool holding a position's tokens, if its protocol has one
+    async fn pool_curve(&self, position: &PositionResponse) -> AppResult<Option<SwapCurve>> {
+        let Some(protocol) = self.protocol_registry.get_protocol(&position.protocol_id) else {
+            return Ok(None);
+        };
+        
+        let tickers = sorted_tickers(&position.tokens);
+        let pools = protocol.get_swap_pools().await?;
+        Ok(pools.into_iter()
+            .find(|pool| sorted_tickers(&pool.token_ids) == tickers)
+            .map(|pool| pool.curve))
+    }
+    
     // Helper method to update position values from blockchain
     async fn update_position_values(&self, positions: Vec<Position>) -> AppResult<Vec<Position>> {
         let mut updated_positions = Vec::with_capacity(positions.len());
@@ -832,6 +1413,7 @@ impl PositionService {
                     entry_date, last_rebalance, 
                     rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
                     allocation as "allocation: Vec<TokenAllocation>", metadata,
+                    compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
                     created_at, updated_at
                 "#,
                 position.id,
@@ -846,4 +1428,22 @@ impl PositionService {
         
         Ok(position)
     }
+}
+
+// Token prices and amounts recorded in a position's metadata when it was opened; empty for older positions
+fn stored_entry(position: &Position) -> (HashMap<String, f64>, HashMap<String, f64>) {
+    let stored = |key: &str| -> HashMap<String, f64> {
+        position.metadata.0.get(key)
+            .and_then(|values| serde_json::from_value(values.clone()).ok())
+            .unwrap_or_default()
+    };
+    (stored("entry_prices"), stored("entry_amounts"))
+}
+
+// Distinct price tickers of a set of tokens, sorted
+fn sorted_tickers(tokens: &[String]) -> Vec<String> {
+    let mut tickers: Vec<String> = tokens.iter().map(|t| price_ticker(t)).collect();
+    tickers.sort();
+    tickers.dedup();
+    tickers
 }
\ No newline at end of file
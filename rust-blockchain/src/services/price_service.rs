@@ -0,0 +1,427 @@
+use crate::error::{AppError, AppResult};
+use crate::models::position::{PositionResponse, TokenAllocation};
+use crate::models::price::{ImpermanentLoss, PositionValuation, TokenPrice, Valuation};
+use crate::smart_contracts::ProtocolRegistry;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tracing::{info, error, warn};
+
+// Prices older than this are refreshed from the feeds before being used
+const PRICE_MAX_AGE_SECONDS: i64 = 60;
+// How often prices are recorded in the history table
+const PRICE_RECORD_INTERVAL_SECONDS: u64 = 300;
+// Protocol whose pair reserves price the tokens
+const XEXCHANGE_PROTOCOL_ID: &str = "maiar-exchange";
+
+// Source of USD token prices, keyed by ticker
+#[async_trait]
+pub trait PriceFeed {
+    fn get_id(&self) -> &str;
+    
+    async fn get_prices(&self) -> AppResult<HashMap<String, f64>>;
+}
+
+// Prices derived from xExchange pair reserves, routed through the WEGLD/USDC pair
+pub struct XExchangePriceFeed {
+    protocol_registry: ProtocolRegistry,
+}
+
+impl XExchangePriceFeed {
+    pub fn new(protocol_registry: ProtocolRegistry) -> Self {
+        Self { protocol_registry }
+    }
+}
+
+#[async_trait]
+impl PriceFeed for XExchangePriceFeed {
+    fn get_id(&self) -> &str {
+        "xexchange"
+    }
+    
+    async fn get_prices(&self) -> AppResult<HashMap<String, f64>> {
+        let protocol = self.protocol_registry.get_protocol(XEXCHANGE_PROTOCOL_ID)
+            .ok_or_else(|| AppError::NotFound(format!("Protocol {} not registered", XEXCHANGE_PROTOCOL_ID)))?;
+        protocol.get_token_prices().await
+    }
+}
+
+// Fixed prices from a local JSON file mapping tickers to USD prices, for development
+// and for tokens without an xExchange pair
+pub struct FixturePriceFeed {
+    prices: HashMap<String, f64>,
+}
+
+impl FixturePriceFeed {
+    pub fn new(prices: HashMap<String, f64>) -> Self {
+        Self { prices }
+    }
+    
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let prices: HashMap<String, f64> = serde_json::from_str(&content)?;
+        Ok(Self::new(prices))
+    }
+}
+
+#[async_trait]
+impl PriceFeed for FixturePriceFeed {
+    fn get_id(&self) -> &str {
+        "fixture"
+    }
+    
+    async fn get_prices(&self) -> AppResult<HashMap<String, f64>> {
+        Ok(self.prices.clone())
+    }
+}
+
+// Normalize a token identifier to the ticker prices are keyed by; wrapped EGLD is priced as EGLD
+pub fn price_ticker(token: &str) -> String {
+    let ticker = token.split('-').next().unwrap_or(token).to_uppercase();
+    if ticker == "WEGLD" {
+        "EGLD".to_string()
+    } else {
+        ticker
+    }
+}
+
+// Value of an equal-weight constant product pool against holding its tokens, given each token's
+// price relative to entry, minus one. Stableswap pools follow another invariant, so it doesn't
+// apply to them
+pub fn impermanent_loss(price_ratios: &[f64]) -> f64 {
+    if price_ratios.len() < 2 || price_ratios.iter().any(|r| !r.is_finite() || *r <= 0.0) {
+        return 0.0;
+    }
+    
+    let n = price_ratios.len() as f64;
+    let pooled = price_ratios.iter().map(|r| r.powf(1.0 / n)).product::<f64>();
+    let held = price_ratios.iter().sum::<f64>() / n;
+    pooled / held - 1.0
+}
+
+// Units of each token a deposit in EGLD was split into following its allocation, at the given
+// prices by ticker; tokens that can't be priced are left out
+pub fn entry_amounts(deposited: f64, allocation: &[TokenAllocation], prices: &HashMap<String, f64>) -> HashMap<String, f64> {
+    let mut amounts = HashMap::new();
+    let Some(egld_price) = prices.get("EGLD") else {
+        return amounts;
+    };
+    
+    for token_allocation in allocation {
+        let ticker = price_ticker(&token_allocation.token);
+        if let Some(price) = prices.get(&ticker).filter(|price| **price > 0.0) {
+            *amounts.entry(ticker).or_insert(0.0) += deposited * egld_price * token_allocation.percentage / 100.0 / price;
+        }
+    }
+    amounts
+}
+
+#[derive(Clone)]
+pub struct PriceService {
+    db_pool: PgPool,
+    // Feeds in order of preference; a token is priced by the first feed that knows it
+    feeds: Vec<Arc<dyn PriceFeed + Send + Sync>>,
+    latest: Arc<RwLock<HashMap<String, TokenPrice>>>,
+    last_refresh: Arc<RwLock<Option<DateTime<Utc>>>>,
+}
+
+impl PriceService {
+    pub fn new(db_pool: PgPool, protocol_registry: ProtocolRegistry) -> Self {
+        Self {
+            db_pool,
+            feeds: vec![Arc::new(XExchangePriceFeed::new(protocol_registry))],
+            latest: Arc::new(RwLock::new(HashMap::new())),
+            last_refresh: Arc::new(RwLock::new(None)),
+        }
+    }
+    
+    // Add a feed, consulted for the tokens the previous feeds can't price
+    pub fn with_feed(mut self, feed: Arc<dyn PriceFeed + Send + Sync>) -> Self {
+        self.feeds.push(feed);
+        self
+    }
+    
+    // Get the latest prices from every feed
+    pub async fn refresh_prices(&self) -> AppResult<Vec<TokenPrice>> {
+        let now = Utc::now();
+        
+        // Mark as refreshed up front so failing feeds aren't hammered on every read
+        *self.last_refresh.write().unwrap() = Some(now);
+        
+        let mut prices: HashMap<String, TokenPrice> = HashMap::new();
+        for feed in &self.feeds {
+            match feed.get_prices().await {
+                Ok(feed_prices) => {
+                    for (token, price_usd) in feed_prices {
+                        if !price_usd.is_finite() || price_usd <= 0.0 {
+                            continue;
+                        }
+                        
+                        let token = price_ticker(&token);
+                        prices.entry(token.clone()).or_insert_with(|| TokenPrice {
+                            token,
+                            price_usd,
+                            source: feed.get_id().to_string(),
+                            recorded_at: now,
+                        });
+                    }
+                },
+                Err(e) => warn!("Price feed {} failed: {}", feed.get_id(), e),
+            }
+        }
+        
+        if prices.is_empty() {
+            return Err(AppError::Blockchain("No price feed returned any price".to_string()));
+        }
+        
+        // Tokens missing from this refresh keep their previous price
+        let mut latest = self.latest.write().unwrap();
+        latest.extend(prices.clone());
+        
+        Ok(prices.into_values().collect())
+    }
+    
+    // Refresh prices and record them in the price history
+    pub async fn record_prices(&self) -> AppResult<usize> {
+        let prices = self.refresh_prices().await?;
+        
+        for price in &prices {
+            sqlx::query!(
+                r#"
+                INSERT INTO token_prices (token, price_usd, source, recorded_at)
+                VALUES ($1, $2, $3, $4)
+                "#,
+                price.token,
+                price.price_usd,
+                price.source,
+                price.recorded_at
+            )
+            .execute(&self.db_pool)
+            .await?;
+        }
+        
+        Ok(prices.len())
+    }
+    
+    // Record prices periodically in the background
+    pub fn start_price_recording(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(PRICE_RECORD_INTERVAL_SECONDS));
+            loop {
+                interval.tick().await;
+                match service.record_prices().await {
+                    Ok(count) => info!("Recorded {} token prices", count),
+                    Err(e) => error!("Failed to record token prices: {}", e),
+                }
+            }
+        });
+    }
+    
+    // Get the current USD price of a token, by identifier or ticker
+    pub async fn get_price(&self, token: &str) -> AppResult<f64> {
+        let ticker = price_ticker(token);
+        if ticker == "USD" {
+            return Ok(1.0);
+        }
+        
+        let is_stale = {
+            let last_refresh = self.last_refresh.read().unwrap();
+            last_refresh.map_or(true, |t| (Utc::now() - t).num_seconds() >= PRICE_MAX_AGE_SECONDS)
+        };
+        if is_stale {
+            if let Err(e) = self.refresh_prices().await {
+                warn!("Failed to refresh token prices, using previous ones: {}", e);
+            }
+        }
+        
+        let latest = self.latest.read().unwrap();
+        latest.get(&ticker)
+            .map(|price| price.price_usd)
+            .ok_or_else(|| AppError::NotFound(format!("No price available for token {}", ticker)))
+    }
+    
+    // Get the latest price of every priced token
+    pub async fn get_all_prices(&self) -> AppResult<Vec<TokenPrice>> {
+        self.get_price("EGLD").await?;
+        
+        let mut prices: Vec<TokenPrice> = self.latest.read().unwrap().values().cloned().collect();
+        prices.sort_by(|a, b| a.token.cmp(&b.token));
+        Ok(prices)
+    }
+    
+    // Get the USD price of a token at a past time from the price history,
+    // falling back to the current price before the history starts
+    pub async fn get_price_at(&self, token: &str, at: DateTime<Utc>) -> AppResult<f64> {
+        let ticker = price_ticker(token);
+        if ticker == "USD" {
+            return Ok(1.0);
+        }
+        
+        let recorded: Option<f64> = sqlx::query_scalar!(
+            r#"
+            SELECT price_usd FROM token_prices
+            WHERE token = $1 AND recorded_at <= $2
+            ORDER BY recorded_at DESC
+            LIMIT 1
+            "#,
+            ticker,
+            at
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+        
+        match recorded {
+            Some(price) => Ok(price),
+            None => self.get_price(&ticker).await,
+        }
+    }
+    
+    // Get the recorded prices of a token over a period
+    pub async fn get_price_history(&self,
+                                  token: &str,
+                                  from: DateTime<Utc>,
+                                  to: DateTime<Utc>) -> AppResult<Vec<TokenPrice>> {
+        let history = sqlx::query_as!(
+            TokenPrice,
+            r#"
+            SELECT token, price_usd, source, recorded_at
+            FROM token_prices
+            WHERE token = $1 AND recorded_at BETWEEN $2 AND $3
+            ORDER BY recorded_at
+            "#,
+            price_ticker(token),
+            from,
+            to
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        Ok(history)
+    }
+    
+    // Express an amount of a token, or of USD, in USD and EGLD
+    pub async fn value(&self, amount: f64, token: &str) -> AppResult<Valuation> {
+        let usd = amount * self.get_price(token).await?;
+        let egld_price = self.get_price("EGLD").await?;
+        
+        Ok(Valuation {
+            usd,
+            egld: usd / egld_price,
+        })
+    }
+    
+    // Value a position and its profit; positions are denominated in EGLD and their deposit
+    // is valued at the EGLD price of the entry date
+    pub async fn value_position(&self, position: &PositionResponse) -> AppResult<PositionValuation> {
+        let value = self.value(position.current_value, "EGLD").await?;
+        let entry_price = self.get_price_at("EGLD", position.entry_date).await?;
+        let deposited = Valuation {
+            usd: position.deposited * entry_price,
+            egld: position.deposited,
+        };
+        
+        Ok(PositionValuation {
+            pnl: Valuation {
+                usd: value.usd - deposited.usd,
+                egld: value.egld - deposited.egld,
+            },
+            value,
+            deposited,
+        })
+    }
+    
+    // Current prices of a position's tokens and of EGLD, recorded when the position is opened;
+    // tokens that can't be priced are left out
+    pub async fn entry_prices(&self, tokens: &[String]) -> HashMap<String, f64> {
+        let mut prices = HashMap::new();
+        for token in tokens.iter().map(|t| t.as_str()).chain(std::iter::once("EGLD")) {
+            let ticker = price_ticker(token);
+            if prices.contains_key(&ticker) {
+                continue;
+            }
+            match self.get_price(&ticker).await {
+                Ok(price) => { prices.insert(ticker, price); },
+                Err(e) => warn!("No entry price for {}: {}", ticker, e),
+            }
+        }
+        prices
+    }
+    
+    // Impermanent loss of a constant product liquidity position, from the token prices and amounts
+    // recorded when it was opened. Positions opened before they were recorded use the price history
+    // of their entry date, and their allocation for the amounts
+    pub async fn value_impermanent_loss(&self,
+                                        position: &PositionResponse,
+                                        entry_prices: &HashMap<String, f64>,
+                                        entry_amounts: &HashMap<String, f64>) -> AppResult<Option<ImpermanentLoss>> {
+        let mut tickers: Vec<String> = position.tokens.iter().map(|t| price_ticker(t)).collect();
+        tickers.sort();
+        tickers.dedup();
+        if tickers.len() < 2 {
+            return Ok(None);
+        }
+        
+        let mut entry = HashMap::new();
+        let mut current = HashMap::new();
+        for ticker in tickers.iter().map(|t| t.as_str()).chain(std::iter::once("EGLD")) {
+            let entry_price = match entry_prices.get(ticker) {
+                Some(price) => *price,
+                None => self.get_price_at(ticker, position.entry_date).await?,
+            };
+            entry.insert(ticker.to_string(), entry_price);
+            current.insert(ticker.to_string(), self.get_price(ticker).await?);
+        }
+        
+        let amounts = if entry_amounts.is_empty() {
+            self::entry_amounts(position.deposited, &position.allocation, &entry)
+        } else {
+            entry_amounts.clone()
+        };
+        let mut deposited_amounts = HashMap::new();
+        for ticker in &tickers {
+            let amount = amounts.get(ticker).copied().ok_or_else(|| {
+                AppError::Validation(format!("No entry amount of {} for position {}", ticker, position.id))
+            })?;
+            deposited_amounts.insert(ticker.clone(), amount);
+        }
+        
+        // Held tokens follow their own price, while the pool keeps its value at
+        // entry times the geometric mean of the price changes
+        let entry_usd: f64 = tickers.iter().map(|t| deposited_amounts[t] * entry[t]).sum();
+        let held_usd: f64 = tickers.iter().map(|t| deposited_amounts[t] * current[t]).sum();
+        if held_usd <= 0.0 {
+            return Err(AppError::Validation(format!("Position {} held no tokens at entry", position.id)));
+        }
+        let pooled_usd = entry_usd * tickers.iter()
+            .map(|t| (current[t] / entry[t]).powf(1.0 / tickers.len() as f64))
+            .product::<f64>();
+        let loss = pooled_usd / held_usd - 1.0;
+        
+        let value_if_held = Valuation {
+            usd: held_usd,
+            egld: held_usd / current["EGLD"],
+        };
+        
+        // Only the pool tokens are reported; EGLD was needed to value the deposit
+        if !tickers.iter().any(|t| t == "EGLD") {
+            entry.remove("EGLD");
+            current.remove("EGLD");
+        }
+        
+        Ok(Some(ImpermanentLoss {
+            loss,
+            loss_value: Valuation {
+                usd: value_if_held.usd * loss,
+                egld: value_if_held.egld * loss,
+            },
+            value_if_held,
+            entry_amounts: deposited_amounts,
+            entry_prices: entry,
+            current_prices: current,
+        }))
+    }
+}
\ No newline at end of file
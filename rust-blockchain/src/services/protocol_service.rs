@@ -1,19 +1,30 @@
 use crate::error::{AppError, AppResult};
 use crate::models::pool::{Pool, PoolResponse};
+use crate::models::price::Valuation;
 use crate::models::protocol::{Protocol, ProtocolResponse, RiskLevel};
+use crate::services::PriceService;
+use crate::smart_contracts::monitored::ProtocolHealth;
 use crate::smart_contracts::ProtocolRegistry;
 use sqlx::PgPool;
 use std::collections::HashMap;
-use tracing::{info, error};
+use std::sync::{Arc, Mutex};
+use tracing::{info, error, warn};
 
+// How often protocol activation is read back from the database
+const STATUS_SYNC_INTERVAL_SECONDS: u64 = 5;
+
+#[derive(Clone)]
 pub struct ProtocolService {
     db_pool: PgPool,
     protocol_registry: ProtocolRegistry,
+    price_service: PriceService,
+    // Activation changes not yet written to the database; the sync leaves these protocols as they are
+    pending_status: Arc<Mutex<HashMap<String, bool>>>,
 }
 
 impl ProtocolService {
-    pub fn new(db_pool: PgPool, protocol_registry: ProtocolRegistry) -> Self {
-        Self { db_pool, protocol_registry }
+    pub fn new(db_pool: PgPool, protocol_registry: ProtocolRegistry, price_service: PriceService) -> Self {
+        Self { db_pool, protocol_registry, price_service, pending_status: Arc::new(Mutex::new(HashMap::new())) }
     }
     
     pub async fn get_all_protocols(&self) -> AppResult<Vec<ProtocolResponse>> {
@@ -40,7 +51,8 @@ impl ProtocolService {
             // Try to get real-time data from blockchain
             if let Some(blockchain_protocol) = self.protocol_registry.get_protocol(&protocol.id) {
                 // Get real-time TVL and APY
-                let tvl = blockchain_protocol.get_tvl().await.unwrap_or(protocol.tvl);
+                let live_tvl = blockchain_protocol.get_tvl().await.ok();
+                let tvl = live_tvl.unwrap_or(protocol.tvl);
                 let apy = blockchain_protocol.get_apy().await.unwrap_or(protocol.apy);
                 
                 // Create updated protocol
@@ -50,7 +62,9 @@ impl ProtocolService {
                     ..protocol
                 };
                 
-                updated_protocols.push(ProtocolResponse::from(updated_protocol));
+                let mut response = ProtocolResponse::from(updated_protocol);
+                response.tvl_value = self.value_tvl(live_tvl, blockchain_protocol.get_tvl_denomination()).await;
+                updated_protocols.push(response);
             } else {
                 // If not available in blockchain, use database data
                 updated_protocols.push(ProtocolResponse::from(protocol));
@@ -80,7 +94,8 @@ impl ProtocolService {
         // Then, update with real-time data from blockchain
         if let Some(blockchain_protocol) = self.protocol_registry.get_protocol(id) {
             // Get real-time TVL and APY
-            let tvl = blockchain_protocol.get_tvl().await.unwrap_or(protocol.tvl);
+            let live_tvl = blockchain_protocol.get_tvl().await.ok();
+            let tvl = live_tvl.unwrap_or(protocol.tvl);
             let apy = blockchain_protocol.get_apy().await.unwrap_or(protocol.apy);
             
             // Create updated protocol
@@ -90,7 +105,9 @@ impl ProtocolService {
                 ..protocol
             };
             
-            Ok(ProtocolResponse::from(updated_protocol))
+            let mut response = ProtocolResponse::from(updated_protocol);
+            response.tvl_value = self.value_tvl(live_tvl, blockchain_protocol.get_tvl_denomination()).await;
+            Ok(response)
         } else {
             // If not available in blockchain, use database data
             Ok(ProtocolResponse::from(protocol))
@@ -139,7 +156,8 @@ impl ProtocolService {
                 let pool_id = &pool.id;
                 
                 // Get real-time TVL and APY if available
-                let tvl = blockchain_protocol.get_pool_tvl(pool_id).await.unwrap_or(pool.tvl);
+                let live_tvl = blockchain_protocol.get_pool_tvl(pool_id).await.ok();
+                let tvl = live_tvl.unwrap_or(pool.tvl);
                 let apy = blockchain_protocol.get_pool_apy(pool_id).await.unwrap_or(pool.apy);
                 
                 // Create updated pool
@@ -149,7 +167,9 @@ impl ProtocolService {
                     ..pool
                 };
                 
-                updated_pools.push(updated_pool.to_response(protocol.name.clone()));
+                let mut response = updated_pool.to_response(protocol.name.clone());
+                response.tvl_value = self.value_tvl(live_tvl, blockchain_protocol.get_tvl_denomination()).await;
+                updated_pools.push(response);
             }
         } else {
             // If protocol not available in blockchain, use database data
@@ -209,7 +229,8 @@ impl ProtocolService {
                 let pool_id = &pool.id;
                 
                 // Get real-time TVL and APY if available
-                let tvl = blockchain_protocol.get_pool_tvl(pool_id).await.unwrap_or(pool.tvl);
+                let live_tvl = blockchain_protocol.get_pool_tvl(pool_id).await.ok();
+                let tvl = live_tvl.unwrap_or(pool.tvl);
                 let apy = blockchain_protocol.get_pool_apy(pool_id).await.unwrap_or(pool.apy);
                 
                 // Create updated pool
@@ -219,7 +240,9 @@ impl ProtocolService {
                     ..pool
                 };
                 
-                updated_pools.push(updated_pool.to_response(protocol_name));
+                let mut response = updated_pool.to_response(protocol_name);
+                response.tvl_value = self.value_tvl(live_tvl, blockchain_protocol.get_tvl_denomination()).await;
+                updated_pools.push(response);
             } else {
                 // If protocol not available in blockchain, use database data
                 updated_pools.push(pool.to_response(protocol_name));
@@ -235,7 +258,7 @@ impl ProtocolService {
             Pool,
             r#"
             SELECT 
-                id, protocol_id, name, tvl, a py, tokens,
+                id, protocol_id, name, tvl, apy, tokens,
                 risk as "risk: RiskLevel", contract_address, metadata, is_active
             FROM pools
             WHERE id = $1 AND is_active = true
@@ -266,7 +289,8 @@ impl ProtocolService {
             let pool_id = &pool.id;
             
             // Get real-time TVL and APY if available
-            let tvl = blockchain_protocol.get_pool_tvl(pool_id).await.unwrap_or(pool.tvl);
+            let live_tvl = blockchain_protocol.get_pool_tvl(pool_id).await.ok();
+            let tvl = live_tvl.unwrap_or(pool.tvl);
             let apy = blockchain_protocol.get_pool_apy(pool_id).await.unwrap_or(pool.apy);
             
             // Create updated pool
@@ -276,10 +300,140 @@ impl ProtocolService {
                 ..pool
             };
             
-            Ok(updated_pool.to_response(protocol.name))
+            let mut response = updated_pool.to_response(protocol.name);
+            response.tvl_value = self.value_tvl(live_tvl, blockchain_protocol.get_tvl_denomination()).await;
+            Ok(response)
         } else {
             // If protocol not available in blockchain, use database data
             Ok(pool.to_response(protocol.name))
         }
     }
+    
+    // Helper method to value a live TVL, reported in the protocol's denomination, in USD and EGLD
+    async fn value_tvl(&self, live_tvl: Option<f64>, denomination: &str) -> Option<Valuation> {
+        let tvl = live_tvl?;
+        match self.price_service.value(tvl, denomination).await {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("Failed to value TVL in {}: {}", denomination, e);
+                None
+            }
+        }
+    }
+    
+    pub fn get_protocol_health(&self) -> Vec<ProtocolHealth> {
+        self.protocol_registry.get_all_health()
+    }
+    
+    // Stop deposits into a protocol right away, and deactivate it for every other instance
+    pub async fn pause_protocol(&self, id: &str, reason: &str) -> AppResult<ProtocolHealth> {
+        self.protocol_registry.pause_protocol(id, reason)?;
+        self.write_protocol_status(id, false).await;
+        
+        info!("Protocol {} paused: {}", id, reason);
+        self.protocol_registry.get_health(id)
+    }
+    
+    pub async fn resume_protocol(&self, id: &str) -> AppResult<ProtocolHealth> {
+        self.protocol_registry.resume_protocol(id)?;
+        self.write_protocol_status(id, true).await;
+        
+        info!("Protocol {} resumed", id);
+        self.protocol_registry.get_health(id)
+    }
+    
+    // Pause the protocols that are inactive in the database and resume the active ones
+    pub async fn sync_protocol_status(&self) -> AppResult<()> {
+        // Retry the activation changes that failed to be written first
+        let pending: Vec<(String, bool)> = self.pending_status.lock().unwrap()
+            .iter()
+            .map(|(id, is_active)| (id.clone(), *is_active))
+            .collect();
+        for (id, is_active) in pending {
+            self.flush_protocol_status(&id, is_active).await;
+        }
+        
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, is_active FROM protocols
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        for row in rows {
+            // Protocols only known to the database have nothing to pause
+            if self.protocol_registry.get_protocol(&row.id).is_none() {
+                continue;
+            }
+            
+            // The database is behind a local change that is still being written
+            if self.pending_status.lock().unwrap().contains_key(&row.id) {
+                continue;
+            }
+            
+            let paused = self.protocol_registry.is_paused(&row.id);
+            if !row.is_active && !paused {
+                self.protocol_registry.pause_protocol(&row.id, "Deactivated in the database")?;
+            } else if row.is_active && paused {
+                self.protocol_registry.resume_protocol(&row.id)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Keep following the database so a pause on any instance reaches all of them within seconds
+    pub fn start_status_sync(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(STATUS_SYNC_INTERVAL_SECONDS));
+            loop {
+                interval.tick().await;
+                if let Err(e) = service.sync_protocol_status().await {
+                    error!("Failed to sync protocol status: {}", e);
+                }
+            }
+        });
+    }
+    
+    // Write an activation change, keeping it pending until the write succeeds so the
+    // sync doesn't revert it from the stale database state
+    async fn write_protocol_status(&self, id: &str, is_active: bool) {
+        self.pending_status.lock().unwrap().insert(id.to_string(), is_active);
+        self.flush_protocol_status(id, is_active).await;
+    }
+    
+    // Helper method to write a pending activation change, clearing it once written
+    async fn flush_protocol_status(&self, id: &str, is_active: bool) {
+        match self.set_protocol_active(id, is_active).await {
+            Ok(()) => {
+                let mut pending_status = self.pending_status.lock().unwrap();
+                // A later change may have been made while this one was being written
+                if pending_status.get(id) == Some(&is_active) {
+                    pending_status.remove(id);
+                }
+            },
+            Err(e) => warn!("Failed to write the status of protocol {}, will retry: {}", id, e),
+        }
+    }
+    
+    // Helper method to update the activation of a protocol in the database
+    async fn set_protocol_active(&self, id: &str, is_active: bool) -> AppResult<()> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE protocols SET is_active = $2 WHERE id = $1
+            "#,
+            id,
+            is_active
+        )
+        .execute(&self.db_pool)
+        .await?;
+        
+        if result.rows_affected() == 0 {
+            warn!("Protocol {} has no database entry; its status only applies to this instance", id);
+        }
+        
+        Ok(())
+    }
 }
\ No newline at end of file
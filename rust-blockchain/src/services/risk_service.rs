@@ -0,0 +1,227 @@
+use crate::error::{AppError, AppResult};
+use crate::services::PriceService;
+use crate::smart_contracts::protocol_interface::ProtocolInterface;
+use crate::smart_contracts::risk::{score_protocol, FactorScore, RiskConfig, RiskInputs, RiskScore};
+use crate::smart_contracts::ProtocolRegistry;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, error, warn};
+
+// How often every protocol is scored
+const RISK_SCORING_INTERVAL_SECONDS: u64 = 3600;
+
+#[derive(Clone)]
+pub struct RiskService {
+    db_pool: PgPool,
+    protocol_registry: ProtocolRegistry,
+    price_service: PriceService,
+    config: Arc<RiskConfig>,
+}
+
+impl RiskService {
+    pub fn new(db_pool: PgPool, protocol_registry: ProtocolRegistry, price_service: PriceService) -> Self {
+        Self {
+            db_pool,
+            protocol_registry,
+            price_service,
+            config: Arc::new(RiskConfig::default()),
+        }
+    }
+    
+    // Use factor weights and protocol facts from a configuration instead of the defaults
+    pub fn with_config(mut self, config: RiskConfig) -> Self {
+        self.config = Arc::new(config);
+        self
+    }
+    
+    // Score a protocol from its live data and recorded history, store the score and
+    // share it with every user of the protocol
+    pub async fn refresh_risk_score(&self, protocol_id: &str) -> AppResult<RiskScore> {
+        let protocol = self.protocol_registry.get_protocol(protocol_id)
+            .ok_or_else(|| AppError::NotFound(format!("Protocol {} not found", protocol_id)))?;
+        
+        let (inputs, apy) = self.collect_inputs(protocol.as_ref()).await?;
+        let score = score_protocol(protocol_id, &inputs, &self.config);
+        
+        sqlx::query!(
+            r#"
+            INSERT INTO protocol_risk_scores (protocol_id, score, level, tvl_usd, apy, factors, computed_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            score.protocol_id,
+            score.score,
+            score.level,
+            inputs.tvl_usd,
+            apy,
+            Json(&score.factors) as _,
+            score.computed_at
+        )
+        .execute(&self.db_pool)
+        .await?;
+        
+        self.protocol_registry.set_risk_score(score.clone())?;
+        Ok(score)
+    }
+    
+    // Score every protocol in the registry
+    pub async fn record_risk_scores(&self) -> AppResult<usize> {
+        let mut count = 0;
+        for protocol in self.protocol_registry.get_all_protocols() {
+            // A failing protocol shouldn't stop the scoring of the others
+            match self.refresh_risk_score(protocol.get_id()).await {
+                Ok(_) => count += 1,
+                Err(e) => warn!("Failed to score risk of protocol {}: {}", protocol.get_id(), e),
+            }
+        }
+        
+        Ok(count)
+    }
+    
+    // Keep scoring protocols in the background so strategies filter on current scores
+    pub fn start_risk_scoring(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(RISK_SCORING_INTERVAL_SECONDS));
+            loop {
+                interval.tick().await;
+                match service.record_risk_scores().await {
+                    Ok(count) => info!("Scored risk of {} protocols", count),
+                    Err(e) => error!("Failed to score protocol risk: {}", e),
+                }
+            }
+        });
+    }
+    
+    // Get the latest score of a protocol, from the registry or else the last one recorded;
+    // scoring is left to the background loop so requests can't trigger it
+    pub async fn get_risk_score(&self, protocol_id: &str) -> AppResult<RiskScore> {
+        if let Some(score) = self.protocol_registry.get_risk_score(protocol_id)? {
+            return Ok(score);
+        }
+        
+        let row = sqlx::query!(
+            r#"
+            SELECT protocol_id, score, level, factors as "factors: Json<Vec<FactorScore>>", computed_at
+            FROM protocol_risk_scores
+            WHERE protocol_id = $1
+            ORDER BY computed_at DESC
+            LIMIT 1
+            "#,
+            protocol_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Protocol {} has not been scored yet", protocol_id)))?;
+        
+        Ok(RiskScore {
+            protocol_id: row.protocol_id,
+            score: row.score,
+            level: row.level,
+            factors: row.factors.0,
+            computed_at: row.computed_at,
+        })
+    }
+    
+    pub fn get_all_risk_scores(&self) -> Vec<RiskScore> {
+        self.protocol_registry.get_all_risk_scores()
+    }
+    
+    // Get the recorded scores of a protocol over a period
+    pub async fn get_risk_history(&self,
+                                 protocol_id: &str,
+                                 from: DateTime<Utc>,
+                                 to: DateTime<Utc>) -> AppResult<Vec<RiskScore>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT protocol_id, score, level, factors as "factors: Json<Vec<FactorScore>>", computed_at
+            FROM protocol_risk_scores
+            WHERE protocol_id = $1 AND computed_at BETWEEN $2 AND $3
+            ORDER BY computed_at
+            "#,
+            protocol_id,
+            from,
+            to
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        Ok(rows.into_iter()
+            .map(|row| RiskScore {
+                protocol_id: row.protocol_id,
+                score: row.score,
+                level: row.level,
+                factors: row.factors.0,
+                computed_at: row.computed_at,
+            })
+            .collect())
+    }
+    
+    // Default period of the risk history, the window trends are measured over
+    pub fn history_window(&self) -> Duration {
+        Duration::days(self.config.history_days)
+    }
+    
+    // Helper method to gather what a protocol is scored from, along with its current APY;
+    // data a protocol fails to report leaves the matching factor unknown
+    async fn collect_inputs(&self, protocol: &(dyn ProtocolInterface + Send + Sync)) -> AppResult<(RiskInputs, Option<f64>)> {
+        let protocol_id = protocol.get_id();
+        let mut inputs = RiskInputs::default();
+        
+        match protocol.get_tvl().await {
+            Ok(tvl) => match self.price_service.value(tvl, protocol.get_tvl_denomination()).await {
+                Ok(value) => inputs.tvl_usd = Some(value.usd),
+                Err(e) => warn!("Failed to value TVL of protocol {}: {}", protocol_id, e),
+            },
+            Err(e) => warn!("Failed to get TVL of protocol {}: {}", protocol_id, e),
+        }
+        
+        let apy = match protocol.get_apy().await {
+            Ok(apy) => Some(apy),
+            Err(e) => {
+                warn!("Failed to get APY of protocol {}: {}", protocol_id, e);
+                None
+            }
+        };
+        
+        let history = sqlx::query!(
+            r#"
+            SELECT tvl_usd, apy
+            FROM protocol_risk_scores
+            WHERE protocol_id = $1 AND computed_at >= $2
+            ORDER BY computed_at
+            "#,
+            protocol_id,
+            Utc::now() - self.history_window()
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        
+        inputs.past_tvl_usd = history.iter().find_map(|row| row.tvl_usd);
+        inputs.apy_history = history.iter().filter_map(|row| row.apy).chain(apy).collect();
+        
+        inputs.utilization = protocol.get_utilization().await.unwrap_or_else(|e| {
+            warn!("Failed to get utilization of protocol {}: {}", protocol_id, e);
+            None
+        });
+        
+        // Split each pool's TVL evenly across its tokens
+        let mut token_tvl: HashMap<String, f64> = HashMap::new();
+        for pool_id in protocol.get_pools().await.unwrap_or_default() {
+            let (info, tvl) = match (protocol.get_pool_info(&pool_id).await, protocol.get_pool_tvl(&pool_id).await) {
+                (Ok(info), Ok(tvl)) if !info.tokens.is_empty() => (info, tvl),
+                _ => continue,
+            };
+            
+            let share = tvl / info.tokens.len() as f64;
+            for token in info.tokens {
+                *token_tvl.entry(token).or_insert(0.0) += share;
+            }
+        }
+        inputs.token_tvl = token_tvl;
+        
+        Ok((inputs, apy))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,119 @@
+use crate::error::{AppError, AppResult};
+use crate::models::sponsorship::{SponsorshipBudget, SponsorshipBudgetResponse};
+use chrono::Utc;
+use sqlx::PgPool;
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct SponsorshipService {
+    db_pool: PgPool,
+}
+
+impl SponsorshipService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+    
+    pub async fn get_budget(&self, user_id: Uuid) -> AppResult<SponsorshipBudgetResponse> {
+        let budget = sqlx::query_as!(
+            SponsorshipBudget,
+            r#"
+            SELECT user_id, allowance, spent, created_at, updated_at
+            FROM sponsorship_budgets
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No sponsorship budget for user {}", user_id)))?;
+        
+        Ok(SponsorshipBudgetResponse::from(budget))
+    }
+    
+    pub async fn set_allowance(&self, user_id: Uuid, allowance: i64) -> AppResult<SponsorshipBudgetResponse> {
+        if allowance < 0 {
+            return Err(AppError::Validation("Sponsorship allowance cannot be negative".to_string()));
+        }
+        
+        let now = Utc::now();
+        let budget = sqlx::query_as!(
+            SponsorshipBudget,
+            r#"
+            INSERT INTO sponsorship_budgets (user_id, allowance, spent, created_at, updated_at)
+            VALUES ($1, $2, 0, $3, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET allowance = EXCLUDED.allowance, updated_at = EXCLUDED.updated_at
+            RETURNING user_id, allowance, spent, created_at, updated_at
+            "#,
+            user_id,
+            allowance,
+            now
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        info!("Set sponsorship allowance for user {} to {}", user_id, allowance);
+        Ok(SponsorshipBudgetResponse::from(budget))
+    }
+    
+    // Check that the user's remaining budget covers a gas fee before a sponsored transaction is built
+    pub async fn check_allowance(&self, user_id: Uuid, fee: i64) -> AppResult<()> {
+        let budget = self.get_budget(user_id).await?;
+        
+        if budget.remaining < fee {
+            return Err(AppError::Validation(format!(
+                "Insufficient sponsorship budget: {} remaining for a fee of {}",
+                budget.remaining, fee
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    // Reserve a gas fee against the budget of the user owning the address.
+    // The check and the update happen in one statement so concurrent requests cannot overspend.
+    pub async fn reserve(&self, address: &str, fee: i64) -> AppResult<Uuid> {
+        let reserved = sqlx::query!(
+            r#"
+            UPDATE sponsorship_budgets AS b
+            SET spent = b.spent + $2, updated_at = $3
+            FROM users AS u
+            WHERE u.id = b.user_id AND u.address = $1 AND b.spent + $2 <= b.allowance
+            RETURNING b.user_id
+            "#,
+            address,
+            fee,
+            Utc::now()
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+        
+        match reserved {
+            Some(row) => Ok(row.user_id),
+            None => Err(AppError::Validation(format!(
+                "Insufficient sponsorship budget for {} to cover a fee of {}",
+                address, fee
+            ))),
+        }
+    }
+    
+    // Give back a reserved fee when the sponsored transaction never made it on chain
+    pub async fn release(&self, user_id: Uuid, fee: i64) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE sponsorship_budgets
+            SET spent = GREATEST(spent - $2, 0), updated_at = $3
+            WHERE user_id = $1
+            "#,
+            user_id,
+            fee,
+            Utc::now()
+        )
+        .execute(&self.db_pool)
+        .await?;
+        
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,67 +1,88 @@
 use crate::blockchain::{BlockchainProvider, MultiversXClient, TransactionRequest};
+use crate::elizaos::transaction_manager::TransactionManager;
 use crate::error::{AppError, AppResult};
+use crate::guardian::GuardianSigner;
+use crate::models::position::{Position, PositionStrategy, RebalanceFrequency, TokenAllocation};
 use crate::models::transaction::{
-    CreateTransactionRequest, Transaction, TransactionResponse, TransactionStatus, TransactionType,
+    BroadcastTransactionRequest, BuildTransactionRequest, CreateTransactionRequest,
+    DelegationOperation, DelegationTransactionRequest, FarmOperation, FarmTransactionRequest,
+    LendingOperation, LendingTransactionRequest, Transaction,
+    TransactionResponse, TransactionStatus, TransactionType, UnsignedTransactionResponse,
 };
-use crate::utils::{egld_to_denomination, denomination_to_egld};
+use crate::services::SponsorshipService;
+use crate::smart_contracts::maiar_farm::to_raw_amount;
+use crate::smart_contracts::ProtocolRegistry;
+use crate::smart_contracts::protocol_interface::ProtocolInterface;
 use crate::wallet::Wallet;
 use chrono::Utc;
+use num_bigint::BigUint;
 use sqlx::PgPool;
-use tracing::{info, error, debug};
+use std::sync::Arc;
+use tracing::info;
 use uuid::Uuid;
-use base64::{Engine as _, engine::general_purpose};
 
+// A protocol call to build into an unsigned transaction, with what to record about it
+struct UnsignedCall {
+    tx_type: TransactionType,
+    amount: Option<String>,
+    token: Option<String>,
+    protocol_id: String,
+    position_id: Option<Uuid>,
+    receiver: String,
+    value: String,
+    data: String,
+    gas_limit: u64,
+    sponsored: bool,
+}
+
+#[derive(Clone)]
 pub struct TransactionService {
     db_pool: PgPool,
     blockchain_client: MultiversXClient,
+    protocol_registry: ProtocolRegistry,
+    guardian_signer: Option<Arc<dyn GuardianSigner>>,
+    relayed_transactions: Option<(Arc<TransactionManager>, SponsorshipService)>,
 }
 
 impl TransactionService {
-    pub fn new(db_pool: PgPool, blockchain_client: MultiversXClient) -> Self {
+    pub fn new(db_pool: PgPool, blockchain_client: MultiversXClient, protocol_registry: ProtocolRegistry) -> Self {
         Self {
             db_pool,
             blockchain_client,
+            protocol_registry,
+            guardian_signer: None,
+            relayed_transactions: None,
         }
     }
     
+    // Co-sign transactions of accounts guarded by this signer when the user supplies a 2FA code
+    pub fn with_guardian_signer(mut self, guardian_signer: Arc<dyn GuardianSigner>) -> Self {
+        self.guardian_signer = Some(guardian_signer);
+        self
+    }
+    
+    // Build and submit sponsored transactions through the relayer of this transaction manager
+    pub fn with_relayed_transactions(mut self, tx_manager: Arc<TransactionManager>, sponsorship: SponsorshipService) -> Self {
+        self.relayed_transactions = Some((tx_manager, sponsorship));
+        self
+    }
+    
+    fn relayed_transactions(&self) -> AppResult<&(Arc<TransactionManager>, SponsorshipService)> {
+        self.relayed_transactions.as_ref()
+            .ok_or_else(|| AppError::Validation("Gas sponsorship is not enabled".to_string()))
+    }
+    
     pub async fn create_transaction(
         &self,
         request: CreateTransactionRequest,
     ) -> AppResult<TransactionResponse> {
-        // Get user wallet address for blockchain operations
-        let user = sqlx::query!(
-            r#"
-            SELECT address FROM users WHERE id = $1
-            "#,
-            request.user_id
-        )
-        .fetch_one(&self.db_pool)
-        .await?;
-        
-        // Get protocol contract address if provided
-        let contract_address = if let Some(ref protocol_id) = request.protocol_id {
-            let protocol = sqlx::query!(
-                r#"
-                SELECT contract_address FROM protocols WHERE id = $1
-                "#,
-                protocol_id
-            )
-            .fetch_one(&self.db_pool)
-            .await?;
-            
-            protocol.contract_address
-        } else {
-            None
-        };
-        
         // Begin database transaction
         let mut tx = self.db_pool.begin().await?;
         
         let now = Utc::now();
         let tx_id = Uuid::new_v4();
-        let tx_hash = format!("pending_{}", Uuid::new_v4().to_string().replace("-", ""));
         
-        // Create transaction record
+        // Create transaction record; the hash is set once a signed transaction is broadcast
         let transaction = sqlx::query_as!(
             Transaction,
             r#"
@@ -69,7 +90,7 @@ impl TransactionService {
                 id, user_id, tx_hash, tx_type, amount, token,
                 status, protocol_id, position_id, created_at, updated_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            VALUES ($1, $2, NULL, $3, $4, $5, $6, $7, $8, $9, $10)
             RETURNING 
                 id, user_id, tx_hash, tx_type as "tx_type: TransactionType",
                 amount, token, status as "status: TransactionStatus",
@@ -77,7 +98,6 @@ impl TransactionService {
             "#,
             tx_id,
             request.user_id,
-            tx_hash,
             request.tx_type as _,
             request.amount,
             request.token,
@@ -95,12 +115,508 @@ impl TransactionService {
         
         info!("Created transaction: {} for user: {}", tx_id, request.user_id);
         
-        // Process the transaction on the blockchain
-        self.process_blockchain_transaction(transaction.clone(), user.address, contract_address).await?;
+        Ok(TransactionResponse::from(transaction))
+    }
+    
+    // Build an unsigned transaction for the user to sign with their own wallet
+    pub async fn build_unsigned_transaction(
+        &self,
+        user_id: Uuid,
+        request: BuildTransactionRequest,
+    ) -> AppResult<UnsignedTransactionResponse> {
+        // Get user wallet address for blockchain operations
+        let user = sqlx::query!(
+            r#"
+            SELECT address FROM users WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        let protocol = self.protocol_registry.get_protocol(&request.protocol_id)
+            .ok_or_else(|| AppError::NotFound(format!("Protocol {} not found", request.protocol_id)))?;
+        
+        let token = request.token.clone().unwrap_or_else(|| "EGLD".to_string());
+        let amount = request.amount.unwrap_or(0.0);
+        
+        // Encode the protocol call and the value to transfer for the requested operation
+        let (operation, data, value) = match request.tx_type {
+            TransactionType::Deposit | TransactionType::Stake => {
+                if amount <= 0.0 {
+                    return Err(AppError::Validation("Amount must be greater than zero".to_string()));
+                }
+                
+                let data = protocol.build_deposit_data(amount, &token).await?;
+                
+                // EGLD is sent as value, tokens must be paid through an ESDT transfer in the data
+                let value = if token == "EGLD" {
+                    to_raw_amount(amount, 18)?.to_string()
+                } else if data.starts_with("ESDTTransfer@") || data.starts_with("MultiESDTNFTTransfer@") {
+                    "0".to_string()
+                } else {
+                    return Err(AppError::Validation(format!(
+                        "{} deposits of {} are not supported",
+                        request.protocol_id, token
+                    )));
+                };
+                
+                ("deposit", data, value)
+            },
+            TransactionType::Withdraw | TransactionType::Unstake => {
+                if amount <= 0.0 {
+                    return Err(AppError::Validation("Amount must be greater than zero".to_string()));
+                }
+                
+                ("withdraw", protocol.build_withdraw_data(amount, &token).await?, "0".to_string())
+            },
+            TransactionType::Rebalance => {
+                let position = self.get_user_position(request.position_id, user_id).await?;
+                
+                ("rebalance", protocol.build_rebalance_data(&position).await?, "0".to_string())
+            },
+            TransactionType::Claim => {
+                let position = self.get_user_position(request.position_id, user_id).await?;
+                
+                ("claimRewards", protocol.build_claim_rewards_data(&position.id.to_string()).await?, "0".to_string())
+            },
+        };
+        
+        // Some protocols are called through a pool's own contract, such as a staking provider
+        let receiver = protocol.get_operation_receiver(operation, request.pool_id.as_deref(), &user.address, amount).await?;
+        
+        self.build_and_record(user_id, &user.address, operation, UnsignedCall {
+            tx_type: request.tx_type,
+            amount: request.amount.map(|a| a.to_string()),
+            token: Some(token),
+            protocol_id: request.protocol_id,
+            position_id: request.position_id,
+            receiver,
+            value,
+            data,
+            gas_limit: protocol.get_gas_limit_for_operation(operation),
+            sponsored: request.sponsored,
+        }).await
+    }
+    
+    // Build an unsigned xExchange farm or metastaking transaction for the user to sign
+    pub async fn build_farm_transaction(
+        &self,
+        user_id: Uuid,
+        request: FarmTransactionRequest,
+    ) -> AppResult<UnsignedTransactionResponse> {
+        let user = sqlx::query!(
+            r#"
+            SELECT address FROM users WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        let maiar_exchange = self.protocol_registry.maiar_exchange();
+        
+        let pool_id = request.pool_id.as_str();
+        let address = user.address.as_str();
+        
+        let (tx_type, call) = match request.operation {
+            FarmOperation::EnterFarm => {
+                self.check_deposits_open(maiar_exchange.get_id())?;
+                let lp_amount = request.lp_amount
+                    .filter(|amount| *amount > 0.0)
+                    .ok_or_else(|| AppError::Validation("LP amount must be greater than zero".to_string()))?;
+                
+                (TransactionType::Stake, maiar_exchange.build_enter_farm_call(pool_id, lp_amount).await?)
+            },
+            FarmOperation::ExitFarm => {
+                let (nonce, amount) = farm_token_amount(&request)?;
+                (TransactionType::Unstake, maiar_exchange.build_exit_farm_call(address, pool_id, nonce, &amount).await?)
+            },
+            FarmOperation::ClaimFarmRewards => {
+                let (nonce, amount) = farm_token_amount(&request)?;
+                (TransactionType::Claim, maiar_exchange.build_claim_farm_rewards_call(address, pool_id, nonce, &amount).await?)
+            },
+            FarmOperation::EnterMetastaking => {
+                self.check_deposits_open(maiar_exchange.get_id())?;
+                let (nonce, amount) = farm_token_amount(&request)?;
+                (TransactionType::Stake, maiar_exchange.build_enter_metastaking_call(address, pool_id, nonce, &amount).await?)
+            },
+            FarmOperation::ExitMetastaking => {
+                let (nonce, amount) = farm_token_amount(&request)?;
+                (TransactionType::Unstake, maiar_exchange.build_exit_metastaking_call(address, pool_id, nonce, &amount).await?)
+            },
+            FarmOperation::ClaimMetastaking => {
+                let (nonce, amount) = farm_token_amount(&request)?;
+                (TransactionType::Claim, maiar_exchange.build_claim_metastaking_call(address, pool_id, nonce, &amount).await?)
+            },
+        };
+        
+        let amount = match request.operation {
+            FarmOperation::EnterFarm => request.lp_amount.map(|a| a.to_string()),
+            _ => request.token_amount.clone(),
+        };
+        
+        let operation = format!("{:?}", request.operation);
+        self.build_and_record(user_id, &user.address, &operation, UnsignedCall {
+            tx_type,
+            amount,
+            token: None,
+            protocol_id: maiar_exchange.get_id().to_string(),
+            position_id: None,
+            receiver: call.receiver,
+            value: call.value,
+            data: call.data,
+            gas_limit: call.gas_limit,
+            sponsored: request.sponsored,
+        }).await
+    }
+    
+    // Build an unsigned Hatom borrow, repay or collateral transaction for the user to sign
+    pub async fn build_lending_transaction(
+        &self,
+        user_id: Uuid,
+        request: LendingTransactionRequest,
+    ) -> AppResult<UnsignedTransactionResponse> {
+        let user = sqlx::query!(
+            r#"
+            SELECT address FROM users WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        if request.amount <= 0.0 {
+            return Err(AppError::Validation("Amount must be greater than zero".to_string()));
+        }
+        
+        let hatom = self.protocol_registry.hatom();
+        let token = request.token.as_deref()
+            .ok_or_else(|| AppError::Validation("Token is required".to_string()));
+        let pool_id = request.pool_id.as_deref()
+            .ok_or_else(|| AppError::Validation("Pool ID is required".to_string()));
+        
+        let (tx_type, call, recorded_token) = match request.operation {
+            LendingOperation::Borrow => {
+                let token = token?;
+                (TransactionType::Withdraw, hatom.build_borrow_call(request.amount, token).await?, token.to_string())
+            },
+            LendingOperation::Repay => {
+                let token = token?;
+                (TransactionType::Deposit, hatom.build_repay_call(request.amount, token).await?, token.to_string())
+            },
+            LendingOperation::EnterMarket => {
+                self.check_deposits_open(hatom.get_id())?;
+                let pool_id = pool_id?;
+                (TransactionType::Stake, hatom.build_enter_market_call(pool_id, request.amount).await?, pool_id.to_string())
+            },
+            LendingOperation::ExitMarket => {
+                let pool_id = pool_id?;
+                (TransactionType::Unstake, hatom.build_exit_market_call(pool_id, request.amount).await?, pool_id.to_string())
+            },
+        };
+        
+        let operation = format!("{:?}", request.operation);
+        self.build_and_record(user_id, &user.address, &operation, UnsignedCall {
+            tx_type,
+            amount: Some(request.amount.to_string()),
+            token: Some(recorded_token),
+            protocol_id: hatom.get_id().to_string(),
+            position_id: None,
+            receiver: call.receiver,
+            value: call.value,
+            data: call.data,
+            gas_limit: call.gas_limit,
+            sponsored: request.sponsored,
+        }).await
+    }
+    
+    // Build an unsigned transaction withdrawing unbonded stake from, or redelegating the rewards of, a staking provider
+    pub async fn build_delegation_transaction(
+        &self,
+        user_id: Uuid,
+        request: DelegationTransactionRequest,
+    ) -> AppResult<UnsignedTransactionResponse> {
+        let user = sqlx::query!(
+            r#"
+            SELECT address FROM users WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        let delegation = self.protocol_registry.delegation();
+        let provider = delegation.get_provider(&request.provider).await?;
+        
+        let (tx_type, call) = match request.operation {
+            DelegationOperation::WithdrawUnbonded => (TransactionType::Withdraw, delegation.build_withdraw_call(&provider.address)),
+            DelegationOperation::RedelegateRewards => {
+                self.check_deposits_open(delegation.get_id())?;
+                (TransactionType::Stake, delegation.build_redelegate_call(&provider.address))
+            },
+        };
+        
+        let operation = format!("{:?}", request.operation);
+        self.build_and_record(user_id, &user.address, &operation, UnsignedCall {
+            tx_type,
+            amount: None,
+            token: Some("EGLD".to_string()),
+            protocol_id: delegation.get_id().to_string(),
+            position_id: None,
+            receiver: call.receiver,
+            value: call.value,
+            data: call.data,
+            gas_limit: call.gas_limit,
+            sponsored: request.sponsored,
+        }).await
+    }
+    
+    // Farm, lending and delegation calls are built on the protocols directly, so the pause that the
+    // monitored protocol applies to deposits is checked here; exits and repayments stay open
+    fn check_deposits_open(&self, protocol_id: &str) -> AppResult<()> {
+        if self.protocol_registry.is_paused(protocol_id) {
+            let reason = self.protocol_registry.get_health(protocol_id)
+                .ok()
+                .and_then(|health| health.pause_reason)
+                .unwrap_or_default();
+            return Err(AppError::Validation(format!("{} is paused for deposits: {}", protocol_id, reason)));
+        }
+        Ok(())
+    }
+    
+    // Fill in nonce, gas and chain ID and record the transaction with its payload, naming the
+    // relayer when the platform pays the gas
+    async fn build_and_record(
+        &self,
+        user_id: Uuid,
+        sender: &str,
+        operation: &str,
+        call: UnsignedCall,
+    ) -> AppResult<UnsignedTransactionResponse> {
+        let transaction = if call.sponsored {
+            let (tx_manager, sponsorship) = self.relayed_transactions()?;
+            let transaction = tx_manager.prepare_relayed_transaction(
+                sender,
+                &call.receiver,
+                call.value,
+                Some(call.data),
+                Some(call.gas_limit),
+            ).await?;
+            
+            let fee = i64::try_from(transaction.gasLimit as u128 * transaction.gasPrice as u128)
+                .map_err(|_| AppError::Validation("Transaction fee exceeds the sponsorship limit".to_string()))?;
+            sponsorship.check_allowance(user_id, fee).await?;
+            
+            transaction
+        } else {
+            self.blockchain_client.build_transaction(
+                sender,
+                &call.receiver,
+                call.value,
+                Some(call.data),
+                Some(call.gas_limit),
+            ).await?
+        };
+        
+        // Record the transaction; the hash is only known once the user has signed it
+        let now = Utc::now();
+        let tx_id = Uuid::new_v4();
+        
+        sqlx::query!(
+            r#"
+            INSERT INTO transactions (
+                id, user_id, tx_hash, tx_type, amount, token,
+                status, protocol_id, position_id, created_at, updated_at,
+                receiver, value, data, nonce, gas_limit, gas_price, relayer
+            )
+            VALUES ($1, $2, NULL, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            "#,
+            tx_id,
+            user_id,
+            call.tx_type as _,
+            call.amount,
+            call.token,
+            TransactionStatus::Pending as _,
+            Some(call.protocol_id),
+            call.position_id,
+            now,
+            now,
+            transaction.receiver,
+            transaction.value,
+            transaction.data,
+            transaction.nonce as i64,
+            transaction.gasLimit as i64,
+            transaction.gasPrice as i64,
+            transaction.relayer
+        )
+        .execute(&self.db_pool)
+        .await?;
+        
+        info!("Built unsigned {} transaction {} for user {}", operation, tx_id, user_id);
+        
+        Ok(UnsignedTransactionResponse {
+            transaction_id: tx_id,
+            transaction,
+        })
+    }
+    
+    // Verify and submit a transaction signed by the user, linking it to its transaction record
+    pub async fn broadcast_transaction(
+        &self,
+        user_id: Uuid,
+        request: BroadcastTransactionRequest,
+    ) -> AppResult<TransactionResponse> {
+        let user = sqlx::query!(
+            r#"
+            SELECT address FROM users WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        let transaction = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT 
+                id, user_id, tx_hash, tx_type as "tx_type: TransactionType",
+                amount, token, status as "status: TransactionStatus",
+                protocol_id, position_id, created_at, updated_at
+            FROM transactions
+            WHERE id = $1 AND user_id = $2
+            "#,
+            request.transaction_id,
+            user_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Transaction {} not found", request.transaction_id)))?;
+        
+        if transaction.tx_hash.is_some() {
+            return Err(AppError::Validation(format!(
+                "Transaction {} has already been broadcast",
+                transaction.id
+            )));
+        }
+        
+        let mut signed_tx = request.transaction;
+        
+        // The signed transaction must come from the authenticated user on our network
+        if signed_tx.sender != user.address {
+            return Err(AppError::Auth("Transaction sender does not match the authenticated address".to_string()));
+        }
+        
+        // And carry exactly the call that was built for it
+        self.verify_built_payload(transaction.id, &signed_tx).await?;
+        
+        if signed_tx.chainID != self.blockchain_client.get_network_config().chain_id {
+            return Err(AppError::Validation(format!("Invalid chain ID: {}", signed_tx.chainID)));
+        }
+        
+        if !signed_tx.verify_signature()? {
+            return Err(AppError::Auth("Invalid transaction signature".to_string()));
+        }
+        
+        // Guarded accounts also need a valid guardian co-signature
+        if signed_tx.is_guarded() {
+            if signed_tx.guardianSignature.is_none() {
+                signed_tx.guardianSignature = Some(self.co_sign_as_guardian(&signed_tx, request.guardian_code.as_deref()).await?);
+            }
+            
+            if !signed_tx.verify_guardian_signature()? {
+                return Err(AppError::Auth("Invalid guardian signature".to_string()));
+            }
+        }
         
+        // Submit the transaction to the blockchain, through the relayer when it is sponsored;
+        // confirmation is tracked afterwards so the hash is recorded as soon as it is known
+        let tx_hash = if signed_tx.relayer.is_some() {
+            let (tx_manager, _) = self.relayed_transactions()?;
+            tx_manager.submit_relayed_transaction(signed_tx).await?
+        } else {
+            self.blockchain_client.send_transaction(signed_tx).await?
+        };
+        
+        // Link the transaction record to the submitted hash
+        let transaction = sqlx::query_as!(
+            Transaction,
+            r#"
+            UPDATE transactions
+            SET tx_hash = $1, updated_at = $2
+            WHERE id = $3
+            RETURNING 
+                id, user_id, tx_hash, tx_type as "tx_type: TransactionType",
+                amount, token, status as "status: TransactionStatus",
+                protocol_id, position_id, created_at, updated_at
+            "#,
+            tx_hash,
+            Utc::now(),
+            transaction.id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        info!("Broadcast transaction {} with hash {}", transaction.id, tx_hash);
         Ok(TransactionResponse::from(transaction))
     }
     
+    // Compare a signed transaction with the payload recorded when it was built
+    async fn verify_built_payload(&self, transaction_id: Uuid, signed_tx: &TransactionRequest) -> AppResult<()> {
+        let built = sqlx::query!(
+            r#"
+            SELECT receiver, value, data, nonce, gas_limit, gas_price, relayer
+            FROM transactions
+            WHERE id = $1
+            "#,
+            transaction_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        
+        let (receiver, value, nonce, gas_limit, gas_price) = match (
+            built.receiver,
+            built.value,
+            built.nonce,
+            built.gas_limit,
+            built.gas_price,
+        ) {
+            (Some(receiver), Some(value), Some(nonce), Some(gas_limit), Some(gas_price)) => {
+                (receiver, value, nonce, gas_limit, gas_price)
+            },
+            _ => return Err(AppError::Validation(format!(
+                "Transaction {} was not built for signing",
+                transaction_id
+            ))),
+        };
+        
+        let mismatch = if signed_tx.receiver != receiver {
+            Some("receiver")
+        } else if signed_tx.value != value {
+            Some("value")
+        } else if signed_tx.data != built.data {
+            Some("data")
+        } else if signed_tx.nonce as i64 != nonce {
+            Some("nonce")
+        } else if signed_tx.gasLimit as i64 != gas_limit {
+            Some("gas limit")
+        } else if signed_tx.gasPrice as i64 != gas_price {
+            Some("gas price")
+        } else if signed_tx.relayer != built.relayer {
+            Some("relayer")
+        } else {
+            None
+        };
+        
+        match mismatch {
+            Some(field) => Err(AppError::Validation(format!(
+                "Signed transaction {} does not match the built transaction",
+                field
+            ))),
+            None => Ok(()),
+        }
+    }
+    
     pub async fn get_transactions_by_user(
         &self,
         user_id: Uuid,
@@ -203,322 +719,45 @@ impl TransactionService {
         Ok(TransactionResponse::from(transaction))
     }
     
-    // Helper method to process a transaction on the blockchain
-    async fn process_blockchain_transaction(
-        &self,
-        transaction: Transaction,
-        sender_address: String,
-        receiver_address: Option<String>,
-    ) -> AppResult<()> {
-        // Skip processing for pending transactions that don't have a real hash yet
-        if transaction.tx_hash.starts_with("pending_") {
-            tokio::spawn({
-                let db_pool = self.db_pool.clone();
-                let blockchain_client = self.blockchain_client.clone();
-                let transaction = transaction.clone();
-                let sender_address = sender_address.clone();
-                let receiver_address = receiver_address.clone();
-                
-                async move {
-                    // In a real implementation, this would create and submit a blockchain transaction
-                    debug!("Processing blockchain transaction for transaction ID {}", transaction.id);
-                    
-                    // Simulate blockchain transaction processing
-                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                    
-                    // Generate a realistic transaction hash
-                    let tx_hash = format!("tx_{}", Uuid::new_v4().to_string().replace("-", ""));
-                    
-                    // Update transaction record with hash and status
-                    let result = sqlx::query!(
-                        r#"
-                        UPDATE transactions
-                        SET tx_hash = $1, status = $2, updated_at = $3
-                        WHERE id = $4
-                        "#,
-                        tx_hash,
-                        TransactionStatus::Success as _,
-                        Utc::now(),
-                        transaction.id
-                    )
-                    .execute(&db_pool)
-                    .await;
-                    
-                    if let Err(e) = result {
-                        error!("Failed to update transaction status: {}", e);
-                    } else {
-                        info!("Transaction {} processed successfully with hash {}", transaction.id, tx_hash);
-                    }
-                }
-            });
-            
-            return Ok(());
-        }
-        
-        // For real blockchain transactions (not pending)
-        match transaction.tx_type {
-            TransactionType::Deposit => {
-                self.process_deposit_transaction(&transaction, &sender_address, receiver_address).await?;
-            },
-            TransactionType::Withdraw => {
-                self.process_withdraw_transaction(&transaction, &sender_address, receiver_address).await?;
-            },
-            TransactionType::Rebalance => {
-                self.process_rebalance_transaction(&transaction, &sender_address, receiver_address).await?;
-            },
-            TransactionType::Claim => {
-                self.process_claim_transaction(&transaction, &sender_address, receiver_address).await?;
-            },
-            TransactionType::Stake => {
-                self.process_stake_transaction(&transaction, &sender_address, receiver_address).await?;
-            },
-            TransactionType::Unstake => {
-                self.process_unstake_transaction(&transaction, &sender_address, receiver_address).await?;
-            },
-        }
-        
-        Ok(())
-    }
-    
-    // Helper methods for specific transaction types
-    async fn process_deposit_transaction(
-        &self,
-        transaction: &Transaction,
-        sender_address: &str,
-        receiver_address: Option<String>,
-    ) -> AppResult<()> {
-        // In a real implementation, this would create and submit a deposit transaction to the protocol's smart contract
-        
-        // For now, we'll simulate the transaction
-        tokio::spawn({
-            let db_pool = self.db_pool.clone();
-            let transaction_id = transaction.id;
-            
-            async move {
-                // Simulate blockchain confirmation delay
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                
-                // Update transaction status to success
-                let result = sqlx::query!(
-                    r#"
-                    UPDATE transactions
-                    SET status = $1, updated_at = $2
-                    WHERE id = $3
-                    "#,
-                    TransactionStatus::Success as _,
-                    Utc::now(),
-                    transaction_id
-                )
-                .execute(&db_pool)
-                .await;
-                
-                if let Err(e) = result {
-                    error!("Failed to update transaction status: {}", e);
-                }
-            }
-        });
+    // Helper method to get the guardian co-signature from our guardian signer
+    async fn co_sign_as_guardian(&self, transaction: &TransactionRequest, code: Option<&str>) -> AppResult<String> {
+        let guardian_signer = self.guardian_signer.as_ref()
+            .filter(|signer| transaction.guardian.as_deref() == Some(signer.guardian_address().as_str()))
+            .ok_or_else(|| AppError::Validation("Guarded transactions must include the guardian signature".to_string()))?;
         
-        Ok(())
-    }
-    
-    async fn process_withdraw_transaction(
-        &self,
-        transaction: &Transaction,
-        sender_address: &str,
-        receiver_address: Option<String>,
-    ) -> AppResult<()> {
-        // In a real implementation, this would create and submit a withdrawal transaction from the protocol's smart contract
-        
-        // For now, we'll simulate the transaction
-        tokio::spawn({
-            let db_pool = self.db_pool.clone();
-            let transaction_id = transaction.id;
-            
-            async move {
-                // Simulate blockchain confirmation delay
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                
-                // Update transaction status to success
-                let result = sqlx::query!(
-                    r#"
-                    UPDATE transactions
-                    SET status = $1, updated_at = $2
-                    WHERE id = $3
-                    "#,
-                    TransactionStatus::Success as _,
-                    Utc::now(),
-                    transaction_id
-                )
-                .execute(&db_pool)
-                .await;
-                
-                if let Err(e) = result {
-                    error!("Failed to update transaction status: {}", e);
-                }
-            }
-        });
+        let code = code
+            .ok_or_else(|| AppError::Validation("A 2FA code is required to co-sign guarded transactions".to_string()))?;
         
-        Ok(())
+        guardian_signer.co_sign(transaction, code).await
     }
     
-    async fn process_rebalance_transaction(
-        &self,
-        transaction: &Transaction,
-        sender_address: &str,
-        receiver_address: Option<String>,
-    ) -> AppResult<()> {
-        // In a real implementation, this would create and submit a rebalance transaction to the protocol's smart contract
-        
-        // For now, we'll simulate the transaction
-        tokio::spawn({
-            let db_pool = self.db_pool.clone();
-            let transaction_id = transaction.id;
-            
-            async move {
-                // Simulate blockchain confirmation delay
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                
-                // Update transaction status to success
-                let result = sqlx::query!(
-                    r#"
-                    UPDATE transactions
-                    SET status = $1, updated_at = $2
-                    WHERE id = $3
-                    "#,
-                    TransactionStatus::Success as _,
-                    Utc::now(),
-                    transaction_id
-                )
-                .execute(&db_pool)
-                .await;
-                
-                if let Err(e) = result {
-                    error!("Failed to update transaction status: {}", e);
-                }
-            }
-        });
+    // Helper method to get a position owned by the user
+    async fn get_user_position(&self, position_id: Option<Uuid>, user_id: Uuid) -> AppResult<Position> {
+        let position_id = position_id
+            .ok_or_else(|| AppError::Validation("A position ID is required for this transaction type".to_string()))?;
         
-        Ok(())
-    }
-    
-    async fn process_claim_transaction(
-        &self,
-        transaction: &Transaction,
-        sender_address: &str,
-        receiver_address: Option<String>,
-    ) -> AppResult<()> {
-        // In a real implementation, this would create and submit a claim rewards transaction to the protocol's smart contract
-        
-        // For now, we'll simulate the transaction
-        tokio::spawn({
-            let db_pool = self.db_pool.clone();
-            let transaction_id = transaction.id;
-            
-            async move {
-                // Simulate blockchain confirmation delay
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                
-                // Update transaction status to success
-                let result = sqlx::query!(
-                    r#"
-                    UPDATE transactions
-                    SET status = $1, updated_at = $2
-                    WHERE id = $3
-                    "#,
-                    TransactionStatus::Success as _,
-                    Utc::now(),
-                    transaction_id
-                )
-                .execute(&db_pool)
-                .await;
-                
-                if let Err(e) = result {
-                    error!("Failed to update transaction status: {}", e);
-                }
-            }
-        });
-        
-        Ok(())
-    }
-    
-    async fn process_stake_transaction(
-        &self,
-        transaction: &Transaction,
-        sender_address: &str,
-        receiver_address: Option<String>,
-    ) -> AppResult<()> {
-        // In a real implementation, this would create and submit a stake transaction to the protocol's smart contract
-        
-        // For now, we'll simulate the transaction
-        tokio::spawn({
-            let db_pool = self.db_pool.clone();
-            let transaction_id = transaction.id;
-            
-            async move {
-                // Simulate blockchain confirmation delay
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                
-                // Update transaction status to success
-                let result = sqlx::query!(
-                    r#"
-                    UPDATE transactions
-                    SET status = $1, updated_at = $2
-                    WHERE id = $3
-                    "#,
-                    TransactionStatus::Success as _,
-                    Utc::now(),
-                    transaction_id
-                )
-                .execute(&db_pool)
-                .await;
-                
-                if let Err(e) = result {
-                    error!("Failed to update transaction status: {}", e);
-                }
-            }
-        });
-        
-        Ok(())
-    }
-    
-    async fn process_unstake_transaction(
-        &self,
-        transaction: &Transaction,
-        sender_address: &str,
-        receiver_address: Option<String>,
-    ) -> AppResult<()> {
-        // In a real implementation, this would create and submit an unstake transaction to the protocol's smart contract
-        
-        // For now, we'll simulate the transaction
-        tokio::spawn({
-            let db_pool = self.db_pool.clone();
-            let transaction_id = transaction.id;
-            
-            async move {
-                // Simulate blockchain confirmation delay
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                
-                // Update transaction status to success
-                let result = sqlx::query!(
-                    r#"
-                    UPDATE transactions
-                    SET status = $1, updated_at = $2
-                    WHERE id = $3
-                    "#,
-                    TransactionStatus::Success as _,
-                    Utc::now(),
-                    transaction_id
-                )
-                .execute(&db_pool)
-                .await;
-                
-                if let Err(e) = result {
-                    error!("Failed to update transaction status: {}", e);
-                }
-            }
-        });
+        let position = sqlx::query_as!(
+            Position,
+            r#"
+            SELECT 
+                id, user_id, protocol_id, name, position_type, tokens,
+                deposited, current_value, apy, strategy as "strategy: PositionStrategy",
+                entry_date, last_rebalance, 
+                rebalance_frequency as "rebalance_frequency: RebalanceFrequency",
+                allocation as "allocation: Vec<TokenAllocation>", metadata,
+                compound_frequency as "compound_frequency: RebalanceFrequency", last_compound,
+                created_at, updated_at
+            FROM positions
+            WHERE id = $1 AND user_id = $2
+            "#,
+            position_id,
+            user_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Position with ID {} not found", position_id)))?;
         
-        Ok(())
+        Ok(position)
     }
     
     // Helper method to update transaction statuses from blockchain
@@ -535,10 +774,11 @@ impl TransactionService {
     
     // Helper method to update a single transaction's status from blockchain
     async fn update_transaction_status(&self, transaction: Transaction) -> AppResult<Transaction> {
-        // Skip pending transactions
-        if transaction.tx_hash.starts_with("pending_") {
-            return Ok(transaction);
-        }
+        // Skip transactions that have not been broadcast yet
+        let tx_hash = match transaction.tx_hash {
+            Some(ref tx_hash) => tx_hash.clone(),
+            None => return Ok(transaction),
+        };
         
         // Skip transactions that are already in a final state
         match transaction.status {
@@ -547,7 +787,7 @@ impl TransactionService {
         }
         
         // In a real implementation, this would query the blockchain for the transaction status
-        let blockchain_status = self.blockchain_client.get_transaction_status(&transaction.tx_hash).await?;
+        let blockchain_status = self.blockchain_client.get_transaction_status(&tx_hash).await?;
         
         // Only update in database if status has changed
         if blockchain_status != transaction.status {
@@ -598,7 +838,12 @@ impl TransactionService {
             data: data,
             chainID: self.blockchain_client.get_network_config().chain_id.clone(),
             version: 1,
+            options: 0,
+            guardian: None,
+            relayer: None,
             signature: "".to_string(), // Will be filled below
+            guardianSignature: None,
+            relayerSignature: None,
         };
         
         // Serialize transaction for signing
@@ -626,4 +871,16 @@ impl TransactionService {
         let args_str = args.join(",");
         format!("{}@{}", function, args_str)
     }
+}
+
+// Nonce and raw amount of the farm or metastaking token sent back by a farm operation
+fn farm_token_amount(request: &FarmTransactionRequest) -> AppResult<(u64, BigUint)> {
+    let nonce = request.token_nonce
+        .ok_or_else(|| AppError::Validation("Token nonce is required".to_string()))?;
+    let amount = request.token_amount.as_deref()
+        .and_then(|amount| amount.parse::<BigUint>().ok())
+        .filter(|amount| *amount > BigUint::from(0u8))
+        .ok_or_else(|| AppError::Validation("Token amount must be a positive integer".to_string()))?;
+    
+    Ok((nonce, amount))
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
 use crate::error::AppResult;
 use crate::models::position::PositionStrategy;
 use crate::smart_contracts::ProtocolRegistry;
+use crate::smart_contracts::protocol_interface::ProtocolInterface;
+use crate::smart_contracts::scan::{scan_pools, scan_protocols, ScanLimits, ScanResult};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::{info, debug};
 
 pub struct YieldOpportunity {
@@ -17,77 +20,105 @@ pub struct YieldOpportunity {
 
 pub struct YieldOptimizerService {
     protocol_registry: ProtocolRegistry,
+    scan_limits: ScanLimits,
 }
 
 impl YieldOptimizerService {
     pub fn new(protocol_registry: ProtocolRegistry) -> Self {
         Self {
             protocol_registry,
+            scan_limits: ScanLimits::default(),
         }
     }
     
-    // Find the best yield opportunities across all protocols
+    // Set the concurrency and timeout limits of opportunity searches
+    pub fn with_scan_limits(mut self, scan_limits: ScanLimits) -> Self {
+        self.scan_limits = scan_limits;
+        self
+    }
+    
+    // Find the best yield opportunities across all protocols; protocols are queried concurrently
+    // and those that fail or time out are reported as skipped instead of failing the search
     pub async fn find_best_opportunities(&self, 
                                         strategy: &PositionStrategy, 
                                         token: Option<String>,
-                                        limit: usize) -> AppResult<Vec<YieldOpportunity>> {
+                                        limit: usize) -> AppResult<ScanResult<YieldOpportunity>> {
         debug!("Finding best yield opportunities for strategy {:?}, token: {:?}", strategy, token);
         
-        let mut opportunities = Vec::new();
-        
-        // Iterate through all protocols
-        for protocol in self.protocol_registry.get_all_protocols() {
-            let protocol_id = protocol.get_id().to_string();
-            let protocol_name = protocol.get_name().to_string();
-            
-            // Get pools for this protocol
-            let pools = protocol.get_pools().await?;
-            
-            // Check each pool
-            for pool_id in pools {
-                // Get pool details
-                let apy = protocol.get_pool_apy(&pool_id).await?;
-                let tvl = protocol.get_pool_tvl(&pool_id).await?;
-                let risk = protocol.get_risk_level().to_string();
-                
-                // Get supported tokens for this pool
-                let tokens = protocol.get_supported_tokens().await?;
-                
-                // Filter by token if specified
-                if let Some(ref token_filter) = token {
-                    if !tokens.contains(token_filter) {
-                        continue;
-                    }
+        let scans = self.protocol_registry.get_all_protocols().into_iter()
+            .filter(|protocol| {
+                // Paused protocols can't take the deposits an opportunity leads to
+                if !protocol.accepts_deposits() {
+                    debug!("Skipping protocol {}, which is paused for deposits", protocol.get_id());
                 }
-                
-                // Create opportunity
-                let opportunity = YieldOpportunity {
-                    protocol_id: protocol_id.clone(),
-                    protocol_name: protocol_name.clone(),
-                    pool_id: pool_id.clone(),
-                    pool_name: pool_id.clone(), // In a real implementation, we would get the actual pool name
-                    apy,
-                    tvl,
-                    risk: risk.clone(),
-                    tokens,
-                };
-                
-                opportunities.push(opportunity);
-            }
-        }
+                protocol.accepts_deposits()
+            })
+            .map(|protocol| {
+                let protocol_id = protocol.get_id().to_string();
+                let scan = self.scan_protocol(protocol, token.as_deref());
+                (protocol_id, scan)
+            })
+            .collect();
+        
+        let mut scan = scan_protocols(scans, &self.scan_limits).await;
         
         // Filter opportunities based on strategy
-        self.filter_by_strategy(&mut opportunities, strategy);
+        self.filter_by_strategy(&mut scan.items, strategy);
         
         // Sort by APY (descending)
-        opportunities.sort_by(|a, b| b.apy.partial_cmp(&a.apy).unwrap_or(std::cmp::Ordering::Equal));
+        scan.items.sort_by(|a, b| b.apy.partial_cmp(&a.apy).unwrap_or(std::cmp::Ordering::Equal));
         
         // Limit results
-        if opportunities.len() > limit {
-            opportunities.truncate(limit);
+        if scan.items.len() > limit {
+            scan.items.truncate(limit);
         }
         
-        Ok(opportunities)
+        info!("Found {} yield opportunities, skipped {} protocols or pools", scan.items.len(), scan.skipped.len());
+        Ok(scan)
+    }
+    
+    // Get the opportunities of every pool of a protocol
+    async fn scan_protocol(&self, 
+                          protocol: Arc<dyn ProtocolInterface + Send + Sync>,
+                          token: Option<&str>) -> AppResult<ScanResult<YieldOpportunity>> {
+        let protocol_id = protocol.get_id().to_string();
+        let protocol_name = protocol.get_name().to_string();
+        let risk = protocol.get_risk_level().to_string();
+        
+        // Get pools for this protocol
+        let pools = protocol.get_pools().await?;
+        
+        let (protocol, protocol_id, protocol_name, risk) = (&protocol, &protocol_id, &protocol_name, &risk);
+        let scan = scan_pools(protocol_id, pools, &self.scan_limits, |pool_id| async move {
+            // Get the tokens held by this pool
+            let pool_info = protocol.get_pool_info(&pool_id).await?;
+            
+            // Filter by token if specified
+            if let Some(token_filter) = token {
+                if !pool_info.tokens.iter().any(|t| t == token_filter) {
+                    return Ok(None);
+                }
+            }
+            
+            // Get pool details
+            let (apy, tvl) = tokio::try_join!(
+                protocol.get_pool_apy(&pool_id),
+                protocol.get_pool_tvl(&pool_id),
+            )?;
+            
+            Ok(Some(YieldOpportunity {
+                protocol_id: protocol_id.clone(),
+                protocol_name: protocol_name.clone(),
+                pool_id,
+                pool_name: pool_info.name,
+                apy,
+                tvl,
+                risk: risk.clone(),
+                tokens: pool_info.tokens,
+            }))
+        }).await;
+        
+        Ok(scan)
     }
     
     // Filter opportunities based on strategy
@@ -116,7 +147,7 @@ impl YieldOptimizerService {
         let mut allocation = HashMap::new();
         
         // Get best opportunities
-        let opportunities = self.find_best_opportunities(strategy, None, 5).await?;
+        let opportunities = self.find_best_opportunities(strategy, None, 5).await?.items;
         
         match strategy {
             PositionStrategy::Conservative => {
@@ -1,10 +1,31 @@
 use crate::error::{AppError, AppResult};
 use crate::models::position::{Position, PositionStrategy};
-use crate::smart_contracts::protocol_interface::ProtocolInterface;
+use crate::smart_contracts::maiar_farm::{encode_biguint, esdt_transfer_data, from_raw_amount, to_raw_amount};
+use crate::smart_contracts::protocol_interface::{
+    PendingReward, PoolInfo, ProtocolInterface, SwapCurve, SwapPool,
+};
+use crate::smart_contracts::stableswap::{min_output, LiquidityQuote, StableSwapPool, SwapQuote};
 use crate::blockchain::MultiversXClient;
+use crate::utils::address_to_public_key;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
 use std::collections::HashMap;
-use tracing::{info, debug, error};
+use std::sync::RwLock;
+use tracing::{info, debug, error, warn};
+
+// The pool stores the amplification coefficient scaled by this factor
+const AMP_PRECISION: f64 = 100.0;
+// ASH rewards use 18 decimals
+const REWARD_DECIMALS: u32 = 18;
+
+// Maximum slippage accepted on deposits and withdrawals before the transaction reverts
+const DEFAULT_MAX_SLIPPAGE: f64 = 0.005;
+
+// Fee APY is measured from the growth of the virtual price over at least this long
+const APY_WINDOW_SECONDS: i64 = 3600;
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
 
 pub struct AshSwapProtocol {
     id: String,
@@ -12,16 +33,21 @@ pub struct AshSwapProtocol {
     contract_address: String,
     blockchain_client: MultiversXClient,
     pools: HashMap<String, StablePoolInfo>,
+    // Fee APY per pool ID, once measured, and the virtual price snapshot it is measured from
+    apy_measurements: RwLock<HashMap<String, (Option<f64>, DateTime<Utc>, f64)>>,
 }
 
 struct StablePoolInfo {
     id: String,
     name: String,
     tokens: Vec<String>,
-    apy: f64,
-    tvl: f64,
+    // Token identifiers and decimals, in the pool's token order
+    token_ids: Vec<String>,
+    decimals: Vec<u32>,
+    lp_token: String,
+    pool_address: String,
+    reward_tokens: Vec<String>,
     risk: String,
-    volume_24h: f64,
     fee: f64,
 }
 
@@ -36,10 +62,12 @@ impl AshSwapProtocol {
                 id: "stable-pool".to_string(),
                 name: "Stablecoin Pool".to_string(),
                 tokens: vec!["USDC".to_string(), "USDT".to_string(), "BUSD".to_string()],
-                apy: 9.2,
-                tvl: 32_100_000.0,
+                token_ids: vec!["USDC-c76f1f".to_string(), "USDT-f8c08c".to_string(), "BUSD-40b57e".to_string()],
+                decimals: vec![6, 6, 18],
+                lp_token: "ALP-0fb7a1".to_string(),
+                pool_address: "erd1qqqqqqqqqqqqqpgqs8p2v9wr8j48vqrmudcj94wu47kqra3r4fvshfyd9c".to_string(),
+                reward_tokens: vec!["ASH".to_string()],
                 risk: "Low".to_string(),
-                volume_24h: 1_500_000.0,
                 fee: 0.0004, // 0.04%
             },
         );
@@ -50,6 +78,7 @@ impl AshSwapProtocol {
             contract_address: "erd1qqqqqqqqqqqqqpgq5774jcntdqkzv62tlvvhfn2y7eevpty6rchsq7k4hp".to_string(),
             blockchain_client,
             pools,
+            apy_measurements: RwLock::new(HashMap::new()),
         }
     }
     
@@ -71,29 +100,122 @@ impl AshSwapProtocol {
         )
     }
     
-    // Helper method to update pool data from blockchain
-    async fn update_pool_data(&mut self) -> AppResult<()> {
-        debug!("Updating stable pool data from blockchain for AshSwap");
+    // Helper method to find a stable pool that supports a token
+    fn find_pool_id_for_token(&self, token: &str) -> AppResult<String> {
+        self.pools.values()
+            .find(|p| p.tokens.contains(&token.to_string()))
+            .map(|p| p.id.clone())
+            .ok_or_else(|| AppError::Validation(format!("No stable pool found for token {}", token)))
+    }
+    
+    /// Read the reserves, amplification and LP supply of a pool from the chain
+    pub async fn get_pool_state(&self, pool_id: &str) -> AppResult<StableSwapPool> {
+        let pool = self.get_pool(pool_id)?;
+        let client = &self.blockchain_client;
         
-        // In a real implementation, this would query the blockchain for the latest pool data
-        // For now, we'll just simulate some random variations
+        let (amp, reserves, lp_supply) = futures::try_join!(
+            client.query_vm(&pool.pool_address, "getAmpFactor", Vec::new()),
+            client.query_vm(&pool.pool_address, "getReserves", Vec::new()),
+            client.query_vm(&pool.pool_address, "getTotalSupply", Vec::new()),
+        )?;
         
-        for pool in self.pools.values_mut() {
-            // Simulate small APY changes
-            let apy_change = (rand::random::<f64>() - 0.5) * 0.2; // -0.1% to +0.1%
-            pool.apy = (pool.apy + apy_change).max(0.0);
-            
-            // Simulate small TVL changes
-            let tvl_change = (rand::random::<f64>() - 0.5) * 0.01; // -0.5% to +0.5%
-            pool.tvl = pool.tvl * (1.0 + tvl_change);
-            
-            // Simulate volume changes
-            let volume_change = (rand::random::<f64>() - 0.5) * 0.05; // -2.5% to +2.5%
-            pool.volume_24h = pool.volume_24h * (1.0 + volume_change);
+        if reserves.len() != pool.tokens.len() {
+            return Err(AppError::Blockchain(format!("Unexpected reserves from pool {}", pool.pool_address)));
         }
         
+        let decode = |value: Option<&Vec<u8>>| BigUint::from_bytes_be(value.map(|v| v.as_slice()).unwrap_or_default());
+        
+        Ok(StableSwapPool {
+            balances: reserves.iter()
+                .zip(&pool.decimals)
+                .map(|(reserve, decimals)| from_raw_amount(&BigUint::from_bytes_be(reserve), *decimals))
+                .collect(),
+            amp: decode(amp.first()).to_f64().unwrap_or(0.0) / AMP_PRECISION,
+            fee: pool.fee,
+            lp_supply: from_raw_amount(&decode(lp_supply.first()), 18),
+        })
+    }
+    
+    /// Quote a swap between two tokens of a pool
+    pub async fn quote_swap(&self, 
+                            pool_id: &str, 
+                            token_in: &str, 
+                            token_out: &str, 
+                            amount_in: f64) -> AppResult<SwapQuote> {
+        let i = self.token_index(pool_id, token_in)?;
+        let j = self.token_index(pool_id, token_out)?;
+        self.get_pool_state(pool_id).await?.quote_swap(i, j, amount_in)
+    }
+    
+    /// Quote depositing token amounts into a pool
+    pub async fn quote_add_liquidity(&self, pool_id: &str, amounts: &[(String, f64)]) -> AppResult<LiquidityQuote> {
+        let state = self.get_pool_state(pool_id).await?;
+        let mut deposit = vec![0.0; state.balances.len()];
+        for (token, amount) in amounts {
+            deposit[self.token_index(pool_id, token)?] += amount;
+        }
+        state.quote_add_liquidity(&deposit)
+    }
+    
+    /// Quote burning LP tokens for a proportional share of every token
+    pub async fn quote_remove_liquidity(&self, pool_id: &str, lp_amount: f64) -> AppResult<LiquidityQuote> {
+        self.get_pool_state(pool_id).await?.quote_remove_liquidity(lp_amount)
+    }
+    
+    /// Quote burning LP tokens for a single token
+    pub async fn quote_remove_liquidity_one_coin(&self, 
+                                                 pool_id: &str, 
+                                                 lp_amount: f64, 
+                                                 token: &str) -> AppResult<LiquidityQuote> {
+        let i = self.token_index(pool_id, token)?;
+        self.get_pool_state(pool_id).await?.quote_remove_liquidity_one_coin(lp_amount, i)
+    }
+    
+    // Helper method to find the position of a token in a pool
+    fn token_index(&self, pool_id: &str, token: &str) -> AppResult<usize> {
+        let pool = self.get_pool(pool_id)?;
+        pool.tokens.iter()
+            .position(|t| t == token)
+            .ok_or_else(|| AppError::Validation(format!("Token {} is not in pool {}", token, pool_id)))
+    }
+    
+    // TVL of a pool in USD from its on-chain balances; the pooled stablecoins are valued at their peg
+    async fn read_pool_tvl(&self, pool_id: &str) -> AppResult<f64> {
+        Ok(self.get_pool_state(pool_id).await?.balances.iter().sum())
+    }
+    
+    // Measure the fee APY of a pool from the growth of its virtual price, which only grows from fees;
+    // until a snapshot is APY_WINDOW_SECONDS old the APY stays as last measured
+    async fn measure_pool_apy(&self, pool_id: &str) -> AppResult<()> {
+        let virtual_price = self.get_pool_state(pool_id).await?.virtual_price()?;
+        let now = Utc::now();
+        
+        let mut measurements = self.apy_measurements.write().unwrap();
+        let measurement = match measurements.get(pool_id) {
+            Some((apy, taken_at, previous_price)) => {
+                let elapsed = (now - *taken_at).num_seconds();
+                if elapsed >= APY_WINDOW_SECONDS && *previous_price > 0.0 {
+                    let growth = (virtual_price / previous_price - 1.0).max(0.0);
+                    (Some(growth * SECONDS_PER_YEAR / elapsed as f64 * 100.0), now, virtual_price)
+                } else {
+                    (*apy, *taken_at, *previous_price)
+                }
+            },
+            None => (None, now, virtual_price),
+        };
+        measurements.insert(pool_id.to_string(), measurement);
+        
         Ok(())
     }
+    
+    // Fee APY of a pool, once measured
+    fn measured_apy(&self, pool_id: &str) -> AppResult<f64> {
+        self.get_pool(pool_id)?;
+        let measurements = self.apy_measurements.read().unwrap();
+        measurements.get(pool_id).and_then(|(apy, _, _)| *apy).ok_or_else(||
+            AppError::NotFound(format!("APY of stable pool {} has not been measured yet", pool_id))
+        )
+    }
 }
 
 #[async_trait]
@@ -112,15 +234,28 @@ impl ProtocolInterface for AshSwapProtocol {
     
     async fn get_tvl(&self) -> AppResult<f64> {
         // Sum TVL across all stable pools
-        let total_tvl = self.pools.values().map(|p| p.tvl).sum();
+        let mut total_tvl = 0.0;
+        for pool_id in self.pools.keys() {
+            total_tvl += self.read_pool_tvl(pool_id).await?;
+        }
         Ok(total_tvl)
     }
     
     async fn get_apy(&self) -> AppResult<f64> {
-        // Calculate weighted average APY
-        let total_tvl = self.pools.values().map(|p| p.tvl).sum::<f64>();
-        let weighted_apy = self.pools.values()
-            .map(|p| p.apy * (p.tvl / total_tvl))
+        // Calculate the TVL weighted average APY of the pools whose APY has been measured
+        let mut measured = Vec::new();
+        for pool_id in self.pools.keys() {
+            if let Ok(apy) = self.measured_apy(pool_id) {
+                measured.push((apy, self.read_pool_tvl(pool_id).await?));
+            }
+        }
+        
+        let total_tvl = measured.iter().map(|(_, tvl)| tvl).sum::<f64>();
+        if total_tvl <= 0.0 {
+            return Err(AppError::NotFound(format!("{} APY has not been measured yet", self.name)));
+        }
+        let weighted_apy = measured.iter()
+            .map(|(apy, tvl)| apy * (tvl / total_tvl))
             .sum::<f64>();
         
         Ok(weighted_apy)
@@ -142,14 +277,52 @@ impl ProtocolInterface for AshSwapProtocol {
         Ok(self.pools.keys().cloned().collect())
     }
     
+    async fn get_swap_pools(&self) -> AppResult<Vec<SwapPool>> {
+        let mut swap_pools = Vec::new();
+        for pool in self.pools.values() {
+            // A pool whose state can't be read is left out of routing rather than failing it
+            let state = match self.get_pool_state(&pool.id).await {
+                Ok(state) => state,
+                Err(e) => {
+                    warn!("Failed to read the state of stable pool {}: {}", pool.id, e);
+                    continue;
+                },
+            };
+            
+            swap_pools.push(SwapPool {
+                protocol_id: self.id.clone(),
+                pool_id: pool.id.clone(),
+                contract_address: pool.pool_address.clone(),
+                token_ids: pool.token_ids.clone(),
+                decimals: pool.decimals.clone(),
+                reserves: state.balances,
+                fee: pool.fee,
+                curve: SwapCurve::StableSwap { amp: state.amp },
+            });
+        }
+        
+        Ok(swap_pools)
+    }
+    
     async fn get_pool_apy(&self, pool_id: &str) -> AppResult<f64> {
-        let pool = self.get_pool(pool_id)?;
-        Ok(pool.apy)
+        self.measured_apy(pool_id)
     }
     
     async fn get_pool_tvl(&self, pool_id: &str) -> AppResult<f64> {
+        self.read_pool_tvl(pool_id).await
+    }
+    
+    async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo> {
         let pool = self.get_pool(pool_id)?;
-        Ok(pool.tvl)
+        Ok(PoolInfo {
+            id: pool.id.clone(),
+            name: pool.name.clone(),
+            tokens: pool.tokens.clone(),
+            lp_token: Some(pool.lp_token.clone()),
+            fee_tier: Some(pool.fee),
+            contract_address: pool.pool_address.clone(),
+            reward_tokens: pool.reward_tokens.clone(),
+        })
     }
     
     async fn deposit(&self, 
@@ -158,18 +331,8 @@ impl ProtocolInterface for AshSwapProtocol {
                     token: &str) -> AppResult<String> {
         debug!("Depositing {} {} for user {} in AshSwap", amount, token, user_address);
         
-        // Find a pool that supports this token
-        let pool_id = self.pools.values()
-            .find(|p| p.tokens.contains(&token.to_string()))
-            .ok_or_else(|| AppError::Validation(format!("No stable pool found for token {}", token)))?
-            .id.clone();
-        
-        // Encode the smart contract call
-        let data = self.encode_sc_call("addLiquidity", vec![
-            &pool_id,
-            token,
-            &format!("{}", (amount * 1e18) as u64)
-        ]);
+        // Encode the smart contract call for a pool that supports this token
+        let data = self.build_deposit_data(amount, token).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -184,18 +347,8 @@ impl ProtocolInterface for AshSwapProtocol {
                      token: &str) -> AppResult<String> {
         debug!("Withdrawing {} {} for user {} from AshSwap", amount, token, user_address);
         
-        // Find a pool that supports this token
-        let pool_id = self.pools.values()
-            .find(|p| p.tokens.contains(&token.to_string()))
-            .ok_or_else(|| AppError::Validation(format!("No stable pool found for token {}", token)))?
-            .id.clone();
-        
-        // Encode the smart contract call
-        let data = self.encode_sc_call("removeLiquidity", vec![
-            &pool_id,
-            token,
-            &format!("{}", (amount * 1e18) as u64)
-        ]);
+        // Encode the smart contract call for a pool that supports this token
+        let data = self.build_withdraw_data(amount, token).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -209,19 +362,8 @@ impl ProtocolInterface for AshSwapProtocol {
         
         // For stable pools, rebalancing typically means adjusting the allocation between different stablecoins
         
-        // Get optimal allocation based on strategy
-        let optimal_allocation = self.get_optimal_allocation(&position.strategy).await?;
-        
-        // Encode the smart contract call
-        let mut args = Vec::new();
-        args.push(&position.id.to_string());
-        
-        for (token, percentage) in optimal_allocation {
-            args.push(&token);
-            args.push(&format!("{}", (percentage * 100.0) as u64));
-        }
-        
-        let data = self.encode_sc_call("rebalance", args);
+        // Encode the smart contract call for the optimal allocation
+        let data = self.build_rebalance_data(position).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -236,7 +378,7 @@ impl ProtocolInterface for AshSwapProtocol {
         debug!("Claiming rewards for position {} by user {} from AshSwap", position_id, user_address);
         
         // Encode the smart contract call
-        let data = self.encode_sc_call("claimRewards", vec![position_id]);
+        let data = self.build_claim_rewards_data(position_id).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -245,6 +387,57 @@ impl ProtocolInterface for AshSwapProtocol {
         Ok(tx_hash)
     }
     
+    async fn build_deposit_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        let pool_id = self.find_pool_id_for_token(token)?;
+        let pool = self.get_pool(&pool_id)?;
+        let index = self.token_index(&pool_id, token)?;
+        
+        // Quote the LP tokens and require most of them, so the deposit can't be sandwiched
+        let quote = self.quote_add_liquidity(&pool_id, &[(token.to_string(), amount)]).await?;
+        let min_lp = to_raw_amount(min_output(quote.lp_amount, DEFAULT_MAX_SLIPPAGE), 18)?;
+        
+        let data = esdt_transfer_data(
+            &pool.token_ids[index],
+            &to_raw_amount(amount, pool.decimals[index])?,
+            "addLiquidity",
+        );
+        Ok(format!("{}@{}", data, encode_biguint(&min_lp)))
+    }
+    
+    async fn build_withdraw_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        let pool_id = self.find_pool_id_for_token(token)?;
+        let pool = self.get_pool(&pool_id)?;
+        let index = self.token_index(&pool_id, token)?;
+        
+        // Quote the LP tokens burned for the exact amount, then allow for slippage on both sides
+        let state = self.get_pool_state(&pool_id).await?;
+        let mut amounts = vec![0.0; state.balances.len()];
+        amounts[index] = amount;
+        let quote = state.quote_remove_liquidity_imbalance(&amounts)?;
+        let lp_amount = to_raw_amount(quote.lp_amount * (1.0 + DEFAULT_MAX_SLIPPAGE), 18)?;
+        let min_amount = to_raw_amount(min_output(amount, DEFAULT_MAX_SLIPPAGE), pool.decimals[index])?;
+        
+        let data = esdt_transfer_data(&pool.lp_token, &lp_amount, "removeLiquidityOneCoin");
+        Ok(format!("{}@{}@{}", data, hex::encode(&pool.token_ids[index]), encode_biguint(&min_amount)))
+    }
+    
+    async fn build_rebalance_data(&self, position: &Position) -> AppResult<String> {
+        // Get optimal allocation based on strategy
+        let optimal_allocation = self.get_optimal_allocation(&position.strategy).await?;
+        
+        let mut args = vec![position.id.to_string()];
+        for (token, percentage) in optimal_allocation {
+            args.push(token);
+            args.push(format!("{}", (percentage * 100.0) as u64));
+        }
+        
+        Ok(self.encode_sc_call("rebalance", args.iter().map(|a| a.as_str()).collect()))
+    }
+    
+    async fn build_claim_rewards_data(&self, position_id: &str) -> AppResult<String> {
+        Ok(self.encode_sc_call("claimRewards", vec![position_id]))
+    }
+    
     async fn get_position_value(&self, position_id: &str) -> AppResult<f64> {
         debug!("Getting value for position {} in AshSwap", position_id);
         
@@ -291,4 +484,36 @@ impl ProtocolInterface for AshSwapProtocol {
     fn get_risk_level(&self) -> &str {
         "Low" // Overall protocol risk level for stable pools
     }
+    
+    async fn refresh_state(&self) -> AppResult<()> {
+        for pool_id in self.pools.keys() {
+            if let Err(e) = self.measure_pool_apy(pool_id).await {
+                warn!("Failed to measure APY of AshSwap pool {}: {}", pool_id, e);
+            }
+        }
+        Ok(())
+    }
+    
+    async fn get_pending_rewards(&self, 
+                                user_address: &str, 
+                                position: &Position) -> AppResult<Vec<PendingReward>> {
+        let user_arg = hex::encode(address_to_public_key(user_address)?);
+        
+        // Rewards accrue per pool LP token, one amount per reward token of the pool
+        let mut rewards: HashMap<String, f64> = HashMap::new();
+        for pool in self.pools.values().filter(|p| p.tokens.iter().any(|t| position.tokens.contains(t))) {
+            let pending = self.blockchain_client
+                .query_vm(&self.contract_address, "getPendingRewards", vec![user_arg.clone(), hex::encode(&pool.lp_token)])
+                .await?;
+            
+            for (token, amount) in pool.reward_tokens.iter().zip(&pending) {
+                *rewards.entry(token.clone()).or_insert(0.0) += from_raw_amount(&BigUint::from_bytes_be(amount), REWARD_DECIMALS);
+            }
+        }
+        
+        Ok(rewards.into_iter()
+            .filter(|(_, amount)| *amount > 0.0)
+            .map(|(token, amount)| PendingReward { token, amount })
+            .collect())
+    }
 }
\ No newline at end of file
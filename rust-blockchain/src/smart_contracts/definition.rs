@@ -0,0 +1,423 @@
+use crate::error::{AppError, AppResult};
+use crate::models::position::{Position, PositionStrategy};
+use crate::smart_contracts::maiar_farm::{encode_biguint, esdt_transfer_data, from_raw_amount, to_raw_amount};
+use crate::smart_contracts::protocol_interface::{ContractCall, PendingReward, PoolInfo, ProtocolInterface};
+use crate::blockchain::MultiversXClient;
+use crate::utils::address_to_public_key;
+use async_trait::async_trait;
+use num_bigint::BigUint;
+use serde::Deserialize;
+use std::path::Path;
+use tracing::{info, debug};
+
+// Simple vault-style protocols (deposit one token, withdraw it, maybe claim rewards) can be
+// described in a TOML or JSON file instead of a dedicated implementation
+
+/// Declarative description of a vault-style protocol
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProtocolDefinition {
+    pub id: String,
+    pub name: String,
+    pub contract_address: String,
+    /// Path of the contract ABI, relative to the definition file; used to check the endpoint names
+    #[serde(default)]
+    pub abi_path: Option<String>,
+    /// Low, Medium or High
+    pub risk_level: String,
+    /// Token deposited, "EGLD" or an ESDT identifier
+    pub token: String,
+    #[serde(default = "default_decimals")]
+    pub token_decimals: u32,
+    /// Token received for deposits and paid back on withdrawal, if the vault issues one
+    #[serde(default)]
+    pub share_token: Option<String>,
+    #[serde(default)]
+    pub reward_tokens: Vec<String>,
+    pub endpoints: EndpointDefinitions,
+    pub views: ViewDefinitions,
+    #[serde(default)]
+    pub gas: GasDefinitions,
+}
+
+/// Endpoints called for each operation
+#[derive(Debug, Clone, Deserialize)]
+pub struct EndpointDefinitions {
+    pub deposit: String,
+    /// Called with the share token as payment, or with the amount as argument without one
+    pub withdraw: String,
+    #[serde(default)]
+    pub claim: Option<String>,
+}
+
+/// View functions read for pool data
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewDefinitions {
+    /// Returns the deposited tokens, with the token decimals
+    pub tvl: String,
+    /// Returns the APY in percent, scaled by 10^apy_decimals
+    pub apy: String,
+    #[serde(default)]
+    pub apy_decimals: u32,
+    /// Takes a user address and returns their deposited tokens, with the token decimals
+    #[serde(default)]
+    pub position: Option<String>,
+    /// Takes a user address and returns their claimable amount of each reward token,
+    /// in the order of reward_tokens, scaled by 10^pending_rewards_decimals
+    #[serde(default)]
+    pub pending_rewards: Option<String>,
+    #[serde(default = "default_decimals")]
+    pub pending_rewards_decimals: u32,
+}
+
+/// Gas limit of each operation
+#[derive(Debug, Clone, Deserialize)]
+pub struct GasDefinitions {
+    #[serde(default = "default_gas")]
+    pub deposit: u64,
+    #[serde(default = "default_gas")]
+    pub withdraw: u64,
+    #[serde(default = "default_gas")]
+    pub claim: u64,
+}
+
+impl Default for GasDefinitions {
+    fn default() -> Self {
+        Self {
+            deposit: default_gas(),
+            withdraw: default_gas(),
+            claim: default_gas(),
+        }
+    }
+}
+
+fn default_decimals() -> u32 {
+    18
+}
+
+fn default_gas() -> u64 {
+    20_000_000
+}
+
+// Subset of a MultiversX ABI file listing the contract endpoints and views
+#[derive(Deserialize)]
+struct Abi {
+    #[serde(default)]
+    endpoints: Vec<AbiEndpoint>,
+}
+
+#[derive(Deserialize)]
+struct AbiEndpoint {
+    name: String,
+}
+
+impl ProtocolDefinition {
+    /// Load a definition from a .toml or .json file and check it
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let definition: Self = ::config::Config::builder()
+            .add_source(::config::File::from(path))
+            .build()
+            .and_then(|c| c.try_deserialize())
+            .map_err(|e| AppError::Validation(format!("Invalid protocol definition {}: {}", path.display(), e)))?;
+        
+        definition.validate(path.parent().unwrap_or_else(|| Path::new(".")))?;
+        Ok(definition)
+    }
+    
+    fn validate(&self, base_dir: &Path) -> AppResult<()> {
+        if self.id.is_empty() || self.name.is_empty() {
+            return Err(AppError::Validation("Protocol definitions need an id and a name".to_string()));
+        }
+        address_to_public_key(&self.contract_address)?;
+        
+        if !matches!(self.risk_level.as_str(), "Low" | "Medium" | "High") {
+            return Err(AppError::Validation(format!(
+                "Protocol {} has an unknown risk level {}", self.id, self.risk_level
+            )));
+        }
+        
+        if let Some(abi_path) = &self.abi_path {
+            let abi_path = base_dir.join(abi_path);
+            let abi: Abi = serde_json::from_str(&std::fs::read_to_string(&abi_path)
+                .map_err(|e| AppError::Validation(format!("Failed to read ABI {}: {}", abi_path.display(), e)))?)?;
+            
+            // Every endpoint and view the definition calls must exist in the contract
+            let mut names = vec![&self.endpoints.deposit, &self.endpoints.withdraw, &self.views.tvl, &self.views.apy];
+            names.extend(self.endpoints.claim.as_ref());
+            names.extend(self.views.position.as_ref());
+            names.extend(self.views.pending_rewards.as_ref());
+            for name in names {
+                if !abi.endpoints.iter().any(|e| &e.name == name) {
+                    return Err(AppError::Validation(format!(
+                        "Protocol {} calls {}, which is not in {}", self.id, name, abi_path.display()
+                    )));
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn ticker(&self) -> &str {
+        self.token.split('-').next().unwrap_or(&self.token)
+    }
+}
+
+/// Protocol implementation driven by a definition
+pub struct DefinedProtocol {
+    definition: ProtocolDefinition,
+    blockchain_client: MultiversXClient,
+}
+
+impl DefinedProtocol {
+    pub fn new(definition: ProtocolDefinition, blockchain_client: MultiversXClient) -> Self {
+        Self {
+            definition,
+            blockchain_client,
+        }
+    }
+    
+    // Helper method to read an amount from a view
+    async fn query_amount(&self, function: &str, args: Vec<String>, decimals: u32) -> AppResult<f64> {
+        let results = self.blockchain_client
+            .query_vm(&self.definition.contract_address, function, args)
+            .await?;
+        
+        Ok(results.first()
+            .map(|v| from_raw_amount(&BigUint::from_bytes_be(v), decimals))
+            .unwrap_or(0.0))
+    }
+    
+    fn check_pool(&self, pool_id: &str) -> AppResult<()> {
+        if pool_id == self.definition.id {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("Pool {} not found in {}", pool_id, self.definition.name)))
+        }
+    }
+}
+
+#[async_trait]
+impl ProtocolInterface for DefinedProtocol {
+    fn get_id(&self) -> &str {
+        &self.definition.id
+    }
+    
+    fn get_name(&self) -> &str {
+        &self.definition.name
+    }
+    
+    fn get_contract_address(&self) -> &str {
+        &self.definition.contract_address
+    }
+    
+    async fn get_tvl(&self) -> AppResult<f64> {
+        // TVL in deposited tokens
+        self.query_amount(&self.definition.views.tvl, Vec::new(), self.definition.token_decimals).await
+    }
+    
+    async fn get_apy(&self) -> AppResult<f64> {
+        self.query_amount(&self.definition.views.apy, Vec::new(), self.definition.views.apy_decimals).await
+    }
+    
+    async fn get_supported_tokens(&self) -> AppResult<Vec<String>> {
+        Ok(vec![self.definition.ticker().to_string()])
+    }
+    
+    async fn get_pools(&self) -> AppResult<Vec<String>> {
+        // The vault is the only pool
+        Ok(vec![self.definition.id.clone()])
+    }
+    
+    async fn get_pool_apy(&self, pool_id: &str) -> AppResult<f64> {
+        self.check_pool(pool_id)?;
+        self.get_apy().await
+    }
+    
+    async fn get_pool_tvl(&self, pool_id: &str) -> AppResult<f64> {
+        self.check_pool(pool_id)?;
+        self.get_tvl().await
+    }
+    
+    async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo> {
+        self.check_pool(pool_id)?;
+        Ok(PoolInfo {
+            id: self.definition.id.clone(),
+            name: self.definition.name.clone(),
+            tokens: vec![self.definition.ticker().to_string()],
+            lp_token: self.definition.share_token.clone(),
+            fee_tier: None,
+            contract_address: self.definition.contract_address.clone(),
+            reward_tokens: self.definition.reward_tokens.clone(),
+        })
+    }
+    
+    async fn deposit(&self,
+                    user_address: &str,
+                    amount: f64,
+                    token: &str) -> AppResult<String> {
+        debug!("Depositing {} {} into {} for user {}", amount, token, self.definition.name, user_address);
+        
+        // Encode the smart contract call
+        let data = self.build_deposit_data(amount, token).await?;
+        
+        // Simulate a transaction hash
+        let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        
+        info!("Deposit transaction submitted: {} ({})", tx_hash, data);
+        Ok(tx_hash)
+    }
+    
+    async fn withdraw(&self,
+                     user_address: &str,
+                     amount: f64,
+                     token: &str) -> AppResult<String> {
+        debug!("Withdrawing {} {} from {} for user {}", amount, token, self.definition.name, user_address);
+        
+        // Encode the smart contract call
+        let data = self.build_withdraw_data(amount, token).await?;
+        
+        // Simulate a transaction hash
+        let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        
+        info!("Withdraw transaction submitted: {} ({})", tx_hash, data);
+        Ok(tx_hash)
+    }
+    
+    async fn rebalance(&self, position: &Position) -> AppResult<String> {
+        // A single vault leaves nothing to rebalance
+        Err(AppError::Validation(format!(
+            "Position {} in {} has nothing to rebalance", position.id, self.definition.name
+        )))
+    }
+    
+    async fn claim_rewards(&self,
+                          user_address: &str,
+                          position_id: &str) -> AppResult<String> {
+        debug!("Claiming rewards for position {} by user {}", position_id, user_address);
+        
+        // Encode the smart contract call
+        let data = self.build_claim_rewards_data(position_id).await?;
+        
+        // Simulate a transaction hash
+        let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        
+        info!("Claim rewards transaction submitted: {} ({})", tx_hash, data);
+        Ok(tx_hash)
+    }
+    
+    async fn build_deposit_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        if !self.is_token_supported(token) {
+            return Err(AppError::Validation(format!("{} only accepts {}", self.definition.name, self.definition.token)));
+        }
+        
+        // EGLD is sent as value, ESDTs are paid to the endpoint
+        if self.definition.token == "EGLD" {
+            Ok(self.definition.endpoints.deposit.clone())
+        } else {
+            let raw_amount = to_raw_amount(amount, self.definition.token_decimals)?;
+            Ok(esdt_transfer_data(&self.definition.token, &raw_amount, &self.definition.endpoints.deposit))
+        }
+    }
+    
+    async fn build_withdraw_data(&self, amount: f64, _token: &str) -> AppResult<String> {
+        let raw_amount = to_raw_amount(amount, self.definition.token_decimals)?;
+        
+        match &self.definition.share_token {
+            Some(share_token) => Ok(esdt_transfer_data(share_token, &raw_amount, &self.definition.endpoints.withdraw)),
+            None => Ok(format!("{}@{}", self.definition.endpoints.withdraw, encode_biguint(&raw_amount))),
+        }
+    }
+    
+    async fn build_rebalance_data(&self, position: &Position) -> AppResult<String> {
+        self.rebalance(position).await
+    }
+    
+    async fn build_claim_rewards_data(&self, _position_id: &str) -> AppResult<String> {
+        self.definition.endpoints.claim.clone().ok_or_else(||
+            AppError::Validation(format!("{} has no rewards to claim", self.definition.name))
+        )
+    }
+    
+    async fn get_position_value(&self, position_id: &str) -> AppResult<f64> {
+        debug!("Getting value for position {} in {}", position_id, self.definition.name);
+        
+        // Positions are read by user address from the position view
+        let view = self.definition.views.position.as_ref().ok_or_else(||
+            AppError::Validation(format!("{} has no position view", self.definition.name))
+        )?;
+        let public_key = address_to_public_key(position_id).map_err(|_|
+            AppError::Validation(format!("{} positions are valued by user address", self.definition.name))
+        )?;
+        
+        let user_arg = hex::encode(public_key);
+        self.query_amount(view, vec![user_arg], self.definition.token_decimals).await
+    }
+    
+    async fn get_optimal_allocation(&self,
+                                   strategy: &PositionStrategy) -> AppResult<Vec<(String, f64)>> {
+        debug!("Calculating optimal allocation for strategy {:?} in {}", strategy, self.definition.name);
+        
+        // The vault only takes its token
+        Ok(vec![(self.definition.ticker().to_string(), 1.0)])
+    }
+    
+    fn is_token_supported(&self, token: &str) -> bool {
+        token == self.definition.token || token == self.definition.ticker()
+    }
+    
+    fn get_gas_limit_for_operation(&self, operation: &str) -> u64 {
+        let gas = &self.definition.gas;
+        match operation {
+            "deposit" => gas.deposit,
+            "withdraw" => gas.withdraw,
+            "claimRewards" | "claim" => gas.claim,
+            _ => gas.deposit.max(gas.withdraw), // Default
+        }
+    }
+    
+    fn get_risk_level(&self) -> &str {
+        &self.definition.risk_level
+    }
+    
+    fn get_tvl_denomination(&self) -> &str {
+        &self.definition.token
+    }
+    
+    async fn get_pending_rewards(&self,
+                                user_address: &str,
+                                _position: &Position) -> AppResult<Vec<PendingReward>> {
+        // Without a rewards view, pending rewards can't be read
+        let Some(view) = &self.definition.views.pending_rewards else {
+            return Ok(Vec::new());
+        };
+        
+        let user_arg = hex::encode(address_to_public_key(user_address)?);
+        let results = self.blockchain_client
+            .query_vm(&self.definition.contract_address, view, vec![user_arg])
+            .await?;
+        
+        Ok(self.definition.reward_tokens.iter()
+            .zip(&results)
+            .map(|(token, amount)| PendingReward {
+                token: token.clone(),
+                amount: from_raw_amount(&BigUint::from_bytes_be(amount), self.definition.views.pending_rewards_decimals),
+            })
+            .filter(|reward| reward.amount > 0.0)
+            .collect())
+    }
+    
+    async fn build_claim_rewards_calls(&self,
+                                      user_address: &str,
+                                      position: &Position) -> AppResult<Vec<ContractCall>> {
+        Ok(vec![ContractCall {
+            receiver: self.get_operation_receiver("claimRewards", None, user_address, 0.0).await?,
+            data: self.build_claim_rewards_data(&position.id.to_string()).await?,
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation("claimRewards"),
+        }])
+    }
+    
+    fn supports_reinvestment(&self) -> bool {
+        true
+    }
+}
\ No newline at end of file
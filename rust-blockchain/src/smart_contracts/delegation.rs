@@ -0,0 +1,690 @@
+use crate::error::{AppError, AppResult};
+use crate::models::position::{Position, PositionStrategy};
+use crate::smart_contracts::maiar_farm::{
+    decode_u64, encode_biguint, first_amount, from_raw_amount, to_raw_amount,
+};
+use crate::smart_contracts::protocol_interface::{
+    ContractCall, PendingReward, PoolInfo, ProtocolInterface, UnbondingEntry,
+};
+use crate::blockchain::{BlockchainProvider, MultiversXClient};
+use crate::utils::{address_to_public_key, public_key_to_address};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use num_bigint::BigUint;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tracing::{info, debug, warn};
+
+// System smart contract that deploys and lists the staking providers
+const DELEGATION_MANAGER_ADDRESS: &str = "erd1qqqqqqqqqqqqqqqpqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqylllslmq6y6";
+
+// Undelegated stake can be withdrawn after this many epochs
+const UNBONDING_EPOCHS: u64 = 10;
+
+// Service fees are expressed in hundredths of a percent
+const SERVICE_FEE_BASE: f64 = 10_000.0;
+
+// Gross staking APR used for a provider until its rewards have been observed twice
+const ESTIMATED_NETWORK_APR: f64 = 7.5;
+
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+const REFRESH_INTERVAL_SECONDS: i64 = 600;
+const PROVIDER_QUERY_CONCURRENCY: usize = 8;
+// A user's delegations are read from every provider, so they are reused within this interval
+const DELEGATIONS_TTL_SECONDS: i64 = 60;
+
+// BLS public keys of validator nodes are 96 bytes
+const BLS_KEY_LENGTH: usize = 96;
+
+/// A staking provider (delegation contract)
+#[derive(Debug, Clone, Serialize)]
+pub struct StakingProvider {
+    pub address: String,
+    pub name: String,
+    /// Share of rewards kept by the provider, as a fraction
+    pub service_fee: f64,
+    /// Maximum EGLD that can be delegated, None when uncapped
+    pub delegation_cap: Option<f64>,
+    pub total_active_stake: f64,
+    pub num_nodes: usize,
+    /// Share of successful validator signatures across the provider's nodes
+    pub uptime: Option<f64>,
+    /// Net APR for delegators, after the service fee
+    pub apr: f64,
+    /// Whether the APR is measured from distributed rewards rather than estimated
+    pub apr_measured: bool,
+    #[serde(skip)]
+    cumulated_rewards: Option<(DateTime<Utc>, f64)>,
+    // Cumulated rewards when a distribution was last observed; the APR is measured between distributions
+    #[serde(skip)]
+    distribution_anchor: Option<(DateTime<Utc>, f64)>,
+}
+
+impl StakingProvider {
+    /// EGLD that can still be delegated before the cap is reached
+    pub fn available_capacity(&self) -> Option<f64> {
+        self.delegation_cap.map(|cap| (cap - self.total_active_stake).max(0.0))
+    }
+}
+
+/// A user's stake with a single provider
+#[derive(Debug, Clone, Serialize)]
+pub struct DelegationPosition {
+    pub provider: String,
+    pub active_stake: f64,
+    pub claimable_rewards: f64,
+    /// Undelegated stake that has finished unbonding
+    pub withdrawable: f64,
+    pub unbonding: Vec<UnbondingEntry>,
+}
+
+pub struct DelegationProtocol {
+    id: String,
+    name: String,
+    contract_address: String,
+    blockchain_client: MultiversXClient,
+    providers: RwLock<HashMap<String, StakingProvider>>,
+    last_refresh: RwLock<Option<DateTime<Utc>>>,
+    delegations: RwLock<HashMap<String, (DateTime<Utc>, Vec<DelegationPosition>)>>,
+}
+
+impl DelegationProtocol {
+    pub fn new(blockchain_client: MultiversXClient) -> Self {
+        Self {
+            id: "egld-delegation".to_string(),
+            name: "EGLD Delegation".to_string(),
+            contract_address: DELEGATION_MANAGER_ADDRESS.to_string(),
+            blockchain_client,
+            providers: RwLock::new(HashMap::new()),
+            last_refresh: RwLock::new(None),
+            delegations: RwLock::new(HashMap::new()),
+        }
+    }
+    
+    /// Get all staking providers
+    pub async fn get_providers(&self) -> Vec<StakingProvider> {
+        self.ensure_fresh().await;
+        
+        let providers = self.providers.read().unwrap();
+        providers.values().cloned().collect()
+    }
+    
+    /// Get a staking provider by address
+    pub async fn get_provider(&self, address: &str) -> AppResult<StakingProvider> {
+        self.ensure_fresh().await;
+        
+        let providers = self.providers.read().unwrap();
+        providers.get(address).cloned().ok_or_else(||
+            AppError::NotFound(format!("Staking provider {} not found", address))
+        )
+    }
+    
+    /// Pick the provider with the best APR that has room for an amount
+    pub async fn best_provider_for(&self, amount: f64) -> AppResult<StakingProvider> {
+        self.get_providers().await
+            .into_iter()
+            .filter(|p| p.num_nodes > 0)
+            .filter(|p| p.available_capacity().map_or(true, |capacity| capacity >= amount))
+            .max_by(|a, b| a.apr.partial_cmp(&b.apr).unwrap_or(std::cmp::Ordering::Equal))
+            .ok_or_else(|| AppError::NotFound(format!("No staking provider has room for {} EGLD", amount)))
+    }
+    
+    /// Build the call that delegates EGLD to a provider
+    pub async fn build_delegate_call(&self, provider: &str, amount: f64) -> AppResult<ContractCall> {
+        let provider = self.get_provider(provider).await?;
+        if let Some(capacity) = provider.available_capacity() {
+            if capacity < amount {
+                return Err(AppError::Validation(format!(
+                    "Provider {} only has room for {} EGLD", provider.address, capacity
+                )));
+            }
+        }
+        
+        Ok(ContractCall {
+            receiver: provider.address,
+            data: "delegate".to_string(),
+            value: to_raw_amount(amount, 18)?.to_string(),
+            gas_limit: self.get_gas_limit_for_operation("delegate"),
+        })
+    }
+    
+    /// Build the call that starts unbonding part of the active stake
+    pub fn build_undelegate_call(&self, provider: &str, amount: f64) -> AppResult<ContractCall> {
+        Ok(ContractCall {
+            receiver: provider.to_string(),
+            data: format!("unDelegate@{}", encode_biguint(&to_raw_amount(amount, 18)?)),
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation("unDelegate"),
+        })
+    }
+    
+    /// Build the call that withdraws all stake that has finished unbonding
+    pub fn build_withdraw_call(&self, provider: &str) -> ContractCall {
+        self.build_provider_call(provider, "withdraw")
+    }
+    
+    /// Build the call that claims the rewards of a provider
+    pub fn build_claim_call(&self, provider: &str) -> ContractCall {
+        self.build_provider_call(provider, "claimRewards")
+    }
+    
+    /// Build the call that delegates the rewards of a provider back to it
+    pub fn build_redelegate_call(&self, provider: &str) -> ContractCall {
+        self.build_provider_call(provider, "reDelegateRewards")
+    }
+    
+    /// Get a user's stake with every provider they delegate to, reusing a recent read
+    pub async fn get_delegations(&self, user_address: &str) -> AppResult<Vec<DelegationPosition>> {
+        {
+            let delegations = self.delegations.read().unwrap();
+            if let Some((read_at, positions)) = delegations.get(user_address) {
+                if (Utc::now() - *read_at).num_seconds() < DELEGATIONS_TTL_SECONDS {
+                    return Ok(positions.clone());
+                }
+            }
+        }
+        
+        let positions = self.read_delegations(user_address).await?;
+        
+        let mut delegations = self.delegations.write().unwrap();
+        let now = Utc::now();
+        delegations.retain(|_, (read_at, _)| (now - *read_at).num_seconds() < DELEGATIONS_TTL_SECONDS);
+        delegations.insert(user_address.to_string(), (now, positions.clone()));
+        
+        Ok(positions)
+    }
+    
+    // Read a user's stake from every provider, skipping providers that can't be read
+    async fn read_delegations(&self, user_address: &str) -> AppResult<Vec<DelegationPosition>> {
+        let providers = self.get_providers().await;
+        let current_epoch = self.blockchain_client.get_network_status().await?.erd_epoch_number;
+        let user_arg = hex::encode(address_to_public_key(user_address)?);
+        
+        let positions: Vec<AppResult<Option<DelegationPosition>>> = stream::iter(providers)
+            .map(|provider| {
+                let user_arg = user_arg.clone();
+                async move {
+                    let result = self.query_delegation(&provider.address, user_arg, current_epoch).await;
+                    if let Err(e) = &result {
+                        warn!("Skipping delegation with {}: {}", provider.address, e);
+                    }
+                    result
+                }
+            })
+            .buffer_unordered(PROVIDER_QUERY_CONCURRENCY)
+            .collect()
+            .await;
+        
+        Ok(positions.into_iter().filter_map(|position| position.ok().flatten()).collect())
+    }
+    
+    // Read a user's stake with one provider, None when they have nothing there
+    async fn query_delegation(&self,
+                              provider: &str,
+                              user_arg: String,
+                              current_epoch: u64) -> AppResult<Option<DelegationPosition>> {
+        let client = &self.blockchain_client;
+        let (active, rewards, undelegated) = futures::try_join!(
+            client.query_vm(provider, "getUserActiveStake", vec![user_arg.clone()]),
+            client.query_vm(provider, "getClaimableRewards", vec![user_arg.clone()]),
+            client.query_vm(provider, "getUserUnDelegatedList", vec![user_arg.clone()]),
+        )?;
+        
+        let active_stake = first_amount(&active);
+        let claimable_rewards = first_amount(&rewards);
+        
+        // Undelegations come as pairs of amount and epochs left to unbond
+        let mut unbonding = Vec::new();
+        let mut withdrawable = 0.0;
+        for pair in undelegated.chunks(2) {
+            let amount = from_raw_amount(&BigUint::from_bytes_be(&pair[0]), 18);
+            let remaining_epochs = pair.get(1).map(|v| decode_u64(v)).unwrap_or(0);
+            
+            if remaining_epochs == 0 {
+                withdrawable += amount;
+            }
+            unbonding.push(UnbondingEntry {
+                pool_id: provider.to_string(),
+                amount,
+                unbond_epoch: current_epoch + remaining_epochs,
+                remaining_epochs,
+                withdrawable: remaining_epochs == 0,
+            });
+        }
+        
+        if active_stake == 0.0 && claimable_rewards == 0.0 && unbonding.is_empty() {
+            return Ok(None);
+        }
+        
+        Ok(Some(DelegationPosition {
+            provider: provider.to_string(),
+            active_stake,
+            claimable_rewards,
+            withdrawable,
+            unbonding,
+        }))
+    }
+    
+    // Undelegate from the provider with the largest stake of this user that covers the amount
+    async fn provider_to_undelegate(&self, user_address: &str, amount: f64) -> AppResult<String> {
+        let delegations = self.get_delegations(user_address).await?;
+        delegations.into_iter()
+            .filter(|d| d.active_stake >= amount)
+            .max_by(|a, b| a.active_stake.partial_cmp(&b.active_stake).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|d| d.provider)
+            .ok_or_else(|| AppError::Validation(format!("No provider holds {} EGLD of active stake", amount)))
+    }
+    
+    // Helper method to build a call without arguments or value on a provider
+    fn build_provider_call(&self, provider: &str, function: &str) -> ContractCall {
+        ContractCall {
+            receiver: provider.to_string(),
+            data: function.to_string(),
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation(function),
+        }
+    }
+    
+    // Refresh providers when they are stale, keeping the previous data if the chain can't be reached
+    async fn ensure_fresh(&self) {
+        let is_stale = {
+            let last_refresh = self.last_refresh.read().unwrap();
+            last_refresh.map_or(true, |t| (Utc::now() - t).num_seconds() >= REFRESH_INTERVAL_SECONDS)
+        };
+        
+        if !is_stale {
+            return;
+        }
+        
+        // Only a successful load counts as a refresh, so a failure is retried on the next read
+        match self.update_providers().await {
+            Ok(()) => {
+                let mut last_refresh = self.last_refresh.write().unwrap();
+                *last_refresh = Some(Utc::now());
+            },
+            Err(e) => warn!("Failed to refresh staking providers, using previous data: {}", e),
+        }
+    }
+    
+    // Helper method to update provider data from blockchain
+    async fn update_providers(&self) -> AppResult<()> {
+        debug!("Updating staking providers from the delegation manager");
+        
+        let addresses = self.blockchain_client
+            .query_vm(&self.contract_address, "getAllContractAddresses", Vec::new())
+            .await?
+            .iter()
+            .map(|public_key| public_key_to_address(public_key))
+            .collect::<AppResult<Vec<String>>>()?;
+        
+        // Uptime is optional; providers are still listed without it
+        let statistics = match self.blockchain_client.get_validator_statistics().await {
+            Ok(statistics) => statistics,
+            Err(e) => {
+                warn!("Failed to get validator statistics: {}", e);
+                HashMap::new()
+            }
+        };
+        
+        let providers: Vec<StakingProvider> = stream::iter(addresses)
+            .map(|address| {
+                let statistics = &statistics;
+                async move {
+                    let result = self.query_provider(&address, statistics).await;
+                    if let Err(e) = &result {
+                        warn!("Skipping staking provider {}: {}", address, e);
+                    }
+                    result.ok()
+                }
+            })
+            .buffer_unordered(PROVIDER_QUERY_CONCURRENCY)
+            .filter_map(|provider| async move { provider })
+            .collect()
+            .await;
+        
+        let now = Utc::now();
+        let mut updated = HashMap::new();
+        {
+            let previous = self.providers.read().unwrap();
+            for mut provider in providers {
+                // Rewards are distributed once per epoch, so the APR is the growth of rewards between
+                // two observed distributions; growth since startup would cover an unknown share of an epoch
+                let current_rewards = provider.cumulated_rewards.map(|(_, rewards)| rewards).unwrap_or(0.0);
+                if let Some(previous_provider) = previous.get(&provider.address) {
+                    provider.apr = previous_provider.apr;
+                    provider.apr_measured = previous_provider.apr_measured;
+                    provider.distribution_anchor = previous_provider.distribution_anchor;
+                    
+                    let previous_rewards = previous_provider.cumulated_rewards.map(|(_, rewards)| rewards).unwrap_or(0.0);
+                    if current_rewards > previous_rewards {
+                        if let Some((anchor_at, anchor_rewards)) = previous_provider.distribution_anchor {
+                            let elapsed = (now - anchor_at).num_seconds() as f64;
+                            if provider.total_active_stake > 0.0 && elapsed > 0.0 {
+                                provider.apr = (current_rewards - anchor_rewards) / provider.total_active_stake
+                                    * SECONDS_PER_YEAR / elapsed * 100.0;
+                                provider.apr_measured = true;
+                            }
+                        }
+                        provider.distribution_anchor = Some((now, current_rewards));
+                    }
+                }
+                updated.insert(provider.address.clone(), provider);
+            }
+        }
+        
+        info!("Loaded {} staking providers", updated.len());
+        
+        let mut providers = self.providers.write().unwrap();
+        *providers = updated;
+        
+        Ok(())
+    }
+    
+    // Read the configuration, stake and nodes of a provider
+    async fn query_provider(&self,
+                            address: &str,
+                            statistics: &HashMap<String, crate::blockchain::ValidatorStatistics>) -> AppResult<StakingProvider> {
+        let client = &self.blockchain_client;
+        let (config, active_stake, metadata, rewards, nodes) = futures::try_join!(
+            client.query_vm(address, "getContractConfig", Vec::new()),
+            client.query_vm(address, "getTotalActiveStake", Vec::new()),
+            client.query_vm(address, "getMetaData", Vec::new()),
+            client.query_vm(address, "getTotalCumulatedRewards", Vec::new()),
+            client.query_vm(address, "getAllNodeStates", Vec::new()),
+        )?;
+        
+        if config.len() < 6 {
+            return Err(AppError::Blockchain(format!("Unexpected contract config from {}", address)));
+        }
+        
+        // Config: owner, service fee, max delegation cap, initial owner funds, automatic activation, has cap, ...
+        let service_fee = decode_u64(&config[1]) as f64 / SERVICE_FEE_BASE;
+        let has_cap = config[5] == b"true";
+        let cap = from_raw_amount(&BigUint::from_bytes_be(&config[2]), 18);
+        let delegation_cap = if has_cap && cap > 0.0 { Some(cap) } else { None };
+        
+        // Node states come as a state name followed by the BLS keys in that state
+        let mut staked_keys = Vec::new();
+        let mut in_staked_group = false;
+        for value in &nodes {
+            if value.len() == BLS_KEY_LENGTH {
+                if in_staked_group {
+                    staked_keys.push(hex::encode(value));
+                }
+            } else {
+                in_staked_group = value.as_slice() == b"staked";
+            }
+        }
+        
+        let (successes, failures) = staked_keys.iter()
+            .filter_map(|key| statistics.get(key))
+            .fold((0u64, 0u64), |(s, f), stats| (s + stats.total_validator_success, f + stats.total_validator_failure));
+        let uptime = if successes + failures > 0 {
+            Some(successes as f64 / (successes + failures) as f64)
+        } else {
+            None
+        };
+        
+        let name = metadata.first()
+            .map(|v| String::from_utf8_lossy(v).to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| address.to_string());
+        
+        Ok(StakingProvider {
+            address: address.to_string(),
+            name,
+            service_fee,
+            delegation_cap,
+            total_active_stake: first_amount(&active_stake),
+            num_nodes: staked_keys.len(),
+            uptime,
+            apr: ESTIMATED_NETWORK_APR * (1.0 - service_fee),
+            apr_measured: false,
+            cumulated_rewards: Some((Utc::now(), first_amount(&rewards))),
+            distribution_anchor: None,
+        })
+    }
+}
+
+#[async_trait]
+impl ProtocolInterface for DelegationProtocol {
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+    
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    
+    fn get_contract_address(&self) -> &str {
+        &self.contract_address
+    }
+    
+    async fn get_tvl(&self) -> AppResult<f64> {
+        // TVL in EGLD staked across all providers
+        Ok(self.get_providers().await.iter().map(|p| p.total_active_stake).sum())
+    }
+    
+    async fn get_apy(&self) -> AppResult<f64> {
+        // Stake weighted average APR
+        let providers = self.get_providers().await;
+        let total_stake = providers.iter().map(|p| p.total_active_stake).sum::<f64>();
+        if total_stake <= 0.0 {
+            return Ok(0.0);
+        }
+        
+        Ok(providers.iter().map(|p| p.apr * p.total_active_stake / total_stake).sum())
+    }
+    
+    async fn get_supported_tokens(&self) -> AppResult<Vec<String>> {
+        Ok(vec!["EGLD".to_string()])
+    }
+    
+    async fn get_pools(&self) -> AppResult<Vec<String>> {
+        // Each provider is a pool
+        Ok(self.get_providers().await.into_iter().map(|p| p.address).collect())
+    }
+    
+    async fn get_pool_apy(&self, pool_id: &str) -> AppResult<f64> {
+        Ok(self.get_provider(pool_id).await?.apr)
+    }
+    
+    async fn get_pool_tvl(&self, pool_id: &str) -> AppResult<f64> {
+        Ok(self.get_provider(pool_id).await?.total_active_stake)
+    }
+    
+    async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo> {
+        let provider = self.get_provider(pool_id).await?;
+        Ok(PoolInfo {
+            id: provider.address.clone(),
+            name: provider.name,
+            tokens: vec!["EGLD".to_string()],
+            lp_token: None, // Delegated stake isn't tokenized
+            fee_tier: Some(provider.service_fee),
+            contract_address: provider.address,
+            reward_tokens: vec!["EGLD".to_string()],
+        })
+    }
+    
+    async fn deposit(&self,
+                    user_address: &str,
+                    amount: f64,
+                    token: &str) -> AppResult<String> {
+        debug!("Delegating {} {} for user {}", amount, token, user_address);
+        
+        // Encode the smart contract call for the best provider
+        let provider = self.best_provider_for(amount).await?;
+        let call = self.build_delegate_call(&provider.address, amount).await?;
+        
+        // Simulate a transaction hash
+        let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        
+        info!("Delegate transaction to {} submitted: {}", call.receiver, tx_hash);
+        Ok(tx_hash)
+    }
+    
+    async fn withdraw(&self,
+                     user_address: &str,
+                     amount: f64,
+                     token: &str) -> AppResult<String> {
+        debug!("Undelegating {} {} for user {}", amount, token, user_address);
+        
+        let provider = self.provider_to_undelegate(user_address, amount).await?;
+        let call = self.build_undelegate_call(&provider, amount)?;
+        
+        // Simulate a transaction hash
+        let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        
+        info!("Undelegate transaction to {} submitted: {}", call.receiver, tx_hash);
+        Ok(tx_hash)
+    }
+    
+    async fn rebalance(&self, position: &Position) -> AppResult<String> {
+        // Stake can't be moved between providers without unbonding
+        Err(AppError::Validation(format!(
+            "Delegation position {} can't be rebalanced; undelegate, withdraw after {} epochs and delegate again instead",
+            position.id, UNBONDING_EPOCHS
+        )))
+    }
+    
+    async fn claim_rewards(&self,
+                          user_address: &str,
+                          position_id: &str) -> AppResult<String> {
+        debug!("Claiming delegation rewards for position {} by user {}", position_id, user_address);
+        
+        let delegations = self.get_delegations(user_address).await?;
+        let claimable: Vec<&DelegationPosition> = delegations.iter().filter(|d| d.claimable_rewards > 0.0).collect();
+        if claimable.is_empty() {
+            return Err(AppError::Validation("No delegation rewards to claim".to_string()));
+        }
+        
+        // Simulate a transaction hash per provider
+        let mut tx_hashes = Vec::new();
+        for delegation in claimable {
+            let call = self.build_claim_call(&delegation.provider);
+            let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+            info!("Claim rewards transaction to {} submitted: {}", call.receiver, tx_hash);
+            tx_hashes.push(tx_hash);
+        }
+        
+        Ok(tx_hashes.join(","))
+    }
+    
+    async fn build_deposit_data(&self, _amount: f64, token: &str) -> AppResult<String> {
+        if token != "EGLD" {
+            return Err(AppError::Validation(format!("Only EGLD can be delegated, not {}", token)));
+        }
+        Ok("delegate".to_string())
+    }
+    
+    async fn build_withdraw_data(&self, amount: f64, _token: &str) -> AppResult<String> {
+        Ok(format!("unDelegate@{}", encode_biguint(&to_raw_amount(amount, 18)?)))
+    }
+    
+    async fn build_rebalance_data(&self, position: &Position) -> AppResult<String> {
+        self.rebalance(position).await
+    }
+    
+    async fn build_claim_rewards_data(&self, _position_id: &str) -> AppResult<String> {
+        Ok("claimRewards".to_string())
+    }
+    
+    async fn get_position_value(&self, position_id: &str) -> AppResult<f64> {
+        debug!("Getting value for delegation position {}", position_id);
+        
+        // For an address, value everything it has staked, unbonding or unclaimed
+        if !position_id.starts_with("erd1") {
+            return Err(AppError::Validation("Delegation positions are valued by user address".to_string()));
+        }
+        
+        let delegations = self.get_delegations(position_id).await?;
+        Ok(delegations.iter()
+            .map(|d| d.active_stake + d.claimable_rewards + d.unbonding.iter().map(|u| u.amount).sum::<f64>())
+            .sum())
+    }
+    
+    async fn get_optimal_allocation(&self,
+                                   strategy: &PositionStrategy) -> AppResult<Vec<(String, f64)>> {
+        debug!("Calculating optimal allocation for strategy {:?} in EGLD delegation", strategy);
+        
+        // Delegation only takes EGLD
+        Ok(vec![("EGLD".to_string(), 1.0)])
+    }
+    
+    fn is_token_supported(&self, token: &str) -> bool {
+        token == "EGLD"
+    }
+    
+    fn get_gas_limit_for_operation(&self, operation: &str) -> u64 {
+        match operation {
+            "deposit" | "delegate" => 12_000_000,
+            "withdraw" | "unDelegate" => 12_000_000,
+            "withdrawUnbonded" => 12_000_000,
+            "claimRewards" => 6_000_000,
+            "reDelegateRewards" => 12_000_000,
+            _ => 12_000_000, // Default
+        }
+    }
+    
+    fn get_risk_level(&self) -> &str {
+        "Low" // Native staking carries no smart contract risk beyond the system contracts
+    }
+    
+    fn get_tvl_denomination(&self) -> &str {
+        "EGLD"
+    }
+    
+    async fn get_operation_receiver(&self,
+                                   operation: &str,
+                                   pool_id: Option<&str>,
+                                   user_address: &str,
+                                   amount: f64) -> AppResult<String> {
+        // Calls go to a provider; without one, deposits go to the best provider with room for the
+        // amount and withdrawals to the user's provider holding enough stake
+        match pool_id {
+            Some(provider) => Ok(self.get_provider(provider).await?.address),
+            None if operation == "deposit" => Ok(self.best_provider_for(amount).await?.address),
+            None if operation == "withdraw" => self.provider_to_undelegate(user_address, amount).await,
+            None => Err(AppError::Validation(format!("A staking provider is required to {}", operation))),
+        }
+    }
+    
+    async fn get_unbonding(&self,
+                          user_address: &str) -> AppResult<Vec<UnbondingEntry>> {
+        let delegations = self.get_delegations(user_address).await?;
+        Ok(delegations.into_iter().flat_map(|d| d.unbonding).collect())
+    }
+    
+    async fn get_pending_rewards(&self,
+                                user_address: &str,
+                                _position: &Position) -> AppResult<Vec<PendingReward>> {
+        // Rewards are claimed from every provider the user delegates to
+        let delegations = self.get_delegations(user_address).await?;
+        let amount: f64 = delegations.iter().map(|d| d.claimable_rewards).sum();
+        if amount == 0.0 {
+            return Ok(Vec::new());
+        }
+        
+        Ok(vec![PendingReward {
+            token: "EGLD".to_string(),
+            amount,
+        }])
+    }
+    
+    async fn build_claim_rewards_calls(&self,
+                                      user_address: &str,
+                                      _position: &Position) -> AppResult<Vec<ContractCall>> {
+        // Rewards are claimed from every provider holding some for the user
+        let delegations = self.get_delegations(user_address).await?;
+        Ok(delegations.iter()
+            .filter(|d| d.claimable_rewards > 0.0)
+            .map(|d| self.build_claim_call(&d.provider))
+            .collect())
+    }
+    
+    fn supports_reinvestment(&self) -> bool {
+        true
+    }
+}
\ No newline at end of file
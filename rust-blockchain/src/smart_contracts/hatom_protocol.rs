@@ -1,28 +1,139 @@
 use crate::error::{AppError, AppResult};
 use crate::models::position::{Position, PositionStrategy};
-use crate::smart_contracts::protocol_interface::ProtocolInterface;
+use crate::smart_contracts::maiar_farm::{encode_biguint, esdt_transfer_data, from_raw_amount, to_raw_amount};
+use crate::smart_contracts::protocol_interface::{
+    AccountHealth, ContractCall, LiquidationPrice, PendingReward, PoolInfo, ProtocolInterface,
+};
 use crate::blockchain::MultiversXClient;
+use crate::utils::{address_to_public_key, public_key_to_address};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::RwLock;
 use tracing::{info, debug, error};
 
+// hTokens, rates and prices use 18 decimals
+const H_TOKEN_DECIMALS: u32 = 18;
+const WAD: f64 = 1e18;
+// HTM rewards use 18 decimals
+const REWARD_DECIMALS: u32 = 18;
+// Collateral factors and oracle prices are shared by every account, so they are read once per interval
+const MARKET_PARAMS_TTL_SECONDS: i64 = 60;
+// Account snapshots are reused across requests for the same address within this interval
+const ACCOUNT_SNAPSHOT_TTL_SECONDS: i64 = 30;
+// Money markets accrue interest every second
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+
 pub struct HatomProtocol {
     id: String,
     name: String,
     contract_address: String,
     blockchain_client: MultiversXClient,
     lending_pools: HashMap<String, LendingPoolInfo>,
+    // Collateral factor and oracle price per pool ID, with when they were read
+    market_params: RwLock<Option<(DateTime<Utc>, HashMap<String, (f64, f64)>)>>,
+    account_snapshots: RwLock<HashMap<String, (DateTime<Utc>, AccountSnapshot)>>,
 }
 
 struct LendingPoolInfo {
     id: String,
     name: String,
     token: String,
+    token_id: String,
+    decimals: u32,
+    h_token: String,
+    market_address: String,
+    reward_tokens: Vec<String>,
     supply_apy: f64,
     borrow_apy: f64,
     tvl: f64,
     risk: String,
-    utilization_rate: f64,
+}
+
+/// A user's balances in a single money market
+#[derive(Debug, Clone, Serialize)]
+pub struct MarketSnapshot {
+    pub pool_id: String,
+    pub token: String,
+    /// Underlying tokens supplied, including those used as collateral
+    pub supplied: f64,
+    /// Underlying tokens deposited in the controller as collateral
+    pub collateral: f64,
+    /// Underlying tokens borrowed, including accrued interest
+    pub borrowed: f64,
+    /// Underlying tokens per hToken
+    pub exchange_rate: f64,
+    /// Share of the collateral value that can be borrowed against
+    pub collateral_factor: f64,
+    pub is_collateral: bool,
+    /// Oracle price of the underlying token in EGLD
+    pub price: f64,
+}
+
+/// A user's position across all Hatom money markets
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountSnapshot {
+    pub address: String,
+    pub markets: Vec<MarketSnapshot>,
+}
+
+impl AccountSnapshot {
+    /// Compute the health factor and the liquidation price of each collateral asset, in EGLD
+    pub fn health(&self) -> AccountHealth {
+        let total_collateral = self.markets.iter()
+            .filter(|m| m.is_collateral)
+            .map(|m| m.collateral * m.price)
+            .sum::<f64>();
+        let borrow_limit = self.markets.iter()
+            .filter(|m| m.is_collateral)
+            .map(|m| m.collateral * m.price * m.collateral_factor)
+            .sum::<f64>();
+        let total_borrowed = self.markets.iter()
+            .map(|m| m.borrowed * m.price)
+            .sum::<f64>();
+        
+        let health_factor = if total_borrowed > 0.0 {
+            Some(borrow_limit / total_borrowed)
+        } else {
+            None
+        };
+        
+        // Solve for the price of one asset at which the borrow limit equals the borrows,
+        // holding other prices constant. Borrows of the same asset move with its price.
+        let liquidation_prices = self.markets.iter()
+            .filter(|m| m.is_collateral && m.collateral > 0.0)
+            .map(|m| {
+                let weighted_collateral = m.collateral * m.collateral_factor;
+                let other_limit = borrow_limit - weighted_collateral * m.price;
+                let other_borrowed = total_borrowed - m.borrowed * m.price;
+                let sensitivity = weighted_collateral - m.borrowed;
+                
+                let liquidation_price = if total_borrowed > 0.0 && sensitivity > 0.0 {
+                    let price = (other_borrowed - other_limit) / sensitivity;
+                    if price > 0.0 { Some(price) } else { None }
+                } else {
+                    None
+                };
+                
+                LiquidationPrice {
+                    token: m.token.clone(),
+                    current_price: m.price,
+                    liquidation_price,
+                }
+            })
+            .collect();
+        
+        AccountHealth {
+            health_factor,
+            total_collateral,
+            borrow_limit,
+            total_borrowed,
+            liquidation_prices,
+        }
+    }
 }
 
 impl HatomProtocol {
@@ -36,11 +147,15 @@ impl HatomProtocol {
                 id: "egld-lending".to_string(),
                 name: "EGLD Lending".to_string(),
                 token: "EGLD".to_string(),
+                token_id: "EGLD".to_string(),
+                decimals: 18,
+                h_token: "HEGLD-d61095".to_string(),
+                market_address: "erd1qqqqqqqqqqqqqpgq35qkf34a8svu4r2zmfzuztmeltqclapv78ss5jleq3".to_string(),
+                reward_tokens: vec!["HTM".to_string()],
                 supply_apy: 5.8,
                 borrow_apy: 7.5,
                 tvl: 24_600_000.0,
                 risk: "Low".to_string(),
-                utilization_rate: 0.65,
             },
         );
         
@@ -50,11 +165,15 @@ impl HatomProtocol {
                 id: "usdc-lending".to_string(),
                 name: "USDC Lending".to_string(),
                 token: "USDC".to_string(),
+                token_id: "USDC-c76f1f".to_string(),
+                decimals: 6,
+                h_token: "HUSDC-d80042".to_string(),
+                market_address: "erd1qqqqqqqqqqqqqpgqkrgsvct7hfx7ru30mfzk3uy6pxzxn6jj78ss84aldu".to_string(),
+                reward_tokens: vec!["HTM".to_string()],
                 supply_apy: 8.7,
                 borrow_apy: 10.2,
                 tvl: 18_900_000.0,
                 risk: "Low".to_string(),
-                utilization_rate: 0.78,
             },
         );
         
@@ -64,6 +183,8 @@ impl HatomProtocol {
             contract_address: "erd1qqqqqqqqqqqqqpgq7ykazrzd905zvnlr88dpfw06677lxe9w0n4suz00uh".to_string(),
             blockchain_client,
             lending_pools,
+            market_params: RwLock::new(None),
+            account_snapshots: RwLock::new(HashMap::new()),
         }
     }
     
@@ -85,6 +206,239 @@ impl HatomProtocol {
         )
     }
     
+    // Helper method to find the lending pool for a token
+    fn find_pool_id_for_token(&self, token: &str) -> AppResult<String> {
+        self.lending_pools.values()
+            .find(|p| p.token == token)
+            .map(|p| p.id.clone())
+            .ok_or_else(|| AppError::Validation(format!("No lending pool found for token {}", token)))
+    }
+    
+    /// Get the static supply and borrow APY configured for a lending pool
+    pub fn get_market_rates(&self, pool_id: &str) -> AppResult<(f64, f64)> {
+        let pool = self.get_pool(pool_id)?;
+        Ok((pool.supply_apy, pool.borrow_apy))
+    }
+    
+    /// Read the current supply and borrow APY of a lending pool from its money market
+    pub async fn get_live_market_rates(&self, pool_id: &str) -> AppResult<(f64, f64)> {
+        let pool = self.get_pool(pool_id)?;
+        
+        let (supply_rate, borrow_rate) = futures::try_join!(
+            self.blockchain_client.query_vm(&pool.market_address, "getSupplyRate", Vec::new()),
+            self.blockchain_client.query_vm(&pool.market_address, "getBorrowRate", Vec::new()),
+        )?;
+        
+        // Rates are per second; interest compounds every second
+        let to_apy = |rate: &[Vec<u8>]| -> AppResult<f64> {
+            let per_second = rate.first().map(|v| decode_wad(v)).ok_or_else(||
+                AppError::Blockchain(format!("Money market {} returned no rate", pool.market_address))
+            )?;
+            Ok(((1.0 + per_second).powf(SECONDS_PER_YEAR) - 1.0) * 100.0)
+        };
+        
+        Ok((to_apy(&supply_rate)?, to_apy(&borrow_rate)?))
+    }
+    
+    /// Read the share of a money market's supply that is borrowed, from its cash, borrows and
+    /// reserves, along with the supply in underlying token units
+    pub async fn get_market_utilization(&self, pool_id: &str) -> AppResult<(f64, f64)> {
+        let pool = self.get_pool(pool_id)?;
+        
+        let (cash, borrows, reserves) = futures::try_join!(
+            self.blockchain_client.query_vm(&pool.market_address, "getCash", Vec::new()),
+            self.blockchain_client.query_vm(&pool.market_address, "getTotalBorrows", Vec::new()),
+            self.blockchain_client.query_vm(&pool.market_address, "getTotalReserves", Vec::new()),
+        )?;
+        let amount = |results: &[Vec<u8>]| results.first()
+            .map(|v| from_raw_amount(&BigUint::from_bytes_be(v), pool.decimals))
+            .unwrap_or(0.0);
+        
+        let borrows = amount(&borrows);
+        let supplied = amount(&cash) + borrows - amount(&reserves);
+        if supplied <= 0.0 {
+            return Ok((0.0, 0.0));
+        }
+        Ok((borrows / supplied, supplied))
+    }
+    
+    /// Build the call that borrows from the money market of a token
+    pub async fn build_borrow_call(&self, amount: f64, token: &str) -> AppResult<ContractCall> {
+        let pool = self.get_pool(&self.find_pool_id_for_token(token)?)?;
+        let amount = to_raw_amount(amount, pool.decimals)?;
+        
+        Ok(ContractCall {
+            receiver: pool.market_address.clone(),
+            data: format!("borrow@{}", encode_biguint(&amount)),
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation("borrow"),
+        })
+    }
+    
+    /// Build the call that repays a borrow, paying the underlying token to its money market
+    pub async fn build_repay_call(&self, amount: f64, token: &str) -> AppResult<ContractCall> {
+        let pool = self.get_pool(&self.find_pool_id_for_token(token)?)?;
+        let raw_amount = to_raw_amount(amount, pool.decimals)?;
+        
+        // EGLD is paid as the transaction value, ESDTs through a token transfer
+        let (data, value) = if pool.token_id == "EGLD" {
+            ("repayBorrow".to_string(), raw_amount.to_string())
+        } else {
+            (esdt_transfer_data(&pool.token_id, &raw_amount, "repayBorrow"), "0".to_string())
+        };
+        
+        Ok(ContractCall {
+            receiver: pool.market_address.clone(),
+            data,
+            value,
+            gas_limit: self.get_gas_limit_for_operation("repayBorrow"),
+        })
+    }
+    
+    /// Build the call that deposits hTokens into the controller as collateral
+    pub async fn build_enter_market_call(&self, pool_id: &str, h_token_amount: f64) -> AppResult<ContractCall> {
+        let pool = self.get_pool(pool_id)?;
+        let amount = to_raw_amount(h_token_amount, H_TOKEN_DECIMALS)?;
+        
+        Ok(ContractCall {
+            receiver: self.contract_address.clone(),
+            data: esdt_transfer_data(&pool.h_token, &amount, "enterMarkets"),
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation("enterMarkets"),
+        })
+    }
+    
+    /// Build the call that withdraws hTokens from collateral; the controller
+    /// rejects it if the remaining collateral doesn't cover the borrows
+    pub async fn build_exit_market_call(&self, pool_id: &str, h_token_amount: f64) -> AppResult<ContractCall> {
+        let pool = self.get_pool(pool_id)?;
+        let amount = to_raw_amount(h_token_amount, H_TOKEN_DECIMALS)?;
+        
+        Ok(ContractCall {
+            receiver: self.contract_address.clone(),
+            data: format!(
+                "exitMarket@{}@{}",
+                hex::encode(address_to_public_key(&pool.market_address)?),
+                encode_biguint(&amount),
+            ),
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation("exitMarket"),
+        })
+    }
+    
+    /// Read a user's supply, borrows and collateral in every market, reusing a recent snapshot
+    pub async fn get_account_snapshot(&self, user_address: &str) -> AppResult<AccountSnapshot> {
+        {
+            let account_snapshots = self.account_snapshots.read().unwrap();
+            if let Some((read_at, snapshot)) = account_snapshots.get(user_address) {
+                if (Utc::now() - *read_at).num_seconds() < ACCOUNT_SNAPSHOT_TTL_SECONDS {
+                    return Ok(snapshot.clone());
+                }
+            }
+        }
+        
+        let snapshot = self.read_account_snapshot(user_address).await?;
+        
+        let mut account_snapshots = self.account_snapshots.write().unwrap();
+        let now = Utc::now();
+        account_snapshots.retain(|_, (read_at, _)| (now - *read_at).num_seconds() < ACCOUNT_SNAPSHOT_TTL_SECONDS);
+        account_snapshots.insert(user_address.to_string(), (now, snapshot.clone()));
+        
+        Ok(snapshot)
+    }
+    
+    // Read the account's balances in every market from the chain
+    async fn read_account_snapshot(&self, user_address: &str) -> AppResult<AccountSnapshot> {
+        let account_arg = hex::encode(address_to_public_key(user_address)?);
+        
+        // Markets the user has entered, whose collateral counts towards the borrow limit
+        let collateral_markets = self.blockchain_client
+            .query_vm(&self.contract_address, "getAccountMarkets", vec![account_arg.clone()])
+            .await?
+            .iter()
+            .map(|public_key| public_key_to_address(public_key))
+            .collect::<AppResult<Vec<String>>>()?;
+        
+        let market_params = self.get_market_params().await?;
+        
+        let mut markets = Vec::new();
+        for pool in self.lending_pools.values() {
+            let market_arg = hex::encode(address_to_public_key(&pool.market_address)?);
+            
+            let (snapshot, collateral) = futures::try_join!(
+                self.blockchain_client.query_vm(&pool.market_address, "getAccountSnapshot", vec![account_arg.clone()]),
+                self.blockchain_client.query_vm(&self.contract_address, "getAccountCollateralTokens", vec![account_arg.clone(), market_arg]),
+            )?;
+            
+            if snapshot.len() < 3 {
+                return Err(AppError::Blockchain(format!("Unexpected account snapshot from {}", pool.market_address)));
+            }
+            
+            // hTokens in the wallet and in the controller both accrue supply interest
+            let exchange_rate = decode_wad(&snapshot[2]);
+            let wallet_h_tokens = from_raw_amount(&BigUint::from_bytes_be(&snapshot[0]), H_TOKEN_DECIMALS);
+            let collateral_h_tokens = collateral.first()
+                .map(|v| from_raw_amount(&BigUint::from_bytes_be(v), H_TOKEN_DECIMALS))
+                .unwrap_or(0.0);
+            let to_underlying = exchange_rate * 10f64.powi(H_TOKEN_DECIMALS as i32 - pool.decimals as i32);
+            let (collateral_factor, price) = market_params.get(&pool.id).copied().unwrap_or((0.0, 0.0));
+            
+            markets.push(MarketSnapshot {
+                pool_id: pool.id.clone(),
+                token: pool.token.clone(),
+                supplied: (wallet_h_tokens + collateral_h_tokens) * to_underlying,
+                collateral: collateral_h_tokens * to_underlying,
+                borrowed: from_raw_amount(&BigUint::from_bytes_be(&snapshot[1]), pool.decimals),
+                exchange_rate,
+                collateral_factor,
+                is_collateral: collateral_markets.contains(&pool.market_address),
+                price,
+            });
+        }
+        
+        Ok(AccountSnapshot {
+            address: user_address.to_string(),
+            markets,
+        })
+    }
+    
+    // Collateral factor and oracle price of every market, read once per interval for all accounts
+    async fn get_market_params(&self) -> AppResult<HashMap<String, (f64, f64)>> {
+        {
+            let market_params = self.market_params.read().unwrap();
+            if let Some((read_at, params)) = market_params.as_ref() {
+                if (Utc::now() - *read_at).num_seconds() < MARKET_PARAMS_TTL_SECONDS {
+                    return Ok(params.clone());
+                }
+            }
+        }
+        
+        let oracle = self.blockchain_client
+            .query_vm(&self.contract_address, "getPriceOracle", Vec::new())
+            .await?;
+        let oracle_address = public_key_to_address(oracle.first().map(|v| v.as_slice()).unwrap_or_default())?;
+        
+        let mut params = HashMap::new();
+        for pool in self.lending_pools.values() {
+            let market_arg = hex::encode(address_to_public_key(&pool.market_address)?);
+            
+            let (collateral_factor, price) = futures::try_join!(
+                self.blockchain_client.query_vm(&self.contract_address, "getCollateralFactor", vec![market_arg.clone()]),
+                self.blockchain_client.query_vm(&oracle_address, "getUnderlyingPrice", vec![market_arg]),
+            )?;
+            
+            params.insert(pool.id.clone(), (
+                collateral_factor.first().map(|v| decode_wad(v)).unwrap_or(0.0),
+                price.first().map(|v| decode_wad(v)).unwrap_or(0.0),
+            ));
+        }
+        
+        let mut market_params = self.market_params.write().unwrap();
+        *market_params = Some((Utc::now(), params.clone()));
+        
+        Ok(params)
+    }
+    
     // Helper method to update pool data from blockchain
     async fn update_pool_data(&mut self) -> AppResult<()> {
         debug!("Updating lending pool data from blockchain for Hatom Protocol");
@@ -103,10 +457,6 @@ impl HatomProtocol {
             // Simulate small TVL changes
             let tvl_change = (rand::random::<f64>() - 0.5) * 0.01; // -0.5% to +0.5%
             pool.tvl = pool.tvl * (1.0 + tvl_change);
-            
-            // Simulate utilization rate changes
-            let util_change = (rand::random::<f64>() - 0.5) * 0.02; // -1% to +1%
-            pool.utilization_rate = (pool.utilization_rate + util_change).clamp(0.0, 0.95);
         }
         
         Ok(())
@@ -166,23 +516,27 @@ impl ProtocolInterface for HatomProtocol {
         Ok(pool.tvl)
     }
     
+    async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo> {
+        let pool = self.get_pool(pool_id)?;
+        Ok(PoolInfo {
+            id: pool.id.clone(),
+            name: pool.name.clone(),
+            tokens: vec![pool.token.clone()],
+            lp_token: Some(pool.h_token.clone()),
+            fee_tier: None, // Lending markets charge no swap fee
+            contract_address: pool.market_address.clone(),
+            reward_tokens: pool.reward_tokens.clone(),
+        })
+    }
+    
     async fn deposit(&self, 
                     user_address: &str, 
                     amount: f64, 
                     token: &str) -> AppResult<String> {
         debug!("Depositing {} {} for user {} in Hatom Protocol", amount, token, user_address);
         
-        // Find the lending pool for this token
-        let pool_id = self.lending_pools.values()
-            .find(|p| p.token == token)
-            .ok_or_else(|| AppError::Validation(format!("No lending pool found for token {}", token)))?
-            .id.clone();
-        
-        // Encode the smart contract call
-        let data = self.encode_sc_call("deposit", vec![
-            &pool_id,
-            &format!("{}", (amount * 1e18) as u64)
-        ]);
+        // Encode the smart contract call for the lending pool of this token
+        let data = self.build_deposit_data(amount, token).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -192,23 +546,13 @@ impl ProtocolInterface for HatomProtocol {
     }
     
     async fn withdraw(&self, 
-                 async fn withdraw(&self, 
                      user_address: &str, 
                      amount: f64, 
                      token: &str) -> AppResult<String> {
         debug!("Withdrawing {} {} for user {} from Hatom Protocol", amount, token, user_address);
         
-        // Find the lending pool for this token
-        let pool_id = self.lending_pools.values()
-            .find(|p| p.token == token)
-            .ok_or_else(|| AppError::Validation(format!("No lending pool found for token {}", token)))?
-            .id.clone();
-        
-        // Encode the smart contract call
-        let data = self.encode_sc_call("withdraw", vec![
-            &pool_id,
-            &format!("{}", (amount * 1e18) as u64)
-        ]);
+        // Encode the smart contract call for the lending pool of this token
+        let data = self.build_withdraw_data(amount, token).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -223,23 +567,8 @@ impl ProtocolInterface for HatomProtocol {
         // For lending protocols, rebalancing typically means moving funds between different lending pools
         // based on the optimal allocation for the strategy
         
-        // Get optimal allocation based on strategy
-        let optimal_allocation = self.get_optimal_allocation(&position.strategy).await?;
-        
-        // Encode the smart contract call
-        let mut args = Vec::new();
-        for (token, percentage) in optimal_allocation {
-            // Find the pool ID for this token
-            let pool_id = self.lending_pools.values()
-                .find(|p| p.token == token)
-                .ok_or_else(|| AppError::Validation(format!("No lending pool found for token {}", token)))?
-                .id.clone();
-            
-            args.push(&pool_id);
-            args.push(&format!("{}", (percentage * 100.0) as u64));
-        }
-        
-        let data = self.encode_sc_call("rebalance", args);
+        // Encode the smart contract call for the optimal allocation
+        let data = self.build_rebalance_data(position).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -254,7 +583,7 @@ impl ProtocolInterface for HatomProtocol {
         debug!("Claiming rewards for position {} by user {} from Hatom Protocol", position_id, user_address);
         
         // Encode the smart contract call
-        let data = self.encode_sc_call("claimRewards", vec![position_id]);
+        let data = self.build_claim_rewards_data(position_id).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -263,6 +592,46 @@ impl ProtocolInterface for HatomProtocol {
         Ok(tx_hash)
     }
     
+    async fn build_deposit_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        let pool = self.get_pool(&self.find_pool_id_for_token(token)?)?;
+        let raw_amount = to_raw_amount(amount, pool.decimals)?;
+        
+        // EGLD is sent as value, ESDTs through a token transfer
+        if pool.token_id == "EGLD" {
+            Ok(self.encode_sc_call("deposit", vec![&hex::encode(&pool.id)]))
+        } else {
+            let data = esdt_transfer_data(&pool.token_id, &raw_amount, "deposit");
+            Ok(format!("{}@{}", data, hex::encode(&pool.id)))
+        }
+    }
+    
+    async fn build_withdraw_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        let pool = self.get_pool(&self.find_pool_id_for_token(token)?)?;
+        let raw_amount = to_raw_amount(amount, pool.decimals)?;
+        
+        Ok(self.encode_sc_call("withdraw", vec![
+            &hex::encode(&pool.id),
+            &encode_biguint(&raw_amount),
+        ]))
+    }
+    
+    async fn build_rebalance_data(&self, position: &Position) -> AppResult<String> {
+        // Get optimal allocation based on strategy
+        let optimal_allocation = self.get_optimal_allocation(&position.strategy).await?;
+        
+        let mut args = Vec::new();
+        for (token, percentage) in optimal_allocation {
+            args.push(self.find_pool_id_for_token(&token)?);
+            args.push(format!("{}", (percentage * 100.0) as u64));
+        }
+        
+        Ok(self.encode_sc_call("rebalance", args.iter().map(|a| a.as_str()).collect()))
+    }
+    
+    async fn build_claim_rewards_data(&self, position_id: &str) -> AppResult<String> {
+        Ok(self.encode_sc_call("claimRewards", vec![position_id]))
+    }
+    
     async fn get_position_value(&self, position_id: &str) -> AppResult<f64> {
         debug!("Getting value for position {} in Hatom Protocol", position_id);
         
@@ -318,6 +687,10 @@ impl ProtocolInterface for HatomProtocol {
             "withdraw" => 400_000,
             "rebalance" => 800_000,
             "claimRewards" => 250_000,
+            "borrow" => 30_000_000,
+            "repayBorrow" => 20_000_000,
+            "enterMarkets" => 20_000_000,
+            "exitMarket" => 30_000_000,
             _ => 400_000, // Default
         }
     }
@@ -325,4 +698,55 @@ impl ProtocolInterface for HatomProtocol {
     fn get_risk_level(&self) -> &str {
         "Low" // Overall protocol risk level
     }
+    
+    async fn get_utilization(&self) -> AppResult<Option<f64>> {
+        // Weighted by supply at the oracle price, so a small market running dry doesn't dominate
+        let market_params = self.get_market_params().await?;
+        let mut total_supplied = 0.0;
+        let mut weighted_utilization = 0.0;
+        for pool_id in self.lending_pools.keys() {
+            let (utilization, supplied) = self.get_market_utilization(pool_id).await?;
+            let price = market_params.get(pool_id).map(|(_, price)| *price).unwrap_or(0.0);
+            total_supplied += supplied * price;
+            weighted_utilization += utilization * supplied * price;
+        }
+        
+        if total_supplied <= 0.0 {
+            return Ok(None);
+        }
+        Ok(Some(weighted_utilization / total_supplied))
+    }
+    
+    async fn get_account_health(&self, user_address: &str) -> AppResult<Option<AccountHealth>> {
+        let snapshot = self.get_account_snapshot(user_address).await?;
+        Ok(Some(snapshot.health()))
+    }
+    
+    async fn get_pending_rewards(&self, 
+                                user_address: &str, 
+                                position: &Position) -> AppResult<Vec<PendingReward>> {
+        let account_arg = hex::encode(address_to_public_key(user_address)?);
+        
+        // The controller accrues rewards per market, one amount per reward token of the market
+        let mut rewards: HashMap<String, f64> = HashMap::new();
+        for pool in self.lending_pools.values().filter(|p| position.tokens.contains(&p.token)) {
+            let market_arg = hex::encode(address_to_public_key(&pool.market_address)?);
+            let accrued = self.blockchain_client
+                .query_vm(&self.contract_address, "getAccruedRewards", vec![account_arg.clone(), market_arg])
+                .await?;
+            
+            for (token, amount) in pool.reward_tokens.iter().zip(&accrued) {
+                *rewards.entry(token.clone()).or_insert(0.0) += from_raw_amount(&BigUint::from_bytes_be(amount), REWARD_DECIMALS);
+            }
+        }
+        
+        Ok(rewards.into_iter()
+            .filter(|(_, amount)| *amount > 0.0)
+            .map(|(token, amount)| PendingReward { token, amount })
+            .collect())
+    }
+}
+
+fn decode_wad(value: &[u8]) -> f64 {
+    BigUint::from_bytes_be(value).to_f64().unwrap_or(0.0) / WAD
 }
\ No newline at end of file
@@ -0,0 +1,594 @@
+use crate::error::{AppError, AppResult};
+use crate::models::position::{Position, PositionStrategy};
+use crate::smart_contracts::maiar_farm::{
+    decode_u64, esdt_nft_transfer_data, esdt_transfer_data, first_amount, from_raw_amount,
+    to_raw_amount, NestedDecoder,
+};
+use crate::smart_contracts::protocol_interface::{
+    ContractCall, LiquidStakingToken, PoolInfo, ProtocolInterface, UnbondingEntry,
+};
+use crate::blockchain::{BlockchainProvider, MultiversXClient};
+use crate::utils::address_to_public_key;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use num_bigint::BigUint;
+use serde::Serialize;
+use std::sync::RwLock;
+use tracing::{info, debug, warn};
+
+// Liquid staking protocols delegate EGLD on behalf of their users and mint a liquid token
+// whose exchange rate against EGLD grows as staking rewards are redelegated
+
+const REFRESH_INTERVAL_SECONDS: i64 = 300;
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+// The rate grows in steps once per epoch, so the APY is measured between two steps at least a day apart
+const APY_WINDOW_SECONDS: i64 = 86_400;
+
+/// How a protocol keeps track of stake that is being unbonded
+#[derive(Debug, Clone)]
+pub enum UnstakeTracking {
+    /// Each undelegation mints an NFT that is redeemed for EGLD once unbonded;
+    /// the view returns the NFT collection
+    Nft { collection_view: &'static str },
+    /// Undelegations are kept per user in the contract and listed by the view
+    UserView { function: &'static str },
+}
+
+/// Contract interface of a liquid staking protocol
+#[derive(Debug, Clone)]
+pub struct LiquidStakingConfig {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub contract_address: &'static str,
+    pub liquid_token: &'static str,
+    /// View returning the EGLD value of one liquid token, with 18 decimals
+    pub exchange_rate_view: &'static str,
+    /// View returning the liquid tokens in circulation
+    pub supply_view: &'static str,
+    pub unstake_tracking: UnstakeTracking,
+}
+
+impl LiquidStakingConfig {
+    /// Hatom liquid staking, minting sEGLD
+    pub fn hatom() -> Self {
+        Self {
+            id: "hatom-liquid-staking",
+            name: "Hatom Liquid Staking",
+            contract_address: "erd1qqqqqqqqqqqqqpgq6uzdzy54wnesfnlaycxwymrn9texlnmyah0ssrfvk6",
+            liquid_token: "SEGLD-3ad2d0",
+            exchange_rate_view: "getExchangeRate",
+            supply_view: "getTotalShares",
+            unstake_tracking: UnstakeTracking::Nft { collection_view: "getUndelegateTokenId" },
+        }
+    }
+    
+    /// Salsa liquid staking, minting LEGLD
+    pub fn salsa() -> Self {
+        Self {
+            id: "salsa",
+            name: "Salsa Liquid Staking",
+            contract_address: "erd1qqqqqqqqqqqqqpgqaqxztq0y764dnet95jwtse5u5zkg92sfacts6h9su3",
+            liquid_token: "LEGLD-d74da9",
+            exchange_rate_view: "getTokenPrice",
+            supply_view: "getLiquidTokenSupply",
+            unstake_tracking: UnstakeTracking::UserView { function: "getUserUndelegations" },
+        }
+    }
+    
+    fn ticker(&self) -> &'static str {
+        self.liquid_token.split('-').next().unwrap_or(self.liquid_token)
+    }
+}
+
+/// A user's liquid staking tokens and the stake they are unbonding
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidStakingPosition {
+    pub token: String,
+    pub balance: f64,
+    /// EGLD value of the liquid tokens at the current exchange rate
+    pub underlying_value: f64,
+    pub unbonding: Vec<UnbondingEntry>,
+    /// Unbonded EGLD that can be withdrawn
+    pub withdrawable: f64,
+}
+
+// Exchange rate data, refreshed periodically
+#[derive(Clone)]
+struct RateState {
+    exchange_rate: f64,
+    supply: f64,
+    // None until a full window has been measured
+    apy: Option<f64>,
+    // Rate right after the step the current measurement window starts at
+    anchor: Option<(DateTime<Utc>, f64)>,
+}
+
+// An undelegation with what is needed to withdraw it
+struct Undelegation {
+    entry: UnbondingEntry,
+    // Collection, nonce and amount of the unstake NFT, for NFT tracked undelegations
+    nft: Option<(String, u64, BigUint)>,
+}
+
+pub struct LiquidStakingProtocol {
+    config: LiquidStakingConfig,
+    pool_id: String,
+    blockchain_client: MultiversXClient,
+    state: RwLock<Option<RateState>>,
+    unstake_collection: RwLock<Option<String>>,
+    last_refresh: RwLock<Option<DateTime<Utc>>>,
+}
+
+impl LiquidStakingProtocol {
+    pub fn new(config: LiquidStakingConfig, blockchain_client: MultiversXClient) -> Self {
+        Self {
+            pool_id: format!("{}-staking", config.ticker().to_lowercase()),
+            config,
+            blockchain_client,
+            state: RwLock::new(None),
+            unstake_collection: RwLock::new(None),
+            last_refresh: RwLock::new(None),
+        }
+    }
+    
+    /// EGLD value of one liquid token
+    pub async fn get_exchange_rate(&self) -> AppResult<f64> {
+        Ok(self.get_state().await?.exchange_rate)
+    }
+    
+    /// Build the call that stakes EGLD for the liquid token
+    pub fn build_stake_call(&self, amount: f64) -> AppResult<ContractCall> {
+        Ok(ContractCall {
+            receiver: self.config.contract_address.to_string(),
+            data: "delegate".to_string(),
+            value: to_raw_amount(amount, 18)?.to_string(),
+            gas_limit: self.get_gas_limit_for_operation("delegate"),
+        })
+    }
+    
+    /// Build the call that returns liquid tokens and starts unbonding their EGLD
+    pub fn build_unstake_call(&self, liquid_amount: f64) -> AppResult<ContractCall> {
+        Ok(ContractCall {
+            receiver: self.config.contract_address.to_string(),
+            data: esdt_transfer_data(self.config.liquid_token, &to_raw_amount(liquid_amount, 18)?, "unDelegate"),
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation("unDelegate"),
+        })
+    }
+    
+    /// Build the calls that withdraw all unbonded stake of a user
+    pub async fn build_withdraw_unbonded_calls(&self, user_address: &str) -> AppResult<Vec<ContractCall>> {
+        let undelegations = self.get_undelegations(user_address).await?;
+        let mut calls = Vec::new();
+        
+        match self.config.unstake_tracking {
+            // Every unbonded NFT is sent back to the contract
+            UnstakeTracking::Nft { .. } => {
+                for undelegation in undelegations.iter().filter(|u| u.entry.withdrawable) {
+                    if let Some((collection, nonce, amount)) = &undelegation.nft {
+                        calls.push(ContractCall {
+                            receiver: user_address.to_string(),
+                            data: esdt_nft_transfer_data(collection, *nonce, amount, self.config.contract_address, "withdraw")?,
+                            value: "0".to_string(),
+                            gas_limit: self.get_gas_limit_for_operation("withdraw"),
+                        });
+                    }
+                }
+            },
+            // A single call withdraws everything unbonded
+            UnstakeTracking::UserView { .. } => {
+                if undelegations.iter().any(|u| u.entry.withdrawable) {
+                    calls.push(ContractCall {
+                        receiver: self.config.contract_address.to_string(),
+                        data: "withdraw".to_string(),
+                        value: "0".to_string(),
+                        gas_limit: self.get_gas_limit_for_operation("withdraw"),
+                    });
+                }
+            },
+        }
+        
+        Ok(calls)
+    }
+    
+    /// Get a user's liquid tokens and unbonding stake
+    pub async fn get_liquid_staking_position(&self, user_address: &str) -> AppResult<LiquidStakingPosition> {
+        let exchange_rate = self.get_exchange_rate().await?;
+        
+        let balance = self.blockchain_client.get_esdt_tokens(user_address).await?
+            .iter()
+            .find(|t| t.token_identifier == self.config.liquid_token)
+            .map(|t| t.balance.parse::<BigUint>().map(|b| from_raw_amount(&b, 18)).unwrap_or(0.0))
+            .unwrap_or(0.0);
+        
+        let unbonding: Vec<UnbondingEntry> = self.get_undelegations(user_address).await?
+            .into_iter()
+            .map(|u| u.entry)
+            .collect();
+        let withdrawable = unbonding.iter().filter(|u| u.withdrawable).map(|u| u.amount).sum();
+        
+        Ok(LiquidStakingPosition {
+            token: self.config.liquid_token.to_string(),
+            balance,
+            underlying_value: balance * exchange_rate,
+            unbonding,
+            withdrawable,
+        })
+    }
+    
+    // Read a user's undelegations, from their unstake NFTs or from the contract
+    async fn get_undelegations(&self, user_address: &str) -> AppResult<Vec<Undelegation>> {
+        let current_epoch = self.blockchain_client.get_network_status().await?.erd_epoch_number;
+        let mut undelegations = Vec::new();
+        
+        match self.config.unstake_tracking {
+            UnstakeTracking::Nft { collection_view } => {
+                let collection = self.get_unstake_collection(collection_view).await?;
+                
+                for token in self.blockchain_client.get_esdt_tokens(user_address).await? {
+                    if token.nonce == 0 || token.collection() != collection {
+                        continue;
+                    }
+                    
+                    // Attributes hold the EGLD amount and the epoch it unbonds at
+                    let attributes = token.attribute_bytes()?;
+                    let mut decoder = NestedDecoder::new(&attributes);
+                    let amount = from_raw_amount(&decoder.read_biguint()?, 18);
+                    let unbond_epoch = decoder.read_u64()?;
+                    let balance = token.balance.parse::<BigUint>().map_err(|e|
+                        AppError::Blockchain(format!("Invalid balance of {}: {}", token.token_identifier, e))
+                    )?;
+                    
+                    undelegations.push(Undelegation {
+                        entry: self.unbonding_entry(amount, unbond_epoch, current_epoch),
+                        nft: Some((collection.clone(), token.nonce, balance)),
+                    });
+                }
+            },
+            UnstakeTracking::UserView { function } => {
+                let user_arg = hex::encode(address_to_public_key(user_address)?);
+                let results = self.blockchain_client
+                    .query_vm(self.config.contract_address, function, vec![user_arg])
+                    .await?;
+                
+                // Undelegations come as pairs of amount and unbond epoch
+                for pair in results.chunks(2) {
+                    let amount = from_raw_amount(&BigUint::from_bytes_be(&pair[0]), 18);
+                    let unbond_epoch = pair.get(1).map(|v| decode_u64(v)).unwrap_or(0);
+                    
+                    undelegations.push(Undelegation {
+                        entry: self.unbonding_entry(amount, unbond_epoch, current_epoch),
+                        nft: None,
+                    });
+                }
+            },
+        }
+        
+        Ok(undelegations)
+    }
+    
+    fn unbonding_entry(&self, amount: f64, unbond_epoch: u64, current_epoch: u64) -> UnbondingEntry {
+        let remaining_epochs = unbond_epoch.saturating_sub(current_epoch);
+        UnbondingEntry {
+            pool_id: self.pool_id.clone(),
+            amount,
+            unbond_epoch,
+            remaining_epochs,
+            withdrawable: remaining_epochs == 0,
+        }
+    }
+    
+    // Helper method to get the unstake NFT collection, which never changes once read
+    async fn get_unstake_collection(&self, collection_view: &str) -> AppResult<String> {
+        if let Some(collection) = self.unstake_collection.read().unwrap().clone() {
+            return Ok(collection);
+        }
+        
+        let results = self.blockchain_client
+            .query_vm(self.config.contract_address, collection_view, Vec::new())
+            .await?;
+        let collection = results.first()
+            .map(|v| String::from_utf8_lossy(v).to_string())
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| AppError::Blockchain(format!("{} returned no unstake token", self.config.name)))?;
+        
+        let mut cached = self.unstake_collection.write().unwrap();
+        *cached = Some(collection.clone());
+        Ok(collection)
+    }
+    
+    // Helper method to get the exchange rate data, refreshing it when stale
+    async fn get_state(&self) -> AppResult<RateState> {
+        let is_stale = {
+            let last_refresh = self.last_refresh.read().unwrap();
+            last_refresh.map_or(true, |t| (Utc::now() - t).num_seconds() >= REFRESH_INTERVAL_SECONDS)
+        };
+        
+        if is_stale {
+            // Keep serving the previous rate if the chain can't be reached
+            match self.update_state().await {
+                Ok(()) => {
+                    let mut last_refresh = self.last_refresh.write().unwrap();
+                    *last_refresh = Some(Utc::now());
+                },
+                Err(e) => warn!("Failed to refresh {} exchange rate: {}", self.config.name, e),
+            }
+        }
+        
+        self.state.read().unwrap().clone().ok_or_else(||
+            AppError::Blockchain(format!("{} exchange rate is unavailable", self.config.name))
+        )
+    }
+    
+    // Helper method to update the exchange rate and APY from blockchain
+    async fn update_state(&self) -> AppResult<()> {
+        debug!("Updating {} exchange rate", self.config.name);
+        
+        let client = &self.blockchain_client;
+        let (rate, supply) = futures::try_join!(
+            client.query_vm(self.config.contract_address, self.config.exchange_rate_view, Vec::new()),
+            client.query_vm(self.config.contract_address, self.config.supply_view, Vec::new()),
+        )?;
+        
+        let exchange_rate = first_amount(&rate);
+        if exchange_rate <= 0.0 {
+            return Err(AppError::Blockchain(format!("{} returned an invalid exchange rate", self.config.name)));
+        }
+        let supply = first_amount(&supply);
+        
+        let now = Utc::now();
+        let previous = self.state.read().unwrap().clone();
+        
+        // Measure the compounded growth between two rate steps at least a window apart, so the
+        // measurement spans whole epochs; the first step observed only starts the window
+        let mut apy = previous.as_ref().and_then(|s| s.apy);
+        let mut anchor = previous.as_ref().and_then(|s| s.anchor);
+        if previous.as_ref().map_or(false, |s| exchange_rate > s.exchange_rate) {
+            match anchor {
+                Some((anchor_at, anchor_rate)) if (now - anchor_at).num_seconds() >= APY_WINDOW_SECONDS => {
+                    let elapsed = (now - anchor_at).num_seconds() as f64;
+                    apy = Some(((exchange_rate / anchor_rate).powf(SECONDS_PER_YEAR / elapsed) - 1.0) * 100.0);
+                    anchor = Some((now, exchange_rate));
+                },
+                Some(_) => {},
+                None => anchor = Some((now, exchange_rate)),
+            }
+        }
+        
+        match apy {
+            Some(apy) => info!("{} exchange rate: {} EGLD per {}, APY {:.2}%", self.config.name, exchange_rate, self.config.ticker(), apy),
+            None => info!("{} exchange rate: {} EGLD per {}, APY not measured yet", self.config.name, exchange_rate, self.config.ticker()),
+        }
+        
+        let mut state = self.state.write().unwrap();
+        *state = Some(RateState {
+            exchange_rate,
+            supply,
+            apy,
+            anchor,
+        });
+        
+        Ok(())
+    }
+    
+    fn check_pool(&self, pool_id: &str) -> AppResult<()> {
+        if pool_id == self.pool_id {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("Pool {} not found in {}", pool_id, self.config.name)))
+        }
+    }
+}
+
+#[async_trait]
+impl ProtocolInterface for LiquidStakingProtocol {
+    fn get_id(&self) -> &str {
+        self.config.id
+    }
+    
+    fn get_name(&self) -> &str {
+        self.config.name
+    }
+    
+    fn get_contract_address(&self) -> &str {
+        self.config.contract_address
+    }
+    
+    async fn get_tvl(&self) -> AppResult<f64> {
+        // TVL in EGLD backing the liquid tokens
+        let state = self.get_state().await?;
+        Ok(state.supply * state.exchange_rate)
+    }
+    
+    async fn get_apy(&self) -> AppResult<f64> {
+        self.get_state().await?.apy.ok_or_else(||
+            AppError::NotFound(format!("{} APY has not been measured yet", self.config.name))
+        )
+    }
+    
+    async fn get_supported_tokens(&self) -> AppResult<Vec<String>> {
+        Ok(vec!["EGLD".to_string(), self.config.ticker().to_string()])
+    }
+    
+    async fn get_pools(&self) -> AppResult<Vec<String>> {
+        Ok(vec![self.pool_id.clone()])
+    }
+    
+    async fn get_pool_apy(&self, pool_id: &str) -> AppResult<f64> {
+        self.check_pool(pool_id)?;
+        self.get_apy().await
+    }
+    
+    async fn get_pool_tvl(&self, pool_id: &str) -> AppResult<f64> {
+        self.check_pool(pool_id)?;
+        self.get_tvl().await
+    }
+    
+    async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo> {
+        self.check_pool(pool_id)?;
+        Ok(PoolInfo {
+            id: self.pool_id.clone(),
+            name: format!("EGLD to {}", self.config.ticker()),
+            tokens: vec!["EGLD".to_string()],
+            lp_token: Some(self.config.liquid_token.to_string()),
+            fee_tier: None,
+            contract_address: self.config.contract_address.to_string(),
+            reward_tokens: Vec::new(), // Rewards accrue in the exchange rate
+        })
+    }
+    
+    async fn deposit(&self,
+                    user_address: &str,
+                    amount: f64,
+                    token: &str) -> AppResult<String> {
+        debug!("Staking {} {} with {} for user {}", amount, token, self.config.name, user_address);
+        
+        if token != "EGLD" {
+            return Err(AppError::Validation(format!("Only EGLD can be staked, not {}", token)));
+        }
+        
+        // Encode the smart contract call
+        let call = self.build_stake_call(amount)?;
+        
+        // Simulate a transaction hash
+        let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        
+        info!("Stake transaction to {} submitted: {}", call.receiver, tx_hash);
+        Ok(tx_hash)
+    }
+    
+    async fn withdraw(&self,
+                     user_address: &str,
+                     amount: f64,
+                     token: &str) -> AppResult<String> {
+        debug!("Unstaking {} {} from {} for user {}", amount, token, self.config.name, user_address);
+        
+        // Encode the smart contract call
+        let data = self.build_withdraw_data(amount, token).await?;
+        
+        // Simulate a transaction hash
+        let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+        
+        info!("Unstake transaction submitted: {} ({})", tx_hash, data);
+        Ok(tx_hash)
+    }
+    
+    async fn rebalance(&self, position: &Position) -> AppResult<String> {
+        // A single pool leaves nothing to rebalance
+        Err(AppError::Validation(format!(
+            "Liquid staking position {} has nothing to rebalance", position.id
+        )))
+    }
+    
+    async fn claim_rewards(&self,
+                          user_address: &str,
+                          position_id: &str) -> AppResult<String> {
+        debug!("Withdrawing unbonded stake for position {} by user {}", position_id, user_address);
+        
+        // Staking rewards accrue in the exchange rate, so claiming withdraws unbonded stake
+        let calls = self.build_withdraw_unbonded_calls(user_address).await?;
+        if calls.is_empty() {
+            return Err(AppError::Validation("No unbonded stake to withdraw".to_string()));
+        }
+        
+        // Simulate a transaction hash per call
+        let tx_hashes: Vec<String> = calls.iter()
+            .map(|_| format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", "")))
+            .collect();
+        
+        info!("Withdraw transactions submitted: {}", tx_hashes.join(","));
+        Ok(tx_hashes.join(","))
+    }
+    
+    async fn build_deposit_data(&self, _amount: f64, token: &str) -> AppResult<String> {
+        if token != "EGLD" {
+            return Err(AppError::Validation(format!("Only EGLD can be staked, not {}", token)));
+        }
+        Ok("delegate".to_string())
+    }
+    
+    async fn build_withdraw_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        // Amounts in EGLD are converted to liquid tokens at the current rate
+        let liquid_amount = if token == "EGLD" {
+            amount / self.get_exchange_rate().await?
+        } else {
+            amount
+        };
+        Ok(self.build_unstake_call(liquid_amount)?.data)
+    }
+    
+    async fn build_rebalance_data(&self, position: &Position) -> AppResult<String> {
+        self.rebalance(position).await
+    }
+    
+    async fn build_claim_rewards_data(&self, _position_id: &str) -> AppResult<String> {
+        match self.config.unstake_tracking {
+            UnstakeTracking::UserView { .. } => Ok("withdraw".to_string()),
+            // NFT withdrawals depend on the NFTs the user holds
+            UnstakeTracking::Nft { .. } => Err(AppError::Validation(format!(
+                "{} withdrawals are built per unstake NFT", self.config.name
+            ))),
+        }
+    }
+    
+    async fn get_position_value(&self, position_id: &str) -> AppResult<f64> {
+        debug!("Getting value for liquid staking position {}", position_id);
+        
+        // For an address, value the liquid tokens and the stake being unbonded
+        if !position_id.starts_with("erd1") {
+            return Err(AppError::Validation("Liquid staking positions are valued by user address".to_string()));
+        }
+        
+        let position = self.get_liquid_staking_position(position_id).await?;
+        Ok(position.underlying_value + position.unbonding.iter().map(|u| u.amount).sum::<f64>())
+    }
+    
+    async fn get_optimal_allocation(&self,
+                                   strategy: &PositionStrategy) -> AppResult<Vec<(String, f64)>> {
+        debug!("Calculating optimal allocation for strategy {:?} in {}", strategy, self.config.name);
+        
+        // Staking only takes EGLD
+        Ok(vec![("EGLD".to_string(), 1.0)])
+    }
+    
+    fn is_token_supported(&self, token: &str) -> bool {
+        token == "EGLD" || token == self.config.ticker() || token == self.config.liquid_token
+    }
+    
+    fn get_gas_limit_for_operation(&self, operation: &str) -> u64 {
+        match operation {
+            "deposit" | "delegate" => 15_000_000,
+            "unDelegate" => 20_000_000,
+            "withdraw" => 20_000_000,
+            _ => 15_000_000, // Default
+        }
+    }
+    
+    fn get_risk_level(&self) -> &str {
+        "Medium" // Contract risk, and the liquid token can trade below its rate
+    }
+    
+    fn get_tvl_denomination(&self) -> &str {
+        "EGLD"
+    }
+    
+    fn supports_reinvestment(&self) -> bool {
+        true
+    }
+    
+    async fn get_unbonding(&self,
+                          user_address: &str) -> AppResult<Vec<UnbondingEntry>> {
+        Ok(self.get_undelegations(user_address).await?.into_iter().map(|u| u.entry).collect())
+    }
+    
+    async fn get_liquid_staking_token(&self) -> AppResult<Option<LiquidStakingToken>> {
+        let state = self.get_state().await?;
+        Ok(Some(LiquidStakingToken {
+            ticker: self.config.ticker().to_string(),
+            token_id: self.config.liquid_token.to_string(),
+            underlying: "EGLD".to_string(),
+            exchange_rate: state.exchange_rate,
+            apy: state.apy,
+        }))
+    }
+}
\ No newline at end of file
@@ -1,54 +1,159 @@
 use crate::error::{AppError, AppResult};
 use crate::models::position::{Position, PositionStrategy};
-use crate::smart_contracts::protocol_interface::ProtocolInterface;
+use crate::smart_contracts::maiar_farm::{
+    decode_u64, encode_biguint, esdt_nft_transfer_data, esdt_transfer_data, from_raw_amount, to_raw_amount,
+    DualYieldTokenAttributes, FarmPosition, FarmTokenAttributes, LpFarmPosition,
+    MaiarFarmConfig, MetastakingPosition,
+};
+use crate::smart_contracts::protocol_interface::{
+    ContractCall, PendingReward, PoolInfo, ProtocolInterface, SwapCurve, SwapPool,
+};
 use crate::blockchain::MultiversXClient;
+use crate::utils::public_key_to_address;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
 use std::collections::HashMap;
-use tracing::{info, debug, error};
+use std::sync::RwLock;
+use tracing::{info, debug, error, warn};
+
+// xExchange router, which keeps the list of all deployed pair contracts
+pub(crate) const ROUTER_ADDRESS: &str = "erd1qqqqqqqqqqqqqpgqq66xk9gfr4esuhem3jru86wg5hvp33a62jps2fy57p";
+
+const WEGLD_TOKEN_ID: &str = "WEGLD-bd4d79";
+const USDC_TOKEN_ID: &str = "USDC-c76f1f";
+
+// Pair fees are expressed in hundredths of a basis point
+const FEE_PERCENT_BASE: f64 = 100_000.0;
+
+// 6 second rounds
+const BLOCKS_PER_YEAR: f64 = 5_256_000.0;
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+
+// Pool data older than this is refreshed from the chain by the background refresh
+const REFRESH_INTERVAL_SECONDS: i64 = 60;
+
+// Fee APRs are measured over at least this window; shorter windows are dominated by noise
+const APR_WINDOW_SECONDS: i64 = 3600;
+
+// Number of pairs queried concurrently during a refresh
+const PAIR_QUERY_CONCURRENCY: usize = 8;
+
+// Tokens with less USD liquidity than this in their deepest pair are too easily moved to be priced
+const MIN_PRICE_LIQUIDITY_USD: f64 = 10_000.0;
 
 pub struct MaiarExchangeProtocol {
     id: String,
     name: String,
     contract_address: String,
+    router_address: String,
     blockchain_client: MultiversXClient,
-    pools: HashMap<String, PoolInfo>,
+    pools: RwLock<HashMap<String, LiquidityPoolInfo>>,
+    // Farm contract per pool ID, used to add farm rewards on top of trading fees
+    farms: RwLock<HashMap<String, String>>,
+    // Metastaking contract per pool ID, accepting the pool's farm tokens
+    metastaking: RwLock<HashMap<String, String>>,
+    // Configured farm and metastaking contracts, matched to their pool on each refresh
+    farm_config: RwLock<MaiarFarmConfig>,
+    // Token identifiers read from farm and metastaking contracts
+    token_ids: RwLock<HashMap<String, String>>,
+    // USD prices derived from the pair reserves, by ticker
+    token_prices: RwLock<HashMap<String, f64>>,
+    last_refresh: RwLock<Option<DateTime<Utc>>>,
 }
 
-struct PoolInfo {
+#[derive(Clone)]
+struct LiquidityPoolInfo {
     id: String,
     name: String,
     tokens: Vec<String>,
-    apy: f64,
-    tvl: f64,
+    // Token identifiers, in the pair's token order
+    token_ids: Vec<String>,
+    lp_token: String,
+    pair_address: String,
+    fee: f64,
+    reward_tokens: Vec<String>,
+    // Unknown until measured from the pair reserves and LP share growth
+    apy: Option<f64>,
+    tvl: Option<f64>,
     risk: String,
+    reserves: Vec<f64>,
+    lp_supply: f64,
+    // USD value of a single LP token
+    lp_token_value: f64,
+    // Price of the second token expressed in the first token
+    spot_price: f64,
+    // Fee APR, once measured over at least APR_WINDOW_SECONDS
+    fee_apr: Option<f64>,
+    farm_apr: f64,
+    // Value of one LP share in pooled liquidity, sqrt(k) / supply, which only grows from fees
+    share_value: Option<(DateTime<Utc>, f64)>,
+}
+
+// Raw state of a pair contract as returned by its views
+struct PairState {
+    address: String,
+    first_token_id: String,
+    second_token_id: String,
+    lp_token_id: String,
+    first_reserve: f64,
+    second_reserve: f64,
+    lp_supply: f64,
+    total_fee_percent: u64,
 }
 
 impl MaiarExchangeProtocol {
     pub fn new(blockchain_client: MultiversXClient) -> Self {
         let mut pools = HashMap::new();
         
-        // Initialize with known pools
+        // Seed with known pools, used until the first refresh from the router succeeds
         pools.insert(
             "egld-mex".to_string(),
-            PoolInfo {
+            LiquidityPoolInfo {
                 id: "egld-mex".to_string(),
                 name: "EGLD-MEX LP".to_string(),
                 tokens: vec!["EGLD".to_string(), "MEX".to_string()],
-                apy: 18.5,
-                tvl: 42_500_000.0,
+                token_ids: vec![WEGLD_TOKEN_ID.to_string(), "MEX-455c57".to_string()],
+                lp_token: "EGLDMEX-0be9e5".to_string(),
+                pair_address: "erd1qqqqqqqqqqqqqpgqa0fsfshnff4n76jhcye6k7uvd7qacsq42jpsp6shh2".to_string(),
+                fee: 0.003, // 0.3%
+                reward_tokens: vec!["MEX".to_string()],
+                apy: None,
+                tvl: None,
                 risk: "Medium".to_string(),
+                reserves: Vec::new(),
+                lp_supply: 0.0,
+                lp_token_value: 0.0,
+                spot_price: 0.0,
+                fee_apr: None,
+                farm_apr: 0.0,
+                share_value: None,
             },
         );
         
         pools.insert(
             "egld-usdc".to_string(),
-            PoolInfo {
+            LiquidityPoolInfo {
                 id: "egld-usdc".to_string(),
                 name: "EGLD-USDC LP".to_string(),
                 tokens: vec!["EGLD".to_string(), "USDC".to_string()],
-                apy: 12.3,
-                tvl: 38_700_000.0,
+                token_ids: vec![WEGLD_TOKEN_ID.to_string(), USDC_TOKEN_ID.to_string()],
+                lp_token: "EGLDUSDC-594e5e".to_string(),
+                pair_address: "erd1qqqqqqqqqqqqqpgqeel2kumf0r8ffyhth7pqdujjat9nx0862jpsg2pqaq".to_string(),
+                fee: 0.003, // 0.3%
+                reward_tokens: vec!["MEX".to_string()],
+                apy: None,
+                tvl: None,
                 risk: "Low".to_string(),
+                reserves: Vec::new(),
+                lp_supply: 0.0,
+                lp_token_value: 0.0,
+                spot_price: 0.0,
+                fee_apr: None,
+                farm_apr: 0.0,
+                share_value: None,
             },
         );
         
@@ -56,11 +161,42 @@ impl MaiarExchangeProtocol {
             id: "maiar-exchange".to_string(),
             name: "Maiar Exchange".to_string(),
             contract_address: "erd1qqqqqqqqqqqqqpgqd77fnev2sthnczp2lnfx0y5jdycynjfhzzgq6p3rax".to_string(),
+            router_address: ROUTER_ADDRESS.to_string(),
             blockchain_client,
-            pools,
+            pools: RwLock::new(pools),
+            farms: RwLock::new(HashMap::new()),
+            metastaking: RwLock::new(HashMap::new()),
+            farm_config: RwLock::new(MaiarFarmConfig::default()),
+            token_ids: RwLock::new(HashMap::new()),
+            token_prices: RwLock::new(HashMap::new()),
+            last_refresh: RwLock::new(None),
         }
     }
     
+    /// Set the farm and metastaking contracts to follow; each is matched to its pool on the next refresh
+    pub fn set_farm_config(&self, config: MaiarFarmConfig) {
+        let mut farm_config = self.farm_config.write().unwrap();
+        *farm_config = config;
+    }
+    
+    // Set the farm contract rewarding LP tokens of a pool
+    fn set_farm_address(&self, pool_id: &str, farm_address: &str) {
+        let mut farms = self.farms.write().unwrap();
+        farms.insert(pool_id.to_string(), farm_address.to_string());
+    }
+    
+    /// Get the USD value of one LP token of a pool
+    pub async fn get_lp_token_value(&self, pool_id: &str) -> AppResult<f64> {
+        let pool = self.get_pool(pool_id).await?;
+        Ok(pool.lp_token_value)
+    }
+    
+    /// Get the spot price of the second token of a pool, expressed in the first token
+    pub async fn get_spot_price(&self, pool_id: &str) -> AppResult<f64> {
+        let pool = self.get_pool(pool_id).await?;
+        Ok(pool.spot_price)
+    }
+    
     // Helper method to encode a smart contract call
     fn encode_sc_call(&self, function: &str, args: Vec<&str>) -> String {
         // In a real implementation, this would properly encode the function call for the MultiversX VM
@@ -73,31 +209,675 @@ impl MaiarExchangeProtocol {
     }
     
     // Helper method to get pool by ID
-    fn get_pool(&self, pool_id: &str) -> AppResult<&PoolInfo> {
-        self.pools.get(pool_id).ok_or_else(|| 
+    async fn get_pool(&self, pool_id: &str) -> AppResult<LiquidityPoolInfo> {
+        let pools = self.pools.read().unwrap();
+        pools.get(pool_id).cloned().ok_or_else(|| 
             AppError::NotFound(format!("Pool with ID {} not found", pool_id))
         )
     }
     
+    // Helper method to get a snapshot of all pools
+    async fn get_all_pools(&self) -> Vec<LiquidityPoolInfo> {
+        let pools = self.pools.read().unwrap();
+        pools.values().cloned().collect()
+    }
+    
+    // Match configured farm contracts to pools by the LP token they accept, then
+    // metastaking contracts to pools by the farm they stake into
+    async fn resolve_farms(&self, pairs: &[PairState]) {
+        let config = self.farm_config.read().unwrap().clone();
+        let unresolved: Vec<String> = {
+            let farms = self.farms.read().unwrap();
+            config.farms.iter()
+                .filter(|address| !farms.values().any(|farm| farm == *address))
+                .cloned()
+                .collect()
+        };
+        
+        for farm_address in unresolved {
+            match self.get_token_id(&farm_address, "getFarmingTokenId").await {
+                Ok(lp_token) => match pairs.iter().find(|pair| pair.lp_token_id == lp_token) {
+                    Some(pair) => self.set_farm_address(&pool_id(pair), &farm_address),
+                    None => warn!("Farm {} accepts {}, which is not the LP token of any pair", farm_address, lp_token),
+                },
+                Err(e) => warn!("Failed to read the farming token of farm {}: {}", farm_address, e),
+            }
+        }
+        
+        let unresolved: Vec<String> = {
+            let metastaking = self.metastaking.read().unwrap();
+            config.metastaking.iter()
+                .filter(|address| !metastaking.values().any(|contract| contract == *address))
+                .cloned()
+                .collect()
+        };
+        
+        for metastaking_address in unresolved {
+            match self.query_lp_farm_address(&metastaking_address).await {
+                Ok(farm_address) => {
+                    let pool_id = self.farms.read().unwrap().iter()
+                        .find(|(_, farm)| **farm == farm_address)
+                        .map(|(pool_id, _)| pool_id.clone());
+                    match pool_id {
+                        Some(pool_id) => self.set_metastaking_address(&pool_id, &metastaking_address),
+                        None => warn!("Metastaking {} stakes into farm {}, which is not configured", metastaking_address, farm_address),
+                    }
+                },
+                Err(e) => warn!("Failed to read the farm of metastaking {}: {}", metastaking_address, e),
+            }
+        }
+    }
+    
+    // Farm whose tokens a metastaking contract accepts
+    async fn query_lp_farm_address(&self, metastaking_address: &str) -> AppResult<String> {
+        let results = self.blockchain_client
+            .query_vm(metastaking_address, "getLpFarmAddress", Vec::new())
+            .await?;
+        
+        let public_key = results.first()
+            .ok_or_else(|| AppError::Blockchain("Empty query result".to_string()))?;
+        public_key_to_address(public_key)
+    }
+    
     // Helper method to update pool data from blockchain
-    async fn update_pool_data(&mut self) -> AppResult<()> {
+    async fn update_pool_data(&self) -> AppResult<()> {
         debug!("Updating pool data from blockchain for Maiar Exchange");
         
-        // In a real implementation, this would query the blockchain for the latest pool data
-        // For now, we'll just simulate some random variations
+        let pair_addresses = self.query_pair_addresses().await?;
+        
+        let pairs: Vec<PairState> = stream::iter(pair_addresses)
+            .map(|address| async move {
+                let result = self.query_pair_state(&address).await;
+                if let Err(e) = &result {
+                    warn!("Skipping pair {}: {}", address, e);
+                }
+                result.ok()
+            })
+            .buffer_unordered(PAIR_QUERY_CONCURRENCY)
+            .filter_map(|pair| async move { pair })
+            .collect()
+            .await;
+        
+        if pairs.is_empty() {
+            return Err(AppError::Blockchain("No pairs could be read from the router".to_string()));
+        }
+        
+        self.resolve_farms(&pairs).await;
+        
+        let prices = Self::derive_token_prices(&pairs);
+        let farms = self.farms.read().unwrap().clone();
+        let now = Utc::now();
         
-        for pool in self.pools.values_mut() {
-            // Simulate small APY changes
-            let apy_change = (rand::random::<f64>() - 0.5) * 0.5; // -0.25% to +0.25%
-            pool.apy = (pool.apy + apy_change).max(0.0);
+        let mut updated: HashMap<String, LiquidityPoolInfo> = HashMap::new();
+        for pair in &pairs {
+            let tokens = vec![display_ticker(&pair.first_token_id), display_ticker(&pair.second_token_id)];
+            let id = pool_id(pair);
+            
+            let first_price = prices.get(&pair.first_token_id).copied().unwrap_or(0.0);
+            let second_price = prices.get(&pair.second_token_id).copied().unwrap_or(0.0);
+            let tvl = pair.first_reserve * first_price + pair.second_reserve * second_price;
+            let lp_token_value = if pair.lp_supply > 0.0 { tvl / pair.lp_supply } else { 0.0 };
+            let spot_price = if pair.second_reserve > 0.0 { pair.first_reserve / pair.second_reserve } else { 0.0 };
+            
+            // Trading fees accrue to the reserves, so the fee APR is the growth rate of an LP share
+            let share_value = if pair.lp_supply > 0.0 {
+                (pair.first_reserve * pair.second_reserve).sqrt() / pair.lp_supply
+            } else {
+                0.0
+            };
+            let previous = {
+                let pools = self.pools.read().unwrap();
+                pools.get(&id).filter(|p| p.pair_address == pair.address).cloned()
+            };
+            
+            // The share value is compared with a snapshot at least APR_WINDOW_SECONDS old; until
+            // then the previous APR is kept
+            let previous_apr = previous.as_ref().and_then(|p| p.fee_apr);
+            let (fee_apr, share_snapshot) = match previous.as_ref().and_then(|p| p.share_value) {
+                Some((taken_at, previous_value)) => {
+                    let elapsed = (now - taken_at).num_seconds();
+                    if elapsed >= APR_WINDOW_SECONDS && previous_value > 0.0 {
+                        let growth = (share_value / previous_value - 1.0).max(0.0);
+                        (Some(growth * SECONDS_PER_YEAR / elapsed as f64 * 100.0), (now, share_value))
+                    } else {
+                        (previous_apr, (taken_at, previous_value))
+                    }
+                },
+                None => (previous_apr, (now, share_value)),
+            };
+            
+            // Farm rewards are kept from the previous refresh when the farm can't be read
+            let mut reward_tokens = previous.as_ref().map(|p| p.reward_tokens.clone()).unwrap_or_default();
+            let mut farm_apr = previous.as_ref().map_or(0.0, |p| p.farm_apr);
+            if let Some(farm_address) = farms.get(&id) {
+                match self.query_farm_apr(farm_address, lp_token_value, &prices).await {
+                    Ok((reward_token, apr)) => {
+                        reward_tokens = vec![display_ticker(&reward_token)];
+                        farm_apr = apr;
+                    },
+                    Err(e) => warn!("Failed to read farm {} for pool {}: {}", farm_address, id, e),
+                }
+            }
             
-            // Simulate small TVL changes
-            let tvl_change = (rand::random::<f64>() - 0.5) * 0.01; // -0.5% to +0.5%
-            pool.tvl = pool.tvl * (1.0 + tvl_change);
+            // The APY stays unknown until the fee APR has been measured
+            let apy = fee_apr.map(|fee_apr| fee_apr + farm_apr);
+            
+            let risk = if tvl < 1_000_000.0 {
+                "High"
+            } else if is_stable(&pair.first_token_id) || is_stable(&pair.second_token_id) {
+                "Low"
+            } else {
+                "Medium"
+            };
+            
+            let pool = LiquidityPoolInfo {
+                id: id.clone(),
+                name: format!("{} LP", tokens.join("-")),
+                tokens,
+                token_ids: vec![pair.first_token_id.clone(), pair.second_token_id.clone()],
+                lp_token: pair.lp_token_id.clone(),
+                pair_address: pair.address.clone(),
+                fee: pair.total_fee_percent as f64 / FEE_PERCENT_BASE,
+                reward_tokens,
+                apy,
+                tvl: Some(tvl),
+                risk: risk.to_string(),
+                reserves: vec![pair.first_reserve, pair.second_reserve],
+                lp_supply: pair.lp_supply,
+                lp_token_value,
+                spot_price,
+                fee_apr,
+                farm_apr,
+                share_value: Some(share_snapshot),
+            };
+            
+            // Several pairs can share the same tickers; keep the deepest one
+            match updated.get(&id) {
+                Some(existing) if existing.tvl.unwrap_or(0.0) >= tvl => {},
+                _ => { updated.insert(id, pool); },
+            }
         }
         
+        info!("Loaded {} Maiar Exchange pools from the router", updated.len());
+        
+        // Several tokens can share a ticker; the WEGLD and USDC anchors always win, otherwise the
+        // token with the deepest pair does, as long as it is deep enough to be priced at all
+        let liquidity = Self::token_liquidity(&pairs, &prices);
+        let mut ticker_prices: HashMap<String, (f64, f64)> = HashMap::new();
+        for (token_id, price) in &prices {
+            let ticker = display_ticker(token_id);
+            if token_id == WEGLD_TOKEN_ID || token_id == USDC_TOKEN_ID {
+                ticker_prices.insert(ticker, (*price, f64::INFINITY));
+                continue;
+            }
+            
+            let depth = liquidity.get(token_id).copied().unwrap_or(0.0);
+            if depth < MIN_PRICE_LIQUIDITY_USD {
+                continue;
+            }
+            if ticker_prices.get(&ticker).map_or(true, |(_, best_depth)| depth > *best_depth) {
+                ticker_prices.insert(ticker, (*price, depth));
+            }
+        }
+        *self.token_prices.write().unwrap() = ticker_prices.into_iter()
+            .map(|(ticker, (price, _))| (ticker, price))
+            .collect();
+        
+        let mut pools = self.pools.write().unwrap();
+        *pools = updated;
+        
         Ok(())
     }
+    
+    // Read the addresses of all pairs deployed by the router
+    async fn query_pair_addresses(&self) -> AppResult<Vec<String>> {
+        let results = self.blockchain_client
+            .query_vm(&self.router_address, "getAllPairsManagedAddresses", Vec::new())
+            .await?;
+        
+        results.iter().map(|public_key| public_key_to_address(public_key)).collect()
+    }
+    
+    // Read token identifiers, reserves, LP supply and fees of a pair
+    async fn query_pair_state(&self, address: &str) -> AppResult<PairState> {
+        let client = &self.blockchain_client;
+        let (first_token, second_token, lp_token, reserves, fee) = futures::try_join!(
+            client.query_vm(address, "getFirstTokenId", Vec::new()),
+            client.query_vm(address, "getSecondTokenId", Vec::new()),
+            client.query_vm(address, "getLpTokenIdentifier", Vec::new()),
+            client.query_vm(address, "getReservesAndTotalSupply", Vec::new()),
+            client.query_vm(address, "getTotalFeePercent", Vec::new()),
+        )?;
+        
+        let first_token_id = decode_string(&first_token)?;
+        let second_token_id = decode_string(&second_token)?;
+        
+        if reserves.len() < 3 {
+            return Err(AppError::Blockchain(format!("Unexpected reserves result for pair {}", address)));
+        }
+        
+        Ok(PairState {
+            address: address.to_string(),
+            first_reserve: decode_amount(&reserves[0], token_decimals(&first_token_id)),
+            second_reserve: decode_amount(&reserves[1], token_decimals(&second_token_id)),
+            lp_supply: decode_amount(&reserves[2], 18),
+            lp_token_id: decode_string(&lp_token)?,
+            total_fee_percent: fee.first().map(|v| decode_u64(v)).unwrap_or(0),
+            first_token_id,
+            second_token_id,
+        })
+    }
+    
+    // Farm APR from the reward emission rate and the value of staked LP tokens
+    async fn query_farm_apr(&self, 
+                            farm_address: &str, 
+                            lp_token_value: f64, 
+                            prices: &HashMap<String, f64>) -> AppResult<(String, f64)> {
+        let client = &self.blockchain_client;
+        let (reward_token, per_block, farm_supply) = futures::try_join!(
+            client.query_vm(farm_address, "getRewardTokenId", Vec::new()),
+            client.query_vm(farm_address, "getPerBlockRewardAmount", Vec::new()),
+            client.query_vm(farm_address, "getFarmTokenSupply", Vec::new()),
+        )?;
+        
+        let reward_token_id = decode_string(&reward_token)?;
+        let per_block = per_block.first().map(|v| decode_amount(v, token_decimals(&reward_token_id))).unwrap_or(0.0);
+        // Farm tokens are minted one to one for the LP tokens staked
+        let farm_supply = farm_supply.first().map(|v| decode_amount(v, 18)).unwrap_or(0.0);
+        let reward_price = prices.get(&reward_token_id).copied().unwrap_or(0.0);
+        
+        let staked_value = farm_supply * lp_token_value;
+        if staked_value <= 0.0 {
+            return Ok((reward_token_id, 0.0));
+        }
+        
+        let yearly_rewards = per_block * BLOCKS_PER_YEAR * reward_price;
+        Ok((reward_token_id, yearly_rewards / staked_value * 100.0))
+    }
+    
+    // Set the metastaking contract that accepts the farm tokens of a pool
+    fn set_metastaking_address(&self, pool_id: &str, metastaking_address: &str) {
+        let mut metastaking = self.metastaking.write().unwrap();
+        metastaking.insert(pool_id.to_string(), metastaking_address.to_string());
+    }
+    
+    /// Build the call that stakes LP tokens of a pool into its farm
+    pub async fn build_enter_farm_call(&self, pool_id: &str, lp_amount: f64) -> AppResult<ContractCall> {
+        let pool = self.get_pool(pool_id).await?;
+        let farm_address = self.get_farm_address(pool_id)?;
+        let amount = to_raw_amount(lp_amount, 18)?;
+        
+        Ok(ContractCall {
+            receiver: farm_address,
+            data: esdt_transfer_data(&pool.lp_token, &amount, "enterFarm"),
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation("enterFarm"),
+        })
+    }
+    
+    /// Build the call that exits a farm position, returning the LP tokens and rewards
+    pub async fn build_exit_farm_call(&self, 
+                                      user_address: &str, 
+                                      pool_id: &str, 
+                                      nonce: u64, 
+                                      amount: &BigUint) -> AppResult<ContractCall> {
+        self.build_farm_token_call(user_address, pool_id, nonce, amount, "exitFarm").await
+    }
+    
+    /// Build the call that claims the rewards of a farm position, which is reissued under a new nonce
+    pub async fn build_claim_farm_rewards_call(&self, 
+                                               user_address: &str, 
+                                               pool_id: &str, 
+                                               nonce: u64, 
+                                               amount: &BigUint) -> AppResult<ContractCall> {
+        self.build_farm_token_call(user_address, pool_id, nonce, amount, "claimRewards").await
+    }
+    
+    /// Build the call that moves a farm position into metastaking
+    pub async fn build_enter_metastaking_call(&self, 
+                                              user_address: &str, 
+                                              pool_id: &str, 
+                                              nonce: u64, 
+                                              amount: &BigUint) -> AppResult<ContractCall> {
+        let farm_address = self.get_farm_address(pool_id)?;
+        let metastaking_address = self.get_metastaking_address(pool_id)?;
+        let farm_token = self.get_token_id(&farm_address, "getFarmTokenId").await?;
+        
+        Ok(ContractCall {
+            receiver: user_address.to_string(),
+            data: esdt_nft_transfer_data(&farm_token, nonce, amount, &metastaking_address, "stakeFarmTokens")?,
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation("stakeFarmTokens"),
+        })
+    }
+    
+    /// Build the call that exits a metastaking position, returning the LP tokens and both rewards
+    pub async fn build_exit_metastaking_call(&self, 
+                                             user_address: &str, 
+                                             pool_id: &str, 
+                                             nonce: u64, 
+                                             amount: &BigUint) -> AppResult<ContractCall> {
+        self.build_dual_yield_call(user_address, pool_id, nonce, amount, "unstakeFarmTokens").await
+    }
+    
+    /// Build the call that claims both rewards of a metastaking position
+    pub async fn build_claim_metastaking_call(&self, 
+                                              user_address: &str, 
+                                              pool_id: &str, 
+                                              nonce: u64, 
+                                              amount: &BigUint) -> AppResult<ContractCall> {
+        self.build_dual_yield_call(user_address, pool_id, nonce, amount, "claimDualYield").await
+    }
+    
+    /// Get a user's LP, farm and metastaking holdings for every pool they are in
+    pub async fn get_lp_farm_positions(&self, user_address: &str) -> AppResult<Vec<LpFarmPosition>> {
+        let tokens = self.blockchain_client.get_esdt_tokens(user_address).await?;
+        let pools = self.get_all_pools().await;
+        let farms = self.farms.read().unwrap().clone();
+        let metastaking = self.metastaking.read().unwrap().clone();
+        
+        let mut positions = Vec::new();
+        for pool in pools {
+            let lp_balance = tokens.iter()
+                .find(|t| t.nonce == 0 && t.token_identifier == pool.lp_token)
+                .and_then(|t| BigUint::parse_bytes(t.balance.as_bytes(), 10))
+                .map(|amount| from_raw_amount(&amount, 18))
+                .unwrap_or(0.0);
+            
+            let mut farm_positions = Vec::new();
+            if let Some(farm_address) = farms.get(&pool.id) {
+                let farm_token = self.get_token_id(farm_address, "getFarmTokenId").await?;
+                let reward_token = self.get_token_id(farm_address, "getRewardTokenId").await?;
+                
+                for token in tokens.iter().filter(|t| t.nonce > 0 && t.collection() == farm_token) {
+                    let amount = BigUint::parse_bytes(token.balance.as_bytes(), 10).unwrap_or_default();
+                    let attributes = token.attribute_bytes()?;
+                    let decoded = FarmTokenAttributes::decode(&attributes)?;
+                    let pending = self.query_pending_rewards(farm_address, &amount, &attributes).await?;
+                    let lp_amount = from_raw_amount(&amount, 18);
+                    
+                    farm_positions.push(FarmPosition {
+                        pool_id: pool.id.clone(),
+                        farm_address: farm_address.clone(),
+                        token_identifier: token.token_identifier.clone(),
+                        nonce: token.nonce,
+                        amount: token.balance.clone(),
+                        lp_amount,
+                        entering_epoch: decoded.entering_epoch,
+                        reward_token: reward_token.clone(),
+                        pending_rewards: from_raw_amount(&pending, token_decimals(&reward_token)),
+                        value: lp_amount * pool.lp_token_value,
+                    });
+                }
+            }
+            
+            let mut metastaking_positions = Vec::new();
+            if let (Some(metastaking_address), Some(farm_address)) = (metastaking.get(&pool.id), farms.get(&pool.id)) {
+                let dual_yield_token = self.get_token_id(metastaking_address, "getDualYieldTokenId").await?;
+                let farm_token = self.get_token_id(farm_address, "getFarmTokenId").await?;
+                let reward_token = self.get_token_id(farm_address, "getRewardTokenId").await?;
+                
+                for token in tokens.iter().filter(|t| t.nonce > 0 && t.collection() == dual_yield_token) {
+                    let decoded = DualYieldTokenAttributes::decode(&token.attribute_bytes()?)?;
+                    
+                    // The wrapped farm token is held by the metastaking contract; its rewards
+                    // are shared pro rata between the dual yield tokens of that nonce
+                    let farm_nft = self.blockchain_client
+                        .get_nft(metastaking_address, &farm_token, decoded.lp_farm_token_nonce)
+                        .await?;
+                    let pending = self.query_pending_rewards(
+                        farm_address,
+                        &decoded.lp_farm_token_amount,
+                        &farm_nft.attribute_bytes()?,
+                    ).await?;
+                    let lp_amount = from_raw_amount(&decoded.lp_farm_token_amount, 18);
+                    
+                    metastaking_positions.push(MetastakingPosition {
+                        pool_id: pool.id.clone(),
+                        metastaking_address: metastaking_address.clone(),
+                        token_identifier: token.token_identifier.clone(),
+                        nonce: token.nonce,
+                        amount: token.balance.clone(),
+                        lp_amount,
+                        reward_token: reward_token.clone(),
+                        pending_rewards: from_raw_amount(&pending, token_decimals(&reward_token)),
+                        value: lp_amount * pool.lp_token_value,
+                    });
+                }
+            }
+            
+            if lp_balance == 0.0 && farm_positions.is_empty() && metastaking_positions.is_empty() {
+                continue;
+            }
+            
+            let total_lp_amount = lp_balance
+                + farm_positions.iter().map(|p| p.lp_amount).sum::<f64>()
+                + metastaking_positions.iter().map(|p| p.lp_amount).sum::<f64>();
+            let pending_rewards = farm_positions.iter().map(|p| p.pending_rewards).sum::<f64>()
+                + metastaking_positions.iter().map(|p| p.pending_rewards).sum::<f64>();
+            
+            positions.push(LpFarmPosition {
+                pool_id: pool.id.clone(),
+                lp_token: pool.lp_token.clone(),
+                lp_balance,
+                farm_positions,
+                metastaking_positions,
+                total_lp_amount,
+                pending_rewards,
+                total_value: total_lp_amount * pool.lp_token_value,
+            });
+        }
+        
+        Ok(positions)
+    }
+    
+    // Helper method to build the claim call for a farm or metastaking token held by a user
+    async fn build_claim_call_for_token(&self, 
+                                        user_address: &str, 
+                                        token_identifier: &str) -> AppResult<Option<ContractCall>> {
+        // Platform position IDs are UUIDs, not token identifiers
+        if uuid::Uuid::parse_str(token_identifier).is_ok() {
+            return Ok(None);
+        }
+        
+        let tokens = self.blockchain_client.get_esdt_tokens(user_address).await?;
+        let Some(token) = tokens.iter().find(|t| t.nonce > 0 && t.token_identifier == token_identifier) else {
+            return Ok(None);
+        };
+        let amount = BigUint::parse_bytes(token.balance.as_bytes(), 10).unwrap_or_default();
+        
+        let farms = self.farms.read().unwrap().clone();
+        for (pool_id, farm_address) in &farms {
+            if self.get_token_id(farm_address, "getFarmTokenId").await? == token.collection() {
+                let call = self.build_claim_farm_rewards_call(user_address, pool_id, token.nonce, &amount).await?;
+                return Ok(Some(call));
+            }
+        }
+        
+        let metastaking = self.metastaking.read().unwrap().clone();
+        for (pool_id, metastaking_address) in &metastaking {
+            if self.get_token_id(metastaking_address, "getDualYieldTokenId").await? == token.collection() {
+                let call = self.build_claim_metastaking_call(user_address, pool_id, token.nonce, &amount).await?;
+                return Ok(Some(call));
+            }
+        }
+        
+        Ok(None)
+    }
+    
+    // Helper method to build a call paying a farm token back to its farm
+    async fn build_farm_token_call(&self, 
+                                   user_address: &str, 
+                                   pool_id: &str, 
+                                   nonce: u64, 
+                                   amount: &BigUint, 
+                                   function: &str) -> AppResult<ContractCall> {
+        let farm_address = self.get_farm_address(pool_id)?;
+        let farm_token = self.get_token_id(&farm_address, "getFarmTokenId").await?;
+        
+        Ok(ContractCall {
+            receiver: user_address.to_string(),
+            data: esdt_nft_transfer_data(&farm_token, nonce, amount, &farm_address, function)?,
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation(if function == "claimRewards" { "claimFarmRewards" } else { function }),
+        })
+    }
+    
+    // Helper method to build a call paying a dual yield token back to its metastaking contract
+    async fn build_dual_yield_call(&self, 
+                                   user_address: &str, 
+                                   pool_id: &str, 
+                                   nonce: u64, 
+                                   amount: &BigUint, 
+                                   function: &str) -> AppResult<ContractCall> {
+        let metastaking_address = self.get_metastaking_address(pool_id)?;
+        let dual_yield_token = self.get_token_id(&metastaking_address, "getDualYieldTokenId").await?;
+        
+        Ok(ContractCall {
+            receiver: user_address.to_string(),
+            data: esdt_nft_transfer_data(&dual_yield_token, nonce, amount, &metastaking_address, function)?,
+            value: "0".to_string(),
+            gas_limit: self.get_gas_limit_for_operation(function),
+        })
+    }
+    
+    // Rewards accrued by a farm position, computed by the farm itself
+    async fn query_pending_rewards(&self, 
+                                   farm_address: &str, 
+                                   amount: &BigUint, 
+                                   attributes: &[u8]) -> AppResult<BigUint> {
+        let results = self.blockchain_client
+            .query_vm(
+                farm_address,
+                "calculateRewardsForGivenPosition",
+                vec![encode_biguint(amount), hex::encode(attributes)],
+            )
+            .await?;
+        
+        Ok(results.first().map(|v| BigUint::from_bytes_be(v)).unwrap_or_default())
+    }
+    
+    // Token identifier returned by a contract view, cached since it never changes
+    async fn get_token_id(&self, contract_address: &str, function: &str) -> AppResult<String> {
+        let key = format!("{}:{}", contract_address, function);
+        if let Some(token_id) = self.token_ids.read().unwrap().get(&key) {
+            return Ok(token_id.clone());
+        }
+        
+        let results = self.blockchain_client.query_vm(contract_address, function, Vec::new()).await?;
+        let token_id = decode_string(&results)?;
+        
+        let mut token_ids = self.token_ids.write().unwrap();
+        token_ids.insert(key, token_id.clone());
+        Ok(token_id)
+    }
+    
+    fn get_farm_address(&self, pool_id: &str) -> AppResult<String> {
+        let farms = self.farms.read().unwrap();
+        farms.get(pool_id).cloned().ok_or_else(|| 
+            AppError::NotFound(format!("No farm configured for pool {}", pool_id))
+        )
+    }
+    
+    fn get_metastaking_address(&self, pool_id: &str) -> AppResult<String> {
+        let metastaking = self.metastaking.read().unwrap();
+        metastaking.get(pool_id).cloned().ok_or_else(|| 
+            AppError::NotFound(format!("No metastaking configured for pool {}", pool_id))
+        )
+    }
+    
+    // USD value of each token's reserve in its deepest pair
+    fn token_liquidity(pairs: &[PairState], prices: &HashMap<String, f64>) -> HashMap<String, f64> {
+        let mut liquidity: HashMap<String, f64> = HashMap::new();
+        for pair in pairs {
+            for (token_id, reserve) in [(&pair.first_token_id, pair.first_reserve), (&pair.second_token_id, pair.second_reserve)] {
+                if let Some(price) = prices.get(token_id) {
+                    let depth = liquidity.entry(token_id.clone()).or_insert(0.0);
+                    *depth = depth.max(reserve * price);
+                }
+            }
+        }
+        liquidity
+    }
+    
+    // Derive USD prices from pair reserves, anchored on USDC and the WEGLD-USDC pair
+    fn derive_token_prices(pairs: &[PairState]) -> HashMap<String, f64> {
+        let mut prices = HashMap::new();
+        prices.insert(USDC_TOKEN_ID.to_string(), 1.0);
+        
+        if let Some(egld_price) = pairs.iter().find_map(|p| {
+            match (p.first_token_id.as_str(), p.second_token_id.as_str()) {
+                (WEGLD_TOKEN_ID, USDC_TOKEN_ID) if p.first_reserve > 0.0 => Some(p.second_reserve / p.first_reserve),
+                (USDC_TOKEN_ID, WEGLD_TOKEN_ID) if p.second_reserve > 0.0 => Some(p.first_reserve / p.second_reserve),
+                _ => None,
+            }
+        }) {
+            prices.insert(WEGLD_TOKEN_ID.to_string(), egld_price);
+        } else {
+            error!("WEGLD-USDC pair not found, pool TVLs will only count USDC");
+        }
+        
+        // Price the remaining tokens through their pair with an already priced token,
+        // preferring WEGLD and USDC pairs since they are processed first
+        for _ in 0..2 {
+            for pair in pairs {
+                let first_price = prices.get(&pair.first_token_id).copied();
+                let second_price = prices.get(&pair.second_token_id).copied();
+                
+                match (first_price, second_price) {
+                    (Some(price), None) if pair.second_reserve > 0.0 => {
+                        prices.insert(pair.second_token_id.clone(), pair.first_reserve * price / pair.second_reserve);
+                    },
+                    (None, Some(price)) if pair.first_reserve > 0.0 => {
+                        prices.insert(pair.first_token_id.clone(), pair.second_reserve * price / pair.first_reserve);
+                    },
+                    _ => {},
+                }
+            }
+        }
+        
+        prices
+    }
+}
+
+// Pool ID of a pair, from its tickers
+fn pool_id(pair: &PairState) -> String {
+    format!("{}-{}", display_ticker(&pair.first_token_id), display_ticker(&pair.second_token_id)).to_lowercase()
+}
+
+// Ticker shown to users; wrapped EGLD is shown as EGLD
+fn display_ticker(token_id: &str) -> String {
+    let ticker = token_id.split('-').next().unwrap_or(token_id);
+    if ticker == "WEGLD" {
+        "EGLD".to_string()
+    } else {
+        ticker.to_string()
+    }
+}
+
+fn is_stable(token_id: &str) -> bool {
+    matches!(token_id.split('-').next(), Some("USDC") | Some("USDT"))
+}
+
+// Decimals of the tokens that don't use the default 18
+fn token_decimals(token_id: &str) -> u32 {
+    match token_id.split('-').next() {
+        Some("USDC") | Some("USDT") => 6,
+        Some("WBTC") => 8,
+        _ => 18,
+    }
+}
+
+fn decode_string(results: &[Vec<u8>]) -> AppResult<String> {
+    let value = results.first()
+        .ok_or_else(|| AppError::Blockchain("Empty query result".to_string()))?;
+    String::from_utf8(value.clone())
+        .map_err(|e| AppError::Blockchain(format!("Invalid token identifier: {}", e)))
+}
+
+fn decode_amount(value: &[u8], decimals: u32) -> f64 {
+    let amount = BigUint::from_bytes_be(value).to_f64().unwrap_or(0.0);
+    amount / 10f64.powi(decimals as i32)
 }
 
 #[async_trait]
@@ -115,16 +895,25 @@ impl ProtocolInterface for MaiarExchangeProtocol {
     }
     
     async fn get_tvl(&self) -> AppResult<f64> {
-        // Sum TVL across all pools
-        let total_tvl = self.pools.values().map(|p| p.tvl).sum();
-        Ok(total_tvl)
+        // Sum TVL across the pools read from the chain
+        let tvls: Vec<f64> = self.get_all_pools().await.iter().filter_map(|p| p.tvl).collect();
+        if tvls.is_empty() {
+            return Err(AppError::NotFound(format!("{} TVL has not been read yet", self.name)));
+        }
+        Ok(tvls.iter().sum())
     }
     
     async fn get_apy(&self) -> AppResult<f64> {
-        // Calculate weighted average APY
-        let total_tvl = self.pools.values().map(|p| p.tvl).sum::<f64>();
-        let weighted_apy = self.pools.values()
-            .map(|p| p.apy * (p.tvl / total_tvl))
+        // Calculate the TVL weighted average APY of the pools whose APY has been measured
+        let measured: Vec<(f64, f64)> = self.get_all_pools().await.iter()
+            .filter_map(|p| Some((p.apy?, p.tvl?)))
+            .collect();
+        let total_tvl = measured.iter().map(|(_, tvl)| tvl).sum::<f64>();
+        if total_tvl <= 0.0 {
+            return Err(AppError::NotFound(format!("{} APY has not been measured yet", self.name)));
+        }
+        let weighted_apy = measured.iter()
+            .map(|(apy, tvl)| apy * (tvl / total_tvl))
             .sum::<f64>();
         
         Ok(weighted_apy)
@@ -133,27 +922,91 @@ impl ProtocolInterface for MaiarExchangeProtocol {
     async fn get_supported_tokens(&self) -> AppResult<Vec<String>> {
         // Collect unique tokens from all pools
         let mut tokens = std::collections::HashSet::new();
-        for pool in self.pools.values() {
-            for token in &pool.tokens {
-                tokens.insert(token.clone());
+        for pool in self.get_all_pools().await {
+            for token in pool.tokens {
+                tokens.insert(token);
             }
         }
         
         Ok(tokens.into_iter().collect())
     }
     
+    async fn refresh_state(&self) -> AppResult<()> {
+        let is_stale = {
+            let last_refresh = self.last_refresh.read().unwrap();
+            last_refresh.map_or(true, |t| (Utc::now() - t).num_seconds() >= REFRESH_INTERVAL_SECONDS)
+        };
+        
+        if is_stale {
+            self.update_pool_data().await?;
+            let mut last_refresh = self.last_refresh.write().unwrap();
+            *last_refresh = Some(Utc::now());
+        }
+        
+        Ok(())
+    }
+    
+    async fn get_contract_addresses(&self) -> AppResult<Vec<String>> {
+        // Pairs, and the farm and metastaking contracts users stake their LP tokens in
+        let mut addresses = vec![self.contract_address.clone()];
+        addresses.extend(self.get_all_pools().await.into_iter().map(|pool| pool.pair_address));
+        addresses.extend(self.farms.read().unwrap().values().cloned());
+        addresses.extend(self.metastaking.read().unwrap().values().cloned());
+        addresses.sort();
+        addresses.dedup();
+        Ok(addresses)
+    }
+    
+    async fn get_token_prices(&self) -> AppResult<HashMap<String, f64>> {
+        let prices = self.token_prices.read().unwrap();
+        if prices.is_empty() {
+            return Err(AppError::Blockchain("No token prices could be derived from the pairs yet".to_string()));
+        }
+        Ok(prices.clone())
+    }
+    
+    async fn get_swap_pools(&self) -> AppResult<Vec<SwapPool>> {
+        // Seeded pools have no reserves until the first refresh, so they can't be quoted
+        Ok(self.get_all_pools().await.into_iter()
+            .filter(|p| p.reserves.len() == 2 && p.reserves.iter().all(|r| *r > 0.0))
+            .map(|p| SwapPool {
+                protocol_id: self.id.clone(),
+                decimals: p.token_ids.iter().map(|t| token_decimals(t)).collect(),
+                pool_id: p.id,
+                contract_address: p.pair_address,
+                token_ids: p.token_ids,
+                reserves: p.reserves,
+                fee: p.fee,
+                curve: SwapCurve::ConstantProduct,
+            })
+            .collect())
+    }
+    
     async fn get_pools(&self) -> AppResult<Vec<String>> {
-        Ok(self.pools.keys().cloned().collect())
+        Ok(self.get_all_pools().await.into_iter().map(|p| p.id).collect())
     }
     
     async fn get_pool_apy(&self, pool_id: &str) -> AppResult<f64> {
-        let pool = self.get_pool(pool_id)?;
-        Ok(pool.apy)
+        let pool = self.get_pool(pool_id).await?;
+        pool.apy.ok_or_else(|| AppError::NotFound(format!("APY of pool {} has not been measured yet", pool_id)))
     }
     
     async fn get_pool_tvl(&self, pool_id: &str) -> AppResult<f64> {
-        let pool = self.get_pool(pool_id)?;
-        Ok(pool.tvl)
+        let pool = self.get_pool(pool_id).await?;
+        pool.tvl.ok_or_else(|| AppError::NotFound(format!("TVL of pool {} has not been read yet", pool_id)))
+    }
+    
+    async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo> {
+        let pool = self.get_pool(pool_id).await?;
+        Ok(PoolInfo {
+            id: pool.id,
+            name: pool.name,
+            tokens: pool.tokens,
+            lp_token: Some(pool.lp_token),
+            fee_tier: Some(pool.fee),
+            contract_address: pool.pair_address,
+            reward_tokens: pool.reward_tokens,
+        })
     }
     
     async fn deposit(&self, 
@@ -166,7 +1019,7 @@ impl ProtocolInterface for MaiarExchangeProtocol {
         // to deposit funds into the protocol's smart contract
         
         // Encode the smart contract call
-        let data = self.encode_sc_call("deposit", vec![&format!("{}", (amount * 1e18) as u64)]);
+        let data = self.build_deposit_data(amount, token).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -185,7 +1038,7 @@ impl ProtocolInterface for MaiarExchangeProtocol {
         // to withdraw funds from the protocol's smart contract
         
         // Encode the smart contract call
-        let data = self.encode_sc_call("withdraw", vec![&format!("{}", (amount * 1e18) as u64)]);
+        let data = self.build_withdraw_data(amount, token).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -200,17 +1053,8 @@ impl ProtocolInterface for MaiarExchangeProtocol {
         // In a real implementation, this would create and submit a blockchain transaction
         // to rebalance the position according to the strategy
         
-        // Get optimal allocation based on strategy
-        let optimal_allocation = self.get_optimal_allocation(&position.strategy).await?;
-        
-        // Encode the smart contract call
-        let mut args = Vec::new();
-        for (token, percentage) in optimal_allocation {
-            args.push(token.as_str());
-            args.push(&format!("{}", (percentage * 100.0) as u64));
-        }
-        
-        let data = self.encode_sc_call("rebalance", args);
+        // Encode the smart contract call for the optimal allocation
+        let data = self.build_rebalance_data(position).await?;
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -227,8 +1071,11 @@ impl ProtocolInterface for MaiarExchangeProtocol {
         // In a real implementation, this would create and submit a blockchain transaction
         // to claim rewards from the protocol's smart contract
         
-        // Encode the smart contract call
-        let data = self.encode_sc_call("claimRewards", vec![position_id]);
+        // Farm and metastaking positions are claimed by their token identifier
+        let data = match self.build_claim_call_for_token(user_address, position_id).await? {
+            Some(call) => call.data,
+            None => self.build_claim_rewards_data(position_id).await?,
+        };
         
         // Simulate a transaction hash
         let tx_hash = format!("tx_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
@@ -237,17 +1084,47 @@ impl ProtocolInterface for MaiarExchangeProtocol {
         Ok(tx_hash)
     }
     
+    async fn build_deposit_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        // EGLD is sent as value, ESDTs are paid to the endpoint
+        if token == "EGLD" {
+            Ok(self.encode_sc_call("deposit", Vec::new()))
+        } else {
+            Ok(esdt_transfer_data(token, &to_raw_amount(amount, token_decimals(token))?, "deposit"))
+        }
+    }
+    
+    async fn build_withdraw_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        let raw_amount = to_raw_amount(amount, token_decimals(token))?;
+        Ok(self.encode_sc_call("withdraw", vec![&encode_biguint(&raw_amount)]))
+    }
+    
+    async fn build_rebalance_data(&self, position: &Position) -> AppResult<String> {
+        // Get optimal allocation based on strategy
+        let optimal_allocation = self.get_optimal_allocation(&position.strategy).await?;
+        
+        let mut args = Vec::new();
+        for (token, percentage) in optimal_allocation {
+            args.push(token);
+            args.push(format!("{}", (percentage * 100.0) as u64));
+        }
+        
+        Ok(self.encode_sc_call("rebalance", args.iter().map(|a| a.as_str()).collect()))
+    }
+    
+    async fn build_claim_rewards_data(&self, position_id: &str) -> AppResult<String> {
+        Ok(self.encode_sc_call("claimRewards", vec![position_id]))
+    }
+    
     async fn get_position_value(&self, position_id: &str) -> AppResult<f64> {
         debug!("Getting value for position {}", position_id);
         
-        // In a real implementation, this would query the blockchain for the current value
-        // of the position in the protocol's smart contract
-        
-        // For now, we'll just return a simulated value
-        let base_value = 1000.0; // Example base value
-        let random_factor = 1.0 + (rand::random::<f64>() - 0.5) * 0.02; // -1% to +1%
+        // For an address, value the LP tokens it holds in its wallet, farms and metastaking
+        if !position_id.starts_with("erd1") {
+            return Err(AppError::Validation("Maiar Exchange positions are valued by user address".to_string()));
+        }
         
-        Ok(base_value * random_factor)
+        let positions = self.get_lp_farm_positions(position_id).await?;
+        Ok(positions.iter().map(|p| p.total_value).sum())
     }
     
     async fn get_optimal_allocation(&self, 
@@ -282,7 +1159,8 @@ impl ProtocolInterface for MaiarExchangeProtocol {
     
     fn is_token_supported(&self, token: &str) -> bool {
         // Check if any pool supports this token
-        self.pools.values().any(|p| p.tokens.contains(&token.to_string()))
+        let pools = self.pools.read().unwrap();
+        pools.values().any(|p| p.tokens.contains(&token.to_string()))
     }
     
     fn get_gas_limit_for_operation(&self, operation: &str) -> u64 {
@@ -291,6 +1169,12 @@ impl ProtocolInterface for MaiarExchangeProtocol {
             "withdraw" => 500_000,
             "rebalance" => 1_000_000,
             "claimRewards" => 300_000,
+            "enterFarm" => 30_000_000,
+            "exitFarm" => 30_000_000,
+            "claimFarmRewards" => 25_000_000,
+            "stakeFarmTokens" => 50_000_000,
+            "unstakeFarmTokens" => 70_000_000,
+            "claimDualYield" => 60_000_000,
             _ => 500_000, // Default
         }
     }
@@ -298,4 +1182,61 @@ impl ProtocolInterface for MaiarExchangeProtocol {
     fn get_risk_level(&self) -> &str {
         "Medium" // Overall protocol risk level
     }
+    
+    async fn get_pending_rewards(&self, 
+                                user_address: &str, 
+                                position: &Position) -> AppResult<Vec<PendingReward>> {
+        let pools = self.get_all_pools().await;
+        
+        // Farm and metastaking rewards of the pools made of the position's tokens
+        let mut rewards: HashMap<String, f64> = HashMap::new();
+        for lp_position in self.get_lp_farm_positions(user_address).await? {
+            let in_position = pools.iter().any(|p| {
+                p.id == lp_position.pool_id && p.tokens.iter().all(|t| position.tokens.contains(t))
+            });
+            if !in_position {
+                continue;
+            }
+            
+            let farm_rewards = lp_position.farm_positions.iter().map(|p| (&p.reward_token, p.pending_rewards));
+            let metastaking_rewards = lp_position.metastaking_positions.iter().map(|p| (&p.reward_token, p.pending_rewards));
+            for (token, amount) in farm_rewards.chain(metastaking_rewards) {
+                *rewards.entry(token.clone()).or_insert(0.0) += amount;
+            }
+        }
+        
+        Ok(rewards.into_iter()
+            .filter(|(_, amount)| *amount > 0.0)
+            .map(|(token, amount)| PendingReward { token, amount })
+            .collect())
+    }
+    
+    async fn build_claim_rewards_calls(&self, 
+                                      user_address: &str, 
+                                      position: &Position) -> AppResult<Vec<ContractCall>> {
+        let pools = self.get_all_pools().await;
+        let raw_amount = |amount: &str| amount.parse::<BigUint>().map_err(|e|
+            AppError::Blockchain(format!("Invalid token balance {}: {}", amount, e))
+        );
+        
+        // Claim each farm and metastaking token with rewards, by nonce, in the pools made of the position's tokens
+        let mut calls = Vec::new();
+        for lp_position in self.get_lp_farm_positions(user_address).await? {
+            let in_position = pools.iter().any(|p| {
+                p.id == lp_position.pool_id && p.tokens.iter().all(|t| position.tokens.contains(t))
+            });
+            if !in_position {
+                continue;
+            }
+            
+            for farm in lp_position.farm_positions.iter().filter(|p| p.pending_rewards > 0.0) {
+                calls.push(self.build_claim_farm_rewards_call(user_address, &farm.pool_id, farm.nonce, &raw_amount(&farm.amount)?).await?);
+            }
+            for metastaking in lp_position.metastaking_positions.iter().filter(|p| p.pending_rewards > 0.0) {
+                calls.push(self.build_claim_metastaking_call(user_address, &metastaking.pool_id, metastaking.nonce, &raw_amount(&metastaking.amount)?).await?);
+            }
+        }
+        
+        Ok(calls)
+    }
 }
\ No newline at end of file
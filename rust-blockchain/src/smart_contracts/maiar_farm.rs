@@ -0,0 +1,223 @@
+use crate::error::{AppError, AppResult};
+use crate::utils::{address_to_public_key, public_key_to_address};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Farm and metastaking positions on xExchange are NFTs whose attributes carry the
+// position state, nested encoded the way the MultiversX VM serializes structs
+
+/// Attributes of a farm token, minted when LP tokens enter a farm
+#[derive(Debug, Clone)]
+pub struct FarmTokenAttributes {
+    /// Farm reward per share at the time the position was last updated
+    pub reward_per_share: BigUint,
+    pub entering_epoch: u64,
+    pub compounded_reward: BigUint,
+    pub current_farm_amount: BigUint,
+    pub original_owner: String,
+}
+
+impl FarmTokenAttributes {
+    pub fn decode(bytes: &[u8]) -> AppResult<Self> {
+        let mut decoder = NestedDecoder::new(bytes);
+        Ok(Self {
+            reward_per_share: decoder.read_biguint()?,
+            entering_epoch: decoder.read_u64()?,
+            compounded_reward: decoder.read_biguint()?,
+            current_farm_amount: decoder.read_biguint()?,
+            original_owner: decoder.read_address()?,
+        })
+    }
+}
+
+/// Attributes of a metastaking (dual yield) token, which wraps a farm token
+#[derive(Debug, Clone)]
+pub struct DualYieldTokenAttributes {
+    pub lp_farm_token_nonce: u64,
+    pub lp_farm_token_amount: BigUint,
+    pub virtual_pos_token_nonce: u64,
+    pub virtual_pos_token_amount: BigUint,
+}
+
+impl DualYieldTokenAttributes {
+    pub fn decode(bytes: &[u8]) -> AppResult<Self> {
+        let mut decoder = NestedDecoder::new(bytes);
+        Ok(Self {
+            lp_farm_token_nonce: decoder.read_u64()?,
+            lp_farm_token_amount: decoder.read_biguint()?,
+            virtual_pos_token_nonce: decoder.read_u64()?,
+            virtual_pos_token_amount: decoder.read_biguint()?,
+        })
+    }
+}
+
+/// A farm token held by a user
+#[derive(Debug, Clone, Serialize)]
+pub struct FarmPosition {
+    pub pool_id: String,
+    pub farm_address: String,
+    /// Farm token identifier, including the nonce
+    pub token_identifier: String,
+    pub nonce: u64,
+    /// Raw farm token balance
+    pub amount: String,
+    /// LP tokens represented by the farm tokens, which are minted one to one
+    pub lp_amount: f64,
+    pub entering_epoch: u64,
+    pub reward_token: String,
+    pub pending_rewards: f64,
+    /// USD value of the underlying LP tokens
+    pub value: f64,
+}
+
+/// A metastaking token held by a user
+#[derive(Debug, Clone, Serialize)]
+pub struct MetastakingPosition {
+    pub pool_id: String,
+    pub metastaking_address: String,
+    /// Dual yield token identifier, including the nonce
+    pub token_identifier: String,
+    pub nonce: u64,
+    /// Raw dual yield token balance
+    pub amount: String,
+    /// LP tokens represented by the wrapped farm tokens
+    pub lp_amount: f64,
+    pub reward_token: String,
+    /// Pending rewards of the wrapped LP farm position
+    pub pending_rewards: f64,
+    /// USD value of the underlying LP tokens
+    pub value: f64,
+}
+
+/// Everything a user holds in a pool: LP tokens plus the farm and metastaking tokens built on them
+#[derive(Debug, Clone, Serialize)]
+pub struct LpFarmPosition {
+    pub pool_id: String,
+    pub lp_token: String,
+    /// LP tokens held directly in the wallet
+    pub lp_balance: f64,
+    pub farm_positions: Vec<FarmPosition>,
+    pub metastaking_positions: Vec<MetastakingPosition>,
+    /// Total LP tokens across the wallet, farm and metastaking
+    pub total_lp_amount: f64,
+    pub pending_rewards: f64,
+    /// USD value of all LP tokens, excluding pending rewards
+    pub total_value: f64,
+}
+
+/// Farm and metastaking contracts to follow; farms are matched to their pool by the LP token
+/// they accept, metastaking contracts by the farm whose tokens they accept
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MaiarFarmConfig {
+    #[serde(default)]
+    pub farms: Vec<String>,
+    #[serde(default)]
+    pub metastaking: Vec<String>,
+}
+
+impl MaiarFarmConfig {
+    /// Load the configuration from a JSON file
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+// Encode a call that pays a fungible token to a contract
+pub(crate) fn esdt_transfer_data(token: &str, amount: &BigUint, function: &str) -> String {
+    format!("ESDTTransfer@{}@{}@{}", hex::encode(token), encode_biguint(amount), hex::encode(function))
+}
+
+// Encode a call that pays an NFT or SFT to a contract, sent by the owner to itself
+pub(crate) fn esdt_nft_transfer_data(collection: &str,
+                                     nonce: u64,
+                                     amount: &BigUint,
+                                     destination: &str,
+                                     function: &str) -> AppResult<String> {
+    Ok(format!(
+        "ESDTNFTTransfer@{}@{}@{}@{}@{}",
+        hex::encode(collection),
+        encode_biguint(&BigUint::from(nonce)),
+        encode_biguint(amount),
+        hex::encode(address_to_public_key(destination)?),
+        hex::encode(function),
+    ))
+}
+
+// Hex encode a number as a VM argument, with an even number of digits
+pub(crate) fn encode_biguint(value: &BigUint) -> String {
+    let hex = value.to_str_radix(16);
+    if hex.len() % 2 == 1 {
+        format!("0{}", hex)
+    } else {
+        hex
+    }
+}
+
+// Convert a token amount to its raw denomination
+pub(crate) fn to_raw_amount(amount: f64, decimals: u32) -> AppResult<BigUint> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err(AppError::Validation(format!("Invalid amount: {}", amount)));
+    }
+    Ok(BigUint::from((amount * 10f64.powi(decimals as i32)) as u128))
+}
+
+// Convert a raw denomination to a token amount
+pub(crate) fn from_raw_amount(amount: &BigUint, decimals: u32) -> f64 {
+    amount.to_f64().unwrap_or(0.0) / 10f64.powi(decimals as i32)
+}
+
+// Decode the first result of a view as an 18 decimal token amount, zero when the view returned nothing
+pub(crate) fn first_amount(results: &[Vec<u8>]) -> f64 {
+    results.first()
+        .map(|v| from_raw_amount(&BigUint::from_bytes_be(v), 18))
+        .unwrap_or(0.0)
+}
+
+// Decode a big endian unsigned integer of up to eight bytes
+pub(crate) fn decode_u64(value: &[u8]) -> u64 {
+    value.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+pub(crate) struct NestedDecoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> NestedDecoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+    
+    fn read_bytes(&mut self, length: usize) -> AppResult<&'a [u8]> {
+        let end = self.position + length;
+        if end > self.bytes.len() {
+            return Err(AppError::Blockchain("Token attributes are truncated".to_string()));
+        }
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+    
+    fn read_u32(&mut self) -> AppResult<u32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    
+    pub(crate) fn read_u64(&mut self) -> AppResult<u64> {
+        let bytes = self.read_bytes(8)?;
+        Ok(decode_u64(bytes))
+    }
+    
+    // Big numbers are length prefixed
+    pub(crate) fn read_biguint(&mut self) -> AppResult<BigUint> {
+        let length = self.read_u32()? as usize;
+        Ok(BigUint::from_bytes_be(self.read_bytes(length)?))
+    }
+    
+    pub(crate) fn read_address(&mut self) -> AppResult<String> {
+        public_key_to_address(self.read_bytes(32)?)
+    }
+}
\ No newline at end of file
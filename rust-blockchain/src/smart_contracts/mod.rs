@@ -1,55 +1,210 @@
 pub mod protocol_interface;
 pub mod maiar_exchange;
+pub mod maiar_farm;
 pub mod hatom_protocol;
 pub mod ashswap;
+pub mod stableswap;
+pub mod delegation;
+pub mod liquid_staking;
+pub mod definition;
+pub mod monitored;
+pub mod scan;
+pub mod router;
+pub mod risk;
 
 use crate::blockchain::MultiversXClient;
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
 use crate::smart_contracts::protocol_interface::ProtocolInterface;
 use crate::smart_contracts::maiar_exchange::MaiarExchangeProtocol;
 use crate::smart_contracts::hatom_protocol::HatomProtocol;
 use crate::smart_contracts::ashswap::AshSwapProtocol;
+use crate::smart_contracts::delegation::DelegationProtocol;
+use crate::smart_contracts::liquid_staking::{LiquidStakingConfig, LiquidStakingProtocol};
+use crate::smart_contracts::definition::{DefinedProtocol, ProtocolDefinition};
+use crate::smart_contracts::monitored::{MonitoredProtocol, ProtocolHealth};
+use crate::smart_contracts::risk::RiskScore;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
+use tracing::{info, debug, warn};
 
+/// Registry of the protocols, shared by every service; cloning it gives another handle to the same protocols
+#[derive(Clone)]
 pub struct ProtocolRegistry {
-    protocols: HashMap<String, Arc<dyn ProtocolInterface + Send + Sync>>,
+    protocols: Arc<RwLock<HashMap<String, Arc<MonitoredProtocol>>>>,
+    // xExchange is also reached directly for its farm and metastaking operations
+    maiar_exchange: Arc<MaiarExchangeProtocol>,
+    // Hatom is also reached directly for its borrow and collateral operations
+    hatom: Arc<HatomProtocol>,
+    // Delegation is also reached directly to withdraw unbonded stake and redelegate rewards
+    delegation: Arc<DelegationProtocol>,
+    // Notified whenever a protocol is registered
+    changes: Arc<watch::Sender<()>>,
 }
 
 impl ProtocolRegistry {
     pub fn new(blockchain_client: MultiversXClient) -> Self {
-        let mut registry = Self {
-            protocols: HashMap::new(),
+        let registry = Self {
+            protocols: Arc::new(RwLock::new(HashMap::new())),
+            maiar_exchange: Arc::new(MaiarExchangeProtocol::new(blockchain_client.clone())),
+            hatom: Arc::new(HatomProtocol::new(blockchain_client.clone())),
+            delegation: Arc::new(DelegationProtocol::new(blockchain_client.clone())),
+            changes: Arc::new(watch::channel(()).0),
         };
         
         // Register protocols
-        registry.register_protocol(Arc::new(MaiarExchangeProtocol::new(blockchain_client.clone())));
-        registry.register_protocol(Arc::new(HatomProtocol::new(blockchain_client.clone())));
+        registry.register_protocol(registry.maiar_exchange.clone());
+        registry.register_protocol(registry.hatom.clone());
         registry.register_protocol(Arc::new(AshSwapProtocol::new(blockchain_client.clone())));
+        registry.register_protocol(registry.delegation.clone());
+        registry.register_protocol(Arc::new(LiquidStakingProtocol::new(LiquidStakingConfig::hatom(), blockchain_client.clone())));
+        registry.register_protocol(Arc::new(LiquidStakingProtocol::new(LiquidStakingConfig::salsa(), blockchain_client.clone())));
         
         registry
     }
     
-    pub fn register_protocol(&mut self, protocol: Arc<dyn ProtocolInterface + Send + Sync>) {
-        self.protocols.insert(protocol.get_id().to_string(), protocol);
+    /// Register the protocols defined by the .toml and .json files in a directory and its subdirectories; invalid
+    /// definitions are skipped so a bad file can't keep the others from loading
+    pub fn load_definitions(&self, dir: &Path, blockchain_client: MultiversXClient) -> AppResult<usize> {
+        if !dir.is_dir() {
+            debug!("No protocol definitions directory at {}", dir.display());
+            return Ok(0);
+        }
+        
+        let mut paths = Vec::new();
+        collect_definition_paths(dir, &mut paths)?;
+        paths.sort();
+        
+        let mut loaded = 0;
+        for path in paths {
+            let definition = match ProtocolDefinition::load(&path) {
+                Ok(definition) => definition,
+                Err(e) => {
+                    warn!("Skipping protocol definition {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            
+            if self.protocols.read().unwrap().contains_key(&definition.id) {
+                warn!("Skipping protocol definition {}: protocol {} is already registered", path.display(), definition.id);
+                continue;
+            }
+            
+            info!("Loaded protocol {} from {}", definition.id, path.display());
+            self.register_protocol(Arc::new(DefinedProtocol::new(definition, blockchain_client.clone())));
+            loaded += 1;
+        }
+        
+        Ok(loaded)
+    }
+    
+    pub fn register_protocol(&self, protocol: Arc<dyn ProtocolInterface + Send + Sync>) {
+        let mut protocols = self.protocols.write().unwrap();
+        protocols.insert(protocol.get_id().to_string(), Arc::new(MonitoredProtocol::new(protocol)));
+        self.changes.send_replace(());
+    }
+    
+    /// Subscribe to protocol registrations
+    pub fn subscribe_changes(&self) -> watch::Receiver<()> {
+        self.changes.subscribe()
+    }
+    
+    pub fn maiar_exchange(&self) -> Arc<MaiarExchangeProtocol> {
+        self.maiar_exchange.clone()
+    }
+    
+    pub fn hatom(&self) -> Arc<HatomProtocol> {
+        self.hatom.clone()
+    }
+    
+    pub fn delegation(&self) -> Arc<DelegationProtocol> {
+        self.delegation.clone()
     }
     
     pub fn get_protocol(&self, id: &str) -> Option<Arc<dyn ProtocolInterface + Send + Sync>> {
-        self.protocols.get(id).cloned()
+        let protocols = self.protocols.read().unwrap();
+        protocols.get(id).map(|p| p.clone() as Arc<dyn ProtocolInterface + Send + Sync>)
     }
     
     pub fn get_all_protocols(&self) -> Vec<Arc<dyn ProtocolInterface + Send + Sync>> {
-        self.protocols.values().cloned().collect()
+        let protocols = self.protocols.read().unwrap();
+        protocols.values().map(|p| p.clone() as Arc<dyn ProtocolInterface + Send + Sync>).collect()
+    }
+    
+    /// Stop a protocol from taking new deposits; withdrawals stay open
+    pub fn pause_protocol(&self, id: &str, reason: &str) -> AppResult<()> {
+        self.get_monitored(id)?.pause(reason);
+        warn!("Protocol {} paused for deposits: {}", id, reason);
+        Ok(())
+    }
+    
+    /// Let a paused protocol take deposits again
+    pub fn resume_protocol(&self, id: &str) -> AppResult<()> {
+        self.get_monitored(id)?.resume();
+        info!("Protocol {} resumed", id);
+        Ok(())
+    }
+    
+    pub fn is_paused(&self, id: &str) -> bool {
+        self.get_monitored(id).map(|p| p.is_paused()).unwrap_or(false)
+    }
+    
+    pub fn get_health(&self, id: &str) -> AppResult<ProtocolHealth> {
+        Ok(self.get_monitored(id)?.health())
+    }
+    
+    pub fn get_all_health(&self) -> Vec<ProtocolHealth> {
+        let protocols = self.protocols.read().unwrap();
+        let mut health: Vec<ProtocolHealth> = protocols.values().map(|p| p.health()).collect();
+        health.sort_by(|a, b| a.id.cmp(&b.id));
+        health
+    }
+    
+    /// Share a computed risk score with every user of the protocol
+    pub fn set_risk_score(&self, score: RiskScore) -> AppResult<()> {
+        self.get_monitored(&score.protocol_id)?.set_risk_score(score);
+        Ok(())
+    }
+    
+    pub fn get_risk_score(&self, id: &str) -> AppResult<Option<RiskScore>> {
+        Ok(self.get_monitored(id)?.get_risk_score())
+    }
+    
+    pub fn get_all_risk_scores(&self) -> Vec<RiskScore> {
+        let protocols = self.protocols.read().unwrap();
+        let mut scores: Vec<RiskScore> = protocols.values().filter_map(|p| p.get_risk_score()).collect();
+        scores.sort_by(|a, b| a.protocol_id.cmp(&b.protocol_id));
+        scores
     }
     
     pub async fn get_all_pools(&self) -> AppResult<HashMap<String, Vec<String>>> {
         let mut result = HashMap::new();
         
-        for (id, protocol) in &self.protocols {
+        for protocol in self.get_all_protocols() {
             let pools = protocol.get_pools().await?;
-            result.insert(id.clone(), pools);
+            result.insert(protocol.get_id().to_string(), pools);
         }
         
         Ok(result)
     }
+    
+    fn get_monitored(&self, id: &str) -> AppResult<Arc<MonitoredProtocol>> {
+        let protocols = self.protocols.read().unwrap();
+        protocols.get(id).cloned().ok_or_else(|| AppError::NotFound(format!("Protocol {} not found", id)))
+    }
+}
+
+// Collect the .toml and .json files under a directory, descending into subdirectories
+fn collect_definition_paths(dir: &Path, paths: &mut Vec<std::path::PathBuf>) -> AppResult<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_definition_paths(&path, paths)?;
+        } else if matches!(path.extension().and_then(|e| e.to_str()), Some("toml") | Some("json")) {
+            paths.push(path);
+        }
+    }
+    
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,361 @@
+use crate::error::{AppError, AppResult};
+use crate::models::position::{Position, PositionStrategy};
+use crate::smart_contracts::protocol_interface::{
+    AccountHealth, ContractCall, LiquidStakingToken, PendingReward, PoolInfo, ProtocolInterface,
+    SwapPool, UnbondingEntry,
+};
+use crate::smart_contracts::risk::RiskScore;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+// Every protocol in the registry is wrapped so that failures of its calls are tracked
+// and deposits can be stopped at runtime without touching the implementation
+
+// Number of recent calls the error rate is computed over
+const HEALTH_WINDOW: usize = 50;
+// Error rate from which a protocol is reported as degraded
+const DEGRADED_ERROR_RATE: f64 = 0.2;
+// Consecutive failures after which a protocol is reported as offline
+const OFFLINE_FAILURES: u32 = 5;
+
+/// Health status of a protocol
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum HealthStatus {
+    Online,
+    Degraded,
+    Offline,
+    Paused,
+}
+
+/// Health of a protocol, derived from its recent calls
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtocolHealth {
+    pub id: String,
+    pub name: String,
+    pub status: HealthStatus,
+    /// Whether new deposits are refused
+    pub paused: bool,
+    pub pause_reason: Option<String>,
+    pub paused_at: Option<DateTime<Utc>>,
+    /// Share of failed calls among the recent ones
+    pub error_rate: f64,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct HealthState {
+    // Outcome of the recent calls, true for a success
+    outcomes: VecDeque<bool>,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    last_error_at: Option<DateTime<Utc>>,
+    last_success_at: Option<DateTime<Utc>>,
+    pause: Option<(String, DateTime<Utc>)>,
+}
+
+/// Protocol wrapper tracking health and refusing deposits while paused
+pub struct MonitoredProtocol {
+    inner: Arc<dyn ProtocolInterface + Send + Sync>,
+    state: Mutex<HealthState>,
+    risk_score: Mutex<Option<RiskScore>>,
+}
+
+impl MonitoredProtocol {
+    pub fn new(inner: Arc<dyn ProtocolInterface + Send + Sync>) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(HealthState::default()),
+            risk_score: Mutex::new(None),
+        }
+    }
+    
+    /// Refuse new deposits until resumed
+    pub fn pause(&self, reason: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.pause = Some((reason.to_string(), Utc::now()));
+    }
+    
+    /// Accept deposits again
+    pub fn resume(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.pause = None;
+    }
+    
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().pause.is_some()
+    }
+    
+    /// Replace the protocol's risk level with a computed score
+    pub fn set_risk_score(&self, score: RiskScore) {
+        *self.risk_score.lock().unwrap() = Some(score);
+    }
+    
+    /// Get the current health of the protocol
+    pub fn health(&self) -> ProtocolHealth {
+        let state = self.state.lock().unwrap();
+        
+        let failures = state.outcomes.iter().filter(|ok| !**ok).count();
+        let error_rate = if state.outcomes.is_empty() {
+            0.0
+        } else {
+            failures as f64 / state.outcomes.len() as f64
+        };
+        
+        let status = if state.pause.is_some() {
+            HealthStatus::Paused
+        } else if state.consecutive_failures >= OFFLINE_FAILURES {
+            HealthStatus::Offline
+        } else if error_rate >= DEGRADED_ERROR_RATE {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Online
+        };
+        
+        ProtocolHealth {
+            id: self.inner.get_id().to_string(),
+            name: self.inner.get_name().to_string(),
+            status,
+            paused: state.pause.is_some(),
+            pause_reason: state.pause.as_ref().map(|(reason, _)| reason.clone()),
+            paused_at: state.pause.as_ref().map(|(_, at)| *at),
+            error_rate,
+            consecutive_failures: state.consecutive_failures,
+            last_error: state.last_error.clone(),
+            last_error_at: state.last_error_at,
+            last_success_at: state.last_success_at,
+        }
+    }
+    
+    // Record the outcome of a call; errors caused by the caller don't count against the protocol
+    fn observe<T>(&self, result: AppResult<T>) -> AppResult<T> {
+        let failed = match &result {
+            Ok(_) => false,
+            Err(AppError::Validation(_)) | Err(AppError::NotFound(_)) | Err(AppError::Auth(_)) => return result,
+            Err(_) => true,
+        };
+        
+        let mut state = self.state.lock().unwrap();
+        if state.outcomes.len() == HEALTH_WINDOW {
+            state.outcomes.pop_front();
+        }
+        state.outcomes.push_back(!failed);
+        
+        if let Err(e) = &result {
+            state.consecutive_failures += 1;
+            state.last_error = Some(e.to_string());
+            state.last_error_at = Some(Utc::now());
+            
+            if state.consecutive_failures == OFFLINE_FAILURES {
+                warn!("Protocol {} is offline after {} consecutive failures: {}",
+                      self.inner.get_id(), OFFLINE_FAILURES, e);
+            }
+        } else {
+            state.consecutive_failures = 0;
+            state.last_success_at = Some(Utc::now());
+        }
+        
+        result
+    }
+    
+    fn check_not_paused(&self) -> AppResult<()> {
+        match &self.state.lock().unwrap().pause {
+            Some((reason, _)) => Err(AppError::Validation(format!(
+                "{} is paused for deposits: {}", self.inner.get_name(), reason
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl ProtocolInterface for MonitoredProtocol {
+    fn get_id(&self) -> &str {
+        self.inner.get_id()
+    }
+    
+    fn get_name(&self) -> &str {
+        self.inner.get_name()
+    }
+    
+    fn get_contract_address(&self) -> &str {
+        self.inner.get_contract_address()
+    }
+    
+    async fn get_tvl(&self) -> AppResult<f64> {
+        self.observe(self.inner.get_tvl().await)
+    }
+    
+    async fn get_apy(&self) -> AppResult<f64> {
+        self.observe(self.inner.get_apy().await)
+    }
+    
+    async fn get_supported_tokens(&self) -> AppResult<Vec<String>> {
+        self.observe(self.inner.get_supported_tokens().await)
+    }
+    
+    async fn get_pools(&self) -> AppResult<Vec<String>> {
+        self.observe(self.inner.get_pools().await)
+    }
+    
+    async fn get_pool_apy(&self, pool_id: &str) -> AppResult<f64> {
+        self.observe(self.inner.get_pool_apy(pool_id).await)
+    }
+    
+    async fn get_pool_tvl(&self, pool_id: &str) -> AppResult<f64> {
+        self.observe(self.inner.get_pool_tvl(pool_id).await)
+    }
+    
+    async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo> {
+        self.observe(self.inner.get_pool_info(pool_id).await)
+    }
+    
+    async fn deposit(&self,
+                    user_address: &str,
+                    amount: f64,
+                    token: &str) -> AppResult<String> {
+        self.check_not_paused()?;
+        self.observe(self.inner.deposit(user_address, amount, token).await)
+    }
+    
+    async fn withdraw(&self,
+                     user_address: &str,
+                     amount: f64,
+                     token: &str) -> AppResult<String> {
+        // Withdrawals stay open while paused so users can exit
+        self.observe(self.inner.withdraw(user_address, amount, token).await)
+    }
+    
+    async fn rebalance(&self, position: &Position) -> AppResult<String> {
+        // Rebalancing moves funds into pools, so it is a deposit too
+        self.check_not_paused()?;
+        self.observe(self.inner.rebalance(position).await)
+    }
+    
+    async fn claim_rewards(&self,
+                          user_address: &str,
+                          position_id: &str) -> AppResult<String> {
+        self.observe(self.inner.claim_rewards(user_address, position_id).await)
+    }
+    
+    async fn build_deposit_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        self.check_not_paused()?;
+        self.observe(self.inner.build_deposit_data(amount, token).await)
+    }
+    
+    async fn build_withdraw_data(&self, amount: f64, token: &str) -> AppResult<String> {
+        self.observe(self.inner.build_withdraw_data(amount, token).await)
+    }
+    
+    async fn build_rebalance_data(&self, position: &Position) -> AppResult<String> {
+        self.check_not_paused()?;
+        self.observe(self.inner.build_rebalance_data(position).await)
+    }
+    
+    async fn build_claim_rewards_data(&self, position_id: &str) -> AppResult<String> {
+        self.observe(self.inner.build_claim_rewards_data(position_id).await)
+    }
+    
+    async fn get_position_value(&self, position_id: &str) -> AppResult<f64> {
+        self.observe(self.inner.get_position_value(position_id).await)
+    }
+    
+    async fn get_optimal_allocation(&self,
+                                   strategy: &PositionStrategy) -> AppResult<Vec<(String, f64)>> {
+        self.observe(self.inner.get_optimal_allocation(strategy).await)
+    }
+    
+    fn is_token_supported(&self, token: &str) -> bool {
+        self.inner.is_token_supported(token)
+    }
+    
+    fn get_gas_limit_for_operation(&self, operation: &str) -> u64 {
+        self.inner.get_gas_limit_for_operation(operation)
+    }
+    
+    fn get_risk_level(&self) -> &str {
+        // The computed score takes over from the level hardcoded in the implementation
+        match self.risk_score.lock().unwrap().as_ref().map(|score| score.level.as_str()) {
+            Some("Low") => "Low",
+            Some("Medium") => "Medium",
+            Some("High") => "High",
+            _ => self.inner.get_risk_level(),
+        }
+    }
+    
+    fn get_risk_score(&self) -> Option<RiskScore> {
+        self.risk_score.lock().unwrap().clone()
+    }
+    
+    fn accepts_deposits(&self) -> bool {
+        !self.is_paused() && self.inner.accepts_deposits()
+    }
+    
+    fn get_tvl_denomination(&self) -> &str {
+        self.inner.get_tvl_denomination()
+    }
+    
+    async fn get_token_prices(&self) -> AppResult<HashMap<String, f64>> {
+        self.observe(self.inner.get_token_prices().await)
+    }
+    
+    async fn get_swap_pools(&self) -> AppResult<Vec<SwapPool>> {
+        self.observe(self.inner.get_swap_pools().await)
+    }
+    
+    async fn get_operation_receiver(&self,
+                                   operation: &str,
+                                   pool_id: Option<&str>,
+                                   user_address: &str,
+                                   amount: f64) -> AppResult<String> {
+        self.observe(self.inner.get_operation_receiver(operation, pool_id, user_address, amount).await)
+    }
+    
+    async fn get_unbonding(&self,
+                          user_address: &str) -> AppResult<Vec<UnbondingEntry>> {
+        self.observe(self.inner.get_unbonding(user_address).await)
+    }
+    
+    async fn get_liquid_staking_token(&self) -> AppResult<Option<LiquidStakingToken>> {
+        self.observe(self.inner.get_liquid_staking_token().await)
+    }
+    
+    async fn get_pending_rewards(&self,
+                                user_address: &str,
+                                position: &Position) -> AppResult<Vec<PendingReward>> {
+        self.observe(self.inner.get_pending_rewards(user_address, position).await)
+    }
+    
+    async fn build_claim_rewards_calls(&self,
+                                      user_address: &str,
+                                      position: &Position) -> AppResult<Vec<ContractCall>> {
+        self.observe(self.inner.build_claim_rewards_calls(user_address, position).await)
+    }
+    
+    fn supports_reinvestment(&self) -> bool {
+        self.inner.supports_reinvestment()
+    }
+    
+    async fn get_utilization(&self) -> AppResult<Option<f64>> {
+        self.observe(self.inner.get_utilization().await)
+    }
+    
+    async fn get_account_health(&self,
+                               user_address: &str) -> AppResult<Option<AccountHealth>> {
+        self.observe(self.inner.get_account_health(user_address).await)
+    }
+    
+    async fn refresh_state(&self) -> AppResult<()> {
+        self.observe(self.inner.refresh_state().await)
+    }
+    
+    async fn get_contract_addresses(&self) -> AppResult<Vec<String>> {
+        self.observe(self.inner.get_contract_addresses().await)
+    }
+}
\ No newline at end of file
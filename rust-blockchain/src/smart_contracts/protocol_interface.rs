@@ -1,6 +1,121 @@
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
 use crate::models::position::{Position, PositionStrategy};
+use crate::smart_contracts::risk::RiskScore;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Metadata of a single pool within a protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolInfo {
+    /// Pool ID within the protocol
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// Tokens held by the pool (a pair, a stable basket, or a single lending token)
+    pub tokens: Vec<String>,
+    /// Token identifier received for a deposit (LP token or interest-bearing token)
+    pub lp_token: Option<String>,
+    /// Swap fee as a fraction (0.003 = 0.3%), for pools that charge one
+    pub fee_tier: Option<f64>,
+    /// Address of the contract holding the pool
+    pub contract_address: String,
+    /// Tokens paid out as rewards on top of the pool yield
+    pub reward_tokens: Vec<String>,
+}
+
+/// A smart contract call ready to be wrapped in a transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractCall {
+    /// Receiver of the transaction; the sender itself for NFT transfers
+    pub receiver: String,
+    pub data: String,
+    /// EGLD value to send along, in its smallest denomination
+    pub value: String,
+    pub gas_limit: u64,
+}
+
+/// Price at which a collateral asset would make an account liquidatable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidationPrice {
+    pub token: String,
+    pub current_price: f64,
+    /// None when a price drop of this asset alone can't trigger a liquidation
+    pub liquidation_price: Option<f64>,
+}
+
+/// Collateral health of a user's borrowing account, with values in the protocol's price unit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountHealth {
+    /// Weighted collateral over borrows; below 1.0 the account can be liquidated,
+    /// None when nothing is borrowed
+    pub health_factor: Option<f64>,
+    pub total_collateral: f64,
+    /// Collateral weighted by the collateral factors
+    pub borrow_limit: f64,
+    pub total_borrowed: f64,
+    pub liquidation_prices: Vec<LiquidationPrice>,
+}
+
+/// Stake waiting out its unbonding period before it can be withdrawn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnbondingEntry {
+    /// Pool the stake was unbonded from
+    pub pool_id: String,
+    pub amount: f64,
+    /// Epoch from which the stake can be withdrawn
+    pub unbond_epoch: u64,
+    pub remaining_epochs: u64,
+    pub withdrawable: bool,
+}
+
+/// A liquid staking token, which accrues staking yield through its exchange rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidStakingToken {
+    /// Ticker as it appears in the tokens of other pools
+    pub ticker: String,
+    pub token_id: String,
+    pub underlying: String,
+    /// Underlying tokens per liquid token
+    pub exchange_rate: f64,
+    /// Staking APY earned by holding the token, None until it has been measured
+    pub apy: Option<f64>,
+}
+
+/// Rewards a position has accrued and not yet claimed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingReward {
+    /// Reward token, by ticker or identifier
+    pub token: String,
+    pub amount: f64,
+}
+
+/// Pricing curve of a swap pool
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SwapCurve {
+    /// Constant product pair, x * y = k
+    ConstantProduct,
+    /// Stableswap basket with an amplification coefficient
+    StableSwap { amp: f64 },
+}
+
+/// A pool tokens can be swapped through, with its current reserves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapPool {
+    pub protocol_id: String,
+    pub pool_id: String,
+    /// Contract the swap is sent to
+    pub contract_address: String,
+    /// Token identifiers, in the pool's token order
+    pub token_ids: Vec<String>,
+    pub decimals: Vec<u32>,
+    /// Reserves in token units, in the pool's token order
+    pub reserves: Vec<f64>,
+    /// Swap fee as a fraction
+    pub fee: f64,
+    pub curve: SwapCurve,
+}
 
 /// Protocol interface that all protocol implementations must follow
 #[async_trait]
@@ -32,6 +147,9 @@ pub trait ProtocolInterface {
     /// Get the TVL for a specific pool
     async fn get_pool_tvl(&self, pool_id: &str) -> AppResult<f64>;
     
+    /// Get the metadata of a specific pool
+    async fn get_pool_info(&self, pool_id: &str) -> AppResult<PoolInfo>;
+    
     /// Deposit funds into the protocol
     async fn deposit(&self, 
                     user_address: &str, 
@@ -53,6 +171,24 @@ pub trait ProtocolInterface {
                           user_address: &str, 
                           position_id: &str) -> AppResult<String>;
     
+    /// Build the smart contract call data for a deposit
+    async fn build_deposit_data(&self, 
+                               amount: f64, 
+                               token: &str) -> AppResult<String>;
+    
+    /// Build the smart contract call data for a withdrawal
+    async fn build_withdraw_data(&self, 
+                                amount: f64, 
+                                token: &str) -> AppResult<String>;
+    
+    /// Build the smart contract call data for rebalancing a position
+    async fn build_rebalance_data(&self, 
+                                 position: &Position) -> AppResult<String>;
+    
+    /// Build the smart contract call data for claiming rewards
+    async fn build_claim_rewards_data(&self, 
+                                     position_id: &str) -> AppResult<String>;
+    
     /// Get the current value of a position
     async fn get_position_value(&self, 
                                position_id: &str) -> AppResult<f64>;
@@ -69,4 +205,101 @@ pub trait ProtocolInterface {
     
     /// Get the protocol risk level
     fn get_risk_level(&self) -> &str;
+    
+    /// Get the latest risk score of the protocol, once it has been scored
+    fn get_risk_score(&self) -> Option<RiskScore> {
+        None
+    }
+    
+    /// Whether the protocol takes new deposits
+    fn accepts_deposits(&self) -> bool {
+        true
+    }
+    
+    /// Get the unit TVL is expressed in, a token ticker or USD
+    fn get_tvl_denomination(&self) -> &str {
+        "USD"
+    }
+    
+    /// Get USD prices by token ticker, for protocols whose reserves imply prices
+    async fn get_token_prices(&self) -> AppResult<HashMap<String, f64>> {
+        Ok(HashMap::new())
+    }
+    
+    /// Get the pools tokens can be swapped through, for exchange protocols
+    async fn get_swap_pools(&self) -> AppResult<Vec<SwapPool>> {
+        Ok(Vec::new())
+    }
+    
+    /// Get the receiver of the transaction for an operation by a user, when it isn't the protocol contract
+    async fn get_operation_receiver(&self, 
+                                   _operation: &str, 
+                                   _pool_id: Option<&str>, 
+                                   _user_address: &str, 
+                                   _amount: f64) -> AppResult<String> {
+        Ok(self.get_contract_address().to_string())
+    }
+    
+    /// Get a user's stake that is still unbonding, for protocols with an unbonding period
+    async fn get_unbonding(&self, 
+                          _user_address: &str) -> AppResult<Vec<UnbondingEntry>> {
+        Ok(Vec::new())
+    }
+    
+    /// Get the liquid token minted for deposits, for liquid staking protocols
+    async fn get_liquid_staking_token(&self) -> AppResult<Option<LiquidStakingToken>> {
+        Ok(None)
+    }
+    
+    /// Get the rewards a user's position can claim, per reward token; empty for protocols
+    /// whose yield accrues in the position itself
+    async fn get_pending_rewards(&self, 
+                                _user_address: &str, 
+                                _position: &Position) -> AppResult<Vec<PendingReward>> {
+        Ok(Vec::new())
+    }
+    
+    /// Build the calls claiming a user's pending rewards for a position, each sent to the contract
+    /// holding them; protocols whose claims can't be built from their on-chain state refuse
+    async fn build_claim_rewards_calls(&self, 
+                                      _user_address: &str, 
+                                      _position: &Position) -> AppResult<Vec<ContractCall>> {
+        Err(AppError::Validation(format!("{} can't build reward claims", self.get_name())))
+    }
+    
+    /// Whether deposits built from `build_deposit_data` and `get_operation_receiver` reach the
+    /// protocol's pools, so claimed rewards can be deposited back
+    fn supports_reinvestment(&self) -> bool {
+        false
+    }
+    
+    /// Get the share of supplied funds that is borrowed, for lending protocols
+    async fn get_utilization(&self) -> AppResult<Option<f64>> {
+        Ok(None)
+    }
+    
+    /// Get the collateral health of a user, for protocols that support borrowing
+    async fn get_account_health(&self, 
+                               _user_address: &str) -> AppResult<Option<AccountHealth>> {
+        Ok(None)
+    }
+    
+    /// Refresh on-chain state the protocol keeps in memory; called in the background so
+    /// reads never wait on a full scan of the chain
+    async fn refresh_state(&self) -> AppResult<()> {
+        Ok(())
+    }
+    
+    /// Get every contract address whose transactions affect positions in the protocol:
+    /// the protocol contract and the contract of each pool
+    async fn get_contract_addresses(&self) -> AppResult<Vec<String>> {
+        let mut addresses = vec![self.get_contract_address().to_string()];
+        for pool_id in self.get_pools().await? {
+            let address = self.get_pool_info(&pool_id).await?.contract_address;
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        Ok(addresses)
+    }
 }
\ No newline at end of file
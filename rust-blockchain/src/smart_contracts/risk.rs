@@ -0,0 +1,282 @@
+use crate::error::AppResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+// Protocol risk is scored from explicit factors, each rated from 0 (safe) to 100 (risky)
+// and combined by configurable weights into a score on the same scale. Factors whose input
+// is unknown or that don't apply to the protocol are left out, and the weights of the others
+// renormalized
+
+// Number of known factors a score needs before it rates the protocol
+const MIN_KNOWN_FACTORS: usize = 4;
+// TVL in USD rated as riskless, and the number of orders of magnitude below it rated as fully risky
+const SAFE_TVL_USD: f64 = 100_000_000.0;
+const TVL_DECADES: f64 = 4.0;
+// TVL drop over the history window rated as fully risky
+const MAX_TVL_DROP: f64 = 0.5;
+// Age from which contracts are rated as battle-tested, in days
+const MATURE_CONTRACT_DAYS: f64 = 730.0;
+// Utilization from which lenders may not be able to withdraw, and the rating grows
+const SAFE_UTILIZATION: f64 = 0.5;
+// Coefficient of variation of the APY rated as fully risky
+const MAX_APY_VARIATION: f64 = 0.5;
+
+/// Factor contributing to the risk score of a protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskFactor {
+    TvlSize,
+    TvlTrend,
+    ContractAge,
+    Audits,
+    AdminKeys,
+    Utilization,
+    ApyVolatility,
+    TokenConcentration,
+}
+
+impl RiskFactor {
+    pub const ALL: [RiskFactor; 8] = [
+        RiskFactor::TvlSize,
+        RiskFactor::TvlTrend,
+        RiskFactor::ContractAge,
+        RiskFactor::Audits,
+        RiskFactor::AdminKeys,
+        RiskFactor::Utilization,
+        RiskFactor::ApyVolatility,
+        RiskFactor::TokenConcentration,
+    ];
+}
+
+/// Facts about a protocol that can't be read from the chain
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProtocolRiskFacts {
+    /// Deployment date of the protocol's contracts
+    #[serde(default)]
+    pub deployed_at: Option<DateTime<Utc>>,
+    /// Security audits of the contracts, by auditor; None when unknown
+    #[serde(default)]
+    pub audits: Option<Vec<String>>,
+    /// Whether the contracts can be upgraded by their owner; None when unknown
+    #[serde(default)]
+    pub upgradeable: Option<bool>,
+    /// Whether an admin key can change parameters or move funds without a timelock; None when unknown
+    #[serde(default)]
+    pub admin_key: Option<bool>,
+}
+
+/// Weights of the factors and the facts known about each protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskConfig {
+    /// Weight of each factor; factors left out weigh 1.0, and factors weighing 0.0 don't apply
+    #[serde(default)]
+    pub weights: HashMap<RiskFactor, f64>,
+    /// Score from which a protocol is rated Medium
+    #[serde(default = "default_medium_threshold")]
+    pub medium_threshold: f64,
+    /// Score from which a protocol is rated High
+    #[serde(default = "default_high_threshold")]
+    pub high_threshold: f64,
+    /// Window TVL trend and APY volatility are measured over, in days
+    #[serde(default = "default_history_days")]
+    pub history_days: i64,
+    /// Facts by protocol ID
+    #[serde(default)]
+    pub protocols: HashMap<String, ProtocolRiskFacts>,
+}
+
+fn default_medium_threshold() -> f64 {
+    35.0
+}
+
+fn default_high_threshold() -> f64 {
+    65.0
+}
+
+fn default_history_days() -> i64 {
+    30
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            weights: HashMap::new(),
+            medium_threshold: default_medium_threshold(),
+            high_threshold: default_high_threshold(),
+            history_days: default_history_days(),
+            protocols: HashMap::new(),
+        }
+    }
+}
+
+impl RiskConfig {
+    /// Load the configuration from a JSON file
+    pub fn load(path: &Path) -> AppResult<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+    
+    pub fn weight(&self, factor: RiskFactor) -> f64 {
+        self.weights.get(&factor).copied().unwrap_or(1.0).max(0.0)
+    }
+    
+    /// Risk level of a score: Low, Medium or High
+    pub fn level(&self, score: f64) -> &'static str {
+        if score >= self.high_threshold {
+            "High"
+        } else if score >= self.medium_threshold {
+            "Medium"
+        } else {
+            "Low"
+        }
+    }
+}
+
+/// Live and recorded data a protocol is scored from
+#[derive(Debug, Clone, Default)]
+pub struct RiskInputs {
+    pub tvl_usd: Option<f64>,
+    /// TVL at the start of the history window, in USD
+    pub past_tvl_usd: Option<f64>,
+    /// APYs recorded over the history window, including the current one
+    pub apy_history: Vec<f64>,
+    /// Utilization of the protocol's lending pools, for lending protocols
+    pub utilization: Option<f64>,
+    /// TVL by token, in the protocol's TVL unit
+    pub token_tvl: HashMap<String, f64>,
+}
+
+/// Rating of a single factor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FactorScore {
+    pub factor: RiskFactor,
+    /// Input the factor was rated from, e.g. the TVL in USD or the number of audits; scores
+    /// recorded before unknown factors were left out hold None for them
+    pub value: Option<f64>,
+    /// Risk from 0 (safe) to 100 (risky)
+    pub score: f64,
+    pub weight: f64,
+}
+
+/// Risk score of a protocol with its breakdown by the factors it was rated from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskScore {
+    pub protocol_id: String,
+    /// Weighted average of the known factor ratings, from 0 (safe) to 100 (risky)
+    pub score: f64,
+    /// Low, Medium or High; Unknown until enough factors are known
+    pub level: String,
+    pub factors: Vec<FactorScore>,
+    pub computed_at: DateTime<Utc>,
+}
+
+impl RiskScore {
+    /// Get the rating of a factor, when its input was known
+    pub fn factor(&self, factor: RiskFactor) -> Option<&FactorScore> {
+        self.factors.iter().find(|f| f.factor == factor && f.value.is_some())
+    }
+    
+    /// Whether enough factors are known for the score to rate the protocol
+    pub fn is_conclusive(&self) -> bool {
+        self.factors.iter().filter(|f| f.value.is_some() && f.weight > 0.0).count() >= MIN_KNOWN_FACTORS
+    }
+}
+
+/// Score a protocol from its inputs
+pub fn score_protocol(protocol_id: &str, inputs: &RiskInputs, config: &RiskConfig) -> RiskScore {
+    let facts = config.protocols.get(protocol_id);
+    
+    let factors: Vec<FactorScore> = RiskFactor::ALL.iter()
+        .filter(|factor| config.weight(**factor) > 0.0)
+        .filter_map(|factor| {
+            let (value, score) = rate_factor(*factor, inputs, facts)?;
+            Some(FactorScore {
+                factor: *factor,
+                value: Some(value),
+                score: score.clamp(0.0, 100.0),
+                weight: config.weight(*factor),
+            })
+        })
+        .collect();
+    
+    let total_weight: f64 = factors.iter().map(|f| f.weight).sum();
+    let score = if total_weight > 0.0 {
+        factors.iter().map(|f| f.score * f.weight).sum::<f64>() / total_weight
+    } else {
+        0.0
+    };
+    
+    let mut risk_score = RiskScore {
+        protocol_id: protocol_id.to_string(),
+        score,
+        level: "Unknown".to_string(),
+        factors,
+        computed_at: Utc::now(),
+    };
+    if risk_score.is_conclusive() {
+        risk_score.level = config.level(score).to_string();
+    }
+    risk_score
+}
+
+// Rate a factor, returning the input it was rated from and the rating; None when the input is unknown
+fn rate_factor(factor: RiskFactor, inputs: &RiskInputs, facts: Option<&ProtocolRiskFacts>) -> Option<(f64, f64)> {
+    match factor {
+        RiskFactor::TvlSize => {
+            let tvl = inputs.tvl_usd?;
+            let decades_below = (SAFE_TVL_USD.log10() - tvl.max(1.0).log10()).max(0.0);
+            Some((tvl, decades_below / TVL_DECADES * 100.0))
+        },
+        RiskFactor::TvlTrend => {
+            let (tvl, past_tvl) = (inputs.tvl_usd?, inputs.past_tvl_usd?);
+            if past_tvl <= 0.0 {
+                return None;
+            }
+            // Only outflows count; growth is rated as riskless
+            let change = tvl / past_tvl - 1.0;
+            Some((change, -change / MAX_TVL_DROP * 100.0))
+        },
+        RiskFactor::ContractAge => {
+            let deployed_at = facts?.deployed_at?;
+            let days = (Utc::now() - deployed_at).num_days().max(0) as f64;
+            Some((days, (1.0 - days / MATURE_CONTRACT_DAYS) * 100.0))
+        },
+        RiskFactor::Audits => {
+            let audits = facts?.audits.as_ref()?.len() as f64;
+            Some((audits, 100.0 - audits * 40.0))
+        },
+        RiskFactor::AdminKeys => {
+            let facts = facts?;
+            let flags = facts.upgradeable? as u8 + facts.admin_key? as u8;
+            Some((flags as f64, flags as f64 * 50.0))
+        },
+        RiskFactor::Utilization => {
+            let utilization = inputs.utilization?;
+            Some((utilization, (utilization - SAFE_UTILIZATION) / (1.0 - SAFE_UTILIZATION) * 100.0))
+        },
+        RiskFactor::ApyVolatility => {
+            if inputs.apy_history.len() < 2 {
+                return None;
+            }
+            let count = inputs.apy_history.len() as f64;
+            let mean = inputs.apy_history.iter().sum::<f64>() / count;
+            if mean <= 0.0 {
+                return None;
+            }
+            let variance = inputs.apy_history.iter().map(|apy| (apy - mean).powi(2)).sum::<f64>() / count;
+            let variation = variance.sqrt() / mean;
+            Some((variation, variation / MAX_APY_VARIATION * 100.0))
+        },
+        RiskFactor::TokenConcentration => {
+            let total: f64 = inputs.token_tvl.values().sum();
+            if total <= 0.0 {
+                return None;
+            }
+            // Herfindahl index of the TVL shares: 1.0 when everything sits in a single token
+            let index: f64 = inputs.token_tvl.values().map(|tvl| (tvl / total).powi(2)).sum();
+            Some((index, index * 100.0))
+        },
+    }
+}
\ No newline at end of file
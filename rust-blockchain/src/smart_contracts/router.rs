@@ -0,0 +1,519 @@
+use crate::error::{AppError, AppResult};
+use crate::smart_contracts::maiar_exchange::ROUTER_ADDRESS;
+use crate::smart_contracts::maiar_farm::{encode_biguint, esdt_transfer_data, to_raw_amount};
+use crate::smart_contracts::protocol_interface::{ContractCall, SwapCurve, SwapPool};
+use crate::smart_contracts::scan::{scan_protocols, ScanLimits, ScanResult, SkippedProtocol};
+use crate::smart_contracts::stableswap::{min_output, StableSwapPool};
+use crate::smart_contracts::ProtocolRegistry;
+use crate::utils::address_to_public_key;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Routes are searched over a graph whose nodes are token identifiers and whose edges are
+// the pools of the exchange protocols. Constant product pools are swapped through with the
+// xExchange pair endpoints, and stableswap pools with the AshSwap pool endpoints.
+
+// Longest path considered, in pools
+const MAX_HOPS: usize = 3;
+
+// Number of best paths the input can be split across
+const MAX_SPLIT_PATHS: usize = 3;
+
+// The input is split in this many equal parts, each sent down the path paying the most for it
+const SPLIT_PARTS: usize = 20;
+
+// Maximum slippage accepted before the swap reverts, when the caller doesn't set one
+pub const DEFAULT_MAX_SLIPPAGE: f64 = 0.005;
+
+const PAIR_SWAP_GAS_LIMIT: u64 = 20_000_000;
+const MULTI_PAIR_SWAP_GAS_PER_HOP: u64 = 25_000_000;
+const STABLE_SWAP_GAS_LIMIT: u64 = 30_000_000;
+
+// Swaps of wrapped EGLD are requested as EGLD
+const WEGLD_TOKEN_ID: &str = "WEGLD-bd4d79";
+
+// Contract wrapping EGLD into WEGLD, so EGLD input can be swapped through the pools, and unwrapping
+// WEGLD output back into EGLD
+const WEGLD_WRAPPER_ADDRESS: &str = "erd1qqqqqqqqqqqqqpgqhe8t5jewej70zupmh44jurgn29psua5l2jps3ntjj3";
+const WRAP_EGLD_GAS_LIMIT: u64 = 5_000_000;
+
+/// A pool crossed by a route
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteHop {
+    pub protocol_id: String,
+    pub pool_id: String,
+    pub contract_address: String,
+    pub token_in: String,
+    pub token_out: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    /// Fee paid to the pool, in the output token
+    pub fee: f64,
+    /// Loss against the marginal price of the pool, excluding the fee, as a fraction
+    pub price_impact: f64,
+    #[serde(skip)]
+    decimals_in: u32,
+    #[serde(skip)]
+    decimals_out: u32,
+    #[serde(skip)]
+    curve: SwapCurve,
+}
+
+/// A path taking part of the input
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitRoute {
+    /// Fraction of the input sent down this path
+    pub share: f64,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    pub hops: Vec<RouteHop>,
+}
+
+/// Best route found to swap an amount of one token for another
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapRoute {
+    pub token_in: String,
+    pub token_out: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    /// Output below which the swap reverts
+    pub min_amount_out: f64,
+    pub max_slippage: f64,
+    /// Loss against the marginal price of the best path, excluding fees, as a fraction
+    pub price_impact: f64,
+    /// Fees paid across all hops, valued in the output token
+    pub fees: f64,
+    pub splits: Vec<SplitRoute>,
+    /// Calls to submit in order to execute the swap
+    pub calls: Vec<ContractCall>,
+    /// Exchange protocols whose pools couldn't be read
+    pub skipped: Vec<SkippedProtocol>,
+}
+
+// A directed edge of the graph: swapping token i for token j in a pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Edge {
+    pool: usize,
+    i: usize,
+    j: usize,
+}
+
+type Path = Vec<Edge>;
+
+/// Routes swaps across the pools of every exchange protocol in the registry
+#[derive(Clone)]
+pub struct SwapRouter {
+    protocol_registry: ProtocolRegistry,
+    scan_limits: ScanLimits,
+}
+
+impl SwapRouter {
+    pub fn new(protocol_registry: ProtocolRegistry) -> Self {
+        Self {
+            protocol_registry,
+            scan_limits: ScanLimits::default(),
+        }
+    }
+    
+    pub fn with_scan_limits(mut self, scan_limits: ScanLimits) -> Self {
+        self.scan_limits = scan_limits;
+        self
+    }
+    
+    /// Read the swap pools of every protocol that isn't paused
+    pub async fn load_pools(&self) -> ScanResult<SwapPool> {
+        let scans = self.protocol_registry.get_all_protocols().into_iter()
+            .filter(|protocol| !self.protocol_registry.is_paused(protocol.get_id()))
+            .map(|protocol| {
+                let id = protocol.get_id().to_string();
+                (id, async move {
+                    Ok(ScanResult {
+                        items: protocol.get_swap_pools().await?,
+                        skipped: Vec::new(),
+                    })
+                })
+            })
+            .collect();
+        
+        scan_protocols(scans, &self.scan_limits).await
+    }
+    
+    /// Find the best route for swapping an amount of a token, by identifier or ticker,
+    /// splitting it across several paths when that pays more
+    pub async fn quote(&self,
+                       token_in: &str,
+                       token_out: &str,
+                       amount_in: f64,
+                       max_slippage: f64) -> AppResult<SwapRoute> {
+        if !amount_in.is_finite() || amount_in <= 0.0 {
+            return Err(AppError::Validation(format!("Invalid amount: {}", amount_in)));
+        }
+        if !(0.0..1.0).contains(&max_slippage) {
+            return Err(AppError::Validation(format!("Invalid slippage: {}", max_slippage)));
+        }
+        
+        let ScanResult { items: pools, skipped } = self.load_pools().await;
+        // Pools trade WEGLD, so EGLD input is wrapped first
+        let wrap_input = token_in.eq_ignore_ascii_case("EGLD");
+        let unwrap_output = token_out.eq_ignore_ascii_case("EGLD");
+        let token_in = resolve_token(&pools, token_in)?;
+        let token_out = resolve_token(&pools, token_out)?;
+        if token_in == token_out {
+            return Err(AppError::Validation("Input and output tokens are the same".to_string()));
+        }
+        
+        let mut paths: Vec<(Path, f64)> = find_paths(&pools, &token_in, &token_out)
+            .into_iter()
+            .filter_map(|path| {
+                let mut state = pools.clone();
+                simulate(&mut state, &path, amount_in).ok().map(|hops| {
+                    let amount_out = hops.last().map(|h| h.amount_out).unwrap_or(0.0);
+                    (path, amount_out)
+                })
+            })
+            .collect();
+        if paths.is_empty() {
+            return Err(AppError::NotFound(format!("No route from {} to {}", token_in, token_out)));
+        }
+        paths.sort_by(|a, b| b.1.total_cmp(&a.1));
+        paths.truncate(MAX_SPLIT_PATHS);
+        
+        // Keep the split only when it beats sending everything down the best path
+        let best_single = vec![(paths[0].0.clone(), amount_in)];
+        let split = split_input(&pools, &paths, amount_in);
+        let single_splits = execute(&pools, &best_single, amount_in)?;
+        let splits = match split.and_then(|allocation| execute(&pools, &allocation, amount_in)) {
+            Ok(splits) if total_out(&splits) > total_out(&single_splits) => splits,
+            _ => single_splits,
+        };
+        
+        let amount_out = total_out(&splits);
+        let spot_rate = marginal_rate(&pools, &paths[0].0)?;
+        let fees = splits.iter()
+            .flat_map(|split| split.hops.iter().map(move |hop| hop.fee * split.amount_out / hop.amount_out))
+            .sum();
+        
+        let mut calls = Vec::new();
+        if wrap_input {
+            calls.push(ContractCall {
+                receiver: WEGLD_WRAPPER_ADDRESS.to_string(),
+                data: "wrapEgld".to_string(),
+                value: to_raw_amount(amount_in, 18)?.to_string(),
+                gas_limit: WRAP_EGLD_GAS_LIMIT,
+            });
+        }
+        let mut min_amount_out = 0.0;
+        for split in &splits {
+            let (split_calls, split_min) = build_calls(split, max_slippage)?;
+            calls.extend(split_calls);
+            min_amount_out += split_min;
+        }
+        // EGLD output arrives as WEGLD; only the guaranteed output is unwrapped, so the unwrap
+        // can't fail on a swap that filled at the minimum
+        if unwrap_output {
+            calls.push(ContractCall {
+                receiver: WEGLD_WRAPPER_ADDRESS.to_string(),
+                data: esdt_transfer_data(&token_out, &to_raw_amount(min_amount_out, 18)?, "unwrapEgld"),
+                value: "0".to_string(),
+                gas_limit: WRAP_EGLD_GAS_LIMIT,
+            });
+        }
+        
+        Ok(SwapRoute {
+            token_in,
+            token_out,
+            amount_in,
+            amount_out,
+            min_amount_out,
+            max_slippage,
+            price_impact: (1.0 - amount_out / (amount_in * spot_rate)).max(0.0),
+            fees,
+            splits,
+            calls,
+            skipped,
+        })
+    }
+}
+
+/// Decimals of a token, from the pools that trade it
+pub fn token_decimals(pools: &[SwapPool], token_id: &str) -> Option<u32> {
+    pools.iter().find_map(|pool| {
+        pool.token_ids.iter()
+            .position(|t| t == token_id)
+            .map(|index| pool.decimals[index])
+    })
+}
+
+// Match a token identifier, or a ticker to its best connected identifier; ties between
+// identifiers in as many pools go to the one with the largest reserves, then the lowest identifier
+fn resolve_token(pools: &[SwapPool], token: &str) -> AppResult<String> {
+    let mut counts: HashMap<&str, (usize, f64)> = HashMap::new();
+    for pool in pools {
+        for (token_id, reserve) in pool.token_ids.iter().zip(&pool.reserves) {
+            let (count, liquidity) = counts.entry(token_id.as_str()).or_insert((0, 0.0));
+            *count += 1;
+            *liquidity += reserve;
+        }
+    }
+    
+    if counts.contains_key(token) {
+        return Ok(token.to_string());
+    }
+    if token.eq_ignore_ascii_case("EGLD") && counts.contains_key(WEGLD_TOKEN_ID) {
+        return Ok(WEGLD_TOKEN_ID.to_string());
+    }
+    
+    counts.into_iter()
+        .filter(|(token_id, _)| token_id.split('-').next().map_or(false, |t| t.eq_ignore_ascii_case(token)))
+        .max_by(|(a_id, (a_count, a_liquidity)), (b_id, (b_count, b_liquidity))| {
+            a_count.cmp(b_count)
+                .then(a_liquidity.total_cmp(b_liquidity))
+                .then(b_id.cmp(a_id))
+        })
+        .map(|(token_id, _)| token_id.to_string())
+        .ok_or_else(|| AppError::NotFound(format!("Token {} is not traded in any pool", token)))
+}
+
+// Every path from one token to another that visits no token or pool twice
+fn find_paths(pools: &[SwapPool], token_in: &str, token_out: &str) -> Vec<Path> {
+    let mut edges: HashMap<&str, Vec<Edge>> = HashMap::new();
+    for (index, pool) in pools.iter().enumerate() {
+        for (i, from) in pool.token_ids.iter().enumerate() {
+            for j in 0..pool.token_ids.len() {
+                if i != j {
+                    edges.entry(from.as_str()).or_default().push(Edge { pool: index, i, j });
+                }
+            }
+        }
+    }
+    
+    let mut paths = Vec::new();
+    let mut stack: Vec<(String, Path)> = vec![(token_in.to_string(), Vec::new())];
+    while let Some((token, path)) = stack.pop() {
+        if token == token_out {
+            paths.push(path);
+            continue;
+        }
+        if path.len() == MAX_HOPS {
+            continue;
+        }
+        
+        for edge in edges.get(token.as_str()).into_iter().flatten() {
+            let next = &pools[edge.pool].token_ids[edge.j];
+            let visited = next == token_in
+                || path.iter().any(|e| e.pool == edge.pool || &pools[e.pool].token_ids[e.j] == next);
+            if !visited {
+                let mut extended = path.clone();
+                extended.push(*edge);
+                stack.push((next.clone(), extended));
+            }
+        }
+    }
+    
+    paths
+}
+
+// Output and fee, both in the output token, of swapping through a pool
+fn swap_output(pool: &SwapPool, i: usize, j: usize, amount_in: f64) -> AppResult<(f64, f64)> {
+    match pool.curve {
+        SwapCurve::ConstantProduct => {
+            let (reserve_in, reserve_out) = (pool.reserves[i], pool.reserves[j]);
+            let net_in = amount_in * (1.0 - pool.fee);
+            let amount_out = reserve_out * net_in / (reserve_in + net_in);
+            let without_fee = reserve_out * amount_in / (reserve_in + amount_in);
+            Ok((amount_out, without_fee - amount_out))
+        },
+        SwapCurve::StableSwap { amp } => {
+            let state = StableSwapPool {
+                balances: pool.reserves.clone(),
+                amp,
+                fee: pool.fee,
+                lp_supply: 0.0,
+            };
+            let quote = state.quote_swap(i, j, amount_in)?;
+            Ok((quote.amount_out, quote.fee))
+        },
+    }
+}
+
+// Output per unit of input of a path for an amount too small to move the price
+fn marginal_rate(pools: &[SwapPool], path: &[Edge]) -> AppResult<f64> {
+    let mut rate = 1.0;
+    for edge in path {
+        let pool = &pools[edge.pool];
+        let probe = pool.reserves[edge.i] * 1e-6;
+        rate *= swap_output(pool, edge.i, edge.j, probe)?.0 / probe;
+    }
+    Ok(rate)
+}
+
+// Swap an amount down a path, moving the reserves of the pools it crosses
+fn simulate(pools: &mut [SwapPool], path: &[Edge], amount_in: f64) -> AppResult<Vec<RouteHop>> {
+    let mut hops = Vec::with_capacity(path.len());
+    let mut amount = amount_in;
+    
+    for edge in path {
+        let pool = &mut pools[edge.pool];
+        let probe = pool.reserves[edge.i] * 1e-6;
+        let spot_rate = swap_output(pool, edge.i, edge.j, probe)?.0 / probe;
+        let (amount_out, fee) = swap_output(pool, edge.i, edge.j, amount)?;
+        if amount_out <= 0.0 || amount_out >= pool.reserves[edge.j] {
+            return Err(AppError::Validation(format!("Swap exceeds the liquidity of pool {}", pool.pool_id)));
+        }
+        
+        hops.push(RouteHop {
+            protocol_id: pool.protocol_id.clone(),
+            pool_id: pool.pool_id.clone(),
+            contract_address: pool.contract_address.clone(),
+            token_in: pool.token_ids[edge.i].clone(),
+            token_out: pool.token_ids[edge.j].clone(),
+            amount_in: amount,
+            amount_out,
+            fee,
+            price_impact: (1.0 - amount_out / (amount * spot_rate)).max(0.0),
+            decimals_in: pool.decimals[edge.i],
+            decimals_out: pool.decimals[edge.j],
+            curve: pool.curve,
+        });
+        
+        // Fees stay in the pool
+        pool.reserves[edge.i] += amount;
+        pool.reserves[edge.j] -= amount_out;
+        amount = amount_out;
+    }
+    
+    Ok(hops)
+}
+
+// Split the input in equal parts, sending each down the path that pays the most for it
+// given the parts already routed, which can share pools with it
+fn split_input(pools: &[SwapPool], paths: &[(Path, f64)], amount_in: f64) -> AppResult<Vec<(Path, f64)>> {
+    let part = amount_in / SPLIT_PARTS as f64;
+    let mut state = pools.to_vec();
+    let mut allocation = vec![0.0; paths.len()];
+    
+    for _ in 0..SPLIT_PARTS {
+        let mut best: Option<(usize, f64)> = None;
+        for (index, (path, _)) in paths.iter().enumerate() {
+            let mut trial = state.clone();
+            if let Ok(hops) = simulate(&mut trial, path, part) {
+                let amount_out = hops.last().map(|h| h.amount_out).unwrap_or(0.0);
+                if best.map_or(true, |(_, best_out)| amount_out > best_out) {
+                    best = Some((index, amount_out));
+                }
+            }
+        }
+        
+        let (index, _) = best.ok_or_else(|| AppError::Validation("Swap exceeds the liquidity of every path".to_string()))?;
+        simulate(&mut state, &paths[index].0, part)?;
+        allocation[index] += part;
+    }
+    
+    Ok(paths.iter()
+        .zip(allocation)
+        .filter(|(_, amount)| *amount > 0.0)
+        .map(|((path, _), amount)| (path.clone(), amount))
+        .collect())
+}
+
+// Quote the paths in the order their calls are submitted, each seeing the pools moved by the previous ones
+fn execute(pools: &[SwapPool], allocation: &[(Path, f64)], amount_in: f64) -> AppResult<Vec<SplitRoute>> {
+    let mut state = pools.to_vec();
+    allocation.iter()
+        .map(|(path, amount)| {
+            let hops = simulate(&mut state, path, *amount)?;
+            Ok(SplitRoute {
+                share: amount / amount_in,
+                amount_in: *amount,
+                amount_out: hops.last().map(|h| h.amount_out).unwrap_or(0.0),
+                hops,
+            })
+        })
+        .collect()
+}
+
+fn total_out(splits: &[SplitRoute]) -> f64 {
+    splits.iter().map(|s| s.amount_out).sum()
+}
+
+// Build the calls of a path with the least output they accept. Consecutive constant product hops
+// go through the xExchange router in one call; every other hop is its own call, which can only
+// spend the guaranteed output of the previous one
+fn build_calls(split: &SplitRoute, max_slippage: f64) -> AppResult<(Vec<ContractCall>, f64)> {
+    let mut calls = Vec::new();
+    let mut amount_in = split.amount_in;
+    // Ratio of the input a call spends to the input that was quoted for it
+    let mut scale = 1.0;
+    let mut min_out = 0.0;
+    
+    let mut start = 0;
+    while start < split.hops.len() {
+        let mut end = start + 1;
+        if matches!(split.hops[start].curve, SwapCurve::ConstantProduct) {
+            while end < split.hops.len() && matches!(split.hops[end].curve, SwapCurve::ConstantProduct) {
+                end += 1;
+            }
+        }
+        let group = &split.hops[start..end];
+        let first = &group[0];
+        let last = &group[group.len() - 1];
+        
+        let mut args = Vec::with_capacity(group.len());
+        for hop in group {
+            let min_hop_out = to_raw_amount(min_output(hop.amount_out * scale, max_slippage), hop.decimals_out)?;
+            args.push((hop, min_hop_out));
+        }
+        
+        let raw_in = to_raw_amount(amount_in, first.decimals_in)?;
+        let call = match (first.curve, group.len()) {
+            (SwapCurve::ConstantProduct, 1) => ContractCall {
+                receiver: first.contract_address.clone(),
+                data: format!(
+                    "{}@{}@{}",
+                    esdt_transfer_data(&first.token_in, &raw_in, "swapTokensFixedInput"),
+                    hex::encode(&first.token_out),
+                    encode_biguint(&args[0].1),
+                ),
+                value: "0".to_string(),
+                gas_limit: PAIR_SWAP_GAS_LIMIT,
+            },
+            (SwapCurve::ConstantProduct, _) => {
+                let mut data = esdt_transfer_data(&first.token_in, &raw_in, "multiPairSwap");
+                for (hop, min_hop_out) in &args {
+                    data.push_str(&format!(
+                        "@{}@{}@{}@{}",
+                        hex::encode(address_to_public_key(&hop.contract_address)?),
+                        hex::encode("swapTokensFixedInput"),
+                        hex::encode(&hop.token_out),
+                        encode_biguint(min_hop_out),
+                    ));
+                }
+                ContractCall {
+                    receiver: ROUTER_ADDRESS.to_string(),
+                    data,
+                    value: "0".to_string(),
+                    gas_limit: MULTI_PAIR_SWAP_GAS_PER_HOP * group.len() as u64,
+                }
+            },
+            (SwapCurve::StableSwap { .. }, _) => ContractCall {
+                receiver: first.contract_address.clone(),
+                data: format!(
+                    "{}@{}@{}",
+                    esdt_transfer_data(&first.token_in, &raw_in, "exchange"),
+                    hex::encode(&first.token_out),
+                    encode_biguint(&args[0].1),
+                ),
+                value: "0".to_string(),
+                gas_limit: STABLE_SWAP_GAS_LIMIT,
+            },
+        };
+        calls.push(call);
+        
+        min_out = min_output(last.amount_out * scale, max_slippage);
+        scale *= 1.0 - max_slippage;
+        amount_in = min_out;
+        start = end;
+    }
+    
+    Ok((calls, min_out))
+}
\ No newline at end of file
@@ -0,0 +1,129 @@
+use crate::error::AppResult;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+// Scans query every protocol and pool at once; a slow or failing protocol is left out
+// of the results instead of holding up or failing the whole scan
+
+/// Limits applied when scanning protocols concurrently
+#[derive(Debug, Clone)]
+pub struct ScanLimits {
+    /// Maximum number of protocols, or pools of a protocol, queried at once
+    pub max_concurrency: usize,
+    /// Time given to each protocol to answer for all its pools
+    pub protocol_timeout: Duration,
+}
+
+impl Default for ScanLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 8,
+            protocol_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A protocol, or one of its pools, left out of a scan
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedProtocol {
+    pub protocol_id: String,
+    /// Set when only this pool of the protocol was left out
+    pub pool_id: Option<String>,
+    pub reason: String,
+}
+
+/// Partial results of a scan, with what was left out
+#[derive(Debug, Clone)]
+pub struct ScanResult<T> {
+    pub items: Vec<T>,
+    pub skipped: Vec<SkippedProtocol>,
+}
+
+impl<T> Default for ScanResult<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+/// Run the scan of each protocol concurrently, skipping the protocols that fail or time out
+pub async fn scan_protocols<T, Fut>(scans: Vec<(String, Fut)>, limits: &ScanLimits) -> ScanResult<T>
+where
+    Fut: Future<Output = AppResult<ScanResult<T>>>,
+{
+    let timeout = limits.protocol_timeout;
+    let outcomes: Vec<_> = stream::iter(scans)
+        .map(|(protocol_id, scan)| async move {
+            (protocol_id, tokio::time::timeout(timeout, scan).await)
+        })
+        .buffer_unordered(limits.max_concurrency.max(1))
+        .collect()
+        .await;
+    
+    let mut result = ScanResult::default();
+    for (protocol_id, outcome) in outcomes {
+        let reason = match outcome {
+            Ok(Ok(scanned)) => {
+                result.items.extend(scanned.items);
+                result.skipped.extend(scanned.skipped);
+                continue;
+            },
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => format!("timed out after {}s", timeout.as_secs()),
+        };
+        
+        warn!("Skipping protocol {} in scan: {}", protocol_id, reason);
+        result.skipped.push(SkippedProtocol {
+            protocol_id,
+            pool_id: None,
+            reason,
+        });
+    }
+    
+    result
+}
+
+/// Scan the pools of a protocol concurrently, skipping the pools that fail;
+/// a pool scanned to None is filtered out rather than skipped
+pub async fn scan_pools<T, F, Fut>(
+    protocol_id: &str,
+    pools: Vec<String>,
+    limits: &ScanLimits,
+    scan: F,
+) -> ScanResult<T>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = AppResult<Option<T>>>,
+{
+    let outcomes: Vec<_> = stream::iter(pools)
+        .map(|pool_id| {
+            let scanned = scan(pool_id.clone());
+            async move { (pool_id, scanned.await) }
+        })
+        .buffer_unordered(limits.max_concurrency.max(1))
+        .collect()
+        .await;
+    
+    let mut result = ScanResult::default();
+    for (pool_id, outcome) in outcomes {
+        match outcome {
+            Ok(Some(item)) => result.items.push(item),
+            Ok(None) => {},
+            Err(e) => {
+                warn!("Skipping pool {} of {} in scan: {}", pool_id, protocol_id, e);
+                result.skipped.push(SkippedProtocol {
+                    protocol_id: protocol_id.to_string(),
+                    pool_id: Some(pool_id),
+                    reason: e.to_string(),
+                });
+            },
+        }
+    }
+    
+    result
+}
\ No newline at end of file
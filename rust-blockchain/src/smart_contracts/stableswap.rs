@@ -0,0 +1,301 @@
+use crate::error::{AppError, AppResult};
+use serde::Serialize;
+
+// Curve style stableswap invariant, solved with Newton's method:
+//   A * n^n * sum(x) + D = A * n^n * D + D^(n+1) / (n^n * prod(x))
+// Balances are in token units, so pools are expected to hold tokens of equal value
+
+const MAX_ITERATIONS: usize = 255;
+const CONVERGENCE: f64 = 1e-12;
+
+/// State of a stableswap pool
+#[derive(Debug, Clone, Serialize)]
+pub struct StableSwapPool {
+    /// Token balances, in token units
+    pub balances: Vec<f64>,
+    /// Amplification coefficient A
+    pub amp: f64,
+    /// Swap fee as a fraction
+    pub fee: f64,
+    /// LP tokens in circulation
+    pub lp_supply: f64,
+}
+
+/// Result of a swap quote
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapQuote {
+    pub amount_in: f64,
+    pub amount_out: f64,
+    pub fee: f64,
+    /// Loss against the marginal price, excluding the fee, as a fraction
+    pub price_impact: f64,
+}
+
+/// Result of a liquidity quote
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidityQuote {
+    /// LP tokens minted on deposit or burned on withdrawal
+    pub lp_amount: f64,
+    /// Token amounts deposited or withdrawn
+    pub amounts: Vec<f64>,
+    /// Fees charged on the imbalance, in token units
+    pub fees: Vec<f64>,
+    /// Loss against depositing or withdrawing at the virtual price, as a fraction;
+    /// negative when the operation rebalances the pool and earns a bonus
+    pub slippage: f64,
+}
+
+/// Minimum acceptable output for a quoted amount
+pub fn min_output(amount: f64, max_slippage: f64) -> f64 {
+    amount * (1.0 - max_slippage)
+}
+
+impl StableSwapPool {
+    fn n(&self) -> f64 {
+        self.balances.len() as f64
+    }
+    
+    fn ann(&self) -> f64 {
+        self.amp * self.n().powi(self.balances.len() as i32)
+    }
+    
+    fn check_index(&self, index: usize) -> AppResult<()> {
+        if index < self.balances.len() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(format!("Token index {} out of range", index)))
+        }
+    }
+    
+    /// Invariant D of a set of balances
+    pub fn get_d(&self, balances: &[f64]) -> AppResult<f64> {
+        let sum: f64 = balances.iter().sum();
+        if sum == 0.0 {
+            return Ok(0.0);
+        }
+        if balances.iter().any(|b| *b <= 0.0) {
+            return Err(AppError::Validation("Pool balances must be positive".to_string()));
+        }
+        
+        let n = self.n();
+        let ann = self.ann();
+        let mut d = sum;
+        
+        for _ in 0..MAX_ITERATIONS {
+            let d_p = balances.iter().fold(d, |d_p, x| d_p * d / (x * n));
+            let previous = d;
+            d = (ann * sum + d_p * n) * d / ((ann - 1.0) * d + (n + 1.0) * d_p);
+            
+            if (d - previous).abs() <= CONVERGENCE * d {
+                return Ok(d);
+            }
+        }
+        
+        Err(AppError::Internal("Stableswap invariant did not converge".to_string()))
+    }
+    
+    /// Balance of token j that keeps the invariant d, given the other balances
+    fn get_y(&self, j: usize, balances: &[f64], d: f64) -> AppResult<f64> {
+        let n = self.n();
+        let ann = self.ann();
+        let mut c = d;
+        let mut sum = 0.0;
+        
+        for (k, x) in balances.iter().enumerate() {
+            if k == j {
+                continue;
+            }
+            sum += x;
+            c = c * d / (x * n);
+        }
+        c = c * d / (ann * n);
+        let b = sum + d / ann;
+        
+        let mut y = d;
+        for _ in 0..MAX_ITERATIONS {
+            let previous = y;
+            y = (y * y + c) / (2.0 * y + b - d);
+            
+            if (y - previous).abs() <= CONVERGENCE * y {
+                return Ok(y);
+            }
+        }
+        
+        Err(AppError::Internal("Stableswap balance did not converge".to_string()))
+    }
+    
+    /// Value of one LP token in token units
+    pub fn virtual_price(&self) -> AppResult<f64> {
+        if self.lp_supply <= 0.0 {
+            return Ok(1.0);
+        }
+        Ok(self.get_d(&self.balances)? / self.lp_supply)
+    }
+    
+    /// Quote swapping an amount of token i for token j
+    pub fn quote_swap(&self, i: usize, j: usize, amount_in: f64) -> AppResult<SwapQuote> {
+        self.check_index(i)?;
+        self.check_index(j)?;
+        if i == j || amount_in <= 0.0 {
+            return Err(AppError::Validation("Invalid swap".to_string()));
+        }
+        
+        let d = self.get_d(&self.balances)?;
+        let output = |amount: f64| -> AppResult<f64> {
+            let mut balances = self.balances.clone();
+            balances[i] += amount;
+            Ok(self.balances[j] - self.get_y(j, &balances, d)?)
+        };
+        
+        let gross_out = output(amount_in)?;
+        if gross_out >= self.balances[j] {
+            return Err(AppError::Validation("Swap exceeds pool liquidity".to_string()));
+        }
+        
+        // Marginal price from a swap small enough to have no impact
+        let probe = (self.balances[i] * 1e-6).min(amount_in);
+        let spot_rate = output(probe)? / probe;
+        
+        let fee = gross_out * self.fee;
+        Ok(SwapQuote {
+            amount_in,
+            amount_out: gross_out - fee,
+            fee,
+            price_impact: (1.0 - (gross_out / amount_in) / spot_rate).max(0.0),
+        })
+    }
+    
+    /// Quote depositing token amounts, charging fees on the imbalance they cause
+    pub fn quote_add_liquidity(&self, amounts: &[f64]) -> AppResult<LiquidityQuote> {
+        if amounts.len() != self.balances.len() || amounts.iter().any(|a| *a < 0.0) {
+            return Err(AppError::Validation("Invalid deposit amounts".to_string()));
+        }
+        
+        let new_balances: Vec<f64> = self.balances.iter().zip(amounts).map(|(b, a)| b + a).collect();
+        let (lp_amount, fees) = self.lp_delta(&new_balances)?;
+        
+        let deposited: f64 = amounts.iter().sum();
+        let slippage = if deposited > 0.0 {
+            1.0 - lp_amount * self.virtual_price()? / deposited
+        } else {
+            0.0
+        };
+        
+        Ok(LiquidityQuote {
+            lp_amount,
+            amounts: amounts.to_vec(),
+            fees,
+            slippage,
+        })
+    }
+    
+    /// Quote withdrawing exact token amounts, charging fees on the imbalance they cause
+    pub fn quote_remove_liquidity_imbalance(&self, amounts: &[f64]) -> AppResult<LiquidityQuote> {
+        if amounts.len() != self.balances.len() || amounts.iter().zip(&self.balances).any(|(a, b)| *a < 0.0 || a >= b) {
+            return Err(AppError::Validation("Invalid withdrawal amounts".to_string()));
+        }
+        
+        let new_balances: Vec<f64> = self.balances.iter().zip(amounts).map(|(b, a)| b - a).collect();
+        let (lp_delta, fees) = self.lp_delta(&new_balances)?;
+        let lp_amount = -lp_delta;
+        
+        let withdrawn: f64 = amounts.iter().sum();
+        let slippage = if withdrawn > 0.0 {
+            1.0 - withdrawn / (lp_amount * self.virtual_price()?)
+        } else {
+            0.0
+        };
+        
+        Ok(LiquidityQuote {
+            lp_amount,
+            amounts: amounts.to_vec(),
+            fees,
+            slippage,
+        })
+    }
+    
+    /// Quote burning LP tokens for a proportional share of every token, which charges no fee
+    pub fn quote_remove_liquidity(&self, lp_amount: f64) -> AppResult<LiquidityQuote> {
+        if lp_amount <= 0.0 || lp_amount > self.lp_supply {
+            return Err(AppError::Validation("Invalid LP amount".to_string()));
+        }
+        
+        let share = lp_amount / self.lp_supply;
+        Ok(LiquidityQuote {
+            lp_amount,
+            amounts: self.balances.iter().map(|b| b * share).collect(),
+            fees: vec![0.0; self.balances.len()],
+            slippage: 0.0,
+        })
+    }
+    
+    /// Quote burning LP tokens for a single token
+    pub fn quote_remove_liquidity_one_coin(&self, lp_amount: f64, i: usize) -> AppResult<LiquidityQuote> {
+        self.check_index(i)?;
+        if lp_amount <= 0.0 || lp_amount >= self.lp_supply {
+            return Err(AppError::Validation("Invalid LP amount".to_string()));
+        }
+        
+        let d0 = self.get_d(&self.balances)?;
+        let d1 = d0 - lp_amount * d0 / self.lp_supply;
+        let new_y = self.get_y(i, &self.balances, d1)?;
+        
+        // Every balance moves towards its share of the reduced invariant; fees apply to the difference
+        let imbalance_fee = self.imbalance_fee();
+        let mut reduced = self.balances.clone();
+        for (k, balance) in self.balances.iter().enumerate() {
+            let expected = if k == i {
+                balance * d1 / d0 - new_y
+            } else {
+                balance - balance * d1 / d0
+            };
+            reduced[k] -= imbalance_fee * expected;
+        }
+        
+        let amount = reduced[i] - self.get_y(i, &reduced, d1)?;
+        let without_fee = self.balances[i] - new_y;
+        
+        let mut amounts = vec![0.0; self.balances.len()];
+        amounts[i] = amount;
+        let mut fees = vec![0.0; self.balances.len()];
+        fees[i] = without_fee - amount;
+        
+        Ok(LiquidityQuote {
+            lp_amount,
+            amounts,
+            fees,
+            slippage: 1.0 - amount / (lp_amount * self.virtual_price()?),
+        })
+    }
+    
+    // Fee on each token's deviation from a balanced change: fee * n / (4 * (n - 1))
+    fn imbalance_fee(&self) -> f64 {
+        let n = self.n();
+        self.fee * n / (4.0 * (n - 1.0))
+    }
+    
+    // LP tokens minted (positive) or burned (negative) to move to new balances, with imbalance fees
+    fn lp_delta(&self, new_balances: &[f64]) -> AppResult<(f64, Vec<f64>)> {
+        let d0 = self.get_d(&self.balances)?;
+        let d1 = self.get_d(new_balances)?;
+        
+        // The first deposit sets the LP supply to the invariant
+        if self.lp_supply <= 0.0 || d0 <= 0.0 {
+            return Ok((d1, vec![0.0; self.balances.len()]));
+        }
+        
+        let imbalance_fee = self.imbalance_fee();
+        let mut fees = Vec::with_capacity(self.balances.len());
+        let mut adjusted = new_balances.to_vec();
+        
+        for (k, (old, new)) in self.balances.iter().zip(new_balances).enumerate() {
+            let ideal = d1 * old / d0;
+            let fee = imbalance_fee * (ideal - new).abs();
+            adjusted[k] -= fee;
+            fees.push(fee);
+        }
+        
+        let d2 = self.get_d(&adjusted)?;
+        Ok((self.lp_supply * (d2 - d0) / d0, fees))
+    }
+}
\ No newline at end of file
@@ -1,4 +1,5 @@
 use crate::error::{AppError, AppResult};
+use bech32::{FromBase32, ToBase32, Variant};
 use num_bigint::BigUint;
 use std::str::FromStr;
 
@@ -52,4 +53,32 @@ pub fn generate_nonce() -> String {
     let mut rng = thread_rng();
     let nonce: u64 = rng.gen();
     format!("{:016x}", nonce)
+}
+
+// Decode a bech32 "erd1..." address into the 32-byte public key it encodes
+pub fn address_to_public_key(address: &str) -> AppResult<Vec<u8>> {
+    let (hrp, data, _variant) = bech32::decode(address).map_err(|e| {
+        AppError::Validation(format!("Invalid address {}: {}", address, e))
+    })?;
+    
+    if hrp != "erd" {
+        return Err(AppError::Validation(format!("Invalid address prefix: {}", hrp)));
+    }
+    
+    let public_key = Vec::<u8>::from_base32(&data).map_err(|e| {
+        AppError::Validation(format!("Invalid address {}: {}", address, e))
+    })?;
+    
+    if public_key.len() != 32 {
+        return Err(AppError::Validation(format!("Invalid address length: {}", address)));
+    }
+    
+    Ok(public_key)
+}
+
+// Encode a 32-byte public key as a bech32 "erd1..." address
+pub fn public_key_to_address(public_key: &[u8]) -> AppResult<String> {
+    bech32::encode("erd", public_key.to_base32(), Variant::Bech32).map_err(|e| {
+        AppError::Validation(format!("Failed to encode address: {}", e))
+    })
 }
\ No newline at end of file
@@ -2,8 +2,6 @@ use crate::error::{AppError, AppResult};
 use bip39::{Mnemonic, Language};
 use ed25519_dalek::{Keypair, SecretKey, PublicKey, Signer};
 use rand::rngs::OsRng;
-use sha2::{Sha256, Digest};
-use base64::{Engine as _, engine::general_purpose};
 
 pub struct Wallet {
     keypair: Keypair,
@@ -62,16 +60,9 @@ impl Wallet {
     }
     
     pub fn address(&self) -> String {
-        // MultiversX addresses start with "erd1"
-        let mut hasher = Sha256::new();
-        hasher.update(self.keypair.public.as_bytes());
-        let hash = hasher.finalize();
-        
-        // Use the first 20 bytes of the hash for the address
-        let address_bytes = &hash[0..20];
-        
-        // Encode with bech32 (simplified here - in a real implementation, use a proper bech32 library)
-        format!("erd1{}", hex::encode(address_bytes))
+        // MultiversX addresses are the bech32 encoding ("erd1...") of the public key
+        crate::utils::public_key_to_address(self.keypair.public.as_bytes())
+            .expect("a 32-byte public key always encodes to a valid address")
     }
     
     pub fn sign_message(&self, message: &[u8]) -> Vec<u8> {
@@ -79,9 +70,10 @@ impl Wallet {
         signature.to_bytes().to_vec()
     }
     
+    // Sign the serialized transaction, returning the hex signature expected by the gateway
     pub fn sign_transaction(&self, transaction_data: &[u8]) -> String {
         let signature = self.keypair.sign(transaction_data);
-        general_purpose::STANDARD.encode(signature.to_bytes())
+        hex::encode(signature.to_bytes())
     }
     
     pub fn private_key(&self) -> Vec<u8> {